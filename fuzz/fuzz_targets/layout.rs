@@ -0,0 +1,140 @@
+//! Lays out random histories and checks the invariants of `GitGraph::validate`.
+//!
+//! Run with `cargo +nightly fuzz run layout` from the repository root.
+
+#![no_main]
+
+use git_graph::graph::{GitGraph, HeadInfo};
+use git_graph::print::unicode::print_unicode;
+use git_graph::settings::{BranchOrder, BranchSettings, BranchSettingsDef, CommitOrder, Settings};
+use git_graph::source::memory::{MemorySource, MemorySourceDef};
+use git_graph::source::{CommitData, Oid, RefData, Signature};
+use libfuzzer_sys::fuzz_target;
+
+/// Branches and tags placed on random commits, also used in merge summaries.
+const REFS: [&str; 10] = [
+    "refs/heads/main",
+    "refs/heads/develop",
+    "refs/heads/feature/a",
+    "refs/heads/feature/b",
+    "refs/heads/release/1.0",
+    "refs/heads/hotfix/1.0.1",
+    "refs/heads/fix",
+    "refs/remotes/origin/main",
+    "refs/remotes/origin/feature/a",
+    "refs/tags/v1.0",
+];
+/// Maximum number of commits, to keep runs fast.
+const MAX_COMMITS: usize = 200;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&options, data)) = data.split_first() else {
+        return;
+    };
+    let Some(source) = history(data) else {
+        return;
+    };
+    let settings = settings(options);
+    let graph = GitGraph::from_source(source, &settings, None).unwrap();
+    let violations = graph.validate();
+    assert!(violations.is_empty(), "{}", violations.join("\n"));
+    print_unicode(&graph, &settings).unwrap();
+});
+
+/// A history with three bytes per commit: the first parent, an optional second parent, and a reference.
+/// Commits are created oldest first, so parents are always older than their children.
+fn history(data: &[u8]) -> Option<MemorySource> {
+    let mut commits: Vec<CommitData> = vec![];
+    let mut refs: Vec<RefData> = vec![];
+    // Whether a commit is an ancestor of another one, or the commit itself, by commit index
+    let mut ancestors: Vec<Vec<bool>> = vec![];
+
+    for (idx, bytes) in data.chunks_exact(3).take(MAX_COMMITS).enumerate() {
+        let id: Oid = format!("{:040x}", idx + 1).parse().unwrap();
+        let mut parents = vec![];
+        // Byte 255 starts a new root commit
+        if idx > 0 && bytes[0] != 255 {
+            parents.push(idx - 1 - bytes[0] as usize % idx.min(8));
+        }
+        // Like Git, only merge commits that are not ancestors of each other
+        if let (Some(&first), true) = (parents.first(), bytes[1] & 1 == 1) {
+            let second = (bytes[1] >> 1) as usize % idx;
+            let related = |a: usize, b: usize| ancestors[a].get(b) == Some(&true);
+            if !related(first, second) && !related(second, first) {
+                parents.push(second);
+            }
+        }
+        let mut own_ancestors = vec![false; idx + 1];
+        own_ancestors[idx] = true;
+        for &parent in &parents {
+            for (own, &ancestor) in own_ancestors.iter_mut().zip(&ancestors[parent]) {
+                *own |= ancestor;
+            }
+        }
+        ancestors.push(own_ancestors);
+
+        let name = REFS[bytes[2] as usize % REFS.len()];
+        let message = if parents.len() > 1 {
+            let branch = name.splitn(3, '/').last().unwrap_or(name);
+            format!("Merge branch '{}'", branch)
+        } else {
+            format!("Commit {}", idx)
+        };
+        if bytes[2] >= 128 && refs.iter().all(|rf| rf.name != name) {
+            refs.push(RefData {
+                name: name.to_string(),
+                target: id,
+            });
+        }
+        let signature = Signature {
+            name: "Fuzz".to_string(),
+            email: "fuzz@example.com".to_string(),
+            time: idx as i64 * 60,
+            offset: 0,
+        };
+        commits.push(CommitData {
+            id,
+            parents: parents.iter().map(|&parent| commits[parent].id).collect(),
+            message,
+            author: signature.clone(),
+            committer: signature,
+            tree: None,
+            encoding: None,
+        });
+    }
+
+    let head = commits.last()?.id;
+    commits.reverse();
+    Some(MemorySource::from(MemorySourceDef {
+        commits,
+        refs,
+        head: HeadInfo {
+            oid: head,
+            name: "HEAD".to_string(),
+            is_branch: false,
+        },
+    }))
+}
+
+/// Settings with the branching model, density, order and commit order selected by the bits of `options`.
+fn settings(options: u8) -> Settings {
+    let model = match options & 3 {
+        0 => BranchSettingsDef::git_flow(),
+        1 => BranchSettingsDef::simple(),
+        2 => BranchSettingsDef::trunk(),
+        _ => BranchSettingsDef::gitlab_flow(),
+    };
+    Settings {
+        reverse_commit_order: options & 4 != 0,
+        density: (options >> 3) % 3,
+        commit_order: if options & 32 != 0 {
+            CommitOrder::Topo
+        } else {
+            CommitOrder::Date
+        },
+        deterministic: true,
+        branch_order: BranchOrder::ShortestFirst(options & 64 == 0),
+        branches: BranchSettings::from(model).unwrap(),
+        ..Default::default()
+    }
+}
@@ -1,24 +1,34 @@
-use crate::settings::{BranchSettingsDef, RepoSettings};
-use git2::Repository;
+use crate::settings::{BranchSettingsDef, Indent, RepoSettings, UserConfig};
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 
+/// Name of the file storing a repository's settings, inside the `.git` directory.
+pub const REPO_CONFIG_FILE: &str = "git-graph.toml";
+
 /// Creates the directory `APP_DATA/git-graph/models` if it does not exist,
 /// and writes the files for built-in branching models there.
 pub fn create_config<P: AsRef<Path> + AsRef<OsStr>>(app_model_path: &P) -> Result<(), String> {
     let path: &Path = app_model_path.as_ref();
     if !path.exists() {
         std::fs::create_dir_all(app_model_path).map_err(|err| err.to_string())?;
+    }
 
-        let models = [
-            (BranchSettingsDef::git_flow(), "git-flow.toml"),
-            (BranchSettingsDef::simple(), "simple.toml"),
-            (BranchSettingsDef::none(), "none.toml"),
-        ];
-        for (model, file) in &models {
-            let mut path = PathBuf::from(&app_model_path);
-            path.push(file);
-            let str = toml::to_string_pretty(&model).map_err(|err| err.to_string())?;
+    let models = [
+        (BranchSettingsDef::git_flow(), "git-flow.toml"),
+        (BranchSettingsDef::simple(), "simple.toml"),
+        (BranchSettingsDef::trunk(), "trunk.toml"),
+        (BranchSettingsDef::gitlab_flow(), "gitlab-flow.toml"),
+        (BranchSettingsDef::none(), "none.toml"),
+    ];
+    // Presets added in later versions are written on demand, existing files are kept
+    for (model, file) in &models {
+        let mut path = PathBuf::from(&app_model_path);
+        path.push(file);
+        if !path.exists() {
+            // Via a TOML value, which emits top-level arrays before the color tables
+            let value = toml::Value::try_from(model).map_err(|err| err.to_string())?;
+            let str = toml::to_string_pretty(&value).map_err(|err| err.to_string())?;
             std::fs::write(&path, str).map_err(|err| err.to_string())?;
         }
     }
@@ -26,6 +36,29 @@ pub fn create_config<P: AsRef<Path> + AsRef<OsStr>>(app_model_path: &P) -> Resul
     Ok(())
 }
 
+/// Reads the user configuration file `APP_DATA/git-graph/<file_name>`.
+/// Returns the default configuration if the file does not exist.
+pub fn get_user_config<P: AsRef<Path>>(
+    app_path: &P,
+    file_name: &str,
+) -> Result<UserConfig, String> {
+    let mut config_path = PathBuf::from(app_path.as_ref());
+    config_path.push(file_name);
+
+    if config_path.exists() {
+        toml::from_str(&std::fs::read_to_string(&config_path).map_err(|err| err.to_string())?)
+            .map_err(|err| {
+                format!(
+                    "ERROR: Can't read {}\n       {}",
+                    config_path.display(),
+                    err
+                )
+            })
+    } else {
+        Ok(UserConfig::default())
+    }
+}
+
 /// Get models available in `APP_DATA/git-graph/models`.
 pub fn get_available_models<P: AsRef<Path>>(app_model_path: &P) -> Result<Vec<String>, String> {
     let models = std::fs::read_dir(app_model_path)
@@ -50,26 +83,66 @@ pub fn get_available_models<P: AsRef<Path>>(app_model_path: &P) -> Result<Vec<St
     Ok(models)
 }
 
-/// Get the currently set branching model for a repo.
-pub fn get_model_name(repository: &Repository, file_name: &str) -> Result<Option<String>, String> {
-    let mut config_path = PathBuf::from(repository.path());
-    config_path.push(file_name);
+/// Reads a repo's settings, given the path of its `.git` directory. `None` if the file does not exist.
+fn get_repo_settings(git_dir: &Path, file_name: &str) -> Result<Option<RepoSettings>, String> {
+    let config_path = git_dir.join(file_name);
 
     if config_path.exists() {
-        let repo_config: RepoSettings =
-            toml::from_str(&std::fs::read_to_string(config_path).map_err(|err| err.to_string())?)
-                .map_err(|err| err.to_string())?;
-
-        Ok(Some(repo_config.model))
+        toml::from_str(&std::fs::read_to_string(&config_path).map_err(|err| err.to_string())?)
+            .map(Some)
+            .map_err(|err| {
+                format!(
+                    "ERROR: Can't read {}\n       {}",
+                    config_path.display(),
+                    err
+                )
+            })
     } else {
         Ok(None)
     }
 }
 
+/// Get the currently set branching model for a repo, given the path of its `.git` directory.
+pub fn get_model_name(git_dir: &Path, file_name: &str) -> Result<Option<String>, String> {
+    Ok(get_repo_settings(git_dir, file_name)?.and_then(|settings| settings.model))
+}
+
+/// Get the named commit formats of the user configuration and the repo, given the path of its `.git` directory.
+/// The repo's formats take precedence over equally named ones of the user.
+pub fn get_formats(
+    user_config: &UserConfig,
+    git_dir: Option<&Path>,
+    file_name: &str,
+) -> Result<HashMap<String, String>, String> {
+    let mut formats = user_config.formats.clone();
+    if let Some(git_dir) = git_dir {
+        if let Some(settings) = get_repo_settings(git_dir, file_name)? {
+            formats.extend(settings.formats);
+        }
+    }
+    Ok(formats)
+}
+
+/// Get the hanging indents of commit formats of the user configuration and the repo, by format name.
+/// The repo's indents take precedence over those of the user.
+pub fn get_format_indents(
+    user_config: &UserConfig,
+    git_dir: Option<&Path>,
+    file_name: &str,
+) -> Result<HashMap<String, Indent>, String> {
+    let mut indents = user_config.format_indents.clone();
+    if let Some(git_dir) = git_dir {
+        if let Some(settings) = get_repo_settings(git_dir, file_name)? {
+            indents.extend(settings.format_indents);
+        }
+    }
+    Ok(indents)
+}
+
 /// Try to get the branch settings for a given model.
-/// If no model name is given, returns the branch settings set for the repo, or the default otherwise.
+/// If no model name is given, returns the branch settings set for the repo (if any), or the default otherwise.
 pub fn get_model<P: AsRef<Path> + AsRef<OsStr>>(
-    repository: &Repository,
+    git_dir: Option<&Path>,
     model: Option<&str>,
     repo_config_file: &str,
     app_model_path: &P,
@@ -77,16 +150,13 @@ pub fn get_model<P: AsRef<Path> + AsRef<OsStr>>(
     match model {
         Some(model) => read_model(model, app_model_path),
         None => {
-            let mut config_path = PathBuf::from(repository.path());
-            config_path.push(repo_config_file);
-
-            if config_path.exists() {
-                let repo_config: RepoSettings = toml::from_str(
-                    &std::fs::read_to_string(config_path).map_err(|err| err.to_string())?,
-                )
-                .map_err(|err| err.to_string())?;
+            let repo_model = match git_dir {
+                Some(git_dir) => get_model_name(git_dir, repo_config_file)?,
+                None => None,
+            };
 
-                read_model(&repo_config.model, app_model_path)
+            if let Some(repo_model) = repo_model {
+                read_model(&repo_model, app_model_path)
             } else {
                 Ok(read_model("git-flow", app_model_path)
                     .unwrap_or_else(|_| BranchSettingsDef::git_flow()))
@@ -95,33 +165,97 @@ pub fn get_model<P: AsRef<Path> + AsRef<OsStr>>(
     }
 }
 
-/// Read a branching model file.
+/// Read a branching model file, resolving inheritance via `extends`.
 fn read_model<P: AsRef<Path> + AsRef<OsStr>>(
     model: &str,
     app_model_path: &P,
 ) -> Result<BranchSettingsDef, String> {
+    read_model_value(model, app_model_path, &mut Vec::new())?
+        .try_into::<BranchSettingsDef>()
+        .map_err(|err| format!("ERROR: Invalid branching model '{}'\n       {}", model, err))
+}
+
+/// Read a branching model file as TOML value, merged with the model it `extends` (if any).
+/// `visited` holds the models in the current chain of inheritance, for cycle detection.
+fn read_model_value<P: AsRef<Path> + AsRef<OsStr>>(
+    model: &str,
+    app_model_path: &P,
+    visited: &mut Vec<String>,
+) -> Result<toml::Value, String> {
+    if visited.iter().any(|m| m == model) {
+        return Err(format!(
+            "ERROR: Cyclic inheritance of branching models: {} -> {}",
+            visited.join(" -> "),
+            model
+        ));
+    }
+    visited.push(model.to_string());
+
     let mut model_file = PathBuf::from(&app_model_path);
     model_file.push(format!("{}.toml", model));
 
-    if model_file.exists() {
-        toml::from_str::<BranchSettingsDef>(
-            &std::fs::read_to_string(model_file).map_err(|err| err.to_string())?,
-        )
-        .map_err(|err| err.to_string())
-    } else {
+    if !model_file.exists() {
         let models = get_available_models(&app_model_path)?;
         let path: &Path = app_model_path.as_ref();
-        Err(format!(
+        return Err(format!(
             "ERROR: No branching model named '{}' found in {}\n       Available models are: {}",
             model,
             path.display(),
             itertools::join(models, ", ")
-        ))
+        ));
+    }
+
+    let mut value = toml::from_str::<toml::Value>(
+        &std::fs::read_to_string(model_file).map_err(|err| err.to_string())?,
+    )
+    .map_err(|err| err.to_string())?;
+
+    let base = match value
+        .as_table_mut()
+        .and_then(|table| table.remove("extends"))
+    {
+        None => return Ok(value),
+        Some(toml::Value::String(base)) => base,
+        Some(_) => {
+            return Err(format!(
+                "ERROR: Invalid branching model '{}'\n       'extends' must be the name of a model",
+                model
+            ))
+        }
+    };
+    let base = read_model_value(&base, app_model_path, visited)?;
+    Ok(merge_models(base, value))
+}
+
+/// Merges a model into the model it extends. Arrays replace the base model's arrays,
+/// except for color `matches`, which take precedence over the base model's `matches`.
+fn merge_models(base: toml::Value, model: toml::Value) -> toml::Value {
+    match (base, model) {
+        (toml::Value::Table(mut base), toml::Value::Table(model)) => {
+            for (key, value) in model {
+                let merged = match (base.remove(&key), value) {
+                    (Some(toml::Value::Array(base)), toml::Value::Array(mut value))
+                        if key == "matches" =>
+                    {
+                        value.extend(base);
+                        toml::Value::Array(value)
+                    }
+                    (Some(base @ toml::Value::Table(_)), value @ toml::Value::Table(_)) => {
+                        merge_models(base, value)
+                    }
+                    (_, value) => value,
+                };
+                base.insert(key, merged);
+            }
+            toml::Value::Table(base)
+        }
+        (_, model) => model,
     }
 }
-/// Permanently sets the branching model for a repository
+
+/// Permanently sets the branching model for a repository, given the path of its `.git` directory.
 pub fn set_model<P: AsRef<Path>>(
-    repository: &Repository,
+    git_dir: &Path,
     model: &str,
     repo_config_file: &str,
     app_model_path: &P,
@@ -137,13 +271,15 @@ pub fn set_model<P: AsRef<Path>>(
         ));
     }
 
-    let mut config_path = PathBuf::from(repository.path());
-    config_path.push(repo_config_file);
-
-    let config = RepoSettings {
-        model: model.to_string(),
-    };
+    // Other settings, like named formats, are kept
+    let mut config = get_repo_settings(git_dir, repo_config_file)?.unwrap_or(RepoSettings {
+        model: None,
+        formats: HashMap::new(),
+        format_indents: HashMap::new(),
+    });
+    config.model = Some(model.to_string());
 
+    let config_path = git_dir.join(repo_config_file);
     let str = toml::to_string_pretty(&config).map_err(|err| err.to_string())?;
     std::fs::write(&config_path, str).map_err(|err| err.to_string())?;
 
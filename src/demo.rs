@@ -0,0 +1,319 @@
+//! Generation of repositories with a realistic branching pattern, for demos, screenshots and test fixtures.
+
+use git2::{Oid, Repository, Signature, Time};
+use std::path::Path;
+
+/// Authors of generated commits.
+const AUTHORS: [(&str, &str); 4] = [
+    ("Alice Example", "alice@example.com"),
+    ("Bob Example", "bob@example.com"),
+    ("Carol Example", "carol@example.com"),
+    ("Dave Example", "dave@example.com"),
+];
+/// Topics of generated feature branches and commit messages.
+const TOPICS: [&str; 12] = [
+    "login", "search", "export", "settings", "cache", "parser", "api", "docs", "layout", "themes",
+    "sync", "reports",
+];
+/// Verbs of generated commit messages.
+const VERBS: [&str; 6] = ["Add", "Fix", "Update", "Refactor", "Test", "Document"];
+/// Time of the first generated commit, in seconds since the Unix epoch.
+const START_TIME: i64 = 1_600_000_000;
+
+/// Branching patterns of generated histories.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DemoPattern {
+    /// Features merged into `develop`, releases and hotfixes merged into `main` and `develop`
+    GitFlow,
+    /// Features merged into `main`, with occasional release tags
+    Simple,
+    /// Mostly direct commits to `main`, short-lived features and frequent release tags
+    Trunk,
+    /// Features merged into `main`, which flows into `staging` and `production`
+    GitLabFlow,
+}
+
+impl DemoPattern {
+    /// The pattern of a branching model preset. Git-flow for `git-flow` and custom models.
+    pub fn for_model(model: &str) -> Self {
+        match model {
+            "simple" | "none" => DemoPattern::Simple,
+            "trunk" => DemoPattern::Trunk,
+            "gitlab-flow" => DemoPattern::GitLabFlow,
+            _ => DemoPattern::GitFlow,
+        }
+    }
+}
+
+/// Creates a repository at `path` with about `commits` commits in the given pattern.
+/// The directory must not exist, or be empty. The history is the same for the same arguments.
+///
+/// Commits have no files, so the working tree of the repository stays empty.
+pub fn create_demo_repository(
+    path: &Path,
+    pattern: DemoPattern,
+    commits: usize,
+) -> Result<(), String> {
+    if path.exists()
+        && path
+            .read_dir()
+            .map_err(|err| err.to_string())?
+            .next()
+            .is_some()
+    {
+        return Err(format!("Directory '{}' is not empty", path.display()));
+    }
+    let repository = Repository::init(path).map_err(|err| err.message().to_string())?;
+    let mut generator = Generator::new(repository)?;
+    generator.generate(pattern, commits.max(1))
+}
+
+struct Generator {
+    repository: Repository,
+    /// The empty tree, shared by all commits
+    tree: Oid,
+    time: i64,
+    rng: u64,
+    commits: usize,
+    features: usize,
+    /// Minor version of the next release
+    version: u32,
+    /// Patch version of the next hotfix
+    patch: u32,
+}
+
+impl Generator {
+    fn new(repository: Repository) -> Result<Self, String> {
+        let tree = repository
+            .treebuilder(None)
+            .and_then(|builder| builder.write())
+            .map_err(|err| err.message().to_string())?;
+        Ok(Generator {
+            repository,
+            tree,
+            time: START_TIME,
+            rng: 0x2545_f491_4f6c_dd1d,
+            commits: 0,
+            features: 0,
+            version: 1,
+            patch: 1,
+        })
+    }
+
+    fn generate(&mut self, pattern: DemoPattern, commits: usize) -> Result<(), String> {
+        let root = self.commit("main", "Initial commit", &[])?;
+        let trunk = match pattern {
+            DemoPattern::GitFlow => {
+                self.set_branch("develop", root)?;
+                "develop"
+            }
+            DemoPattern::GitLabFlow => {
+                self.set_branch("staging", root)?;
+                self.set_branch("production", root)?;
+                "main"
+            }
+            DemoPattern::Simple | DemoPattern::Trunk => "main",
+        };
+
+        while self.commits < commits {
+            match (pattern, self.random(10)) {
+                (DemoPattern::GitFlow, 0) if self.version > 1 => self.hotfix()?,
+                (DemoPattern::GitFlow, 1) => self.release()?,
+                (DemoPattern::Simple, 0) => self.tag("main")?,
+                (DemoPattern::Trunk, 0..=1) => self.tag("main")?,
+                (DemoPattern::Trunk, 2..=6) => {
+                    let message = self.message();
+                    self.commit("main", &message, &[])?;
+                }
+                (DemoPattern::GitLabFlow, 0) => self.promote("main", "staging")?,
+                (DemoPattern::GitLabFlow, 1) => self.promote("staging", "production")?,
+                _ => self.feature(trunk, true)?,
+            }
+        }
+        // Leave a feature in progress
+        self.feature(trunk, false)?;
+
+        self.repository
+            .set_head(&format!("refs/heads/{}", trunk))
+            .map_err(|err| err.message().to_string())
+    }
+
+    /// A feature branch with a few commits, merged into `target` if `merge`.
+    /// Sometimes, `target` gets another commit in the meantime.
+    fn feature(&mut self, target: &str, merge: bool) -> Result<(), String> {
+        let topic = TOPICS[self.random(TOPICS.len())];
+        self.features += 1;
+        let name = format!("feature/{}-{}", topic, self.features);
+        self.set_branch(&name, self.tip(target)?)?;
+        for _ in 0..=self.random(4) {
+            let message = format!("{} {}", VERBS[self.random(VERBS.len())], topic);
+            self.commit(&name, &message, &[])?;
+        }
+        if self.random(3) == 0 {
+            let message = self.message();
+            self.commit(target, &message, &[])?;
+        }
+        if merge {
+            self.merge(&name, target)?;
+            self.delete_branch(&name)?;
+        }
+        Ok(())
+    }
+
+    /// A release branch of git-flow, merged into `main` and tagged, and back into `develop`.
+    fn release(&mut self) -> Result<(), String> {
+        let version = format!("1.{}", self.version);
+        self.version += 1;
+        self.patch = 1;
+        let name = format!("release/{}", version);
+        self.set_branch(&name, self.tip("develop")?)?;
+        self.commit(&name, &format!("Bump version to {}", version), &[])?;
+        let merge = self.merge(&name, "main")?;
+        self.set_tag(&format!("v{}", version), merge)?;
+        self.merge(&name, "develop")?;
+        self.delete_branch(&name)
+    }
+
+    /// A hotfix branch of git-flow, off `main`, merged into `main` and tagged, and into `develop`.
+    fn hotfix(&mut self) -> Result<(), String> {
+        let version = format!("1.{}.{}", self.version - 1, self.patch);
+        self.patch += 1;
+        let name = format!("hotfix/{}", version);
+        self.set_branch(&name, self.tip("main")?)?;
+        let message = format!("Fix {}", TOPICS[self.random(TOPICS.len())]);
+        self.commit(&name, &message, &[])?;
+        let merge = self.merge(&name, "main")?;
+        self.set_tag(&format!("v{}", version), merge)?;
+        self.merge(&name, "develop")?;
+        self.delete_branch(&name)
+    }
+
+    /// Merges `source` into `target` in GitLab flow, like `main` into `staging`.
+    fn promote(&mut self, source: &str, target: &str) -> Result<(), String> {
+        if self.tip(source)? != self.tip(target)? {
+            self.merge(source, target)?;
+        }
+        Ok(())
+    }
+
+    /// Tags the tip of a branch with the next version.
+    fn tag(&mut self, branch: &str) -> Result<(), String> {
+        let version = format!("v1.{}", self.version);
+        self.version += 1;
+        self.set_tag(&version, self.tip(branch)?)
+    }
+
+    /// Creates a merge commit of `source` into `target`, with a summary like Git's.
+    fn merge(&mut self, source: &str, target: &str) -> Result<Oid, String> {
+        let message = if target == "main" || target == "master" {
+            format!("Merge branch '{}'", source)
+        } else {
+            format!("Merge branch '{}' into {}", source, target)
+        };
+        let source_tip = self.tip(source)?;
+        self.commit(target, &message, &[source_tip])
+    }
+
+    /// Creates a commit on a branch, with the branch's tip and `merged` as parents.
+    fn commit(&mut self, branch: &str, message: &str, merged: &[Oid]) -> Result<Oid, String> {
+        let mut parents = vec![];
+        if let Ok(tip) = self.tip(branch) {
+            parents.push(tip);
+        }
+        parents.extend_from_slice(merged);
+
+        let (name, email) = AUTHORS[self.random(AUTHORS.len())];
+        self.time += 600 + self.random(7200) as i64;
+        let signature = Signature::new(name, email, &Time::new(self.time, 0))
+            .map_err(|err| err.message().to_string())?;
+        let repo = &self.repository;
+        let tree = repo
+            .find_tree(self.tree)
+            .map_err(|err| err.message().to_string())?;
+        let parents = parents
+            .iter()
+            .map(|oid| repo.find_commit(*oid))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| err.message().to_string())?;
+        let oid = repo
+            .commit(
+                None,
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parents.iter().collect::<Vec<_>>(),
+            )
+            .map_err(|err| err.message().to_string())?;
+        self.set_branch(branch, oid)?;
+        self.commits += 1;
+        Ok(oid)
+    }
+
+    /// A commit message like `Update cache`.
+    fn message(&mut self) -> String {
+        let verb = VERBS[self.random(VERBS.len())];
+        format!("{} {}", verb, TOPICS[self.random(TOPICS.len())])
+    }
+
+    fn tip(&self, branch: &str) -> Result<Oid, String> {
+        self.repository
+            .refname_to_id(&format!("refs/heads/{}", branch))
+            .map_err(|err| err.message().to_string())
+    }
+
+    fn set_branch(&self, branch: &str, oid: Oid) -> Result<(), String> {
+        self.set_ref(&format!("refs/heads/{}", branch), oid)
+    }
+
+    fn set_tag(&self, tag: &str, oid: Oid) -> Result<(), String> {
+        self.set_ref(&format!("refs/tags/{}", tag), oid)
+    }
+
+    fn set_ref(&self, name: &str, oid: Oid) -> Result<(), String> {
+        self.repository
+            .reference(name, oid, true, "git-graph demo")
+            .map(|_| ())
+            .map_err(|err| err.message().to_string())
+    }
+
+    fn delete_branch(&self, branch: &str) -> Result<(), String> {
+        self.repository
+            .find_reference(&format!("refs/heads/{}", branch))
+            .and_then(|mut reference| reference.delete())
+            .map_err(|err| err.message().to_string())
+    }
+
+    /// A pseudo-random number below `max`, the same in each run (xorshift).
+    fn random(&mut self, max: usize) -> usize {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng % max as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create_demo_repository, DemoPattern};
+    use git2::Repository;
+
+    #[test]
+    fn generate_git_flow() {
+        let dir = std::env::temp_dir().join(format!("git-graph-demo-{}", std::process::id()));
+        create_demo_repository(&dir, DemoPattern::GitFlow, 50).unwrap();
+        assert!(create_demo_repository(&dir, DemoPattern::GitFlow, 50).is_err());
+
+        let repository = Repository::open(&dir).unwrap();
+        let mut walk = repository.revwalk().unwrap();
+        walk.push_glob("refs/heads").unwrap();
+        let commits = walk.count();
+        let head = repository.head().unwrap().shorthand().unwrap().to_string();
+        let tags = repository.tag_names(Some("v1.*")).unwrap().len();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!((50..60).contains(&commits));
+        assert_eq!(head, "develop");
+        assert!(tags > 0);
+    }
+}
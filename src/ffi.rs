@@ -0,0 +1,213 @@
+//! C-compatible interface, for use from editor plugins and other languages.
+//!
+//! Requires feature `ffi`. Build a shared library with
+//!
+//! ```text
+//! cargo rustc --release --lib --features ffi --crate-type cdylib
+//! ```
+//!
+//! All strings are NUL-terminated UTF-8. Strings returned by this library must be released
+//! with `git_graph_string_free`, graphs with `git_graph_free`. On failure, functions return
+//! a null pointer, and the error message can be retrieved with `git_graph_last_error`.
+
+use crate::config::{get_model, REPO_CONFIG_FILE};
+use crate::get_repo;
+use crate::graph::GitGraph;
+use crate::print::json::print_json;
+use crate::print::svg::print_svg;
+use crate::print::unicode::print_unicode;
+use crate::settings::{BranchSettings, Settings};
+use platform_dirs::AppDirs;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// A graph together with the settings it was created with.
+pub struct GitGraphHandle {
+    graph: GitGraph,
+    settings: Settings,
+}
+
+/// Builds the graph for the repository at (or above) `path`.
+///
+/// * `model`: name of the branching model, or null for the repository's model (default: git-flow)
+/// * `max_count`: maximum number of commits, or a negative number for no limit
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated string; `model` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn git_graph_new(
+    path: *const c_char,
+    model: *const c_char,
+    max_count: i64,
+) -> *mut GitGraphHandle {
+    let result = to_str(path).and_then(|path| {
+        let model = if model.is_null() {
+            None
+        } else {
+            Some(to_str(model)?)
+        };
+        let max_count = if max_count < 0 {
+            None
+        } else {
+            Some(max_count as usize)
+        };
+        new_handle(path, model, max_count)
+    });
+    match result {
+        Ok(handle) => Box::into_raw(Box::new(handle)),
+        Err(err) => {
+            set_error(err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Releases a graph created by `git_graph_new`.
+///
+/// # Safety
+///
+/// `handle` must be null or a pointer returned by `git_graph_new`, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn git_graph_free(handle: *mut GitGraphHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Renders the graph as plain text, one line per row.
+///
+/// # Safety
+///
+/// `handle` must be a valid pointer returned by `git_graph_new`.
+#[no_mangle]
+pub unsafe extern "C" fn git_graph_render_text(handle: *const GitGraphHandle) -> *mut c_char {
+    render(handle, |handle| {
+        let (g_lines, t_lines, _) = print_unicode(&handle.graph, &handle.settings)?;
+        Ok(g_lines
+            .iter()
+            .zip(t_lines.iter())
+            .map(|(g_line, t_line)| format!(" {}  {}\n", g_line, t_line))
+            .collect())
+    })
+}
+
+/// Renders the graph as SVG.
+///
+/// # Safety
+///
+/// `handle` must be a valid pointer returned by `git_graph_new`.
+#[no_mangle]
+pub unsafe extern "C" fn git_graph_render_svg(handle: *const GitGraphHandle) -> *mut c_char {
+    render(handle, |handle| print_svg(&handle.graph, &handle.settings))
+}
+
+/// Renders the graph's commits and branches as JSON.
+///
+/// # Safety
+///
+/// `handle` must be a valid pointer returned by `git_graph_new`.
+#[no_mangle]
+pub unsafe extern "C" fn git_graph_render_json(handle: *const GitGraphHandle) -> *mut c_char {
+    render(handle, |handle| print_json(&handle.graph))
+}
+
+/// The width of the graph in text-based output, in characters and excluding the commit text.
+/// Returns 0 if `handle` is null.
+///
+/// # Safety
+///
+/// `handle` must be a valid pointer returned by `git_graph_new`.
+#[no_mangle]
+pub unsafe extern "C" fn git_graph_width(handle: *const GitGraphHandle) -> usize {
+    handle.as_ref().map_or(0, |handle| handle.graph.width())
+}
+
+/// Releases a string returned by this library.
+///
+/// # Safety
+///
+/// `string` must be null or a pointer returned by one of the `git_graph_render_*` functions,
+/// and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn git_graph_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// The message of the last error on the calling thread, or null if there was none.
+/// The pointer is valid until the next call into this library on the same thread.
+#[no_mangle]
+pub extern "C" fn git_graph_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |err| err.as_ptr())
+    })
+}
+
+fn new_handle(
+    path: &str,
+    model: Option<&str>,
+    max_count: Option<usize>,
+) -> Result<GitGraphHandle, String> {
+    let repository = get_repo(path).map_err(|err| err.message().to_string())?;
+
+    let mut models_dir = AppDirs::new(Some("git-graph"), false)
+        .ok_or_else(|| "Can't determine the configuration directory".to_string())?
+        .config_dir;
+    models_dir.push("models");
+
+    let model = get_model(
+        Some(repository.path()),
+        model,
+        REPO_CONFIG_FILE,
+        &models_dir,
+    )?;
+
+    let settings = Settings {
+        branches: BranchSettings::from(model).map_err(|err| err.to_string())?,
+        ..Default::default()
+    };
+
+    let graph = GitGraph::new(repository, &settings, max_count)?;
+    Ok(GitGraphHandle { graph, settings })
+}
+
+unsafe fn render<F>(handle: *const GitGraphHandle, render: F) -> *mut c_char
+where
+    F: FnOnce(&GitGraphHandle) -> Result<String, String>,
+{
+    let result = handle
+        .as_ref()
+        .ok_or_else(|| "Graph handle is null".to_string())
+        .and_then(render)
+        .and_then(|str| CString::new(str).map_err(|err| err.to_string()));
+    match result {
+        Ok(str) => str.into_raw(),
+        Err(err) => {
+            set_error(err);
+            ptr::null_mut()
+        }
+    }
+}
+
+unsafe fn to_str<'a>(str: *const c_char) -> Result<&'a str, String> {
+    if str.is_null() {
+        return Err("String argument is null".to_string());
+    }
+    CStr::from_ptr(str).to_str().map_err(|err| err.to_string())
+}
+
+fn set_error(err: String) {
+    LAST_ERROR.with(|last| {
+        *last.borrow_mut() = CString::new(err).ok();
+    });
+}
@@ -1,18 +1,42 @@
 //! A graph structure representing the history of a Git repository.
 
-use crate::print::colors::to_terminal_color;
-use crate::settings::{BranchOrder, BranchSettings, MergePatterns, Settings};
-use git2::{BranchType, Commit, Error, Oid, Reference, Repository};
+use crate::logging::span;
+use crate::print::colors::{to_terminal_color, ACCESSIBLE_COLORS};
+use crate::print::debug::layout_violations;
+use crate::print::format::Emoji;
+use crate::print::unicode::{graph_rows, GraphRows};
+use crate::settings::{
+    BranchOrder, BranchSettings, Bubble, CommitOrder, Limits, LineStyle, MergePatterns, NodeGlyphs,
+    Settings,
+};
+#[cfg(feature = "git2")]
+use crate::source::git::GitSource;
+use crate::source::memory::{MemorySource, MemorySourceDef};
+use crate::source::{
+    CommitData, CommitSource, Oid, OidIndex, OperationState, RefData, TagData, WorktreeStatus,
+    OID_LEN,
+};
+#[cfg(feature = "git2")]
+use git2::Repository;
 use itertools::Itertools;
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use serde_derive::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::Arc;
 
 const ORIGIN: &str = "origin/";
 const FORK: &str = "fork/";
+const HEADS: &str = "refs/heads/";
+const REMOTES: &str = "refs/remotes/";
+const TAGS: &str = "refs/tags/";
+const BISECT: &str = "refs/bisect/";
+/// Minimum length of abbreviated commit hashes
+const MIN_ABBREV: usize = 7;
 
 /// Represents a git history graph.
 pub struct GitGraph {
-    pub repository: Repository,
+    source: Box<dyn CommitSource + Send>,
     pub commits: Vec<CommitInfo>,
     /// Mapping from commit id to index in `commits`
     pub indices: HashMap<Oid, usize>,
@@ -24,77 +48,149 @@ pub struct GitGraph {
     pub tags: Vec<usize>,
     /// The current HEAD
     pub head: HeadInfo,
+    /// Previous positions of HEAD from the reflog, most recent first (`HEAD@{1}`, `HEAD@{2}`, ...)
+    pub head_trail: Vec<Oid>,
+    /// Commits just outside of a selected range, shown to anchor it
+    pub boundary: Vec<Oid>,
+    /// State of a bisect in progress
+    pub bisect: Option<BisectInfo>,
+    /// Operation the repository is in the middle of, like a merge or a rebase
+    pub operation: Option<OperationState>,
+    /// Uncommitted changes, if requested
+    pub worktree: Option<WorktreeStatus>,
+    /// Whether the history was truncated by `max_count`
+    pub truncated: bool,
+    /// Notice about a graph degraded by the settings' limits
+    pub notice: Option<String>,
+    /// Length of abbreviated commit hashes
+    pub abbrev: usize,
+    /// Rendering of emojis in the messages of commits, as returned by `commit`
+    pub emoji: Emoji,
 }
 
 impl GitGraph {
+    #[cfg(feature = "git2")]
     pub fn new(
-        mut repository: Repository,
+        repository: Repository,
         settings: &Settings,
         max_count: Option<usize>,
     ) -> Result<Self, String> {
-        let mut stashes = HashSet::new();
-        repository
-            .stash_foreach(|_, _, oid| {
-                stashes.insert(*oid);
-                true
-            })
-            .map_err(|err| err.message().to_string())?;
-
-        let mut walk = repository
-            .revwalk()
-            .map_err(|err| err.message().to_string())?;
-
-        walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)
-            .map_err(|err| err.message().to_string())?;
+        Self::from_source(GitSource::new(repository), settings, max_count)
+    }
 
-        walk.push_glob("*")
-            .map_err(|err| err.message().to_string())?;
+    /// Builds the graph from any source of commits, like an in-memory `MemorySource`.
+    pub fn from_source<S: CommitSource + Send + 'static>(
+        source: S,
+        settings: &Settings,
+        max_count: Option<usize>,
+    ) -> Result<Self, String> {
+        let mut graph = GitGraph {
+            head: source.head()?,
+            source: Box::new(source),
+            commits: Vec::new(),
+            indices: HashMap::new(),
+            all_branches: Vec::new(),
+            branches: Vec::new(),
+            tags: Vec::new(),
+            head_trail: Vec::new(),
+            boundary: Vec::new(),
+            bisect: None,
+            operation: None,
+            worktree: None,
+            truncated: false,
+            notice: None,
+            abbrev: MIN_ABBREV,
+            emoji: settings.emoji,
+        };
+        graph.reload(settings, max_count)?;
+        Ok(graph)
+    }
 
-        if repository.is_shallow() {
-            return Err("ERROR: git-graph does not support shallow clones due to a missing feature in the underlying libgit2 library.".to_string());
+    /// Re-reads commits and references from the source, and lays out the graph again.
+    pub fn reload(&mut self, settings: &Settings, max_count: Option<usize>) -> Result<(), String> {
+        let source = self.source.as_ref();
+        let head = source.head()?;
+        let bisect = BisectInfo::from_refs(&source.refs()?);
+        let operation = source.operation()?;
+        let worktree = if settings.uncommitted {
+            source.worktree_status()?
+        } else {
+            None
+        };
+        let head_trail = if settings.head_trail > 0 {
+            source.head_reflog(settings.head_trail + 1)?
+        } else {
+            Vec::new()
+        };
+        let boundary = source.boundary()?;
+
+        let limits = &settings.limits;
+        // One more than the limit, to detect exceeding it
+        let count = match (max_count, limits.max_commits) {
+            (Some(max), 0) => Some(max),
+            (Some(max), limit) => Some(max.min(limit + 1)),
+            (None, 0) => None,
+            (None, limit) => Some(limit + 1),
+        };
+        let walk = span("walk");
+        let mut oids = select_commits(source, settings, head.oid, count)?;
+        drop(walk);
+        log::info!("Selected {} commits", oids.len());
+        let mut notice = None;
+        if limits.max_commits > 0 && oids.len() > limits.max_commits {
+            oids.truncate(limits.max_commits);
+            notice = Some(format!(
+                "Showing only the first {} commits (limit 'max_commits')",
+                limits.max_commits
+            ));
         }
-
-        let head = HeadInfo::new(&repository.head().map_err(|err| err.message().to_string())?)?;
-
-        let mut commits = Vec::new();
-        let mut indices = HashMap::new();
-        let mut idx = 0;
-        for oid in walk {
-            if let Some(max) = max_count {
-                if idx >= max {
-                    break;
-                }
-            }
-            if let Ok(oid) = oid {
-                if !stashes.contains(&oid) {
-                    let commit = repository.find_commit(oid).unwrap();
-
-                    commits.push(CommitInfo::new(&commit));
-                    indices.insert(oid, idx);
-                    idx += 1;
+        if max_count.is_some() && !settings.branches.pinned.is_empty() {
+            // Tips of pinned branches cut off by `max_count` come last, which keeps the topological order
+            let mut included: HashSet<_> = oids.iter().cloned().collect();
+            for rf in source.refs()? {
+                if is_pinned(&rf.name, &settings.branches) && included.insert(rf.target) {
+                    oids.push(rf.target);
                 }
             }
         }
 
-        assign_children(&mut commits, &indices);
-
-        let mut all_branches = assign_branches(&repository, &mut commits, &indices, settings)?;
-        correct_fork_merges(&commits, &indices, &mut all_branches, settings)?;
-        assign_sources_targets(&commits, &indices, &mut all_branches);
+        let (mut commits, mut indices, mut all_branches) = lay_out(source, &oids, settings)?;
+        if let Some(limit) = exceeded_limit(&all_branches, limits) {
+            // Degrades to the first-parent history, which needs a single column
+            oids = first_parents(source, &oids, head.oid)?;
+            (commits, indices, all_branches) = lay_out(source, &oids, settings)?;
+            notice = Some(format!(
+                "Showing only the first-parent history of HEAD (limit '{}')",
+                limit
+            ));
+        }
 
-        let (shortest_first, forward) = match settings.branch_order {
-            BranchOrder::ShortestFirst(fwd) => (true, fwd),
-            BranchOrder::LongestFirst(fwd) => (false, fwd),
+        let truncated = (max_count.is_some() || notice.is_some())
+            && commits.iter().any(|info| {
+                !boundary.contains(&info.oid)
+                    && info
+                        .parents
+                        .iter()
+                        .flatten()
+                        .any(|par| !indices.contains_key(par))
+            });
+
+        let abbrev = match settings.abbrev {
+            Some(abbrev) => abbrev,
+            None => source.abbrev()?.unwrap_or_else(|| unique_abbrev(&commits)),
         };
 
-        assign_branch_columns(
-            &commits,
-            &indices,
-            &mut all_branches,
-            &settings.branches,
-            shortest_first,
-            forward,
-        );
+        // Index of each commit among the filtered commits
+        let mut index_map = Vec::with_capacity(commits.len());
+        let mut filtered_count = 0;
+        for info in &commits {
+            if info.branch_trace.is_some() {
+                index_map.push(Some(filtered_count));
+                filtered_count += 1;
+            } else {
+                index_map.push(None);
+            }
+        }
 
         let filtered_commits: Vec<CommitInfo> = commits
             .into_iter()
@@ -107,27 +203,22 @@ impl GitGraph {
             .map(|(idx, info)| (info.oid, idx))
             .collect();
 
-        let index_map: HashMap<usize, Option<&usize>> = indices
-            .iter()
-            .map(|(oid, index)| (*index, filtered_indices.get(oid)))
-            .collect();
-
         for branch in all_branches.iter_mut() {
             if let Some(mut start_idx) = branch.range.0 {
-                let mut idx0 = index_map[&start_idx];
+                let mut idx0 = index_map[start_idx];
                 while idx0.is_none() {
                     start_idx += 1;
-                    idx0 = index_map[&start_idx];
+                    idx0 = index_map[start_idx];
                 }
-                branch.range.0 = Some(*idx0.unwrap());
+                branch.range.0 = idx0;
             }
             if let Some(mut end_idx) = branch.range.1 {
-                let mut idx0 = index_map[&end_idx];
+                let mut idx0 = index_map[end_idx];
                 while idx0.is_none() {
                     end_idx -= 1;
-                    idx0 = index_map[&end_idx];
+                    idx0 = index_map[end_idx];
                 }
-                branch.range.1 = Some(*idx0.unwrap());
+                branch.range.1 = idx0;
             }
         }
 
@@ -155,51 +246,450 @@ impl GitGraph {
             })
             .collect();
 
-        Ok(GitGraph {
-            repository,
-            commits: filtered_commits,
-            indices: filtered_indices,
-            all_branches,
-            branches,
-            tags,
-            head,
+        self.commits = filtered_commits;
+        self.indices = filtered_indices;
+        self.all_branches = all_branches;
+        self.branches = branches;
+        self.tags = tags;
+        self.head = head;
+        self.head_trail = head_trail.into_iter().skip(1).collect();
+        self.boundary = boundary;
+        self.bisect = bisect;
+        self.operation = operation;
+        self.worktree = worktree;
+        self.truncated = truncated;
+        self.notice = notice;
+        self.abbrev = abbrev;
+        self.emoji = settings.emoji;
+        Ok(())
+    }
+
+    /// The source the graph was built from.
+    pub fn source(&self) -> &dyn CommitSource {
+        self.source.as_ref()
+    }
+
+    /// The data of a commit, with emojis in the message rendered according to `emoji`.
+    pub fn commit(&self, id: Oid) -> Result<CommitData, String> {
+        let mut commit = self.source.commit(id)?;
+        if self.emoji != Emoji::Keep {
+            commit.message = self.emoji.apply(&commit.message);
+        }
+        Ok(commit)
+    }
+
+    /// The source the graph was built from, e.g. to build another graph from a selection of its commits.
+    pub fn into_source(self) -> Box<dyn CommitSource + Send> {
+        self.source
+    }
+
+    /// Restores a graph saved with `to_def`, without laying it out again.
+    pub fn from(def: GitGraphDef) -> Self {
+        let indices = def
+            .commits
+            .iter()
+            .enumerate()
+            .map(|(idx, info)| (info.oid, idx))
+            .collect();
+        GitGraph {
+            source: Box::new(MemorySource::from(def.source)),
+            commits: def.commits,
+            indices,
+            all_branches: def.all_branches,
+            branches: def.branches,
+            tags: def.tags,
+            head: def.head,
+            head_trail: def.head_trail,
+            boundary: def.boundary,
+            bisect: def.bisect,
+            operation: def.operation,
+            worktree: def.worktree,
+            truncated: def.truncated,
+            notice: def.notice,
+            // Saved layouts may be edited
+            abbrev: def.abbrev.clamp(4, OID_LEN * 2),
+            emoji: Emoji::Keep,
+        }
+    }
+
+    /// Sets the rendering of emojis in commit messages.
+    pub fn with_emoji(mut self, emoji: Emoji) -> Self {
+        self.emoji = emoji;
+        self
+    }
+
+    /// Copies the layout and the data of all commits in the graph,
+    /// e.g. to save it for rendering without access to the repository.
+    pub fn to_def(&self) -> Result<GitGraphDef, String> {
+        let commits = self
+            .commits
+            .iter()
+            .map(|info| self.source.commit(info.oid))
+            .collect::<Result<_, _>>()?;
+        Ok(GitGraphDef {
+            source: MemorySourceDef {
+                commits,
+                refs: self.source.refs()?,
+                head: self.head.clone(),
+            },
+            commits: self.commits.clone(),
+            all_branches: self.all_branches.clone(),
+            branches: self.branches.clone(),
+            tags: self.tags.clone(),
+            head: self.head.clone(),
+            head_trail: self.head_trail.clone(),
+            boundary: self.boundary.clone(),
+            bisect: self.bisect.clone(),
+            operation: self.operation.clone(),
+            worktree: self.worktree,
+            truncated: self.truncated,
+            notice: self.notice.clone(),
+            abbrev: self.abbrev,
         })
     }
 
-    pub fn take_repository(self) -> Repository {
-        self.repository
+    /// Lists all branches not matched by some of the branching model's rules,
+    /// together with the kinds of rules they are not matched by (`persistence`, `order` or `colors`).
+    pub fn unmatched_branches(&self, settings: &Settings) -> Vec<(String, Vec<&'static str>)> {
+        let model = &settings.branches;
+        self.branches
+            .iter()
+            .map(|idx| &self.all_branches[*idx].name)
+            .filter_map(|name| {
+                let mut unmatched = Vec::new();
+                if branch_order(name, &model.persistence) == model.persistence.len() {
+                    unmatched.push("persistence");
+                }
+                if branch_order(name, &model.order) == model.order.len() {
+                    unmatched.push("order");
+                }
+                let terminal = model.terminal_colors.iter().map(|(regex, _)| regex);
+                let svg = model.svg_colors.iter().map(|(regex, _)| regex);
+                if !terminal.chain(svg).any(|regex| branch_matches(name, regex)) {
+                    unmatched.push("colors");
+                }
+                if unmatched.is_empty() {
+                    None
+                } else {
+                    Some((name.to_string(), unmatched))
+                }
+            })
+            .collect()
+    }
+
+    /// Reflog numbers `n` of `HEAD@{n}` entries in `head_trail` pointing to the given commit.
+    pub fn head_trail_at(&self, oid: Oid) -> Vec<usize> {
+        self.head_trail
+            .iter()
+            .enumerate()
+            .filter(|(_, trail)| **trail == oid)
+            .map(|(idx, _)| idx + 1)
+            .collect()
+    }
+
+    /// Whether the first-parent line of the commit at the given index continues past the end of a truncated history.
+    pub fn continues_past_end(&self, index: usize) -> bool {
+        match self.commits[index].parents[0] {
+            Some(parent) => self.truncated && !self.indices.contains_key(&parent),
+            None => false,
+        }
+    }
+
+    /// The index of the HEAD commit, if uncommitted changes are shown as a virtual commit on top of it.
+    pub fn worktree_parent(&self) -> Option<usize> {
+        self.worktree.and(self.indices.get(&self.head.oid)).cloned()
+    }
+
+    /// Names of the special references of the operation in progress pointing to the given commit,
+    /// like `MERGE_HEAD`.
+    pub fn operation_heads_at(&self, oid: Oid) -> Vec<&str> {
+        self.operation
+            .iter()
+            .flat_map(|operation| operation.heads.iter())
+            .filter(|head| head.target == oid)
+            .map(|head| head.name.as_str())
+            .collect()
+    }
+
+    /// The annotated tag of a tag in `all_branches`. `None` for lightweight tags.
+    pub fn tag_data(&self, tag: &BranchInfo) -> Result<Option<TagData>, String> {
+        self.source.tag(&format!("refs/{}", tag.name))
+    }
+
+    /// The first annotated tag of a commit, with its name like `v1.0`.
+    pub fn annotated_tag(&self, info: &CommitInfo) -> Result<Option<(&str, TagData)>, String> {
+        for tag_idx in &info.tags {
+            let tag = &self.all_branches[*tag_idx];
+            if let Some(data) = self.tag_data(tag)? {
+                return Ok(Some((tag.display_name(), data)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Partitions the commits at those tagged with a release tag matching the pattern, in the order of the graph.
+    /// The commits above the newest release tag form a release without a name.
+    pub fn releases(&self, pattern: &Regex) -> Vec<Release> {
+        let mut releases: Vec<Release> = vec![];
+        for (idx, info) in self.commits.iter().enumerate() {
+            let names = info
+                .tags
+                .iter()
+                .map(|tag| self.all_branches[*tag].display_name())
+                .filter(|name| pattern.is_match(name))
+                .join(", ");
+            if !names.is_empty() || releases.is_empty() {
+                releases.push(Release {
+                    name: if names.is_empty() { None } else { Some(names) },
+                    start: idx,
+                    count: 0,
+                });
+            }
+            if let Some(release) = releases.last_mut() {
+                release.count += 1;
+            }
+        }
+        releases
+    }
+
+    /// Whether a commit merges a more persistent branch into a less persistent one, like develop into a feature
+    /// branch, by the persistence of the branches the commit and its merged parent are traced to.
+    pub fn is_back_merge(&self, info: &CommitInfo) -> bool {
+        let persistence = |oid: Option<Oid>| {
+            let info = &self.commits[*self.indices.get(&oid?)?];
+            Some(self.all_branches[info.branch_trace?].persistence)
+        };
+        match (persistence(Some(info.oid)), persistence(info.parents[1])) {
+            (Some(branch), Some(merged)) => info.is_merge && merged < branch,
+            _ => false,
+        }
+    }
+
+    /// The number of commits traced to a branch, counted within its range.
+    pub fn branch_commit_count(&self, branch_index: usize) -> usize {
+        let (Some(start), end) = self.all_branches[branch_index].range else {
+            return 0;
+        };
+        let end = end.map_or(self.commits.len(), |end| (end + 1).min(self.commits.len()));
+        self.commits[start.min(end)..end]
+            .iter()
+            .filter(|info| info.branch_trace == Some(branch_index))
+            .count()
+    }
+
+    /// Collapses merged branches into bubbles on their merge commits, except for long-lived branches
+    /// (see `BranchSettings::emphasized`). Returns the commits of the remaining branches,
+    /// and the bubbles by merge commit.
+    pub fn feature_bubbles(&self, settings: &Settings) -> (Vec<Oid>, HashMap<Oid, Bubble>) {
+        let collapsed = |branch: &BranchInfo| {
+            !branch.is_tag
+                && usize::from(branch.persistence) >= settings.branches.emphasized
+                && matches!(branch.merge_target, Some(oid) if self.indices.contains_key(&oid))
+        };
+        let mut kept = vec![];
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        for info in &self.commits {
+            match info.branch_trace {
+                Some(trace) if collapsed(&self.all_branches[trace]) => {
+                    *counts.entry(trace).or_default() += 1
+                }
+                _ => kept.push(info.oid),
+            }
+        }
+
+        let mut bubbles: HashMap<Oid, Bubble> = HashMap::new();
+        for (trace, count) in counts.into_iter().sorted() {
+            let branch = &self.all_branches[trace];
+            let label = match count {
+                1 => format!("{} \u{b7} 1 commit", branch.display_name()),
+                n => format!("{} \u{b7} {} commits", branch.display_name(), n),
+            };
+            let merge = branch.merge_target.unwrap();
+            match bubbles.get_mut(&merge) {
+                Some(bubble) => {
+                    bubble.label.push_str(", ");
+                    bubble.label.push_str(&label);
+                }
+                None => {
+                    let bubble = Bubble {
+                        label,
+                        term_color: branch.visual.term_color,
+                        svg_color: branch.visual.svg_color.to_string(),
+                    };
+                    bubbles.insert(merge, bubble);
+                }
+            }
+        }
+        (kept, bubbles)
     }
 
-    pub fn commit(&self, id: Oid) -> Result<Commit, Error> {
-        self.repository.find_commit(id)
+    /// Whether a commit is just outside of the selected range, and only shown to anchor it.
+    pub fn is_boundary(&self, oid: Oid) -> bool {
+        self.boundary.contains(&oid)
+    }
+
+    /// The role of a commit in a bisect in progress, if any.
+    /// During a bisect, HEAD is the current commit to test.
+    pub fn bisect_mark(&self, oid: Oid) -> Option<BisectMark> {
+        let bisect = self.bisect.as_ref()?;
+        if bisect.bad == Some(oid) {
+            Some(BisectMark::Bad)
+        } else if bisect.good.contains(&oid) {
+            Some(BisectMark::Good)
+        } else if bisect.skipped.contains(&oid) {
+            Some(BisectMark::Skip)
+        } else if self.head.oid == oid {
+            Some(BisectMark::Current)
+        } else {
+            None
+        }
+    }
+
+    /// The width of the graph in text-based output, in characters and excluding the commit text.
+    ///
+    /// Available before rendering, e.g. to decide on the layout of embedding applications.
+    pub fn width(&self) -> usize {
+        2 * self
+            .all_branches
+            .iter()
+            .map(|b| b.visual.column.unwrap_or(0))
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+
+    /// Checks the invariants of the layout, and describes each violation by the commits and branches involved:
+    ///
+    /// * every commit is traced to a branch with a column,
+    /// * branches in the same column do not overlap,
+    /// * lines between commits do not pass through commits of other branches.
+    ///
+    /// Returns an empty list if the layout is consistent. Violations are bugs of the layout,
+    /// or caused by histories it does not support.
+    pub fn validate(&self) -> Vec<String> {
+        layout_violations(self)
+    }
+
+    /// Lays out the graph for text-based output, and returns an iterator over its rows.
+    ///
+    /// Rows are converted to cells on demand, so that embedding applications
+    /// don't need to materialize the complete output as strings.
+    pub fn rows(&self, settings: &Settings) -> Result<GraphRows, String> {
+        graph_rows(self, settings)
+    }
+}
+
+/// A laid out graph together with the data of its commits, e.g. for saving it to a file.
+#[derive(Serialize, Deserialize)]
+pub struct GitGraphDef {
+    pub source: MemorySourceDef,
+    pub commits: Vec<CommitInfo>,
+    pub all_branches: Vec<BranchInfo>,
+    pub branches: Vec<usize>,
+    pub tags: Vec<usize>,
+    pub head: HeadInfo,
+    #[serde(default)]
+    pub head_trail: Vec<Oid>,
+    #[serde(default)]
+    pub boundary: Vec<Oid>,
+    #[serde(default)]
+    pub bisect: Option<BisectInfo>,
+    #[serde(default)]
+    pub operation: Option<OperationState>,
+    #[serde(default)]
+    pub worktree: Option<WorktreeStatus>,
+    #[serde(default)]
+    pub truncated: bool,
+    #[serde(default)]
+    pub notice: Option<String>,
+    #[serde(default = "default_abbrev")]
+    pub abbrev: usize,
+}
+
+fn default_abbrev() -> usize {
+    MIN_ABBREV
+}
+
+/// The commits from a release tag down to the next one, as partitioned by `GitGraph::releases`.
+pub struct Release {
+    /// The release tags, comma-separated, or `None` for the commits above the newest release
+    pub name: Option<String>,
+    /// Index of the first commit
+    pub start: usize,
+    /// Number of commits
+    pub count: usize,
+}
+
+/// State of a bisect in progress, read from the references in `refs/bisect/`.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct BisectInfo {
+    /// The commit known to be bad (or new)
+    pub bad: Option<Oid>,
+    /// Commits known to be good (or old)
+    pub good: Vec<Oid>,
+    pub skipped: Vec<Oid>,
+}
+
+impl BisectInfo {
+    /// Reads the bisect state from references. `None` if no bisect is in progress.
+    pub fn from_refs(refs: &[RefData]) -> Option<Self> {
+        let mut bisect = BisectInfo::default();
+        let mut found = false;
+        for rf in refs {
+            let name = match rf.name.strip_prefix(BISECT) {
+                Some(name) => name,
+                None => continue,
+            };
+            found = true;
+            if name == "bad" || name == "new" {
+                bisect.bad = Some(rf.target);
+            } else if name.starts_with("good-") || name.starts_with("old-") {
+                bisect.good.push(rf.target);
+            } else if name.starts_with("skip-") {
+                bisect.skipped.push(rf.target);
+            }
+        }
+        if found {
+            Some(bisect)
+        } else {
+            None
+        }
+    }
+}
+
+/// The role of a commit in a bisect.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BisectMark {
+    Bad,
+    Good,
+    Skip,
+    /// The commit currently tested
+    Current,
+}
+
+impl BisectMark {
+    /// Label for decorations, like `bisect/bad`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            BisectMark::Bad => "bisect/bad",
+            BisectMark::Good => "bisect/good",
+            BisectMark::Skip => "bisect/skip",
+            BisectMark::Current => "bisect/current",
+        }
     }
 }
 
 /// Information about the current HEAD
+#[derive(Clone, Serialize, Deserialize)]
 pub struct HeadInfo {
     pub oid: Oid,
+    /// Name of the checked out branch, or `HEAD` if detached
     pub name: String,
     pub is_branch: bool,
 }
-impl HeadInfo {
-    fn new(head: &Reference) -> Result<Self, String> {
-        let name = head.name().ok_or_else(|| "No name for HEAD".to_string())?;
-        let name = if name == "HEAD" {
-            name.to_string()
-        } else {
-            name[11..].to_string()
-        };
-
-        let h = HeadInfo {
-            oid: head.target().ok_or_else(|| "No id for HEAD".to_string())?,
-            name,
-            is_branch: head.is_branch(),
-        };
-        Ok(h)
-    }
-}
 
 /// Represents a commit.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CommitInfo {
     pub oid: Oid,
     pub is_merge: bool,
@@ -211,11 +701,14 @@ pub struct CommitInfo {
 }
 
 impl CommitInfo {
-    fn new(commit: &Commit) -> Self {
+    fn new(commit: &CommitData) -> Self {
         CommitInfo {
-            oid: commit.id(),
-            is_merge: commit.parent_count() > 1,
-            parents: [commit.parent_id(0).ok(), commit.parent_id(1).ok()],
+            oid: commit.id,
+            is_merge: commit.parents.len() > 1,
+            parents: [
+                commit.parents.first().cloned(),
+                commit.parents.get(1).cloned(),
+            ],
             children: Vec::new(),
             branches: Vec::new(),
             tags: Vec::new(),
@@ -225,25 +718,42 @@ impl CommitInfo {
 }
 
 /// Represents a branch (real or derived from merge summary).
+#[derive(Clone, Serialize, Deserialize)]
 pub struct BranchInfo {
     pub target: Oid,
     pub merge_target: Option<Oid>,
     pub source_branch: Option<usize>,
     pub target_branch: Option<usize>,
-    pub name: String,
+    /// Shared with other branches of the same name, like the repeatedly merged `develop`
+    pub name: Arc<str>,
     pub persistence: u8,
     pub is_remote: bool,
     pub is_merged: bool,
     pub is_tag: bool,
+    /// Commits ahead of and behind the `origin/` counterpart of a local branch (or vice versa),
+    /// if neither is an ancestor of the other
+    #[serde(default)]
+    pub diverged: Option<(usize, usize)>,
+    /// Commits ahead of and behind the configured upstream of a local branch, if requested
+    #[serde(default)]
+    pub tracking: Option<(usize, usize)>,
     pub visual: BranchVis,
     pub range: (Option<usize>, Option<usize>),
 }
 impl BranchInfo {
+    /// The name of a branch, or of a tag without the `tags/` prefix, like `v1.0`.
+    pub fn display_name(&self) -> &str {
+        match self.name.strip_prefix("tags/") {
+            Some(name) if self.is_tag => name,
+            _ => &self.name,
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn new(
         target: Oid,
         merge_target: Option<Oid>,
-        name: String,
+        name: Arc<str>,
         persistence: u8,
         is_remote: bool,
         is_merged: bool,
@@ -261,6 +771,8 @@ impl BranchInfo {
             is_remote,
             is_merged,
             is_tag,
+            diverged: None,
+            tracking: None,
             visual,
             range: (end_index, None),
         }
@@ -268,6 +780,7 @@ impl BranchInfo {
 }
 
 /// Branch properties for visualization.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct BranchVis {
     /// The branch's column group (left to right)
     pub order_group: usize,
@@ -278,13 +791,19 @@ pub struct BranchVis {
     /// The branch's terminal color (index in 256-color palette)
     pub term_color: u8,
     /// SVG color (name or RGB in hex annotation)
-    pub svg_color: String,
+    pub svg_color: Arc<str>,
     /// The column the branch is located in
     pub column: Option<usize>,
+    /// The branch's line style
+    #[serde(default)]
+    pub line_style: LineStyle,
+    /// Glyphs of the branch's nodes in text-based graphs, if not the default ones
+    #[serde(default)]
+    pub node_glyphs: Option<NodeGlyphs>,
 }
 
 impl BranchVis {
-    fn new(order_group: usize, term_color: u8, svg_color: String) -> Self {
+    fn new(order_group: usize, term_color: u8, svg_color: Arc<str>) -> Self {
         BranchVis {
             order_group,
             target_order_group: None,
@@ -292,12 +811,32 @@ impl BranchVis {
             term_color,
             svg_color,
             column: None,
+            line_style: LineStyle::Solid,
+            node_glyphs: None,
         }
     }
 }
 
+/// Finds the shortest length of abbreviated hashes that identifies all commits uniquely.
+fn unique_abbrev(commits: &[CommitInfo]) -> usize {
+    let mut oids: Vec<_> = commits.iter().map(|info| info.oid).collect();
+    oids.sort_unstable();
+    oids.windows(2)
+        .map(|pair| {
+            let (a, b) = (pair[0].as_bytes(), pair[1].as_bytes());
+            let bytes = a.iter().zip(b).take_while(|(a, b)| a == b).count();
+            let nibble = match (a.get(bytes), b.get(bytes)) {
+                (Some(a), Some(b)) if a >> 4 == b >> 4 => 1,
+                _ => 0,
+            };
+            // One more character than the common prefix
+            2 * bytes + nibble + 1
+        })
+        .fold(MIN_ABBREV, usize::max)
+}
+
 /// Walks through the commits and adds each commit's Oid to the children of its parents.
-fn assign_children(commits: &mut [CommitInfo], indices: &HashMap<Oid, usize>) {
+fn assign_children(commits: &mut [CommitInfo], indices: &OidIndex) {
     for idx in 0..commits.len() {
         let (oid, parents) = {
             let info = &commits[idx];
@@ -318,14 +857,14 @@ fn assign_children(commits: &mut [CommitInfo], indices: &HashMap<Oid, usize>) {
 /// * Sort all branches by persistence
 /// * Iterating over all branches in persistence order, trace back over commit parents until a trace is already assigned
 fn assign_branches(
-    repository: &Repository,
+    source: &dyn CommitSource,
     commits: &mut [CommitInfo],
-    indices: &HashMap<Oid, usize>,
+    indices: &OidIndex,
     settings: &Settings,
 ) -> Result<Vec<BranchInfo>, String> {
     let mut branch_idx = 0;
 
-    let mut branches = extract_branches(repository, commits, indices, settings)?;
+    let mut branches = extract_branches(source, commits, indices, settings)?;
 
     let mut index_map: Vec<_> = (0..branches.len())
         .map(|old_idx| {
@@ -341,9 +880,7 @@ fn assign_branches(
                     info.branches.push(old_idx);
                 }
                 let oid = info.oid;
-                let any_assigned =
-                    trace_branch(repository, commits, indices, &mut branches, oid, old_idx)
-                        .unwrap_or(false);
+                let any_assigned = trace_branch(commits, indices, &mut branches, oid, old_idx);
 
                 if any_assigned || !is_merged {
                     branch_idx += 1;
@@ -379,12 +916,12 @@ fn assign_branches(
     for info in commits.iter_mut() {
         if let Some(trace) = info.branch_trace {
             info.branch_trace = index_map[trace];
-            for br in info.branches.iter_mut() {
-                *br = index_map[*br].unwrap();
-            }
-            for tag in info.tags.iter_mut() {
-                *tag = index_map[*tag].unwrap();
-            }
+            info.branches = info
+                .branches
+                .iter()
+                .filter_map(|br| index_map[*br])
+                .collect();
+            info.tags = info.tags.iter().filter_map(|tag| index_map[*tag]).collect();
         }
     }
 
@@ -403,9 +940,61 @@ fn assign_branches(
     Ok(branches)
 }
 
+/// Assigns the commits not reachable from any branch to anonymous branches, like those of unknown merges.
+/// Each branch starts at the topmost commit left, and follows its first-parent line.
+fn assign_unreachable(
+    commits: &mut [CommitInfo],
+    indices: &OidIndex,
+    branches: &mut Vec<BranchInfo>,
+    settings: &Settings,
+) -> Result<(), String> {
+    let name = "unknown";
+    let mut counter = branches.len();
+    for idx in 0..commits.len() {
+        if commits[idx].branch_trace.is_some() {
+            continue;
+        }
+        counter += 1;
+        let term_col = to_terminal_color(
+            &branch_color(
+                name,
+                &settings.branches.terminal_colors[..],
+                &settings.branches.terminal_colors_unknown,
+                counter,
+            )[..],
+        )?;
+        let svg_col = branch_color(
+            name,
+            &settings.branches.svg_colors,
+            &settings.branches.svg_colors_unknown,
+            counter,
+        )
+        .clone();
+        let oid = commits[idx].oid;
+        branches.push(BranchInfo::new(
+            oid,
+            None,
+            Arc::from(name),
+            branch_order(name, &settings.branches.persistence) as u8,
+            false,
+            true,
+            false,
+            BranchVis::new(
+                branch_order(name, &settings.branches.order),
+                term_col,
+                svg_col,
+            ),
+            Some(idx),
+        ));
+        let branch_index = branches.len() - 1;
+        trace_branch(commits, indices, branches, oid, branch_index);
+    }
+    Ok(())
+}
+
 fn correct_fork_merges(
     commits: &[CommitInfo],
-    indices: &HashMap<Oid, usize>,
+    indices: &OidIndex,
     branches: &mut [BranchInfo],
     settings: &Settings,
 ) -> Result<(), String> {
@@ -433,9 +1022,10 @@ fn correct_fork_merges(
                     &settings.branches.svg_colors,
                     &settings.branches.svg_colors_unknown,
                     idx,
-                );
+                )
+                .clone();
 
-                branches[idx].name = format!("{}{}", FORK, branches[idx].name);
+                branches[idx].name = name.into();
                 branches[idx].visual.order_group = pos;
                 branches[idx].visual.term_color = term_col;
                 branches[idx].visual.svg_color = svg_col;
@@ -444,11 +1034,319 @@ fn correct_fork_merges(
     }
     Ok(())
 }
-fn assign_sources_targets(
-    commits: &[CommitInfo],
-    indices: &HashMap<Oid, usize>,
-    branches: &mut [BranchInfo],
-) {
+
+/// Assigns line styles and node glyphs from the branching model.
+fn assign_line_styles(branches: &mut [BranchInfo], settings: &BranchSettings) {
+    for branch in branches.iter_mut() {
+        branch.visual.line_style = settings
+            .line_styles
+            .iter()
+            .find(|(pattern, _)| branch_matches(&branch.name, pattern))
+            .map(|(_, style)| *style)
+            .unwrap_or_default();
+        branch.visual.node_glyphs = settings
+            .node_glyphs
+            .iter()
+            .find(|(pattern, _)| branch_matches(&branch.name, pattern))
+            .map(|(_, glyphs)| *glyphs);
+    }
+}
+
+/// Reads the given commits, and assigns them to branches laid out in columns.
+#[allow(clippy::type_complexity)]
+fn lay_out(
+    source: &dyn CommitSource,
+    oids: &[Oid],
+    settings: &Settings,
+) -> Result<(Vec<CommitInfo>, OidIndex, Vec<BranchInfo>), String> {
+    let reading = span("reading commits");
+    let mut commits = Vec::new();
+    let mut indices = OidIndex::default();
+    for (idx, oid) in oids.iter().enumerate() {
+        commits.push(CommitInfo::new(&source.commit(*oid)?));
+        indices.insert(*oid, idx);
+    }
+    drop(reading);
+
+    let assignment = span("branch assignment");
+    assign_children(&mut commits, &indices);
+
+    let mut all_branches = assign_branches(source, &mut commits, &indices, settings)?;
+    if source.unreachable_commits() {
+        assign_unreachable(&mut commits, &indices, &mut all_branches, settings)?;
+    }
+    if !settings.branches.hidden.is_empty() {
+        let hidden: Vec<_> = all_branches
+            .iter()
+            .map(|branch| is_hidden(&branch.name, &settings.branches))
+            .collect();
+        hide_branches(&mut commits, &mut all_branches, &hidden);
+    }
+    correct_fork_merges(&commits, &indices, &mut all_branches, settings)?;
+    assign_divergence(&commits, &indices, &mut all_branches);
+    if settings.track_info {
+        assign_tracking(source, &mut all_branches)?;
+    }
+    assign_line_styles(&mut all_branches, &settings.branches);
+    if settings.accessible {
+        assign_accessible_colors(&mut all_branches);
+    }
+    assign_sources_targets(&commits, &indices, &mut all_branches);
+    if let Some(name) = &settings.related {
+        let hidden = unrelated_branches(&commits, &indices, &all_branches, name)?;
+        hide_branches(&mut commits, &mut all_branches, &hidden);
+    }
+    if let Some(listed) = source.listed()? {
+        keep_listed(&mut commits, &mut all_branches, &listed);
+    }
+    drop(assignment);
+    log::info!("Assigned commits to {} branches", all_branches.len());
+
+    let _columns = span("column assignment");
+    let (shortest_first, forward) = match settings.branch_order {
+        BranchOrder::ShortestFirst(fwd) => (true, fwd),
+        BranchOrder::LongestFirst(fwd) => (false, fwd),
+    };
+
+    assign_branch_columns(
+        &commits,
+        &indices,
+        &mut all_branches,
+        &settings.branches,
+        shortest_first,
+        forward,
+        settings.group_separators,
+    );
+
+    Ok((commits, indices, all_branches))
+}
+
+/// The name of the first limit for branches or columns exceeded by the laid out branches, if any.
+fn exceeded_limit(all_branches: &[BranchInfo], limits: &Limits) -> Option<&'static str> {
+    let branches = all_branches
+        .iter()
+        .filter(|br| !br.is_merged && !br.is_tag)
+        .count();
+    let columns = all_branches
+        .iter()
+        .filter_map(|br| br.visual.column)
+        .max()
+        .map_or(0, |column| column + 1);
+    if limits.max_branches > 0 && branches > limits.max_branches {
+        Some("max_branches")
+    } else if limits.max_columns > 0 && columns > limits.max_columns {
+        Some("max_columns")
+    } else {
+        None
+    }
+}
+
+/// The commits on the first-parent line of HEAD, or of the first commit if HEAD is not among them.
+/// Expects commits in topological order.
+fn first_parents(source: &dyn CommitSource, oids: &[Oid], head: Oid) -> Result<Vec<Oid>, String> {
+    let mut next = if oids.contains(&head) {
+        Some(head)
+    } else {
+        oids.first().cloned()
+    };
+    let mut commits = Vec::new();
+    for oid in oids {
+        if next == Some(*oid) {
+            commits.push(*oid);
+            next = source.commit(*oid)?.parents.first().cloned();
+        }
+    }
+    Ok(commits)
+}
+
+/// Selects the commits to show, in the order given by the settings.
+fn select_commits(
+    source: &dyn CommitSource,
+    settings: &Settings,
+    head: Oid,
+    max_count: Option<usize>,
+) -> Result<Vec<Oid>, String> {
+    let from_head = max_count.is_some() && settings.count_from_head;
+    let sort = settings.commit_order != CommitOrder::Date || settings.deterministic;
+    if !sort && !from_head {
+        // The source's own order, which lets it stop early
+        return source.commits(max_count);
+    }
+    let mut oids = source.commits(None)?;
+    if sort {
+        if settings.deterministic {
+            // Ties are broken by the given order
+            oids.sort();
+        }
+        oids = sort_commits(source, oids, settings.commit_order)?;
+    }
+    Ok(match max_count {
+        Some(max) if from_head => commits_from_head(source, oids, head, max)?,
+        Some(max) => oids.into_iter().take(max).collect(),
+        None => oids,
+    })
+}
+
+/// Sorts commits with no parents before all of their children, and by the given order otherwise.
+/// Commits with equal times keep their given order.
+fn sort_commits(
+    source: &dyn CommitSource,
+    oids: Vec<Oid>,
+    order: CommitOrder,
+) -> Result<Vec<Oid>, String> {
+    let mut commits = Vec::with_capacity(oids.len());
+    for oid in &oids {
+        commits.push(source.commit(*oid)?);
+    }
+    let indices: OidIndex = oids
+        .iter()
+        .enumerate()
+        .map(|(idx, oid)| (*oid, idx))
+        .collect();
+
+    let mut num_children = vec![0; oids.len()];
+    for commit in &commits {
+        for parent in &commit.parents {
+            if let Some(idx) = indices.get(parent) {
+                num_children[*idx] += 1;
+            }
+        }
+    }
+
+    let time = |idx: usize| match order {
+        CommitOrder::AuthorDate => commits[idx].author.time,
+        _ => commits[idx].committer.time,
+    };
+    // Highest priority first, then newest first, and in the given order for equal times.
+    // For topological order, released parents get increasing priority, so each line is followed first.
+    let mut queue: BinaryHeap<_> = (0..oids.len())
+        .filter(|idx| num_children[*idx] == 0)
+        .map(|idx| (0, time(idx), Reverse(idx)))
+        .collect();
+    let mut priority = 0;
+
+    let mut sorted = Vec::with_capacity(oids.len());
+    while let Some((_, _, Reverse(idx))) = queue.pop() {
+        sorted.push(oids[idx]);
+        // Merged lines come first, like in Git, as the last parent gets the highest priority
+        for parent in &commits[idx].parents {
+            if let Some(&par_idx) = indices.get(parent) {
+                num_children[par_idx] -= 1;
+                if num_children[par_idx] == 0 {
+                    if order == CommitOrder::Topo {
+                        priority += 1;
+                    }
+                    queue.push((priority, time(par_idx), Reverse(par_idx)));
+                }
+            }
+        }
+    }
+    Ok(sorted)
+}
+
+/// The first `max_count` of the given commits that are reachable from HEAD.
+/// Expects commits in topological order.
+fn commits_from_head(
+    source: &dyn CommitSource,
+    oids: Vec<Oid>,
+    head: Oid,
+    max_count: usize,
+) -> Result<Vec<Oid>, String> {
+    let mut reachable = HashSet::from([head]);
+    let mut commits = Vec::new();
+    // Children are visited before their parents
+    for oid in oids {
+        if commits.len() >= max_count {
+            break;
+        }
+        if reachable.contains(&oid) {
+            reachable.extend(source.commit(oid)?.parents);
+            commits.push(oid);
+        }
+    }
+    Ok(commits)
+}
+
+/// Detects local branches that diverged from their `origin/` counterpart, e.g. after a force-push.
+/// Only commits in the graph are considered, so divergence beyond `max_count` is not detected.
+fn assign_divergence(commits: &[CommitInfo], indices: &OidIndex, branches: &mut [BranchInfo]) {
+    let mut remotes = HashMap::new();
+    for (idx, remote) in branches.iter().enumerate().filter(|(_, br)| br.is_remote) {
+        if let Some(name) = remote.name.strip_prefix(ORIGIN) {
+            remotes.entry(name).or_insert(idx);
+        }
+    }
+    let pairs: Vec<(usize, usize)> = branches
+        .iter()
+        .enumerate()
+        .filter(|(_, local)| !local.is_remote && !local.is_merged && !local.is_tag)
+        .filter_map(|(local_idx, local)| {
+            remotes
+                .get(&*local.name)
+                .map(|remote_idx| (local_idx, *remote_idx))
+        })
+        .collect();
+
+    for (local_idx, remote_idx) in pairs {
+        let (local, remote) = match (
+            indices.get(&branches[local_idx].target),
+            indices.get(&branches[remote_idx].target),
+        ) {
+            (Some(local), Some(remote)) => (local, remote),
+            _ => continue,
+        };
+        let local_ancestors = ancestors(commits, indices, *local);
+        let remote_ancestors = ancestors(commits, indices, *remote);
+        if local_ancestors.contains(remote) || remote_ancestors.contains(local) {
+            continue;
+        }
+        let ahead = local_ancestors.difference(&remote_ancestors).count();
+        let behind = remote_ancestors.difference(&local_ancestors).count();
+        branches[local_idx].diverged = Some((ahead, behind));
+        branches[remote_idx].diverged = Some((behind, ahead));
+    }
+}
+
+/// Compares local branches to their configured upstream, like `git status -sb`.
+fn assign_tracking(source: &dyn CommitSource, branches: &mut [BranchInfo]) -> Result<(), String> {
+    for branch in branches
+        .iter_mut()
+        .filter(|branch| !branch.is_remote && !branch.is_merged && !branch.is_tag)
+    {
+        branch.tracking = source.ahead_behind(&branch.name)?;
+    }
+    Ok(())
+}
+
+/// Indices of a commit and all its ancestors.
+fn ancestors(commits: &[CommitInfo], indices: &OidIndex, index: usize) -> HashSet<usize> {
+    let mut result = HashSet::new();
+    let mut stack = vec![index];
+    while let Some(idx) = stack.pop() {
+        if result.insert(idx) {
+            stack.extend(
+                commits[idx]
+                    .parents
+                    .iter()
+                    .flatten()
+                    .filter_map(|oid| indices.get(oid)),
+            );
+        }
+    }
+    result
+}
+
+/// Colors branches by persistence, from a palette for color vision deficiencies.
+fn assign_accessible_colors(branches: &mut [BranchInfo]) {
+    for branch in branches.iter_mut() {
+        let (term_color, svg_color) =
+            ACCESSIBLE_COLORS[branch.persistence as usize % ACCESSIBLE_COLORS.len()];
+        branch.visual.term_color = term_color;
+        branch.visual.svg_color = svg_color.into();
+    }
+}
+
+fn assign_sources_targets(commits: &[CommitInfo], indices: &OidIndex, branches: &mut [BranchInfo]) {
     for idx in 0..branches.len() {
         let target_branch_idx = branches[idx]
             .merge_target
@@ -507,190 +1405,180 @@ fn assign_sources_targets(
 
 /// Extracts (real or derived from merge summary) and assigns basic properties.
 fn extract_branches(
-    repository: &Repository,
+    source: &dyn CommitSource,
     commits: &[CommitInfo],
-    indices: &HashMap<Oid, usize>,
+    indices: &OidIndex,
     settings: &Settings,
 ) -> Result<Vec<BranchInfo>, String> {
-    let filter = if settings.include_remote {
-        None
-    } else {
-        Some(BranchType::Local)
-    };
-    let actual_branches = repository
-        .branches(filter)
-        .map_err(|err| err.message().to_string())?
-        .collect::<Result<Vec<_>, Error>>()
-        .map_err(|err| err.message().to_string())?;
+    let refs = source.refs()?;
 
     let mut counter = 0;
+    let mut names = HashSet::new();
 
-    let mut valid_branches = actual_branches
+    let mut valid_branches = refs
         .iter()
-        .filter_map(|(br, tp)| {
-            br.get().name().and_then(|n| {
-                br.get().target().map(|t| {
-                    counter += 1;
-                    let start_index = match tp {
-                        BranchType::Local => 11,
-                        BranchType::Remote => 13,
-                    };
-                    let name = &n[start_index..];
-                    let end_index = indices.get(&t).cloned();
-
-                    let term_color = match to_terminal_color(
-                        &branch_color(
-                            name,
-                            &settings.branches.terminal_colors[..],
-                            &settings.branches.terminal_colors_unknown,
-                            counter,
-                        )[..],
-                    ) {
-                        Ok(col) => col,
-                        Err(err) => return Err(err),
-                    };
+        .filter_map(|rf| {
+            let is_remote = rf.name.starts_with(REMOTES);
+            if rf.name.starts_with(HEADS)
+                || (is_remote
+                    && (settings.include_remote || is_pinned(&rf.name, &settings.branches)))
+            {
+                Some((rf, is_remote))
+            } else {
+                None
+            }
+        })
+        .map(|(rf, is_remote)| {
+            counter += 1;
+            let t = rf.target;
+            let name = if is_remote {
+                &rf.name[REMOTES.len()..]
+            } else {
+                &rf.name[HEADS.len()..]
+            };
+            let end_index = indices.get(&t).cloned();
 
-                    Ok(BranchInfo::new(
-                        t,
-                        None,
-                        name.to_string(),
-                        branch_order(name, &settings.branches.persistence) as u8,
-                        &BranchType::Remote == tp,
-                        false,
-                        false,
-                        BranchVis::new(
-                            branch_order(name, &settings.branches.order),
-                            term_color,
-                            branch_color(
-                                name,
-                                &settings.branches.svg_colors,
-                                &settings.branches.svg_colors_unknown,
-                                counter,
-                            ),
-                        ),
-                        end_index,
-                    ))
-                })
-            })
+            let term_color = match to_terminal_color(
+                &branch_color(
+                    name,
+                    &settings.branches.terminal_colors[..],
+                    &settings.branches.terminal_colors_unknown,
+                    counter,
+                )[..],
+            ) {
+                Ok(col) => col,
+                Err(err) => return Err(err),
+            };
+
+            Ok(BranchInfo::new(
+                t,
+                None,
+                intern(&mut names, name),
+                branch_order(name, &settings.branches.persistence) as u8,
+                is_remote,
+                false,
+                false,
+                BranchVis::new(
+                    branch_order(name, &settings.branches.order),
+                    term_color,
+                    branch_color(
+                        name,
+                        &settings.branches.svg_colors,
+                        &settings.branches.svg_colors_unknown,
+                        counter,
+                    )
+                    .clone(),
+                ),
+                end_index,
+            ))
         })
         .collect::<Result<Vec<_>, String>>()?;
 
     for (idx, info) in commits.iter().enumerate() {
-        let commit = repository
-            .find_commit(info.oid)
-            .map_err(|err| err.message().to_string())?;
-        if info.is_merge {
-            if let Some(summary) = commit.summary() {
-                counter += 1;
-
-                let parent_oid = commit
-                    .parent_id(1)
-                    .map_err(|err| err.message().to_string())?;
+        if let (true, Some(parent_oid)) = (info.is_merge, info.parents[1]) {
+            let summary = source.commit(info.oid)?.summary();
+            counter += 1;
 
-                let branch_name = parse_merge_summary(summary, &settings.merge_patterns)
-                    .unwrap_or_else(|| "unknown".to_string());
+            let branch_name = parse_merge_summary(&summary, &settings.merge_patterns)
+                .unwrap_or_else(|| "unknown".to_string());
 
-                let persistence = branch_order(&branch_name, &settings.branches.persistence) as u8;
+            let persistence = branch_order(&branch_name, &settings.branches.persistence) as u8;
 
-                let pos = branch_order(&branch_name, &settings.branches.order);
+            let pos = branch_order(&branch_name, &settings.branches.order);
 
-                let term_col = to_terminal_color(
-                    &branch_color(
-                        &branch_name,
-                        &settings.branches.terminal_colors[..],
-                        &settings.branches.terminal_colors_unknown,
-                        counter,
-                    )[..],
-                )?;
-                let svg_col = branch_color(
+            let term_col = to_terminal_color(
+                &branch_color(
                     &branch_name,
-                    &settings.branches.svg_colors,
-                    &settings.branches.svg_colors_unknown,
+                    &settings.branches.terminal_colors[..],
+                    &settings.branches.terminal_colors_unknown,
                     counter,
-                );
-
-                let branch_info = BranchInfo::new(
-                    parent_oid,
-                    Some(info.oid),
-                    branch_name,
-                    persistence,
-                    false,
-                    true,
-                    false,
-                    BranchVis::new(pos, term_col, svg_col),
-                    Some(idx + 1),
-                );
-                valid_branches.push(branch_info);
-            }
+                )[..],
+            )?;
+            let svg_col = branch_color(
+                &branch_name,
+                &settings.branches.svg_colors,
+                &settings.branches.svg_colors_unknown,
+                counter,
+            )
+            .clone();
+
+            let branch_info = BranchInfo::new(
+                parent_oid,
+                Some(info.oid),
+                intern(&mut names, &branch_name),
+                persistence,
+                false,
+                true,
+                false,
+                BranchVis::new(pos, term_col, svg_col),
+                Some(idx + 1),
+            );
+            valid_branches.push(branch_info);
         }
     }
 
     valid_branches.sort_by_cached_key(|branch| (branch.persistence, !branch.is_merged));
 
-    let mut tags = Vec::new();
-
-    repository
-        .tag_foreach(|oid, name| {
-            tags.push((oid, name.to_vec()));
-            true
-        })
-        .map_err(|err| err.message().to_string())?;
-
-    for (oid, name) in tags {
-        let name = std::str::from_utf8(&name[5..]).map_err(|err| err.to_string())?;
-
-        let target = repository
-            .find_tag(oid)
-            .map(|tag| tag.target_id())
-            .or_else(|_| repository.find_commit(oid).map(|_| oid));
-
-        if let Ok(target_oid) = target {
-            if let Some(target_index) = indices.get(&target_oid) {
-                counter += 1;
-                let term_col = to_terminal_color(
-                    &branch_color(
-                        name,
-                        &settings.branches.terminal_colors[..],
-                        &settings.branches.terminal_colors_unknown,
-                        counter,
-                    )[..],
-                )?;
-                let pos = branch_order(name, &settings.branches.order);
-                let svg_col = branch_color(
+    for rf in refs.iter().filter(|rf| rf.name.starts_with(TAGS)) {
+        let name = &rf.name[5..];
+        let target_oid = rf.target;
+        if let Some(target_index) = indices.get(&target_oid) {
+            counter += 1;
+            let term_col = to_terminal_color(
+                &branch_color(
                     name,
-                    &settings.branches.svg_colors,
-                    &settings.branches.svg_colors_unknown,
+                    &settings.branches.terminal_colors[..],
+                    &settings.branches.terminal_colors_unknown,
                     counter,
-                );
-                let tag_info = BranchInfo::new(
-                    target_oid,
-                    None,
-                    name.to_string(),
-                    settings.branches.persistence.len() as u8 + 1,
-                    false,
-                    false,
-                    true,
-                    BranchVis::new(pos, term_col, svg_col),
-                    Some(*target_index),
-                );
-                valid_branches.push(tag_info);
-            }
+                )[..],
+            )?;
+            let pos = branch_order(name, &settings.branches.order);
+            let svg_col = branch_color(
+                name,
+                &settings.branches.svg_colors,
+                &settings.branches.svg_colors_unknown,
+                counter,
+            )
+            .clone();
+            let tag_info = BranchInfo::new(
+                target_oid,
+                None,
+                intern(&mut names, name),
+                settings.branches.persistence.len() as u8 + 1,
+                false,
+                false,
+                true,
+                BranchVis::new(pos, term_col, svg_col),
+                Some(*target_index),
+            );
+            valid_branches.push(tag_info);
         }
     }
 
     Ok(valid_branches)
 }
 
+/// The shared instance of a name, so that equal names are stored only once.
+fn intern(names: &mut HashSet<Arc<str>>, name: &str) -> Arc<str> {
+    match names.get(name) {
+        Some(name) => name.clone(),
+        None => {
+            let name: Arc<str> = Arc::from(name);
+            names.insert(name.clone());
+            name
+        }
+    }
+}
+
 /// Traces back branches by following 1st commit parent,
 /// until a commit is reached that already has a trace.
 fn trace_branch(
-    repository: &Repository,
     commits: &mut [CommitInfo],
-    indices: &HashMap<Oid, usize>,
+    indices: &OidIndex,
     branches: &mut [BranchInfo],
     oid: Oid,
     branch_index: usize,
-) -> Result<bool, Error> {
+) -> bool {
     let mut curr_oid = oid;
     let mut prev_index: Option<usize> = None;
     let mut start_index: Option<i32> = None;
@@ -698,10 +1586,11 @@ fn trace_branch(
     while let Some(index) = indices.get(&curr_oid) {
         let info = &mut commits[*index];
         if let Some(old_trace) = info.branch_trace {
+            // Shared names and colors, so these are no string copies
             let (old_name, old_term, old_svg, old_range) = {
                 let old_branch = &branches[old_trace];
                 (
-                    &old_branch.name.clone(),
+                    old_branch.name.clone(),
                     old_branch.visual.term_color,
                     old_branch.visual.svg_color.clone(),
                     old_branch.range,
@@ -710,7 +1599,7 @@ fn trace_branch(
             let new_name = &branches[branch_index].name;
             let old_end = old_range.0.unwrap_or(0);
             let new_end = branches[branch_index].range.0.unwrap_or(0);
-            if new_name == old_name && old_end >= new_end {
+            if *new_name == old_name && old_end >= new_end {
                 let old_branch = &mut branches[old_trace];
                 if let Some(old_end) = old_range.1 {
                     if index > &old_end {
@@ -755,15 +1644,14 @@ fn trace_branch(
         info.branch_trace = Some(branch_index);
         any_assigned = true;
 
-        let commit = repository.find_commit(curr_oid)?;
-        match commit.parent_count() {
-            0 => {
+        match info.parents[0] {
+            None => {
                 start_index = Some(*index as i32);
                 break;
             }
-            _ => {
+            Some(parent) => {
                 prev_index = Some(*index);
-                curr_oid = commit.parent_id(0)?;
+                curr_oid = parent;
             }
         }
     }
@@ -783,18 +1671,19 @@ fn trace_branch(
     } else {
         branch.range = (branch.range.0, start_index.map(|si| si as usize));
     }
-    Ok(any_assigned)
+    any_assigned
 }
 
 /// Sorts branches into columns for visualization, that all branches can be
 /// visualizes linearly and without overlaps. Uses Shortest-First scheduling.
 fn assign_branch_columns(
     commits: &[CommitInfo],
-    indices: &HashMap<Oid, usize>,
+    indices: &OidIndex,
     branches: &mut [BranchInfo],
     settings: &BranchSettings,
     shortest_first: bool,
     forward: bool,
+    group_separators: bool,
 ) {
     let mut occupied: Vec<Vec<Vec<(usize, usize)>>> = vec![vec![]; settings.order.len() + 1];
 
@@ -884,49 +1773,168 @@ fn assign_branch_columns(
         group_occ[found].push((start, end));
     }
 
-    let group_offset: Vec<usize> = occupied
-        .iter()
-        .scan(0, |acc, group| {
-            *acc += group.len();
-            Some(*acc)
-        })
-        .collect();
+    // First column of each group, with a blank column in front of groups following other ones for separators
+    let mut group_offset = Vec::with_capacity(occupied.len());
+    let mut next_column = 0;
+    for group in &occupied {
+        if group_separators && next_column > 0 && !group.is_empty() {
+            next_column += 1;
+        }
+        group_offset.push(next_column);
+        next_column += group.len();
+    }
 
     for branch in branches {
         if let Some(column) = branch.visual.column {
-            let offset = if branch.visual.order_group == 0 {
-                0
-            } else {
-                group_offset[branch.visual.order_group - 1]
-            };
-            branch.visual.column = Some(column + offset);
+            branch.visual.column = Some(column + group_offset[branch.visual.order_group]);
+        }
+    }
+}
+
+/// Removes the branches marked as hidden from the layout, together with the commits traced to them.
+/// Their commits are filtered out like those of no branch.
+fn hide_branches(commits: &mut [CommitInfo], branches: &mut [BranchInfo], hidden: &[bool]) {
+    for info in commits.iter_mut() {
+        if matches!(info.branch_trace, Some(trace) if hidden[trace]) {
+            info.branch_trace = None;
+        }
+        info.branches.retain(|branch| !hidden[*branch]);
+        info.tags.retain(|tag| !hidden[*tag]);
+    }
+    for (branch, &hidden) in branches.iter_mut().zip(hidden) {
+        if hidden {
+            branch.range = (None, None);
+        }
+    }
+}
+
+/// Keeps only the listed commits in the layout, the others are filtered out like those of no branch.
+/// Branches only span their listed commits, and tags are removed with their commits.
+fn keep_listed(commits: &mut [CommitInfo], branches: &mut [BranchInfo], listed: &[Oid]) {
+    let listed: HashSet<_> = listed.iter().collect();
+    let mut ranges = vec![(None, None); branches.len()];
+    for (idx, info) in commits.iter_mut().enumerate() {
+        if !listed.contains(&info.oid) {
+            info.branch_trace = None;
+        } else if let Some(trace) = info.branch_trace {
+            ranges[trace].0.get_or_insert(idx);
+            ranges[trace].1 = Some(idx);
+        }
+    }
+    for (branch, range) in branches.iter_mut().zip(ranges) {
+        if !branch.is_tag {
+            branch.range = range;
+        } else if !listed.contains(&branch.target) {
+            branch.range = (None, None);
+        }
+    }
+}
+
+/// Marks the branches not related to the branch with the given name: related are the branch itself,
+/// the branches it forks from and merges into, those forking from or merging into it,
+/// those pointing to its commits, and the tags of all of these.
+fn unrelated_branches(
+    commits: &[CommitInfo],
+    indices: &OidIndex,
+    branches: &[BranchInfo],
+    name: &str,
+) -> Result<Vec<bool>, String> {
+    let focus = branches
+        .iter()
+        .position(|branch| &*branch.name == name)
+        .ok_or_else(|| format!("Branch '{}' not found", name))?;
+    let mut related: Vec<_> = branches
+        .iter()
+        .map(|branch| branch.source_branch == Some(focus) || branch.target_branch == Some(focus))
+        .collect();
+    related[focus] = true;
+    for idx in [branches[focus].source_branch, branches[focus].target_branch]
+        .into_iter()
+        .flatten()
+    {
+        related[idx] = true;
+    }
+    for info in commits
+        .iter()
+        .filter(|info| info.branch_trace == Some(focus))
+    {
+        for &idx in info.branches.iter().chain(&info.tags) {
+            related[idx] = true;
+        }
+    }
+    // Branches merging into several others, like releases, have only one target branch
+    for info in commits {
+        let parents = info
+            .parents
+            .iter()
+            .flatten()
+            .filter_map(|oid| indices.get(oid));
+        for &par_idx in parents {
+            match (info.branch_trace, commits[par_idx].branch_trace) {
+                (Some(trace), Some(par_trace)) if par_trace == focus => related[trace] = true,
+                (Some(trace), Some(par_trace)) if trace == focus => related[par_trace] = true,
+                _ => {}
+            }
+        }
+    }
+    for info in commits {
+        if matches!(info.branch_trace, Some(trace) if related[trace]) {
+            for &tag in &info.tags {
+                related[tag] = true;
+            }
         }
     }
+    Ok(related.into_iter().map(|related| !related).collect())
+}
+
+/// Checks if a branch matches any of the model's `hidden` patterns.
+fn is_hidden(name: &str, settings: &BranchSettings) -> bool {
+    settings
+        .hidden
+        .iter()
+        .any(|pattern| branch_matches(name, pattern))
+}
+
+/// Checks if a reference is a branch matching any of the model's `pinned` patterns.
+fn is_pinned(ref_name: &str, settings: &BranchSettings) -> bool {
+    let name = match ref_name
+        .strip_prefix(HEADS)
+        .or_else(|| ref_name.strip_prefix(REMOTES))
+    {
+        Some(name) => name,
+        None => return false,
+    };
+    settings
+        .pinned
+        .iter()
+        .any(|pattern| branch_matches(name, pattern))
+}
+
+/// Checks if a branch name matches a pattern, with or without the `origin/` prefix.
+fn branch_matches(name: &str, pattern: &Regex) -> bool {
+    (name.starts_with(ORIGIN) && pattern.is_match(&name[7..])) || pattern.is_match(name)
 }
 
 /// Finds the index for a branch name from a slice of prefixes
 fn branch_order(name: &str, order: &[Regex]) -> usize {
     order
         .iter()
-        .position(|b| (name.starts_with(ORIGIN) && b.is_match(&name[7..])) || b.is_match(name))
+        .position(|b| branch_matches(name, b))
         .unwrap_or(order.len())
 }
 
 /// Finds the svg color for a branch name.
-fn branch_color<T: Clone>(
+fn branch_color<'a, T>(
     name: &str,
-    order: &[(Regex, Vec<T>)],
-    unknown: &[T],
+    order: &'a [(Regex, Vec<T>)],
+    unknown: &'a [T],
     counter: usize,
-) -> T {
-    let color = order
+) -> &'a T {
+    order
         .iter()
-        .find_position(|(b, _)| {
-            (name.starts_with(ORIGIN) && b.is_match(&name[7..])) || b.is_match(name)
-        })
+        .find_position(|(b, _)| branch_matches(name, b))
         .map(|(_pos, col)| &col.1[counter % col.1.len()])
-        .unwrap_or_else(|| &unknown[counter % unknown.len()]);
-    color.clone()
+        .unwrap_or_else(|| &unknown[counter % unknown.len()])
 }
 
 /// Tries to extract the name of a merged-in branch from the merge commit summary.
@@ -943,7 +1951,29 @@ pub fn parse_merge_summary(summary: &str, patterns: &MergePatterns) -> Option<St
 
 #[cfg(test)]
 mod tests {
-    use crate::settings::MergePatterns;
+    use super::GitGraph;
+    use crate::print::unicode::print_unicode;
+    use crate::settings::{
+        BranchSettings, BranchSettingsDef, CommitOrder, Limits, MergePatterns, MergePatternsDef,
+        Settings,
+    };
+    use crate::source::memory::{entry_with_times, MemorySource};
+
+    /// The lines of the text-based graph, with the summaries of the commits.
+    fn render(log: &str, settings: &Settings, max_count: Option<usize>) -> (Vec<String>, GitGraph) {
+        let source = MemorySource::from_git_log(log).unwrap();
+        let graph = GitGraph::from_source(source, settings, max_count).unwrap();
+        let (graph_lines, text_lines, _) = print_unicode(&graph, settings).unwrap();
+        let lines = graph_lines
+            .iter()
+            .zip(text_lines)
+            .map(|(graph_line, text_line)| {
+                let summary = text_line.rsplit(' ').next().unwrap_or_default().to_string();
+                format!("{} {}", graph_line, summary).trim_end().to_string()
+            })
+            .collect();
+        (lines, graph)
+    }
 
     #[test]
     fn parse_merge_summary() {
@@ -981,4 +2011,184 @@ mod tests {
             Some("feature/my-feature".to_string()),
         );
     }
+
+    #[test]
+    fn parse_localized_merge_summary() {
+        let patterns = MergePatterns::from(&MergePatternsDef {
+            locale: vec!["fr".to_string(), "de".to_string()],
+        })
+        .unwrap();
+
+        let git_default = "Merge branch 'feature/my-feature' into dev";
+        let french = "Fusionner la branche 'feature/my-feature' dans 'develop'";
+        let german = "Branch 'feature/my-feature' in 'develop' zusammenführen";
+
+        assert_eq!(
+            super::parse_merge_summary(git_default, &patterns),
+            Some("feature/my-feature".to_string()),
+        );
+        assert_eq!(
+            super::parse_merge_summary(french, &patterns),
+            Some("feature/my-feature".to_string()),
+        );
+        assert_eq!(
+            super::parse_merge_summary(german, &patterns),
+            Some("feature/my-feature".to_string()),
+        );
+        assert!(MergePatterns::from(&MergePatternsDef {
+            locale: vec!["xx".to_string()],
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn pinned_branches() {
+        let log = [
+            entry_with_times(4, &[3], "HEAD -> refs/heads/develop", "c4", (4, 4)),
+            entry_with_times(3, &[2], "", "c3", (3, 3)),
+            entry_with_times(2, &[1], "refs/heads/main", "c2", (2, 2)),
+            entry_with_times(1, &[], "", "c1", (1, 1)),
+        ]
+        .concat();
+        let mut model = BranchSettingsDef::git_flow();
+        model.pinned = vec!["^main$".to_string()];
+        let settings = Settings {
+            branches: BranchSettings::from(model).unwrap(),
+            ..Default::default()
+        };
+        // The tip of main is shown, although cut off by the maximum number of commits
+        let (lines, _) = render(&log, &settings, Some(1));
+        assert_eq!(lines, ["  ● c4", "● ⋮ c2", "⋮   more"]);
+    }
+
+    #[test]
+    fn commit_orders() {
+        // A feature branch with older author times, merged into main
+        let log = [
+            entry_with_times(6, &[4, 5], "HEAD -> refs/heads/main", "c6", (6, 6)),
+            entry_with_times(5, &[3], "refs/heads/feature/a", "c5", (2, 5)),
+            entry_with_times(4, &[2], "", "c4", (4, 4)),
+            entry_with_times(3, &[1], "", "c3", (1, 3)),
+            entry_with_times(2, &[1], "", "c2", (2, 2)),
+            entry_with_times(1, &[], "", "c1", (0, 1)),
+        ]
+        .concat();
+        let expected = [
+            (
+                CommitOrder::Date,
+                [
+                    "○<┐ c6",
+                    "│ ● c5",
+                    "● │ c4",
+                    "│ ● c3",
+                    "● │ c2",
+                    "├─┘",
+                    "●   c1",
+                ],
+            ),
+            (
+                CommitOrder::AuthorDate,
+                [
+                    "○<┐ c6",
+                    "● │ c4",
+                    "│ ● c5",
+                    "● │ c2",
+                    "│ ● c3",
+                    "├─┘",
+                    "●   c1",
+                ],
+            ),
+            (
+                CommitOrder::Topo,
+                [
+                    "○<┐ c6",
+                    "│ ● c5",
+                    "│ ● c3",
+                    "● │ c4",
+                    "● │ c2",
+                    "├─┘",
+                    "●   c1",
+                ],
+            ),
+        ];
+        for (order, lines) in expected {
+            let settings = Settings {
+                commit_order: order,
+                ..Default::default()
+            };
+            assert_eq!(render(&log, &settings, None).0, lines, "{:?}", order);
+        }
+    }
+
+    #[test]
+    fn deterministic_order() {
+        // Commits with equal times, in different orders
+        let main = entry_with_times(4, &[1], "HEAD -> refs/heads/main", "c4", (1, 1));
+        let feature_b = entry_with_times(2, &[1], "refs/heads/feature/b", "c2", (1, 1));
+        let feature_a = entry_with_times(3, &[1], "refs/heads/feature/a", "c3", (1, 1));
+        let initial = entry_with_times(1, &[], "", "c1", (0, 0));
+        let settings = Settings {
+            deterministic: true,
+            ..Default::default()
+        };
+        // Ordered by hash
+        let expected = ["    ● c2", "  ● │ c3", "● │ │ c4", "├─┴─┘", "●     c1"];
+        for log in [
+            [&main, &feature_b, &feature_a, &initial],
+            [&feature_a, &main, &feature_b, &initial],
+        ] {
+            let log: String = log.into_iter().map(String::as_str).collect();
+            assert_eq!(render(&log, &settings, None).0, expected);
+        }
+    }
+
+    #[test]
+    fn limits() {
+        let log = [
+            entry_with_times(5, &[3, 4], "HEAD -> refs/heads/main", "c5", (5, 5)),
+            entry_with_times(4, &[2], "refs/heads/feature/b", "c4", (4, 4)),
+            entry_with_times(3, &[1], "", "c3", (3, 3)),
+            entry_with_times(2, &[1], "refs/heads/feature/a", "c2", (2, 2)),
+            entry_with_times(1, &[], "", "c1", (1, 1)),
+        ]
+        .concat();
+        let settings = Settings {
+            limits: Limits {
+                max_commits: 3,
+                ..Limits::none()
+            },
+            ..Default::default()
+        };
+        let (lines, graph) = render(&log, &settings, None);
+        assert_eq!(lines, ["○<┐ c5", "│ ● c4", "● ⋮ c3", "⋮   more"]);
+        assert_eq!(
+            graph.notice.as_deref(),
+            Some("Showing only the first 3 commits (limit 'max_commits')")
+        );
+
+        let settings = Settings {
+            limits: Limits {
+                max_branches: 1,
+                ..Limits::none()
+            },
+            ..Default::default()
+        };
+        let (lines, graph) = render(&log, &settings, None);
+        assert_eq!(lines, ["○ c5", "● c3", "● c1", "  more"]);
+        assert_eq!(
+            graph.notice.as_deref(),
+            Some("Showing only the first-parent history of HEAD (limit 'max_branches')")
+        );
+    }
+
+    #[test]
+    fn saved_abbrev_is_clamped() {
+        let log = entry_with_times(1, &[], "HEAD -> refs/heads/main", "c1", (1, 1));
+        let (_, graph) = render(&log, &Settings::default(), None);
+        let mut def = graph.to_def().unwrap();
+        def.abbrev = 100;
+        let graph = GitGraph::from(def);
+        assert_eq!(graph.abbrev, 40);
+        assert!(print_unicode(&graph, &Settings::default()).is_ok());
+    }
 }
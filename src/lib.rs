@@ -1,13 +1,32 @@
 //! Command line tool to show clear git graphs arranged for your branching model.
 
+#[cfg(feature = "git2")]
 use git2::Repository;
+#[cfg(feature = "git2")]
 use std::path::Path;
 
 pub mod config;
+#[cfg(feature = "git2")]
+pub mod demo;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod graph;
+pub mod lint;
+pub mod logging;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pager;
 pub mod print;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod serve;
 pub mod settings;
+pub mod source;
+pub mod stats;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod terminal;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
+#[cfg(feature = "git2")]
 pub fn get_repo<P: AsRef<Path>>(path: P) -> Result<Repository, git2::Error> {
     Repository::discover(path)
 }
@@ -0,0 +1,115 @@
+//! Checks of the history against the rules of the branching model, for `git-graph lint`.
+
+use crate::graph::GitGraph;
+use crate::settings::Settings;
+use crate::source::Oid;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// A branch violating a rule of the branching model.
+pub struct Violation {
+    pub branch: String,
+    /// The commit the violation refers to: the commit the branch forks from, or the first merge of the branch
+    pub commit: Oid,
+    pub message: String,
+}
+
+/// Checks the branches with commits in the graph against the rules of the branching model:
+/// the branches they fork from, and the branches they are merged into once merged.
+/// Branches with the same name, like those derived from several merge commits, are checked together.
+pub fn lint(graph: &GitGraph, settings: &Settings) -> Vec<Violation> {
+    // Oldest commit of each branch, and merges into other branches
+    let mut oldest: HashMap<usize, usize> = HashMap::new();
+    for (idx, info) in graph.commits.iter().enumerate() {
+        if let Some(trace) = info.branch_trace {
+            oldest.insert(trace, idx);
+        }
+    }
+    let mut merges: HashMap<&str, Vec<(&str, Oid)>> = HashMap::new();
+    for info in graph.commits.iter().rev().filter(|info| info.is_merge) {
+        let source = info.parents[1]
+            .and_then(|oid| graph.indices.get(&oid))
+            .and_then(|idx| graph.commits[*idx].branch_trace);
+        if let (Some(source), Some(target)) = (source, info.branch_trace) {
+            merges
+                .entry(&graph.all_branches[source].name)
+                .or_default()
+                .push((&graph.all_branches[target].name, info.oid));
+        }
+    }
+
+    let mut violations = vec![];
+    let mut checked = vec![];
+    for (branch_idx, branch) in graph.all_branches.iter().enumerate() {
+        if branch.is_tag || branch.is_remote || !oldest.contains_key(&branch_idx) {
+            continue;
+        }
+        let name = &branch.name[..];
+        for rule in settings
+            .branches
+            .lint
+            .iter()
+            .filter(|rule| rule.branches.is_match(name))
+        {
+            if let (Some(pattern), Some(source)) = (&rule.forks_from, branch.source_branch) {
+                let source_name = &graph.all_branches[source].name;
+                let fork = graph.commits[oldest[&branch_idx]].parents[0];
+                if let (false, Some(fork)) = (pattern.is_match(source_name), fork) {
+                    violations.push(Violation {
+                        branch: name.to_string(),
+                        commit: fork,
+                        message: format!(
+                            "forks from {}, not from a branch matching '{}'",
+                            source_name, pattern
+                        ),
+                    });
+                }
+            }
+            if checked.contains(&name) {
+                continue;
+            }
+            let Some(merges) = merges.get(name) else {
+                continue;
+            };
+            for pattern in &rule.merges_into {
+                if !merges.iter().any(|(target, _)| pattern.is_match(target)) {
+                    let targets: Vec<_> = merges.iter().map(|(target, _)| *target).collect();
+                    violations.push(Violation {
+                        branch: name.to_string(),
+                        commit: merges[0].1,
+                        message: format!(
+                            "merged into {}, but not into a branch matching '{}'",
+                            targets.join(", "),
+                            pattern
+                        ),
+                    });
+                }
+            }
+        }
+        checked.push(name);
+    }
+    violations
+}
+
+/// Creates a list of violations, with the branch, the abbreviated hash of the commit and the message.
+pub fn print_violations(graph: &GitGraph, violations: &[Violation]) -> String {
+    let name_width = violations
+        .iter()
+        .map(|violation| violation.branch.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    for violation in violations {
+        let hash = violation.commit.to_string();
+        writeln!(
+            out,
+            "{:<width$}  {}  {}",
+            violation.branch,
+            &hash[..graph.abbrev.min(hash.len())],
+            violation.message,
+            width = name_width
+        )
+        .unwrap();
+    }
+    out
+}
@@ -0,0 +1,95 @@
+//! Logging to stderr by verbosity level, and timing of the stages of building and printing graphs.
+
+use log::{LevelFilter, Log, Metadata, Record};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+/// Logger writing messages to stderr, prefixed by their level.
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}: {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// Whether spans record their duration, for `timings`
+static RECORD: AtomicBool = AtomicBool::new(false);
+/// Durations of the spans since recording started, in milliseconds
+static TIMINGS: Mutex<Vec<(&'static str, f64)>> = Mutex::new(Vec::new());
+
+/// Logs to stderr: warnings for verbosity 0, info for 1 (`-v`), debug for 2 (`-vv`) and trace for more.
+pub fn init(verbosity: u8) -> Result<(), String> {
+    log::set_logger(&LOGGER).map_err(|err| err.to_string())?;
+    log::set_max_level(match verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    });
+    Ok(())
+}
+
+/// Starts recording the duration of spans, independent of the log level.
+pub fn record_timings() {
+    RECORD.store(true, Ordering::Relaxed);
+}
+
+/// The recorded durations of spans in milliseconds, in the order the spans ended.
+/// Clears the record. Durations of spans with the same name, like `render` in the pager, are summed up.
+pub fn timings() -> Vec<(&'static str, f64)> {
+    let mut timings: Vec<(&'static str, f64)> = vec![];
+    for (name, millis) in TIMINGS.lock().unwrap().drain(..) {
+        match timings.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, total)) => *total += millis,
+            None => timings.push((name, millis)),
+        }
+    }
+    timings
+}
+
+/// A stage of building or printing a graph, which logs its duration at level debug when dropped,
+/// like `DEBUG: walk: 12.3 ms`. Created by `span`.
+pub struct Span {
+    name: &'static str,
+    #[cfg(not(target_arch = "wasm32"))]
+    start: Instant,
+}
+
+/// Starts timing a stage, until the returned span is dropped.
+pub fn span(name: &'static str) -> Span {
+    Span {
+        name,
+        #[cfg(not(target_arch = "wasm32"))]
+        start: Instant::now(),
+    }
+}
+
+impl Drop for Span {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn drop(&mut self) {
+        let millis = self.start.elapsed().as_secs_f64() * 1000.0;
+        log::debug!("{}: {:.1} ms", self.name, millis);
+        if RECORD.load(Ordering::Relaxed) {
+            TIMINGS.lock().unwrap().push((self.name, millis));
+        }
+    }
+
+    // Time is not available in WebAssembly
+    #[cfg(target_arch = "wasm32")]
+    fn drop(&mut self) {
+        log::debug!("{}", self.name);
+    }
+}
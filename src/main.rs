@@ -1,25 +1,48 @@
-use clap::{crate_version, Arg, Command};
-use crossterm::cursor::MoveToColumn;
-use crossterm::event::{Event, KeyCode, KeyModifiers};
-use crossterm::style::Print;
-use crossterm::terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType};
-use crossterm::{ErrorKind, ExecutableCommand};
-use git2::Repository;
+use clap::{crate_version, Arg, ArgAction, ArgMatches, Command};
 use git_graph::config::{
-    create_config, get_available_models, get_model, get_model_name, set_model,
+    create_config, get_available_models, get_format_indents, get_formats, get_model,
+    get_model_name, get_user_config, set_model, REPO_CONFIG_FILE,
 };
-use git_graph::get_repo;
-use git_graph::graph::GitGraph;
-use git_graph::print::format::CommitFormat;
+#[cfg(feature = "git2")]
+use git_graph::demo::{create_demo_repository, DemoPattern};
+use git_graph::graph::{GitGraph, GitGraphDef};
+use git_graph::lint::{lint, print_violations};
+use git_graph::logging;
+use git_graph::pager::{KeyBindings, Pager, PagerContent, RowMap};
+use git_graph::print::changelog::print_changelog;
+use git_graph::print::debug::print_debug_dump;
+use git_graph::print::diagram::{print_diagram, DiagramFormat};
+use git_graph::print::format::{CommitFormat, DateTimeZone, Emoji, REBASED_MARKER};
+use git_graph::print::porcelain::{print_porcelain, PorcelainVersion};
 use git_graph::print::svg::print_svg;
-use git_graph::print::unicode::print_unicode;
-use git_graph::settings::{BranchOrder, BranchSettings, Characters, MergePatterns, Settings};
+use git_graph::print::unicode::{
+    format_commit_at, lane_legend, print_unicode, print_unicode_prefix,
+};
+use git_graph::serve::{serve_http, Server};
+use git_graph::settings::{
+    ArrowGlyphs, Arrows, BackMerges, BranchSettings, BranchSettingsDef, Characters, CommitOrder,
+    Decorations, Header, Indent, Limits, LineNumbers, MergePatterns, NodeGlyphs, Settings, Stripes,
+    Wrapping, DENSITY_AGGRESSIVE, DENSITY_COMPACT, DENSITY_SPARSE,
+};
+use git_graph::source::anonymize::AnonymizedSource;
+use git_graph::source::range::RangeSource;
+use git_graph::source::selection::SelectionSource;
+use git_graph::source::{open_repository, Backend, CommitSource, Oid};
+use git_graph::stats::{branch_stats, print_stats};
+use git_graph::terminal::{self, Capabilities};
+use itertools::Itertools;
 use platform_dirs::AppDirs;
-use std::io::stdout;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::Path;
 use std::str::FromStr;
 use std::time::Instant;
+use textwrap::core::display_width;
 
-const REPO_CONFIG_FILE: &str = "git-graph.toml";
+const USER_CONFIG_FILE: &str = "config.toml";
+
+#[cfg(not(any(feature = "git2", feature = "gix")))]
+compile_error!("git-graph requires at least one of the features `git2` and `gix`");
 
 fn main() {
     std::process::exit(match from_args() {
@@ -33,7 +56,7 @@ fn main() {
 
 fn from_args() -> Result<(), String> {
     let app_dir = AppDirs::new(Some("git-graph"), false).unwrap().config_dir;
-    let mut models_dir = app_dir;
+    let mut models_dir = app_dir.clone();
     models_dir.push("models");
 
     create_config(&models_dir)?;
@@ -50,7 +73,8 @@ fn from_args() -> Result<(), String> {
                  git-graph --model <model>   -> Show graph using a certain <model>\n    \
                  git-graph model --list      -> List available branching models\n    \
                  git-graph model             -> Show repo's current branching models\n    \
-                 git-graph model <model>     -> Permanently set model <model> for this repo",
+                 git-graph model <model>     -> Permanently set model <model> for this repo\n    \
+                 git-graph serve             -> Answer JSON-RPC requests on stdin, e.g. for editors",
         )
         .arg(
             Arg::new("reverse")
@@ -68,26 +92,128 @@ fn from_args() -> Result<(), String> {
                 .required(false)
                 .num_args(1),
         )
+        .arg(
+            Arg::new("from-layout")
+                .long("from-layout")
+                .help("Show a graph saved with subcommand 'export-layout' instead of a repository.\n\
+                       Options for the layout, like --model or --max-count, are ignored.")
+                .value_name("file")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("backend")
+                .long("backend")
+                .help("Library for reading the repository. One of [git2|gix].\n\
+                       Default: git2 (if git-graph was built with it).")
+                .required(false)
+                .num_args(1),
+        )
         .arg(
             Arg::new("max-count")
                 .long("max-count")
                 .short('n')
+                .env("GIT_GRAPH_MAX_COUNT")
                 .help("Maximum number of commits")
                 .required(false)
                 .num_args(1)
                 .value_name("n"),
         )
+        .arg(
+            Arg::new("from-head")
+                .long("from-head")
+                .help("With --max-count, show the most recent commits reachable from HEAD,\n\
+                       like `git log -n`, instead of the first commits of all branches.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .help("Order of commits. One of [date|author-date|topo].\n\
+                       Parents are never shown before their children. 'topo' shows the commits\n\
+                       of a line together, like `git log --topo-order`.\n\
+                       Default: date.")
+                .value_name("order")
+                .required(false)
+                .num_args(1)
+                .conflicts_with("topo-order"),
+        )
+        .arg(
+            Arg::new("deterministic")
+                .long("deterministic")
+                .help("Order commits with identical timestamps by hash, so that the output\n\
+                       is identical across runs, platforms and backends.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("no-limits")
+                .long("no-limits")
+                .help("Ignore the safety limits for huge repositories from section [limits]\n\
+                       of the user configuration.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("topo-order")
+                .long("topo-order")
+                .help("Show the commits of a line together, like `git log --topo-order`.\n\
+                       Same as '--sort topo'.")
+                .required(false)
+                .num_args(0),
+        )
         .arg(
             Arg::new("model")
                 .long("model")
                 .short('m')
-                .help("Branching model. Available presets are [simple|git-flow|none].\n\
+                .env("GIT_GRAPH_MODEL")
+                .help("Branching model. Available presets are [simple|git-flow|gitlab-flow|trunk|none].\n\
                        Default: git-flow. \n\
                        Permanently set the model for a repository with\n\
                          > git-graph model <model>")
                 .required(false)
                 .num_args(1),
         )
+        .arg(
+            Arg::new("persistent")
+                .long("persistent")
+                .help("Treat branches matching the RegEx pattern as more persistent than those of the\n\
+                       branching model, for trying out rules. Can be given multiple times.")
+                .value_name("regex")
+                .required(false)
+                .num_args(1)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("order-first")
+                .long("order-first")
+                .help("Show branches matching the RegEx pattern left of those ordered by the\n\
+                       branching model, for trying out rules. Can be given multiple times.")
+                .value_name("regex")
+                .required(false)
+                .num_args(1)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("hide")
+                .long("hide")
+                .help("Hide branches matching the RegEx pattern, and the commits traced to them.\n\
+                       Commits shared with other branches are kept. Can be given multiple times.")
+                .value_name("regex")
+                .required(false)
+                .num_args(1)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("related")
+                .long("related")
+                .help("Show only the given branch and related branches: those it forks from or merges into,\n\
+                       those forking from or merging into it, and those pointing to its commits.")
+                .value_name("branch")
+                .required(false)
+                .num_args(1),
+        )
         .arg(
             Arg::new("local")
                 .long("local")
@@ -103,11 +229,230 @@ fn from_args() -> Result<(), String> {
                 .required(false)
                 .num_args(0),
         )
+        .arg(
+            Arg::new("svg-time-axis")
+                .long("svg-time-axis")
+                .help("Place commits in SVG output on a vertical time axis, with the given pixels per day.\n\
+                       Commits keep a minimum distance of one row.")
+                .value_name("px-per-day")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("svg-divergence")
+                .long("svg-divergence")
+                .help("Connect local and remote branches that diverged, e.g. after a force-push,\n\
+                       with a dotted line in SVG output, labeled with the commits ahead and behind.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("svg-ids")
+                .long("svg-ids")
+                .help("Add commit hashes to SVG output, as 'id' and 'data-oid' attributes of commits,\n\
+                       and 'data-from' and 'data-to' attributes of lines, for diffing and scripting.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("svg-metadata")
+                .long("svg-metadata")
+                .help("Add a <metadata> element with the parameters of the graph to SVG output,\n\
+                       like the version of git-graph, HEAD and the commit order.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("svg-tooltips")
+                .long("svg-tooltips")
+                .help("Add tooltips to commits and lines in SVG output, with the branch, summary,\n\
+                       author and date of the commit, shown when hovering in SVG viewers.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("back-merges")
+                .long("back-merges")
+                .help("Highlight merges of more persistent branches into less persistent ones, like\n\
+                       develop into a feature branch, by the persistence of the branching model.\n\
+                       'dim' draws their lines faint and dotted, 'label' also appends '[back-merge]'.\n\
+                       One of [dim|label].")
+                .value_name("mode")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("svg-stripes")
+                .long("svg-stripes")
+                .help("Shade the background of every other commit row, or of the commits of every other month,\n\
+                       in SVG and HTML output, for following rows across the graph and the text.\n\
+                       One of [rows|months].")
+                .value_name("stripes")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("track-info")
+                .long("track-info")
+                .help("Show how far local branches are ahead of and behind their upstream,\n\
+                       like '[ahead 2, behind 1]'.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("uncommitted")
+                .long("uncommitted")
+                .help("Show uncommitted changes as a virtual commit on top of HEAD,\n\
+                       with the number of staged and unstaged files.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("bisect")
+                .long("bisect")
+                .help("Show only the commits of the bisect in progress: reachable from the bad commit,\n\
+                       but not from any good one.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("no-walk")
+                .long("no-walk")
+                .help("Show only the given commits, without walking their ancestry, like 'git log --no-walk'.\n\
+                       Revisions are references or (abbreviated) commit hashes. With '-', they are read\n\
+                       from stdin, one per line, e.g. from 'git rev-list'.")
+                .value_name("rev")
+                .required(false)
+                .num_args(1..),
+        )
+        .arg(
+            Arg::new("stdin")
+                .long("stdin")
+                .help("Show the commits read from stdin instead of walking the history, e.g. from 'git rev-list'.\n\
+                       One revision per line. Parents are rewritten to the nearest listed ancestors.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("boundary")
+                .long("boundary")
+                .help("With subcommand release or --bisect, show the commits just outside of the range\n\
+                       as hollow squares, like 'git log --boundary'.")
+                .required(false)
+                .num_args(0),
+        )
         .arg(
             Arg::new("debug")
                 .long("debug")
                 .short('d')
-                .help("Additional debug output and graphics.")
+                .help("Debug graphics, and debug output like '-vv'.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("timing")
+                .long("timing")
+                .help("Print a summary of the time taken by each stage to stderr,\n\
+                       with the number of commits and branches. For benchmarking.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .help("Log progress and timings of the graph's stages to stderr.\n\
+                       Use '-vv' for debug output, like the branches and the duration of each stage.")
+                .action(ArgAction::Count),
+        )
+        .arg(
+            Arg::new("anonymize")
+                .long("anonymize")
+                .help("Replace branch names, authors and commit messages by stable pseudonyms, for sharing\n\
+                       graphs of private repositories. Keeps the topology, hashes, times, and words\n\
+                       branching models rely on, like 'main', 'feature' or 'Merge branch'.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("anonymize-key")
+                .long("anonymize-key")
+                .env("GIT_GRAPH_ANONYMIZE_KEY")
+                .help("With --anonymize, the key of the pseudonyms, for the same pseudonyms in each run.\n\
+                       Default: a random key, changing with each run.")
+                .value_name("key")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("debug-dump")
+                .long("debug-dump")
+                .help("Write the layout decisions to a JSON file, for bug reports: branch traces, ranges,\n\
+                       order groups, columns and inserted rows. Contains branch names, but no commit\n\
+                       hashes, messages or authors.")
+                .value_name("file")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("diagram")
+                .long("diagram")
+                .help("Print the source of a diagram of the graph, with a box per branch containing its commits,\n\
+                       for documentation built with Graphviz, PlantUML or D2.\n\
+                       Argument format: [dot|plantuml|d2].")
+                .value_name("format")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("porcelain")
+                .long("porcelain")
+                .help("Print one tab-separated record per commit for scripts, in a format that is stable\n\
+                       across releases: hash, parents, column, branch, references, subject.\n\
+                       Argument format: [v1]. Default: v1.")
+                .value_name("version")
+                .required(false)
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("v1"),
+        )
+        .arg(
+            Arg::new("null")
+                .short('z')
+                .help("Terminate each field of --porcelain output with NUL instead of\n\
+                       separating fields by tabs and records by newlines.")
+                .required(false)
+                .num_args(0)
+                .requires("porcelain"),
+        )
+        .arg(
+            Arg::new("print-width")
+                .long("print-width")
+                .help("Print the width of the graph in characters (without commit text) and exit.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("warn-unmatched")
+                .long("warn-unmatched")
+                .help("Warn about branches not matched by the branching model's\n\
+                       persistence, order or color patterns.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("verify")
+                .long("verify")
+                .help("Check the layout for overlapping branches and lines through commits, and fail\n\
+                       with a list of the commits and branches involved. For CI and bug reports.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("no-use-mailmap")
+                .long("no-use-mailmap")
+                .help("Show authors and committers as recorded, without applying the repository's mailmap.\n\
+                       (The mailmap is only applied with backend git2.)")
                 .required(false)
                 .num_args(0),
         )
@@ -116,10 +461,125 @@ fn from_args() -> Result<(), String> {
                 .long("sparse")
                 .short('S')
                 .help("Print a less compact graph: merge lines point to target lines\n\
-                       rather than merge commits.")
+                       rather than merge commits. Same as '--density 0'.")
+                .required(false)
+                .num_args(0)
+                .conflicts_with("density"),
+        )
+        .arg(
+            Arg::new("density")
+                .long("density")
+                .help("Density of the graph. One of [0|1|2]. Default: 1.\n  \
+                         0: sparse, merge lines point to target lines rather than merge commits\n  \
+                         1: compact, merge lines start in the row of the merge commit\n  \
+                         2: aggressive, lines to parents in other columns also start\n     \
+                            in the commit's row where they cross nothing")
+                .value_name("level")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("accessible")
+                .long("accessible")
+                .help("Color-blind friendly output: use a palette suited for color vision\n\
+                       deficiencies, and distinct commit node shapes per branch type.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("header")
+                .long("header")
+                .help("Print a line describing the graph above it: the repository, the branching model,\n\
+                       options restricting the commits and branches, and the number of commits.\n\
+                       A title block in SVG output, for self-describing exported graphs.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("emphasis")
+                .long("emphasis")
+                .help("Emphasize long-lived branches, like main and develop in git-flow:\n\
+                       heavy lines in text-based graphs, thicker strokes in SVG output.\n\
+                       The branches are defined by the branching model.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("group-separators")
+                .long("group-separators")
+                .help("Separate the groups of branches ordered by the branching model, like main | release |\n\
+                       develop | features in git-flow, with faint dotted lines in text-based graphs.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("by-release")
+                .long("by-release")
+                .help("Group the commits by release: draw a band labeled with the release tag and\n\
+                       the number of commits above each commit tagged with a release tag.\n\
+                       Argument: glob pattern of release tags. Default: v*.")
+                .value_name("pattern")
+                .required(false)
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("v*"),
+        )
+        .arg(
+            Arg::new("collapse-releases")
+                .long("collapse-releases")
+                .help("With --by-release, show only the tagged commit of each release,\n\
+                       and the newest commit for the commits after the last release.")
+                .required(false)
+                .num_args(0)
+                .requires("by-release"),
+        )
+        .arg(
+            Arg::new("feature-bubbles")
+                .long("feature-bubbles")
+                .help("Collapse merged branches into bubbles on their merge commits, labeled with\n\
+                       the branch and its number of commits. Only long-lived branches of the\n\
+                       branching model, and branches not merged yet, are shown as lanes.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("lane-legend")
+                .long("lane-legend")
+                .help("After the graph, print the index of each column and the branches occupying it,\n\
+                       to decode lanes whose branch tips are out of view.")
                 .required(false)
                 .num_args(0),
         )
+        .arg(
+            Arg::new("row-spacing")
+                .long("row-spacing")
+                .help("Number of blank rows between commits. Default: 0.")
+                .value_name("n")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("line-numbers")
+                .long("line-numbers")
+                .help("Prefix the rows of commits with numbers for referring to them, which the pager's\n\
+                       go-to prompt accepts as well. One of [index|row]. Default: index.\n  \
+                         index: index of the commit, 0-based from the top\n  \
+                         row: row number, 1-based like the lines of the pager")
+                .value_name("numbers")
+                .required(false)
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("index"),
+        )
+        .arg(
+            Arg::new("head-trail")
+                .long("head-trail")
+                .help("Mark the last <n> positions of HEAD from the reflog, like '@{1}' for HEAD@{1},\n\
+                       to show recent checkouts and rebases.")
+                .value_name("n")
+                .required(false)
+                .num_args(1),
+        )
         .arg(
             Arg::new("color")
                 .long("color")
@@ -144,12 +604,47 @@ fn from_args() -> Result<(), String> {
                 .required(false)
                 .num_args(0),
         )
+        .arg(
+            Arg::new("copy")
+                .long("copy")
+                .help("Copy the hash of HEAD to the clipboard, or the output for the picked commits\n\
+                       with --pick-format or --pick-multi. In the pager, 'y' copies the selected commit.")
+                .required(false)
+                .num_args(0),
+        )
         .arg(
             Arg::new("style")
                 .long("style")
+                .env("GIT_GRAPH_STYLE")
                 .short('s')
-                .help("Output style. One of [normal/thin|round|bold|double|ascii].\n  \
-                         (First character can be used as abbreviation, e.g. '-s r')")
+                .help("Output style. One of [normal/thin|round|bold|double|ascii|auto].\n  \
+                         (First character can be used as abbreviation, e.g. '-s r')\n  \
+                         Default: 'auto', which uses ascii if the terminal can't display\n  \
+                         box-drawing characters, or the style from the user configuration.")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("nodes")
+                .long("nodes")
+                .help("Glyphs of commit nodes, like '◉◎' for commits and merges, or '*' for both.\n  \
+                         Default: those of the style, or the glyphs from the user configuration.")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("arrows")
+                .long("arrows")
+                .help("Edges drawn with arrows: [none|merges|all]. 'all' includes branches forking off.\n  \
+                         Default: 'merges', or the setting from the user configuration.")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("arrow-glyphs")
+                .long("arrow-glyphs")
+                .help("Glyphs of arrows pointing left and right, like '◂▸'.\n  \
+                         Default: those of the style, or the glyphs from the user configuration.")
                 .required(false)
                 .num_args(1),
         )
@@ -159,6 +654,7 @@ fn from_args() -> Result<(), String> {
                 .short('w')
                 .help("Line wrapping for formatted commit text. Default: 'auto 0 8'\n\
                        Argument format: [<width>|auto|none[ <indent1>[ <indent2>]]]\n\
+                       Indents are numbers of spaces, or the text to indent with.\n\
                        For examples, consult 'git-graph --help'")
                 .long_help("Line wrapping for formatted commit text. Default: 'auto 0 8'\n\
                        Argument format: [<width>|auto|none[ <indent1>[ <indent2>]]]\n\
@@ -167,15 +663,41 @@ fn from_args() -> Result<(), String> {
                            git-graph --wrap auto 0 8\n    \
                            git-graph --wrap none\n    \
                            git-graph --wrap 80\n    \
-                           git-graph --wrap 80 0 8\n\
-                       'auto' uses the terminal's width if on a terminal.")
+                           git-graph --wrap 80 0 8\n    \
+                           git-graph --wrap auto 0 \"  ↳ \"\n\
+                       'auto' uses the terminal's width if on a terminal.\n\
+                       Indents are numbers of spaces, or the text to indent with.\n\
+                       Hanging indents of the lines after a commit's first line are set per format\n\
+                       in section [format_indents] of the configuration.")
                 .required(false)
                 .num_args(0..=3),
         )
+        .arg(
+            Arg::new("pick-format")
+                .long("pick-format")
+                .help("Pick a commit interactively and print it in this format, like \"%H\" or \"%h %s\".\n\
+                       Select a commit by clicking or with 'g', and pick it with 'p' or a double-click.\n\
+                       The graph is shown on stderr, so that the output can be used in commands like\n\
+                       'git rebase -i $(git-graph --pick-format %H)'. Accepts the same formats as --format.")
+                .value_name("format")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("pick-multi")
+                .long("pick-multi")
+                .help("Pick multiple commits interactively and print them oldest first.\n\
+                       Mark commits with space, move with up and down, and pick them with 'p'.\n\
+                       Prints the full hash, or the format given by --pick-format, e.g. for\n\
+                       'git cherry-pick $(git-graph --pick-multi)'.")
+                .required(false)
+                .num_args(0),
+        )
         .arg(
             Arg::new("format")
                 .long("format")
                 .short('f')
+                .env("GIT_GRAPH_FORMAT")
                 .help("Commit format. One of [oneline|short|medium|full|\"<string>\"].\n  \
                          (First character can be used as abbreviation, e.g. '-f m')\n\
                        Default: oneline.\n\
@@ -186,10 +708,15 @@ fn from_args() -> Result<(), String> {
                                 %n    newline\n    \
                                 %H    commit hash\n    \
                                 %h    abbreviated commit hash\n    \
+                                %T    tree hash\n    \
+                                %t    abbreviated tree hash\n    \
                                 %P    parent commit hashes\n    \
                                 %p    abbreviated parent commit hashes\n    \
                                 %d    refs (branches, tags)\n    \
+                                %D    refs like 'git log', without parentheses\n    \
+                                %e    encoding of the message, if not UTF-8\n    \
                                 %s    commit summary\n    \
+                                %f    sanitized summary, suitable for file names\n    \
                                 %b    commit message body\n    \
                                 %B    raw body (subject and body)\n    \
                                 %an   author name\n    \
@@ -200,6 +727,9 @@ fn from_args() -> Result<(), String> {
                                 %ce   committer email\n    \
                                 %cd   committer date\n    \
                                 %cs   committer date in short format 'YYYY-MM-DD'\n    \
+                                %(trailers[:<options>])\n          \
+                                      trailers like 'Signed-off-by', one per line. Options:\n          \
+                                      key=<key>, valueonly, separator=<sep>\n    \
                                 \n    \
                                 If you add a + (plus sign) after % of a placeholder,\n       \
                                    a line-feed is inserted immediately before the expansion if\n       \
@@ -215,11 +745,80 @@ fn from_args() -> Result<(), String> {
                 .required(false)
                 .num_args(1),
         )
+        .arg(
+            Arg::new("date-timezone")
+                .long("date-timezone")
+                .help("Time zone for displaying dates. One of [local|utc|commit].\n\
+                       Default: commit (the time zone recorded in the commit, like Git).")
+                .value_name("zone")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("emoji")
+                .long("emoji")
+                .help("Rendering of emojis and gitmoji shortcodes like :sparkles: in commit messages.\n\
+                       One of [keep|strip|replace]. 'strip' removes them, 'replace' shows shortcodes as emojis.\n\
+                       Default: keep.")
+                .value_name("mode")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("decorations")
+                .long("decorations")
+                .help("Template of the references of commits, with placeholders {branches} and {tags}.\n\
+                       Words with the placeholder of an empty list are omitted, like in 'refs: {branches} {tags}'.\n\
+                       Default: '({branches}) [{tags}]', or the template from the user configuration.")
+                .value_name("template")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("branch-counts")
+                .long("branch-counts")
+                .help("Show the number of commits of each branch after its name, like 'main [12]',\n\
+                       and as a pill next to branch tips in SVG output.\n\
+                       Default: off, or the setting from the user configuration.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("tag-marker")
+                .long("tag-marker")
+                .help("Mark annotated tags in the references, like '--tag-marker \"*\"' for [v1.0*].\n\
+                       Default: no marker, or the marker from the user configuration.")
+                .value_name("marker")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("rebased-marker")
+                .long("rebased-marker")
+                .help("Mark commits with a committer other than the author, like commits rebased\n\
+                       or cherry-picked by someone else. Custom formats use placeholder %(rebased).\n\
+                       Argument: the marker. Default: ↻, and no marker without this option,\n\
+                       or the marker from the user configuration.")
+                .value_name("marker")
+                .required(false)
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value(REBASED_MARKER),
+        )
+        .arg(
+            Arg::new("abbrev")
+                .long("abbrev")
+                .help("Length of abbreviated commit hashes.\n\
+                       Default: the repository's setting 'core.abbrev', or the shortest unique length (at least 7).")
+                .value_name("n")
+                .required(false)
+                .num_args(1),
+        )
         .subcommand(Command::new("model")
             .about("Prints or permanently sets the branching model for a repository.")
             .arg(
                 Arg::new("model")
-                    .help("The branching model to be used. Available presets are [simple|git-flow|none].\n\
+                    .help("The branching model to be used. Available presets are [simple|git-flow|gitlab-flow|trunk|none].\n\
                            When not given, prints the currently set model.")
                     .value_name("model")
                     .num_args(1)
@@ -232,10 +831,122 @@ fn from_args() -> Result<(), String> {
                     .help("List all available branching models.")
                     .required(false)
                     .num_args(0),
-        ));
+        ))
+        .subcommand(Command::new("serve")
+            .about("Keeps the repository open and answers JSON-RPC requests on stdin, one per line.\n\
+                    Methods: layout, format, graph, refresh. See the manual for details.")
+            .arg(
+                Arg::new("http")
+                    .long("http")
+                    .help("Serve the graph as a self-updating HTML page via HTTP instead.\n\
+                           Argument format: [<port>|<address>:<port>]\n\
+                           A port alone listens on localhost only, e.g. use '0.0.0.0:8080' to share the graph.")
+                    .value_name("port")
+                    .required(false)
+                    .num_args(1),
+        ))
+        .subcommand(Command::new("demo")
+            .about("Generates a repository with a realistic branching pattern for a model, and shows its graph.")
+            .arg(
+                Arg::new("dir")
+                    .help("The directory to create the repository in. Must not exist, or be empty.")
+                    .value_name("dir")
+                    .num_args(1)
+                    .required(true)
+                    .index(1))
+            .arg(
+                Arg::new("model")
+                    .long("model")
+                    .short('m')
+                    .help("The branching model to generate the history for, and to show it with. Default: git-flow")
+                    .value_name("model")
+                    .required(false)
+                    .num_args(1))
+            .arg(
+                Arg::new("commits")
+                    .long("commits")
+                    .help("The approximate number of commits. Default: 200")
+                    .value_name("n")
+                    .required(false)
+                    .num_args(1)))
+        .subcommand(Command::new("export-layout")
+            .about("Saves the laid out graph and its commits to a file, to show it later with option --from-layout.")
+            .arg(
+                Arg::new("file")
+                    .help("The file to save the layout to, in JSON format.")
+                    .value_name("file")
+                    .num_args(1)
+                    .required(true)
+                    .index(1)))
+        .subcommand(Command::new("compare-models")
+            .about("Shows the graph under two branching models side by side, to compare their layouts.")
+            .arg(
+                Arg::new("model-a")
+                    .help("The branching model shown on the left.")
+                    .value_name("model-a")
+                    .num_args(1)
+                    .required(true)
+                    .index(1))
+            .arg(
+                Arg::new("model-b")
+                    .help("The branching model shown on the right.")
+                    .value_name("model-b")
+                    .num_args(1)
+                    .required(true)
+                    .index(2))
+            .arg(
+                Arg::new("sequential")
+                    .long("sequential")
+                    .help("Show the graphs one after the other, each below the name of its model.")
+                    .required(false)
+                    .num_args(0)))
+        .subcommand(Command::new("stats")
+            .about("Prints metrics for each branch: number of commits and merges, and lifetime.")
+            .arg(
+                Arg::new("json")
+                    .long("json")
+                    .help("Print the metrics in JSON format instead of as a table.")
+                    .required(false)
+                    .num_args(0)))
+        .subcommand(Command::new("lint")
+            .about("Checks the history against the rules of the branching model, like the branches features\n\
+                    fork from, and lists the violations. Fails if there are any."))
+        .subcommand(Command::new("release")
+            .about("Shows only the commits between two tags (or other references), e.g. for release notes.")
+            .arg(
+                Arg::new("from")
+                    .help("Commits reachable from this reference are excluded, like the previous release's tag.")
+                    .value_name("from-tag")
+                    .num_args(1)
+                    .required(true)
+                    .index(1))
+            .arg(
+                Arg::new("to")
+                    .help("Commits reachable from this reference are included, like the new release's tag.")
+                    .value_name("to-tag")
+                    .num_args(1)
+                    .required(true)
+                    .index(2))
+            .arg(
+                Arg::new("changelog")
+                    .long("changelog")
+                    .help("Append a changelog in Markdown format, with a section for each merge.")
+                    .required(false)
+                    .num_args(0)));
 
     let matches = app.get_matches();
 
+    let verbosity = if matches.get_flag("debug") {
+        matches.get_count("verbose").max(2)
+    } else {
+        matches.get_count("verbose")
+    };
+    logging::init(verbosity)?;
+    let timing = matches.get_flag("timing");
+    if timing {
+        logging::record_timings();
+    }
+
     if let Some(matches) = matches.subcommand_matches("model") {
         if matches.get_flag("list") {
             println!(
@@ -246,21 +957,57 @@ fn from_args() -> Result<(), String> {
         }
     }
 
+    let demo = matches.subcommand_matches("demo");
     let dot = ".".to_string();
-    let path = matches.get_one::<String>("path").unwrap_or(&dot);
-    let repository = get_repo(path)
-        .map_err(|err| format!("ERROR: {}\n       Navigate into a repository before running git-graph, or use option --path", err.message()))?;
+    let path = match demo {
+        Some(demo) => {
+            let dir = demo.get_one::<String>("dir").unwrap();
+            create_demo(demo, dir)?;
+            dir
+        }
+        None => matches.get_one::<String>("path").unwrap_or(&dot),
+    };
+    let backend = matches
+        .get_one::<String>("backend")
+        .map(|s| Backend::from_str(s))
+        .unwrap_or_else(|| Ok(Backend::default()))?;
+    let from_layout = matches.get_one::<String>("from-layout");
+    let repository = match from_layout {
+        Some(_) => None,
+        None => Some(open_repository(path, backend, !matches.get_flag("no-use-mailmap"))
+            .map_err(|err| format!("ERROR: {}\n       Navigate into a repository before running git-graph, or use option --path", err))?),
+    };
+    let repository = match repository {
+        Some((source, git_dir)) if matches.get_flag("anonymize") => {
+            let source = AnonymizedSource::new(source);
+            let source = match matches.get_one::<String>("anonymize-key") {
+                Some(key) => source.with_key(key),
+                None => source,
+            };
+            let source: Box<dyn CommitSource + Send> = Box::new(source);
+            Some((source, git_dir))
+        }
+        None if matches.get_flag("anonymize") => {
+            return Err(
+                "Option --anonymize can't be combined with option --from-layout".to_string(),
+            )
+        }
+        repository => repository,
+    };
+    let git_dir = repository.as_ref().map(|(_, git_dir)| git_dir.as_path());
 
     if let Some(matches) = matches.subcommand_matches("model") {
+        let git_dir = git_dir
+            .ok_or("Subcommand model requires a repository, but option --from-layout was given")?;
         match matches.get_one::<String>("model") {
             None => {
-                let curr_model = get_model_name(&repository, REPO_CONFIG_FILE)?;
+                let curr_model = get_model_name(git_dir, REPO_CONFIG_FILE)?;
                 match curr_model {
                     None => print!("No branching model set"),
                     Some(model) => print!("{}", model),
                 }
             }
-            Some(model) => set_model(&repository, model, REPO_CONFIG_FILE, &models_dir)?,
+            Some(model) => set_model(git_dir, model, REPO_CONFIG_FILE, &models_dir)?,
         };
         return Ok(());
     }
@@ -279,48 +1026,229 @@ fn from_args() -> Result<(), String> {
     };
 
     let include_remote = !matches.get_flag("local");
+    let track_info = matches.get_flag("track-info");
+    let uncommitted = matches.get_flag("uncommitted");
+    let count_from_head = matches.get_flag("from-head");
+    let deterministic = matches.get_flag("deterministic");
+    let no_limits = matches.get_flag("no-limits");
+    let commit_order = if matches.get_flag("topo-order") {
+        CommitOrder::Topo
+    } else {
+        matches
+            .get_one::<String>("sort")
+            .map(|s| CommitOrder::from_str(s))
+            .unwrap_or(Ok(CommitOrder::Date))?
+    };
 
     let reverse_commit_order = matches.get_flag("reverse");
 
     let svg = matches.get_flag("svg");
-    let pager = !matches.get_flag("no-pager");
-    let compact = !matches.get_flag("sparse");
+    let pager = !matches.get_flag("no-pager") && pager_enabled_by_env()?;
     let debug = matches.get_flag("debug");
+    let accessible = matches.get_flag("accessible");
+    let emphasis = matches.get_flag("emphasis");
+    let svg_divergence = matches.get_flag("svg-divergence");
+    let pick_multi = matches.get_flag("pick-multi");
+    let copy = matches.get_flag("copy");
+    let pick_format = matches
+        .get_one::<String>("pick-format")
+        .map(|format| &format[..])
+        .or(if pick_multi { Some("%H") } else { None });
+    // Pickers draw on stderr, while stdout is usually captured
+    let capabilities = if pick_format.is_some() {
+        Capabilities::detect_on(atty::Stream::Stderr)
+    } else {
+        Capabilities::detect()
+    };
+    let user_config = get_user_config(&app_dir, USER_CONFIG_FILE)?;
+
     let style = matches
         .get_one::<String>("style")
-        .map(|s| Characters::from_str(s))
-        .unwrap_or_else(|| Ok(Characters::thin()))?;
-
-    let style = if reverse_commit_order {
-        style.reverse()
-    } else {
-        style
+        .or(user_config.style.as_ref())
+        .map(|s| &s[..]);
+    let style = match style {
+        None | Some("auto") if capabilities.unicode => Characters::thin(),
+        None | Some("auto") => Characters::ascii(),
+        Some(str) => Characters::from_str(str)?,
+    };
+    let nodes = match matches.get_one::<String>("nodes") {
+        Some(nodes) => Some(NodeGlyphs::from_str(nodes)?),
+        None => user_config.nodes,
+    };
+    let arrow_glyphs = match matches.get_one::<String>("arrow-glyphs") {
+        Some(glyphs) => Some(ArrowGlyphs::from_str(glyphs)?),
+        None => user_config.arrow_glyphs,
+    };
+    let style = Characters {
+        nodes,
+        arrows: arrow_glyphs,
+        ..style
+    };
+    let arrows = match matches.get_one::<String>("arrows") {
+        Some(arrows) => Arrows::from_str(arrows)?,
+        None => user_config.arrows.unwrap_or_default(),
     };
 
+    let patterns = |name: &str| -> Vec<String> {
+        matches
+            .get_many::<String>(name)
+            .map(|values| values.cloned().collect())
+            .unwrap_or_default()
+    };
+    let (persistent, order_first) = (patterns("persistent"), patterns("order-first"));
+    let hidden = patterns("hide");
     let model = get_model(
-        &repository,
-        matches.get_one::<String>("model").map(|s| &s[..]),
+        git_dir,
+        demo.unwrap_or(&matches)
+            .get_one::<String>("model")
+            .map(|s| &s[..]),
         REPO_CONFIG_FILE,
         &models_dir,
-    )?;
+    )?
+    .with_persistent(&persistent)
+    .with_order_first(&order_first)
+    .with_hidden(&hidden);
+
+    let compared_models = match matches.subcommand_matches("compare-models") {
+        Some(matches) => ["model-a", "model-b"]
+            .iter()
+            .map(|arg| {
+                let name = matches.get_one::<String>(arg).unwrap();
+                get_model(git_dir, Some(name), REPO_CONFIG_FILE, &models_dir).map(|model| {
+                    let model = model
+                        .with_persistent(&persistent)
+                        .with_order_first(&order_first)
+                        .with_hidden(&hidden);
+                    (name.to_string(), model)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        None => vec![],
+    };
 
+    let formats = get_formats(&user_config, git_dir, REPO_CONFIG_FILE)?;
     let format = match matches.get_one::<String>("format") {
         None => CommitFormat::OneLine,
-        Some(str) => CommitFormat::from_str(str)?,
+        Some(str) => CommitFormat::from_str_with(str, &formats)?,
+    };
+
+    let date_timezone = matches
+        .get_one::<String>("date-timezone")
+        .map(|s| DateTimeZone::from_str(s))
+        .unwrap_or(Ok(DateTimeZone::Commit))?;
+    let emoji = matches
+        .get_one::<String>("emoji")
+        .map(|s| Emoji::from_str(s))
+        .unwrap_or(Ok(Emoji::Keep))?;
+    let tag_marker = matches
+        .get_one::<String>("tag-marker")
+        .cloned()
+        .or_else(|| user_config.tag_marker.clone());
+    let decorations = match matches
+        .get_one::<String>("decorations")
+        .or(user_config.decorations.as_ref())
+    {
+        Some(template) => Decorations::from_str(template)?,
+        None => Decorations::default(),
+    }
+    .with_tag_marker(tag_marker)
+    .with_rebased_marker(
+        matches
+            .get_one::<String>("rebased-marker")
+            .cloned()
+            .or_else(|| user_config.rebased_marker.clone()),
+    )
+    .with_branch_counts(matches.get_flag("branch-counts") || user_config.branch_counts)
+    .with_shorten(user_config.shorten_branches.clone());
+
+    let density = match matches.get_one::<String>("density") {
+        None if matches.get_flag("sparse") => DENSITY_SPARSE,
+        None => DENSITY_COMPACT,
+        Some(str) => match str.parse::<u8>() {
+            Ok(val) if val <= DENSITY_AGGRESSIVE => val,
+            _ => {
+                return Err(format![
+                    "Option density must be one of [0|1|2], but got '{}'",
+                    str
+                ])
+            }
+        },
+    };
+
+    let line_numbers = matches
+        .get_one::<String>("line-numbers")
+        .map(|s| LineNumbers::from_str(s))
+        .transpose()?;
+
+    let row_spacing = match matches.get_one::<String>("row-spacing") {
+        None => 0,
+        Some(str) => match str.parse::<usize>() {
+            Ok(val) => val,
+            Err(_) => {
+                return Err(format![
+                    "Option row-spacing must be a positive number, but got '{}'",
+                    str
+                ])
+            }
+        },
+    };
+
+    let head_trail = match matches.get_one::<String>("head-trail") {
+        None => 0,
+        Some(str) => match str.parse::<usize>() {
+            Ok(val) => val,
+            Err(_) => {
+                return Err(format![
+                    "Option head-trail must be a positive number, but got '{}'",
+                    str
+                ])
+            }
+        },
+    };
+
+    let svg_time_axis = match matches.get_one::<String>("svg-time-axis") {
+        None => None,
+        Some(str) => match str.parse::<f32>() {
+            Ok(val) if val > 0.0 => Some(val),
+            _ => {
+                return Err(format![
+                    "Option svg-time-axis must be a positive number, but got '{}'",
+                    str
+                ])
+            }
+        },
+    };
+
+    let back_merges = matches
+        .get_one::<String>("back-merges")
+        .map(|s| BackMerges::from_str(s))
+        .transpose()?;
+
+    let svg_stripes = matches
+        .get_one::<String>("svg-stripes")
+        .map(|s| Stripes::from_str(s))
+        .transpose()?;
+
+    let abbrev = match matches.get_one::<String>("abbrev") {
+        None => None,
+        Some(str) => match str.parse::<usize>() {
+            Ok(val) if (4..=40).contains(&val) => Some(val),
+            _ => {
+                return Err(format![
+                    "Option abbrev must be a number between 4 and 40, but got '{}'",
+                    str
+                ])
+            }
+        },
     };
 
     let colored = if matches.get_flag("no-color") {
         false
     } else if let Some(mode) = matches.get_one::<String>("color") {
         match &mode[..] {
-            "auto" => {
-                atty::is(atty::Stream::Stdout)
-                    && (!cfg!(windows) || yansi::Paint::enable_windows_ascii())
-            }
+            "auto" => capabilities.ansi,
             "always" => {
-                if cfg!(windows) {
-                    yansi::Paint::enable_windows_ascii();
-                }
+                terminal::enable_ansi();
                 true
             }
             "never" => false,
@@ -332,205 +1260,822 @@ fn from_args() -> Result<(), String> {
             }
         }
     } else {
-        atty::is(atty::Stream::Stdout) && (!cfg!(windows) || yansi::Paint::enable_windows_ascii())
+        capabilities.ansi
+    };
+
+    let format_name = matches
+        .get_one::<String>("format")
+        .map_or("oneline", |name| &name[..]);
+    let format_indents = get_format_indents(&user_config, git_dir, REPO_CONFIG_FILE)?;
+    let hanging = format_indents
+        .get(format_name)
+        .or_else(|| {
+            format
+                .preset_name()
+                .and_then(|name| format_indents.get(name))
+        })
+        .map(|indent| indent.text())
+        .unwrap_or_default();
+    let default_wrapping = Wrapping {
+        width: None,
+        indent1: String::new(),
+        indent2: " ".repeat(8),
+        hanging,
     };
 
     let wrapping = if let Some(wrap_values) = matches.get_many::<String>("wrap") {
         let strings = wrap_values.map(|s| s.as_str()).collect::<Vec<_>>();
-        if strings.is_empty() {
-            Some((None, Some(0), Some(8)))
-        } else {
-            match strings[0] {
-                "none" => None,
-                "auto" => {
-                    let wrap = strings
-                        .iter()
-                        .skip(1)
-                        .map(|str| str.parse::<usize>())
-                        .collect::<Result<Vec<_>, _>>()
-                        .map_err(|_| {
-                            format!(
-                                "ERROR: Can't parse option --wrap '{}' to integers.",
-                                strings.join(" ")
-                            )
-                        })?;
-                    Some((None, wrap.first().cloned(), wrap.get(1).cloned()))
-                }
-                _ => {
-                    let wrap = strings
-                        .iter()
-                        .map(|str| str.parse::<usize>())
-                        .collect::<Result<Vec<_>, _>>()
-                        .map_err(|_| {
-                            format!(
-                                "ERROR: Can't parse option --wrap '{}' to integers.",
-                                strings.join(" ")
-                            )
-                        })?;
-                    Some((
-                        wrap.first().cloned(),
-                        wrap.get(1).cloned(),
-                        wrap.get(2).cloned(),
-                    ))
-                }
+        match strings.first() {
+            None => Some(default_wrapping),
+            Some(&"none") => None,
+            Some(width) => {
+                let width = match *width {
+                    "auto" => None,
+                    width => Some(width.parse::<usize>().map_err(|_| {
+                        format!(
+                            "ERROR: Can't parse width of option --wrap '{}' to an integer.",
+                            strings.join(" ")
+                        )
+                    })?),
+                };
+                let indent = |idx: usize| {
+                    strings
+                        .get(idx)
+                        .map(|indent| Indent::from_str(indent).map(|indent| indent.text()))
+                        .unwrap_or_else(|| Ok(String::new()))
+                };
+                Some(Wrapping {
+                    width,
+                    indent1: indent(1)?,
+                    indent2: indent(2)?,
+                    ..default_wrapping
+                })
             }
         }
     } else {
-        Some((None, Some(0), Some(8)))
+        Some(default_wrapping)
+    };
+
+    let model_name = match demo.unwrap_or(&matches).get_one::<String>("model") {
+        Some(model) => Some(model.clone()),
+        None => git_dir
+            .map(|git_dir| get_model_name(git_dir, REPO_CONFIG_FILE))
+            .transpose()?
+            .flatten(),
     };
+    let header = header(
+        &matches,
+        model_name.as_deref().unwrap_or("git-flow"),
+        git_dir,
+    );
 
-    let settings = Settings {
+    let mut settings = Settings {
         reverse_commit_order,
         debug,
         colored,
-        compact,
+        accessible,
+        emphasis,
+        group_separators: matches.get_flag("group-separators"),
+        back_merges,
+        lane_legend: matches.get_flag("lane-legend"),
+        density,
+        row_spacing,
+        line_numbers,
+        header,
+        svg_time_axis,
+        svg_divergence,
+        svg_ids: matches.get_flag("svg-ids"),
+        svg_metadata: matches.get_flag("svg-metadata"),
+        svg_tooltips: matches.get_flag("svg-tooltips"),
+        svg_stripes,
         include_remote,
+        track_info,
+        head_trail,
+        uncommitted,
+        count_from_head,
+        related: matches.get_one::<String>("related").cloned(),
+        commit_order,
+        deterministic,
+        limits: if no_limits {
+            Limits::none()
+        } else {
+            user_config.limits
+        },
         format,
+        abbrev,
+        date_timezone,
+        emoji,
+        decorations,
         wrapping,
+        arrows,
         characters: style,
-        branch_order: BranchOrder::ShortestFirst(true),
         branches: BranchSettings::from(model).map_err(|err| err.to_string())?,
-        merge_patterns: MergePatterns::default(),
+        merge_patterns: MergePatterns::from(&user_config.merge_patterns)?,
+        ..Default::default()
     };
 
-    run(repository, &settings, svg, commit_limit, pager)
+    let now = Instant::now();
+    let bisect = matches.get_flag("bisect");
+    let boundary = matches.get_flag("boundary");
+    if boundary && !bisect && matches.subcommand_matches("release").is_none() {
+        return Err("Option --boundary requires subcommand release or option --bisect".to_string());
+    }
+    let no_walk = no_walk_revisions(&matches)?;
+    if no_walk.is_some() && (bisect || matches.subcommand_matches("release").is_some()) {
+        return Err(
+            "Option --no-walk can't be used with subcommand release or option --bisect".to_string(),
+        );
+    }
+    let stdin = matches.get_flag("stdin");
+    if stdin && (no_walk.is_some() || bisect || matches.subcommand_matches("release").is_some()) {
+        return Err(
+            "Option --stdin can't be used with subcommand release or options --bisect and --no-walk"
+                .to_string(),
+        );
+    }
+    let graph = match (repository, matches.subcommand_matches("release")) {
+        (_, Some(_)) if bisect => {
+            return Err("Option --bisect can't be used with subcommand release".to_string())
+        }
+        (Some((source, _)), Some(release)) => {
+            let from = release.get_one::<String>("from").unwrap();
+            let to = release.get_one::<String>("to").unwrap();
+            let range = RangeSource::new(source, from, to)?.with_boundary(boundary);
+            GitGraph::from_source(range, &settings, commit_limit)?
+        }
+        (Some((source, _)), None) if bisect => {
+            let range = RangeSource::bisect(source)?.with_boundary(boundary);
+            GitGraph::from_source(range, &settings, commit_limit)?
+        }
+        (Some((source, _)), None) if stdin => {
+            let selection = SelectionSource::new(source, &read_revisions()?)?;
+            GitGraph::from_source(selection, &settings, commit_limit)?
+        }
+        (Some((source, _)), None) if no_walk.is_some() => {
+            let listed = RangeSource::listed(source, no_walk.as_deref().unwrap_or_default())?;
+            GitGraph::from_source(listed, &settings, commit_limit)?
+        }
+        (Some((source, _)), None) => GitGraph::from_source(source, &settings, commit_limit)?,
+        // Without a repository, option --from-layout was given
+        (None, Some(_)) => {
+            return Err(
+                "Subcommand release requires a repository, but option --from-layout was given"
+                    .to_string(),
+            )
+        }
+        (None, None) if stdin || no_walk.is_some() => {
+            return Err(
+                "Options --stdin and --no-walk require a repository, but option --from-layout was given"
+                    .to_string(),
+            )
+        }
+        (None, None) if bisect => {
+            return Err(
+                "Option --bisect requires a repository, but option --from-layout was given"
+                    .to_string(),
+            )
+        }
+        (None, None) => read_layout(from_layout.unwrap())?.with_emoji(settings.emoji),
+    };
+    let graph = match matches.get_one::<String>("by-release") {
+        Some(pattern) => {
+            let (graph, bands) = by_release(
+                graph,
+                &settings,
+                pattern,
+                matches.get_flag("collapse-releases"),
+            )?;
+            settings.release_bands = Some(bands);
+            graph
+        }
+        None => graph,
+    };
+    let graph = if matches.get_flag("feature-bubbles") {
+        let (kept, bubbles) = graph.feature_bubbles(&settings);
+        let revisions: Vec<String> = kept.iter().map(|oid| oid.to_string()).collect();
+        let selection =
+            SelectionSource::new(graph.into_source(), &revisions)?.with_simplified_merges();
+        settings.bubbles = Some(bubbles);
+        GitGraph::from_source(selection, &settings, None)?
+    } else {
+        graph
+    };
+    let duration_graph = now.elapsed().as_micros();
+
+    if matches.get_flag("warn-unmatched") {
+        let unmatched = graph.unmatched_branches(&settings);
+        if !unmatched.is_empty() {
+            eprintln!("WARNING: Branches not matched by the branching model:");
+            for (name, rules) in unmatched {
+                eprintln!("    {} (no {})", name, rules.join(", "));
+            }
+        }
+    }
+
+    if matches.get_flag("verify") {
+        let violations = graph.validate();
+        if !violations.is_empty() {
+            eprintln!("ERROR: Layout invariants violated:");
+            for violation in &violations {
+                eprintln!("    {}", violation);
+            }
+            return Err(format!(
+                "Layout verification failed with {} violation(s)",
+                violations.len()
+            ));
+        }
+    }
+
+    if let Some(file) = matches.get_one::<String>("debug-dump") {
+        std::fs::write(file, print_debug_dump(&graph, &settings)?)
+            .map_err(|err| format!("Unable to write debug dump to '{}': {}", file, err))?;
+    }
+
+    if let Some(version) = matches.get_one::<String>("porcelain") {
+        let version = PorcelainVersion::from_str(version)?;
+        let nul_terminated = matches.get_flag("null");
+        print!("{}", print_porcelain(&graph, version, nul_terminated)?);
+        return Ok(());
+    }
+
+    if let Some(format) = matches.get_one::<String>("diagram") {
+        let format = DiagramFormat::from_str(format)?;
+        print!("{}", print_diagram(&graph, format)?);
+        return Ok(());
+    }
+
+    if matches.get_flag("print-width") {
+        println!("{}", graph.width());
+        return Ok(());
+    }
+
+    if matches.subcommand_matches("lint").is_some() {
+        let violations = lint(&graph, &settings);
+        if !violations.is_empty() {
+            print!("{}", print_violations(&graph, &violations));
+            return Err(format!(
+                "Branching model violated by {} branch(es)",
+                violations
+                    .iter()
+                    .map(|violation| &violation.branch)
+                    .unique()
+                    .count()
+            ));
+        }
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("stats") {
+        let stats = branch_stats(&graph)?;
+        if matches.get_flag("json") {
+            let json = serde_json::to_string(&stats).map_err(|err| err.to_string())?;
+            println!("{}", json);
+        } else {
+            print!("{}", print_stats(&stats));
+        }
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("export-layout") {
+        let file = matches.get_one::<String>("file").unwrap();
+        let json = serde_json::to_string(&graph.to_def()?).map_err(|err| err.to_string())?;
+        return std::fs::write(file, json)
+            .map_err(|err| format!("Unable to write layout to '{}': {}", file, err));
+    }
+
+    if let Some(matches) = matches.subcommand_matches("compare-models") {
+        let sequential = matches.get_flag("sequential");
+        return compare_models(graph, settings, compared_models, commit_limit, sequential);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("serve") {
+        if let Some(http) = matches.get_one::<String>("http") {
+            let address = if http.parse::<u16>().is_ok() {
+                format!("127.0.0.1:{}", http)
+            } else {
+                http.to_string()
+            };
+            return serve_http(graph, &settings, commit_limit, &address);
+        }
+        let stdin = std::io::stdin();
+        return Server::new(graph, settings, commit_limit)?
+            .with_formats(formats)
+            .run(stdin.lock(), std::io::stdout());
+    }
+
+    let changelog = match matches.subcommand_matches("release") {
+        Some(release) if release.get_flag("changelog") => {
+            if svg {
+                return Err("Option --changelog can't be combined with option --svg".to_string());
+            }
+            Some(print_changelog(&graph, &settings)?)
+        }
+        _ => None,
+    };
+
+    let keys = KeyBindings::from(&user_config.keys)?.with_commands(&user_config.actions)?;
+
+    if let Some(pick_format) = pick_format {
+        if svg || changelog.is_some() {
+            return Err(
+                "Options --pick-format and --pick-multi can't be combined with --svg or --changelog"
+                    .to_string(),
+            );
+        }
+        let pick_format = CommitFormat::from_str_with(pick_format, &formats)?;
+        return pick(&graph, &settings, &pick_format, &keys, pick_multi, copy);
+    }
+
+    if copy {
+        copy_to_clipboard(&graph.head.oid.to_string())?;
+    }
+
+    run(
+        graph,
+        duration_graph,
+        &settings,
+        svg,
+        pager && changelog.is_none(),
+        &keys,
+        timing,
+    )?;
+
+    if let Some(changelog) = changelog {
+        println!("\n{}", changelog);
+    }
+    Ok(())
+}
+
+/// Creates the repository of subcommand `demo`.
+#[cfg(feature = "git2")]
+fn create_demo(demo: &ArgMatches, dir: &str) -> Result<(), String> {
+    let model = demo
+        .get_one::<String>("model")
+        .map_or("git-flow", |s| &s[..]);
+    let commits = match demo.get_one::<String>("commits") {
+        Some(str) => str.parse::<usize>().map_err(|_| {
+            format!(
+                "Option commits must be a positive number, but got '{}'",
+                str
+            )
+        })?,
+        None => 200,
+    };
+    create_demo_repository(Path::new(dir), DemoPattern::for_model(model), commits)
+}
+
+#[cfg(not(feature = "git2"))]
+fn create_demo(_demo: &ArgMatches, _dir: &str) -> Result<(), String> {
+    Err("Subcommand demo requires backend git2, but git-graph was built without it".to_string())
+}
+
+/// Whether the pager is enabled by environment variable `GIT_GRAPH_PAGER`, like `--no-pager` for `false`.
+fn pager_enabled_by_env() -> Result<bool, String> {
+    match std::env::var("GIT_GRAPH_PAGER") {
+        Err(_) => Ok(true),
+        Ok(value) => match &value.to_lowercase()[..] {
+            "" | "true" | "yes" | "on" | "1" => Ok(true),
+            "false" | "no" | "off" | "0" => Ok(false),
+            _ => Err(format!(
+                "Invalid value '{}' of environment variable GIT_GRAPH_PAGER. Must be one of [true|false]",
+                value
+            )),
+        },
+    }
+}
+
+/// Reads a graph saved with subcommand `export-layout`.
+fn read_layout(file: &str) -> Result<GitGraph, String> {
+    let json = std::fs::read_to_string(file)
+        .map_err(|err| format!("Unable to read layout from '{}': {}", file, err))?;
+    let def: GitGraphDef = serde_json::from_str(&json)
+        .map_err(|err| format!("Invalid layout file '{}': {}", file, err))?;
+    Ok(GitGraph::from(def))
+}
+
+/// Partitions the graph into releases for option `--by-release`, and creates the labels of their bands.
+/// With `collapse`, builds a graph of only the first commit of each release.
+fn by_release(
+    graph: GitGraph,
+    settings: &Settings,
+    pattern: &str,
+    collapse: bool,
+) -> Result<(GitGraph, HashMap<Oid, String>), String> {
+    let regex = glob_regex(pattern)
+        .map_err(|err| format!("Invalid release tag pattern '{}': {}", pattern, err))?;
+    let releases = graph.releases(&regex);
+    let bands: HashMap<Oid, String> = releases
+        .iter()
+        .map(|release| {
+            let count = match release.count {
+                1 => "1 commit".to_string(),
+                n => format!("{} commits", n),
+            };
+            let name = release.name.as_deref().unwrap_or("unreleased");
+            (
+                graph.commits[release.start].oid,
+                format!("{} \u{b7} {}", name, count),
+            )
+        })
+        .collect();
+    if !collapse {
+        return Ok((graph, bands));
+    }
+    let revisions: Vec<String> = releases
+        .iter()
+        .map(|release| graph.commits[release.start].oid.to_string())
+        .collect();
+    let selection = SelectionSource::new(graph.into_source(), &revisions)?;
+    Ok((GitGraph::from_source(selection, settings, None)?, bands))
+}
+
+/// A regular expression matching the whole string for a glob pattern with wildcards `*` and `?`.
+fn glob_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let mut regex = String::from("^");
+    for char in pattern.chars() {
+        match char {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            _ => regex.push_str(&regex::escape(&char.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex)
+}
+
+/// The header of option `--header`, describing the repository, the model and the options restricting the graph.
+fn header(matches: &ArgMatches, model: &str, git_dir: Option<&Path>) -> Option<Header> {
+    if !matches.get_flag("header") {
+        return None;
+    }
+    let repository = match git_dir {
+        _ if matches.get_flag("anonymize") => "repository".to_string(),
+        Some(git_dir) => {
+            let dir = if git_dir.ends_with(".git") {
+                git_dir.parent()
+            } else {
+                Some(git_dir)
+            };
+            dir.and_then(|dir| dir.file_name())
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        }
+        None => matches
+            .get_one::<String>("from-layout")
+            .cloned()
+            .unwrap_or_default(),
+    };
+
+    let mut filters = vec![];
+    if let Some(release) = matches.subcommand_matches("release") {
+        let from = release.get_one::<String>("from").unwrap();
+        let to = release.get_one::<String>("to").unwrap();
+        filters.push(format!("{}..{}", from, to));
+    }
+    for (flag, filter) in [
+        ("bisect", "bisect"),
+        ("boundary", "boundary"),
+        ("stdin", "stdin"),
+        ("local", "local"),
+        ("from-head", "from HEAD"),
+    ] {
+        if matches.get_flag(flag) {
+            filters.push(filter.to_string());
+        }
+    }
+    if matches.get_many::<String>("no-walk").is_some() {
+        filters.push("no-walk".to_string());
+    }
+    if let Some(related) = matches.get_one::<String>("related") {
+        filters.push(format!("related to {}", related));
+    }
+    if let Some(hidden) = matches.get_many::<String>("hide") {
+        filters.extend(hidden.map(|pattern| format!("hide {}", pattern)));
+    }
+    if let Some(max_count) = matches.get_one::<String>("max-count") {
+        filters.push(format!("max count {}", max_count));
+    }
+    Some(Header {
+        repository,
+        model: model.to_string(),
+        filters,
+    })
+}
+
+/// The revisions of option `--no-walk`, with `-` replaced by those read from stdin.
+fn no_walk_revisions(matches: &ArgMatches) -> Result<Option<Vec<String>>, String> {
+    let Some(values) = matches.get_many::<String>("no-walk") else {
+        return Ok(None);
+    };
+    let mut revisions = vec![];
+    for value in values {
+        if value == "-" {
+            revisions.extend(read_revisions()?);
+        } else {
+            revisions.push(value.to_string());
+        }
+    }
+    Ok(Some(revisions))
+}
+
+/// Reads revisions from stdin, one per line. Of each line, only the first word is used,
+/// so that the output of `git log --oneline` works as well.
+fn read_revisions() -> Result<Vec<String>, String> {
+    let input = std::io::read_to_string(std::io::stdin())
+        .map_err(|err| format!("Unable to read revisions from stdin: {}", err))?;
+    Ok(input
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(|rev| rev.to_string())
+        .collect())
 }
 
 fn run(
-    repository: Repository,
+    graph: GitGraph,
+    duration_graph: u128,
     settings: &Settings,
     svg: bool,
-    max_commits: Option<usize>,
     pager: bool,
+    keys: &KeyBindings,
+    timing: bool,
 ) -> Result<(), String> {
-    let now = Instant::now();
-    let graph = GitGraph::new(repository, settings, max_commits)?;
-
-    let duration_graph = now.elapsed().as_micros();
-
-    if settings.debug {
-        for branch in &graph.all_branches {
-            eprintln!(
-                "{} (col {}) ({:?}) {} s: {:?}, t: {:?}",
-                branch.name,
-                branch.visual.column.unwrap_or(99),
-                branch.range,
-                if branch.is_merged { "m" } else { "" },
-                branch.visual.source_order_group,
-                branch.visual.target_order_group
-            );
-        }
+    for branch in &graph.all_branches {
+        log::debug!(
+            "{} (col {}) ({:?}) {} s: {:?}, t: {:?}",
+            branch.name,
+            branch.visual.column.unwrap_or(99),
+            branch.range,
+            if branch.is_merged { "m" } else { "" },
+            branch.visual.source_order_group,
+            branch.visual.target_order_group
+        );
     }
 
     let now = Instant::now();
 
     if svg {
+        if let Some(notice) = &graph.notice {
+            eprintln!("{}", notice);
+        }
         println!("{}", print_svg(&graph, settings)?);
     } else {
-        let (g_lines, t_lines, _indices) = print_unicode(&graph, settings)?;
+        let operation = graph.operation.as_ref().map(|operation| operation.banner());
+        let banner = match (operation, &graph.notice) {
+            (Some(operation), Some(notice)) => Some(format!("{} \u{2014} {}", operation, notice)),
+            (None, Some(notice)) => Some(notice.clone()),
+            (operation, None) => operation,
+        };
+        let header = settings
+            .header
+            .as_ref()
+            .map(|header| header.line(graph.commits.len()));
         if pager && atty::is(atty::Stream::Stdout) {
-            print_paged(&g_lines, &t_lines).map_err(|err| err.to_string())?;
+            let banner = match (header, banner) {
+                (Some(header), Some(banner)) => Some(format!("{} \u{2014} {}", header, banner)),
+                (header, banner) => header.or(banner),
+            };
+            // Only the commits of the first page are laid out before the pager starts
+            let (_, height) = crossterm::terminal::size().map_err(|err| err.to_string())?;
+            let content = pager_content(&graph, settings, height as usize)?;
+            let rest = || pager_content(&graph, settings, graph.commits.len());
+            let details = |commit_idx| {
+                format_commit_at(
+                    &graph,
+                    commit_idx,
+                    &CommitFormat::Full,
+                    settings.colored,
+                    settings.date_timezone,
+                )
+            };
+            let hashes = |commit_idx: usize| graph.commits[commit_idx].oid.to_string();
+            let partial = content.rows.commit_rows.len() < graph.commits.len();
+            let mut pager = Pager::new(content, &details, keys)
+                .with_banner(banner.as_deref())
+                .with_line_numbers(settings.line_numbers)
+                .with_hashes(&hashes);
+            if partial {
+                pager = pager.with_rest(&rest);
+            }
+            pager.run().map_err(|err| err.to_string())?;
+            print_lane_legend(&graph, settings);
         } else {
+            let (g_lines, t_lines, _) = print_unicode(&graph, settings)?;
+            if let Some(header) = header {
+                println!("{}", header);
+            }
+            if let Some(banner) = banner {
+                println!("{}", banner);
+            }
             print_unpaged(&g_lines, &t_lines);
+            print_lane_legend(&graph, settings);
         }
     };
 
     let duration_print = now.elapsed().as_micros();
 
-    if settings.debug {
-        eprintln!(
-            "Graph construction: {:.1} ms, printing: {:.1} ms ({} commits)",
-            duration_graph as f32 / 1000.0,
-            duration_print as f32 / 1000.0,
-            graph.commits.len()
-        );
+    log::info!(
+        "Graph construction: {:.1} ms, printing: {:.1} ms ({} commits)",
+        duration_graph as f32 / 1000.0,
+        duration_print as f32 / 1000.0,
+        graph.commits.len()
+    );
+    if timing {
+        print_timing(&graph, duration_graph, duration_print);
     }
     Ok(())
 }
 
-/// Print the graph, paged (i.e. wait for user input once the terminal is filled).
-fn print_paged(graph_lines: &[String], text_lines: &[String]) -> Result<(), ErrorKind> {
-    let (width, height) = crossterm::terminal::size()?;
-    let width = width as usize;
-
-    let mut line_idx = 0;
-    let mut print_lines = height - 2;
-    let mut clear = false;
-    let mut abort = false;
+/// Prints the durations of the recorded stages, and of graph construction and printing, to stderr.
+fn print_timing(graph: &GitGraph, duration_graph: u128, duration_print: u128) {
+    eprintln!(
+        "Timing ({} commits, {} branches):",
+        graph.commits.len(),
+        graph.all_branches.len()
+    );
+    let totals = [
+        ("graph construction", duration_graph as f64 / 1000.0),
+        ("printing", duration_print as f64 / 1000.0),
+    ];
+    for (name, millis) in logging::timings().into_iter().chain(totals) {
+        eprintln!("  {:<20}{:>9.1} ms", name, millis);
+    }
+}
 
-    let help = "\r >>> Down: line, PgDown/Enter: page, End: all, Esc/Q/^C: quit\r";
-    let help = if help.len() > width {
-        &help[0..width]
-    } else {
-        help
+/// Lets the user pick a commit, or multiple commits with `multi`, in the pager, drawn on stderr,
+/// and prints them to stdout in the given format, oldest first.
+/// With `copy`, the output is copied to the clipboard as well.
+fn pick(
+    graph: &GitGraph,
+    settings: &Settings,
+    format: &CommitFormat,
+    keys: &KeyBindings,
+    multi: bool,
+    copy: bool,
+) -> Result<(), String> {
+    if !atty::is(atty::Stream::Stderr) {
+        return Err("Picking commits requires a terminal on stderr".to_string());
+    }
+    let (_, height) = crossterm::terminal::size().map_err(|err| err.to_string())?;
+    let content = pager_content(graph, settings, height as usize)?;
+    let rest = || pager_content(graph, settings, graph.commits.len());
+    let details = |commit_idx| {
+        format_commit_at(
+            graph,
+            commit_idx,
+            &CommitFormat::Full,
+            settings.colored,
+            settings.date_timezone,
+        )
     };
+    let hashes = |commit_idx: usize| graph.commits[commit_idx].oid.to_string();
+    let partial = content.rows.commit_rows.len() < graph.commits.len();
+    let mut pager = Pager::new(content, &details, keys)
+        .with_line_numbers(settings.line_numbers)
+        .with_hashes(&hashes)
+        .with_picking(multi);
+    if partial {
+        pager = pager.with_rest(&rest);
+    }
+    let picked = pager.run().map_err(|err| err.to_string())?;
+    if picked.is_empty() {
+        return Err("No commit picked".to_string());
+    }
+    let mut output = vec![];
+    for commit_idx in picked {
+        let lines = format_commit_at(graph, commit_idx, format, false, settings.date_timezone)?;
+        output.push(lines.join("\n"));
+    }
+    println!("{}", output.join("\n"));
+    if copy {
+        copy_to_clipboard(&output.join("\n"))?;
+    }
+    Ok(())
+}
 
-    while line_idx < graph_lines.len() {
-        if print_lines > 0 {
-            if clear {
-                stdout()
-                    .execute(Clear(ClearType::CurrentLine))?
-                    .execute(MoveToColumn(0))?;
-            }
+fn copy_to_clipboard(text: &str) -> Result<(), String> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text))
+        .map_err(|err| format!("Copying to the clipboard failed: {}", err))
+}
 
-            stdout().execute(Print(format!(
-                " {}  {}\n",
-                graph_lines[line_idx], text_lines[line_idx]
-            )))?;
+/// Prints the graph laid out under each of the models, side by side or one after the other.
+///
+/// Side by side, text is wrapped to fit each graph into half of the terminal, unless a width is given by option --wrap.
+fn compare_models(
+    mut graph: GitGraph,
+    mut settings: Settings,
+    models: Vec<(String, BranchSettingsDef)>,
+    max_count: Option<usize>,
+    sequential: bool,
+) -> Result<(), String> {
+    let terminal_width = if atty::is(atty::Stream::Stdout) {
+        crossterm::terminal::size()
+            .ok()
+            .map(|(width, _)| width as usize)
+    } else {
+        None
+    };
+    let wrapping = settings.wrapping.clone();
 
-            if print_lines == 1 && line_idx < graph_lines.len() - 1 {
-                stdout().execute(Print(help))?;
-            }
-            print_lines -= 1;
-            line_idx += 1;
-        } else {
-            enable_raw_mode()?;
-            let input = crossterm::event::read()?;
-            if let Event::Key(evt) = input {
-                match evt.code {
-                    KeyCode::Down => {
-                        clear = true;
-                        print_lines = 1;
-                    }
-                    KeyCode::Enter | KeyCode::PageDown => {
-                        clear = true;
-                        print_lines = height - 2;
-                    }
-                    KeyCode::End => {
-                        clear = true;
-                        print_lines = graph_lines.len() as u16;
-                    }
-                    KeyCode::Char(c) => match c {
-                        'q' => {
-                            abort = true;
-                            break;
-                        }
-                        'c' if evt.modifiers == KeyModifiers::CONTROL => {
-                            abort = true;
-                            break;
-                        }
-                        _ => {}
-                    },
-                    KeyCode::Esc => {
-                        abort = true;
-                        break;
-                    }
-                    _ => {}
-                }
+    let mut columns = vec![];
+    for (name, model) in models {
+        settings.branches = BranchSettings::from(model).map_err(|err| err.to_string())?;
+        graph.reload(&settings, max_count)?;
+        if !sequential {
+            if let (Some(wrapping @ Wrapping { width: None, .. }), Some(width)) =
+                (&wrapping, terminal_width)
+            {
+                // Each line is the graph, framed by 1 and 2 spaces, and the text
+                let text_width = (width.saturating_sub(3) / 2)
+                    .saturating_sub(graph.width() + 3)
+                    .max(1);
+                settings.wrapping = Some(Wrapping {
+                    width: Some(text_width),
+                    ..wrapping.clone()
+                });
             }
         }
+        let (g_lines, t_lines, _) = print_unicode(&graph, &settings)?;
+        let lines: Vec<_> = g_lines
+            .iter()
+            .zip(t_lines.iter())
+            .map(|(g_line, t_line)| format!(" {}  {}", g_line, t_line))
+            .collect();
+        columns.push((name, lines));
     }
-    if abort {
-        stdout()
-            .execute(Clear(ClearType::CurrentLine))?
-            .execute(MoveToColumn(0))?
-            .execute(Print(" ...\n"))?;
+
+    if sequential {
+        let blocks: Vec<_> = columns
+            .iter()
+            .map(|(name, lines)| format!("{}:\n{}", name, lines.join("\n")))
+            .collect();
+        println!("{}", blocks.join("\n\n"));
+        return Ok(());
     }
-    disable_raw_mode()?;
 
+    let (left, right) = (&columns[0], &columns[1]);
+    let left_width = left
+        .1
+        .iter()
+        .map(|line| display_width(line))
+        .chain([display_width(&left.0) + 1])
+        .max()
+        .unwrap_or(0);
+    let pad = |line: &str| " ".repeat(left_width - display_width(line));
+    println!(" {}{}   {}", left.0, pad(&left.0[..]), right.0);
+    for row in 0..left.1.len().max(right.1.len()) {
+        let l_line = left.1.get(row).map(|line| &line[..]).unwrap_or("");
+        let r_line = right.1.get(row).map(|line| &line[..]).unwrap_or("");
+        println!("{}{}   {}", l_line, pad(l_line), r_line);
+    }
     Ok(())
 }
 
+/// Lays out the first `count` commits for the pager.
+fn pager_content(
+    graph: &GitGraph,
+    settings: &Settings,
+    count: usize,
+) -> Result<PagerContent, String> {
+    let (graph_lines, text_lines, indices) = print_unicode_prefix(graph, settings, count)?;
+    let rows = RowMap::new(&indices, graph_lines.len(), settings.reverse_commit_order);
+    let refs = get_ref_rows(graph, &rows);
+    Ok(PagerContent {
+        graph_lines,
+        text_lines,
+        rows,
+        refs,
+    })
+}
+
+/// Collects the names of all branches and tags that are laid out, together with the row of their tip commit.
+fn get_ref_rows(graph: &GitGraph, rows: &RowMap) -> Vec<(String, usize)> {
+    graph
+        .branches
+        .iter()
+        .chain(graph.tags.iter())
+        .filter_map(|branch_idx| {
+            let branch = &graph.all_branches[*branch_idx];
+            let commit_idx = graph.indices.get(&branch.target)?;
+            rows.commit_rows.get(*commit_idx).map(|row| {
+                let row = *row;
+                (branch.display_name().to_string(), row)
+            })
+        })
+        .sorted()
+        .collect()
+}
+
+/// Print the legend of option `--lane-legend`, if requested.
+fn print_lane_legend(graph: &GitGraph, settings: &Settings) {
+    if settings.lane_legend {
+        println!();
+        for line in lane_legend(graph, settings) {
+            println!(" {}", line);
+        }
+    }
+}
+
 /// Print the graph, un-paged.
 fn print_unpaged(graph_lines: &[String], text_lines: &[String]) {
     for (g_line, t_line) in graph_lines.iter().zip(text_lines.iter()) {
@@ -0,0 +1,973 @@
+//! Interactive, scrollable display of text-based graphs.
+
+use crate::settings::{KeysDef, LineNumbers};
+use crossterm::cursor::{Hide, MoveTo, MoveToColumn, Show};
+use crossterm::event::{
+    DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton,
+    MouseEventKind,
+};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, Clear, ClearType, DisableLineWrap, EnableLineWrap,
+    EnterAlternateScreen, LeaveAlternateScreen,
+};
+use crossterm::{ErrorKind, ExecutableCommand, QueueableCommand};
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::io::{stderr, stdout, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Maximum time between two clicks on the same row to count as a double-click.
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+/// Number of rows scrolled per mouse wheel step.
+const WHEEL_STEP: usize = 3;
+
+/// Actions of the pager that can be bound to keys.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Quit,
+    Down,
+    Up,
+    PageDown,
+    PageUp,
+    Home,
+    End,
+    GoToRef,
+    Pick,
+    Mark,
+    Copy,
+    Help,
+    /// A user-defined command, by index in the commands of the key bindings
+    Command(usize),
+}
+
+impl Action {
+    /// Short description, for the help overlay.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Down => "Scroll down one line",
+            Action::Up => "Scroll up one line",
+            Action::PageDown => "Scroll down one page",
+            Action::PageUp => "Scroll up one page",
+            Action::Home => "Go to the first line",
+            Action::End => "Go to the last line",
+            Action::GoToRef => "Go to a branch, tag or commit number (Tab completes)",
+            Action::Pick => "Pick the selected or marked commits (with --pick-format)",
+            Action::Mark => "Mark or unmark the selected commit (with --pick-multi)",
+            Action::Copy => "Copy the hash of the selected commit to the clipboard",
+            Action::Help => "Show this help",
+            Action::Command(_) => "Run a command on the selected commit",
+        }
+    }
+}
+
+/// Validated key bindings of the pager.
+pub struct KeyBindings {
+    bindings: Vec<(KeyCode, KeyModifiers, Action)>,
+    names: Vec<(Action, Vec<String>)>,
+    /// Command templates of user-defined actions
+    commands: Vec<String>,
+}
+
+impl KeyBindings {
+    /// Parses and validates key bindings.
+    /// Fails for unknown key names and for keys bound to more than one action.
+    pub fn from(def: &KeysDef) -> Result<Self, String> {
+        let names = vec![
+            (Action::Quit, def.quit.clone()),
+            (Action::Down, def.down.clone()),
+            (Action::Up, def.up.clone()),
+            (Action::PageDown, def.page_down.clone()),
+            (Action::PageUp, def.page_up.clone()),
+            (Action::Home, def.home.clone()),
+            (Action::End, def.end.clone()),
+            (Action::GoToRef, def.goto_ref.clone()),
+            (Action::Pick, def.pick.clone()),
+            (Action::Mark, def.mark.clone()),
+            (Action::Copy, def.copy.clone()),
+            (Action::Help, def.help.clone()),
+        ];
+
+        let mut bindings = KeyBindings {
+            bindings: vec![],
+            names: vec![],
+            commands: vec![],
+        };
+        for (action, keys) in names {
+            bindings.bind(action, keys)?;
+        }
+        Ok(bindings)
+    }
+
+    /// Adds user-defined commands, given as command templates by key. `{hash}` in a template
+    /// is replaced by the hash of the selected commit.
+    pub fn with_commands(mut self, commands: &HashMap<String, String>) -> Result<Self, String> {
+        let mut commands: Vec<_> = commands.iter().collect();
+        commands.sort();
+        for (key, command) in commands {
+            self.bind(Action::Command(self.commands.len()), vec![key.clone()])?;
+            self.commands.push(command.clone());
+        }
+        Ok(self)
+    }
+
+    fn bind(&mut self, action: Action, keys: Vec<String>) -> Result<(), String> {
+        for key in &keys {
+            let (code, modifiers) = parse_key(key)?;
+            if let Some((_, _, other)) = self
+                .bindings
+                .iter()
+                .find(|(c, m, _)| c == &code && m == &modifiers)
+            {
+                return Err(format!(
+                    "ERROR: Key '{}' is bound to both {:?} and {:?}",
+                    key, other, action
+                ));
+            }
+            self.bindings.push((code, modifiers, action));
+        }
+        self.names.push((action, keys));
+        Ok(())
+    }
+
+    /// The description of an action, with the command of user-defined actions.
+    fn description(&self, action: Action) -> String {
+        match action {
+            Action::Command(idx) => format!("Run '{}'", self.commands[idx]),
+            _ => action.description().to_string(),
+        }
+    }
+
+    /// The action bound to a key, if any.
+    pub fn action(&self, code: KeyCode, modifiers: KeyModifiers) -> Option<Action> {
+        let modifiers = if let KeyCode::Char(_) = code {
+            modifiers - KeyModifiers::SHIFT
+        } else {
+            modifiers
+        };
+        self.bindings
+            .iter()
+            .find(|(c, m, _)| c == &code && m == &modifiers)
+            .map(|(_, _, action)| *action)
+    }
+
+    /// The name of the first key bound to an action, for display.
+    pub fn key_name(&self, action: Action) -> &str {
+        self.names
+            .iter()
+            .find(|(a, _)| a == &action)
+            .and_then(|(_, keys)| keys.first())
+            .map_or("-", |key| &key[..])
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings::from(&KeysDef::default()).unwrap()
+    }
+}
+
+/// Parses a key name like `q`, `pgdn` or `ctrl+c`.
+fn parse_key(key: &str) -> Result<(KeyCode, KeyModifiers), String> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut parts: Vec<&str> = key.split('+').collect();
+    let name = if key.ends_with("++") {
+        parts.truncate(parts.len() - 2);
+        "+"
+    } else {
+        parts.pop().unwrap_or("")
+    };
+    for modifier in parts {
+        modifiers |= match &modifier.to_lowercase()[..] {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => {
+                return Err(format!(
+                    "ERROR: Unknown key modifier '{}' in '{}'",
+                    modifier, key
+                ))
+            }
+        };
+    }
+
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match &name.to_lowercase()[..] {
+            "esc" | "escape" => KeyCode::Esc,
+            "enter" | "return" => KeyCode::Enter,
+            "space" => KeyCode::Char(' '),
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pgup" | "pageup" => KeyCode::PageUp,
+            "pgdn" | "pagedown" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            lower => match lower.strip_prefix('f').map(|num| num.parse::<u8>()) {
+                Some(Ok(num)) if (1..=12).contains(&num) => KeyCode::F(num),
+                _ => return Err(format!("ERROR: Unknown key '{}'", key)),
+            },
+        },
+    };
+    let modifiers = if let KeyCode::Char(_) = code {
+        modifiers - KeyModifiers::SHIFT
+    } else {
+        modifiers
+    };
+    Ok((code, modifiers))
+}
+
+/// Mapping between rows of a text-based graph and the commits they belong to.
+pub struct RowMap {
+    /// The row of each commit (by index in `GitGraph::commits`)
+    pub commit_rows: Vec<usize>,
+    /// The commit (index in `GitGraph::commits`) each row belongs to
+    pub row_commits: Vec<usize>,
+}
+
+impl RowMap {
+    /// Creates the mapping from the commit-to-row index map returned by `print_unicode`.
+    pub fn new(index_map: &[usize], num_rows: usize, reverse: bool) -> Self {
+        let mut row_commits = vec![0; num_rows];
+        for (commit, row) in index_map.iter().enumerate() {
+            let end = index_map.get(commit + 1).cloned().unwrap_or(num_rows);
+            for owner in row_commits.iter_mut().take(end).skip(*row) {
+                *owner = commit;
+            }
+        }
+        let mut commit_rows = index_map.to_vec();
+        if reverse {
+            row_commits.reverse();
+            for row in commit_rows.iter_mut() {
+                *row = num_rows - 1 - *row;
+            }
+        }
+        RowMap {
+            commit_rows,
+            row_commits,
+        }
+    }
+}
+
+/// The lines of a text-based graph shown by a pager.
+pub struct PagerContent {
+    pub graph_lines: Vec<String>,
+    pub text_lines: Vec<String>,
+    pub rows: RowMap,
+    /// Names of branches and tags, with the row of their tip
+    pub refs: Vec<(String, usize)>,
+}
+
+/// Scrollable viewport over the lines of a text-based graph.
+pub struct Pager<'a> {
+    content: PagerContent,
+    rest: Option<&'a dyn Fn() -> Result<PagerContent, String>>,
+    details: &'a dyn Fn(usize) -> Result<Vec<String>, String>,
+    hashes: Option<&'a dyn Fn(usize) -> String>,
+    keys: &'a KeyBindings,
+    banner: Option<&'a str>,
+    /// Message shown in the status line until the next key is pressed
+    message: Option<String>,
+    /// Kept open while the pager runs, as copied text may be lost when closing it
+    clipboard: Option<arboard::Clipboard>,
+    line_numbers: Option<LineNumbers>,
+    picking: bool,
+    /// Commits marked for picking, if multiple commits can be picked
+    marked: Option<Vec<usize>>,
+    top: usize,
+    selected: Option<usize>,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Pager<'a> {
+    /// Creates a pager.
+    ///
+    /// * `details`: formats the details of a commit, given its index
+    pub fn new(
+        content: PagerContent,
+        details: &'a dyn Fn(usize) -> Result<Vec<String>, String>,
+        keys: &'a KeyBindings,
+    ) -> Self {
+        Pager {
+            content,
+            rest: None,
+            details,
+            hashes: None,
+            keys,
+            banner: None,
+            message: None,
+            clipboard: None,
+            line_numbers: None,
+            picking: false,
+            marked: None,
+            top: 0,
+            selected: None,
+            width: 0,
+            height: 0,
+        }
+    }
+
+    /// Shows a banner, like `Merge in progress`, at the start of the status line,
+    /// or above the graph if it is printed at once.
+    pub fn with_banner(mut self, banner: Option<&'a str>) -> Self {
+        self.banner = banner;
+        self
+    }
+
+    /// Sets the kind of numbers shown in front of commits. Numbers entered at the go-to prompt
+    /// refer to commits the same way, or to commit indices if no numbers are shown.
+    pub fn with_line_numbers(mut self, line_numbers: Option<LineNumbers>) -> Self {
+        self.line_numbers = line_numbers;
+        self
+    }
+
+    /// Lets the user pick a commit, or mark and pick multiple commits with `multi`, which are returned by `run`.
+    /// The up and down keys move the selection, which starts at the first commit.
+    /// Draws on stderr instead of stdout, so that stdout only receives the output for the picked commits.
+    pub fn with_picking(mut self, multi: bool) -> Self {
+        self.picking = true;
+        self.marked = if multi { Some(vec![]) } else { None };
+        self.selected = Some(0);
+        self
+    }
+
+    /// Enables copying commit hashes and user-defined commands of the key bindings,
+    /// with `hashes` giving the hash of a commit by index.
+    pub fn with_hashes(mut self, hashes: &'a dyn Fn(usize) -> String) -> Self {
+        self.hashes = Some(hashes);
+        self
+    }
+
+    /// Treats the content as the leading part of the graph. The complete graph is created by `rest`
+    /// when scrolling past the content, jumping to the end, or going to a branch or tag.
+    pub fn with_rest(mut self, rest: &'a dyn Fn() -> Result<PagerContent, String>) -> Self {
+        self.rest = Some(rest);
+        self
+    }
+
+    /// Runs the pager until the user quits or picks commits. Returns the indices of the picked commits,
+    /// oldest first, or nothing if the user quit.
+    ///
+    /// Prints everything at once if the graph fits into the terminal, except when picking.
+    pub fn run(&mut self) -> Result<Vec<usize>, ErrorKind> {
+        let (width, height) = crossterm::terminal::size()?;
+        self.width = width as usize;
+        self.height = height as usize;
+
+        self.load_rows(self.page_size())?;
+        if self.content.graph_lines.len() < self.page_size() && !self.picking {
+            if let Some(banner) = self.banner {
+                println!("{}", banner);
+            }
+            for row in 0..self.content.graph_lines.len() {
+                println!("{}", self.line(row));
+            }
+            return Ok(vec![]);
+        }
+
+        enable_raw_mode()?;
+        self.output()
+            .execute(EnterAlternateScreen)?
+            .execute(EnableMouseCapture)?
+            .execute(DisableLineWrap)?
+            .execute(Hide)?;
+
+        let result = self.event_loop();
+
+        self.output()
+            .execute(Show)?
+            .execute(EnableLineWrap)?
+            .execute(DisableMouseCapture)?
+            .execute(LeaveAlternateScreen)?;
+        disable_raw_mode()?;
+
+        result
+    }
+
+    fn event_loop(&mut self) -> Result<Vec<usize>, ErrorKind> {
+        let mut last_click: Option<(usize, Instant)> = None;
+        loop {
+            self.draw()?;
+            let event = crossterm::event::read()?;
+            if let Event::Key(_) = event {
+                self.message = None;
+            }
+            match event {
+                Event::Key(evt) => match self.keys.action(evt.code, evt.modifiers) {
+                    Some(Action::Down) if self.picking => self.move_selection(true)?,
+                    Some(Action::Up) if self.picking => self.move_selection(false)?,
+                    Some(Action::Down) => self.scroll_by(1)?,
+                    Some(Action::Up) => self.scroll_by(-1)?,
+                    Some(Action::PageDown) => self.scroll_by(self.page_size() as i64)?,
+                    Some(Action::PageUp) => self.scroll_by(-(self.page_size() as i64))?,
+                    Some(Action::Home) => self.top = 0,
+                    Some(Action::End) => {
+                        self.load_rows(usize::MAX)?;
+                        self.top = self.max_top();
+                    }
+                    Some(Action::GoToRef) => {
+                        self.load_rows(usize::MAX)?;
+                        if let Some(row) = self.read_ref()? {
+                            self.select(row);
+                        }
+                    }
+                    Some(Action::Pick) if self.picking => {
+                        let mut picked = match &self.marked {
+                            Some(marked) if !marked.is_empty() => marked.clone(),
+                            _ => self.selected.into_iter().collect(),
+                        };
+                        if !picked.is_empty() {
+                            // Commits are ordered newest first
+                            picked.sort_unstable_by(|a, b| b.cmp(a));
+                            return Ok(picked);
+                        }
+                    }
+                    Some(Action::Mark) if self.marked.is_some() => {
+                        if let Some(commit) = self.selected {
+                            self.toggle_mark(commit);
+                            self.move_selection(true)?;
+                        }
+                    }
+                    Some(Action::Pick) | Some(Action::Mark) => {}
+                    Some(Action::Help) => self.show_help()?,
+                    Some(Action::Copy) => self.copy_hash(),
+                    Some(Action::Command(idx)) => self.run_command(idx)?,
+                    Some(Action::Quit) => break,
+                    None => {}
+                },
+                Event::Mouse(evt) => match evt.kind {
+                    MouseEventKind::ScrollDown => self.scroll_by(WHEEL_STEP as i64)?,
+                    MouseEventKind::ScrollUp => self.scroll_by(-(WHEEL_STEP as i64))?,
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        let row = self.top + evt.row as usize;
+                        if (evt.row as usize) < self.page_size()
+                            && row < self.content.graph_lines.len()
+                        {
+                            let commit = self.content.rows.row_commits[row];
+                            let double = matches!(last_click, Some((c, time)) if c == commit && time.elapsed() < DOUBLE_CLICK);
+                            self.selected = Some(commit);
+                            if double && self.marked.is_some() {
+                                self.toggle_mark(commit);
+                                last_click = None;
+                            } else if double && self.picking {
+                                return Ok(vec![commit]);
+                            } else if double {
+                                self.show_details(commit)?;
+                                last_click = None;
+                            } else {
+                                last_click = Some((commit, Instant::now()));
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+                Event::Resize(width, height) => {
+                    self.width = width as usize;
+                    self.height = height as usize;
+                    self.load_rows(self.top + self.page_size())?;
+                    self.top = self.top.min(self.max_top());
+                }
+                _ => {}
+            }
+        }
+        Ok(vec![])
+    }
+
+    /// Moves the selection to the next commit below or above, loading the rest of the graph if needed.
+    fn move_selection(&mut self, down: bool) -> Result<(), ErrorKind> {
+        let current = match self.selected {
+            Some(commit) => self.content.rows.commit_rows[commit],
+            None => return Ok(()),
+        };
+        let next = |rows: &[usize]| {
+            if down {
+                rows.iter().filter(|row| **row > current).min().copied()
+            } else {
+                rows.iter().filter(|row| **row < current).max().copied()
+            }
+        };
+        if next(&self.content.rows.commit_rows).is_none() {
+            self.load_rows(usize::MAX)?;
+        }
+        if let Some(row) = next(&self.content.rows.commit_rows) {
+            self.select(row);
+        }
+        Ok(())
+    }
+
+    fn toggle_mark(&mut self, commit: usize) {
+        if let Some(marked) = &mut self.marked {
+            match marked.iter().position(|c| *c == commit) {
+                Some(pos) => {
+                    marked.remove(pos);
+                }
+                None => marked.push(commit),
+            }
+        }
+    }
+
+    /// Runs a user-defined command on the selected commit, after asking for confirmation.
+    /// Shows the output of the command, if any, or whether it failed.
+    fn run_command(&mut self, idx: usize) -> Result<(), ErrorKind> {
+        let hash = match self.selected_hash() {
+            Some(hash) => hash,
+            None => return Ok(()),
+        };
+        let command = self.keys.commands[idx].replace("{hash}", &hash);
+
+        let prompt: String = format!(" Run '{}'? [y/N]", command)
+            .chars()
+            .take(self.width)
+            .collect();
+        self.output()
+            .execute(MoveTo(0, self.page_size() as u16))?
+            .execute(Clear(ClearType::CurrentLine))?
+            .execute(Print(prompt))?;
+        let confirmed = loop {
+            if let Event::Key(evt) = crossterm::event::read()? {
+                break matches!(evt.code, KeyCode::Char('y') | KeyCode::Char('Y'));
+            }
+        };
+        if !confirmed {
+            self.message = Some("Cancelled".to_string());
+            return Ok(());
+        }
+
+        let output = if cfg!(windows) {
+            Command::new("cmd")
+                .arg("/C")
+                .arg(&command)
+                .stdin(Stdio::null())
+                .output()
+        } else {
+            Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .stdin(Stdio::null())
+                .output()
+        };
+        let mut lines = match output {
+            Ok(output) => {
+                let mut lines: Vec<String> = String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .chain(String::from_utf8_lossy(&output.stderr).lines())
+                    .map(|line| line.to_string())
+                    .collect();
+                if !output.status.success() {
+                    lines.push(format!("Command failed with {}", output.status));
+                }
+                lines
+            }
+            Err(err) => vec![format!("Command failed: {}", err)],
+        };
+        if lines.is_empty() {
+            self.message = Some(format!("Ran '{}'", command));
+            Ok(())
+        } else {
+            lines.insert(0, format!("$ {}", command));
+            self.show_overlay(&lines)
+        }
+    }
+
+    fn copy_hash(&mut self) {
+        let hash = match self.selected_hash() {
+            Some(hash) => hash,
+            None => return,
+        };
+        let clipboard = match self.clipboard.take() {
+            Some(clipboard) => Ok(clipboard),
+            None => arboard::Clipboard::new(),
+        };
+        self.message = Some(match clipboard {
+            Ok(mut clipboard) => {
+                let result = clipboard.set_text(&hash);
+                self.clipboard = Some(clipboard);
+                match result {
+                    Ok(()) => format!("Copied {}", hash),
+                    Err(err) => format!("Copying failed: {}", err),
+                }
+            }
+            Err(err) => format!("Copying failed: {}", err),
+        });
+    }
+
+    /// The hash of the selected commit. Shows a message if no commit is selected.
+    fn selected_hash(&mut self) -> Option<String> {
+        match (self.selected, self.hashes) {
+            (Some(commit), Some(hashes)) => Some(hashes(commit)),
+            (None, _) => {
+                self.message = Some("Select a commit first".to_string());
+                None
+            }
+            (_, None) => {
+                self.message = Some("Commit hashes are not available here".to_string());
+                None
+            }
+        }
+    }
+
+    /// The stream to draw on.
+    fn output(&self) -> Box<dyn Write> {
+        if self.picking {
+            Box::new(stderr())
+        } else {
+            Box::new(stdout())
+        }
+    }
+
+    /// Number of graph rows visible at once.
+    fn page_size(&self) -> usize {
+        self.height.saturating_sub(1).max(1)
+    }
+
+    fn max_top(&self) -> usize {
+        self.content
+            .graph_lines
+            .len()
+            .saturating_sub(self.page_size())
+    }
+
+    fn scroll_by(&mut self, rows: i64) -> Result<(), ErrorKind> {
+        let top = (self.top as i64 + rows).max(0) as usize;
+        self.load_rows(top + self.page_size())?;
+        self.top = top.min(self.max_top());
+        Ok(())
+    }
+
+    /// Replaces the content by the complete graph if it has less than `count` rows
+    /// and is only the leading part of the graph.
+    fn load_rows(&mut self, count: usize) -> Result<(), ErrorKind> {
+        if count > self.content.graph_lines.len() {
+            if let Some(rest) = self.rest.take() {
+                self.content = rest().map_err(std::io::Error::other)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Selects the commit in the given row and scrolls it into view.
+    fn select(&mut self, row: usize) {
+        self.selected = Some(self.content.rows.row_commits[row]);
+        if row < self.top {
+            self.top = row;
+        } else if row >= self.top + self.page_size() {
+            self.top = (row + 1 - self.page_size()).min(self.max_top());
+        }
+    }
+
+    fn line(&self, row: usize) -> String {
+        let is_selected = self
+            .selected
+            .map(|commit| self.content.rows.commit_rows[commit])
+            == Some(row);
+        let is_marked = match &self.marked {
+            Some(marked) => marked
+                .iter()
+                .any(|commit| self.content.rows.commit_rows[*commit] == row),
+            None => false,
+        };
+        format!(
+            "{}{}{}  {}",
+            if is_selected { ">" } else { " " },
+            match (&self.marked, is_marked) {
+                (Some(_), true) => "*",
+                (Some(_), false) => " ",
+                (None, _) => "",
+            },
+            self.content.graph_lines[row],
+            self.content.text_lines[row]
+        )
+    }
+
+    fn draw(&self) -> Result<(), ErrorKind> {
+        let mut out = self.output();
+        for y in 0..self.page_size() {
+            out.queue(MoveTo(0, y as u16))?
+                .queue(Clear(ClearType::CurrentLine))?;
+            let row = self.top + y;
+            if row < self.content.graph_lines.len() {
+                out.queue(Print(self.line(row)))?;
+            }
+        }
+        let status: String = self.status().chars().take(self.width).collect();
+        out.queue(MoveTo(0, self.page_size() as u16))?
+            .queue(Clear(ClearType::CurrentLine))?
+            .queue(SetAttribute(Attribute::Reverse))?
+            .queue(Print(format!("{:<1$}", status, self.width)))?
+            .queue(SetAttribute(Attribute::Reset))?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// The status line, showing the position and the nearest branch or tag.
+    fn status(&self) -> String {
+        let total = self.content.graph_lines.len();
+        let last = (self.top + self.page_size()).min(total);
+        if let Some(message) = &self.message {
+            return format!(" {}", message);
+        }
+        let mut status = match self.banner {
+            Some(banner) => format!(" {} \u{2014}", banner),
+            None => String::new(),
+        };
+        if self.rest.is_some() {
+            // The total is not known before the rest of the graph is laid out
+            status.push_str(&format!(" line {}/{}+", self.top + 1, total));
+        } else {
+            status.push_str(&format!(
+                " line {}/{} ({}%)",
+                self.top + 1,
+                total,
+                last * 100 / total.max(1)
+            ));
+        }
+        if let Some(name) = self.nearest_ref() {
+            status.push_str(&format!(" \u{2014} viewing commits around {}", name));
+        }
+        if let Some(marked) = &self.marked {
+            status.push_str(&format!(
+                " \u{2014} {} marked, press {} to mark, {} to pick",
+                marked.len(),
+                self.keys.key_name(Action::Mark),
+                self.keys.key_name(Action::Pick)
+            ));
+        } else if self.picking {
+            status.push_str(&format!(
+                " \u{2014} press {} to pick",
+                self.keys.key_name(Action::Pick)
+            ));
+        }
+        status.push_str(&format!(
+            " \u{2014} press {} for help",
+            self.keys.key_name(Action::Help)
+        ));
+        status
+    }
+
+    /// The branch or tag closest above the center of the view,
+    /// or the first one below if there is none above.
+    fn nearest_ref(&self) -> Option<&str> {
+        let center = self.top + self.page_size() / 2;
+        self.content
+            .refs
+            .iter()
+            .filter(|(_, row)| *row <= center)
+            .max_by_key(|(_, row)| *row)
+            .or_else(|| self.content.refs.iter().min_by_key(|(_, row)| *row))
+            .map(|(name, _)| &name[..])
+    }
+
+    /// Shows all key bindings until any key is pressed.
+    fn show_help(&self) -> Result<(), ErrorKind> {
+        let mut lines: Vec<String> = self
+            .keys
+            .names
+            .iter()
+            .map(|(action, keys)| {
+                format!("{:<20} {}", keys.join(", "), self.keys.description(*action))
+            })
+            .collect();
+        lines.push(format!("{:<20} {}", "wheel", "Scroll"));
+        lines.push(format!("{:<20} {}", "click", "Select a commit"));
+        lines.push(format!("{:<20} {}", "double-click", "Show commit details"));
+        self.show_overlay(&lines)
+    }
+
+    /// Shows the details of a commit until any key is pressed.
+    fn show_details(&self, commit: usize) -> Result<(), ErrorKind> {
+        let lines = (self.details)(commit).unwrap_or_else(|err| vec![err]);
+        self.show_overlay(&lines)
+    }
+
+    /// Shows lines of text over the graph until any key is pressed.
+    fn show_overlay(&self, lines: &[String]) -> Result<(), ErrorKind> {
+        let mut out = self.output();
+        out.queue(Clear(ClearType::All))?;
+        for (y, line) in lines.iter().take(self.page_size()).enumerate() {
+            out.queue(MoveTo(0, y as u16))?
+                .queue(Print(format!(" {}", line)))?;
+        }
+        out.queue(MoveTo(0, self.page_size() as u16))?
+            .queue(Print(" >>> Press any key to return"))?;
+        out.flush()?;
+        loop {
+            if let Event::Key(_) = crossterm::event::read()? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// The row of the commit with the given number, according to the line numbers shown.
+    fn numbered_row(&self, number: usize) -> Option<usize> {
+        match self.line_numbers {
+            Some(LineNumbers::Row) => number
+                .checked_sub(1)
+                .filter(|row| *row < self.content.graph_lines.len()),
+            Some(LineNumbers::Index) | None => self.content.rows.commit_rows.get(number).copied(),
+        }
+    }
+
+    /// Prompts for a branch or tag name, with completion on Tab, or a commit number.
+    ///
+    /// Returns the row of the selected ref's tip or commit, or `None` if the prompt was cancelled
+    /// or nothing matches. Refs named like a number take precedence over the commit number.
+    fn read_ref(&self) -> Result<Option<usize>, ErrorKind> {
+        let mut input = String::new();
+        loop {
+            let matches: Vec<_> = self
+                .content
+                .refs
+                .iter()
+                .filter(|(name, _)| name.starts_with(&input))
+                .collect();
+
+            let mut prompt = format!(" Go to: {}", input);
+            if !input.is_empty() && matches.len() > 1 {
+                prompt.push_str(&format!(
+                    "  [{}]",
+                    matches.iter().map(|(name, _)| name).join(", ")
+                ));
+            }
+            let prompt: String = prompt.chars().take(self.width).collect();
+            self.output()
+                .execute(MoveTo(0, self.page_size() as u16))?
+                .execute(Clear(ClearType::CurrentLine))?
+                .execute(MoveToColumn(0))?
+                .execute(Print(prompt))?;
+
+            if let Event::Key(evt) = crossterm::event::read()? {
+                match evt.code {
+                    KeyCode::Esc => return Ok(None),
+                    KeyCode::Enter => {
+                        let exact = self.content.refs.iter().find(|(name, _)| name == &input);
+                        let unique = matches.first().filter(|_| matches.len() == 1);
+                        return Ok(match (exact, input.parse()) {
+                            (Some((_, row)), _) => Some(*row),
+                            (None, Ok(number)) => self.numbered_row(number),
+                            (None, Err(_)) => unique.map(|(_, row)| *row),
+                        });
+                    }
+                    KeyCode::Tab => {
+                        if let Some((first, _)) = matches.first() {
+                            let common =
+                                matches
+                                    .iter()
+                                    .fold(first.chars().count(), |len, (name, _)| {
+                                        first
+                                            .chars()
+                                            .zip(name.chars())
+                                            .take(len)
+                                            .take_while(|(c1, c2)| c1 == c2)
+                                            .count()
+                                    });
+                            input = first.chars().take(common).collect();
+                        }
+                    }
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Char('c') if evt.modifiers == KeyModifiers::CONTROL => {
+                        return Ok(None)
+                    }
+                    KeyCode::Char(c) => input.push(c),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_key, Action, KeyBindings};
+    use crate::settings::KeysDef;
+    use crossterm::event::{KeyCode, KeyModifiers};
+    use std::collections::HashMap;
+
+    #[test]
+    fn parse_keys() {
+        assert_eq!(parse_key("q"), Ok((KeyCode::Char('q'), KeyModifiers::NONE)));
+        assert_eq!(parse_key("G"), Ok((KeyCode::Char('G'), KeyModifiers::NONE)));
+        assert_eq!(
+            parse_key("pgdn"),
+            Ok((KeyCode::PageDown, KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_key("space"),
+            Ok((KeyCode::Char(' '), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_key("ctrl+f"),
+            Ok((KeyCode::Char('f'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            parse_key("ctrl++"),
+            Ok((KeyCode::Char('+'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(parse_key("F5"), Ok((KeyCode::F(5), KeyModifiers::NONE)));
+        assert!(parse_key("f13").is_err());
+        assert!(parse_key("hyper+q").is_err());
+        assert!(parse_key("pagedwn").is_err());
+    }
+
+    #[test]
+    fn validate_bindings() {
+        let keys = KeyBindings::default();
+        assert_eq!(
+            keys.action(KeyCode::Char('c'), KeyModifiers::CONTROL),
+            Some(Action::Quit)
+        );
+        assert_eq!(keys.action(KeyCode::Char('c'), KeyModifiers::NONE), None);
+
+        let vi = KeysDef {
+            down: vec!["j".to_string(), "down".to_string()],
+            up: vec!["k".to_string(), "up".to_string()],
+            ..KeysDef::default()
+        };
+        let keys = KeyBindings::from(&vi).unwrap();
+        assert_eq!(
+            keys.action(KeyCode::Char('j'), KeyModifiers::NONE),
+            Some(Action::Down)
+        );
+        assert_eq!(keys.key_name(Action::Up), "k");
+
+        let conflict = KeysDef {
+            down: vec!["q".to_string()],
+            ..KeysDef::default()
+        };
+        assert!(KeyBindings::from(&conflict).is_err());
+    }
+
+    #[test]
+    fn bind_commands() {
+        let commands: HashMap<_, _> = [
+            ("t".to_string(), "git tag wip {hash}".to_string()),
+            ("c".to_string(), "git checkout {hash}".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let keys = KeyBindings::default().with_commands(&commands).unwrap();
+        assert_eq!(
+            keys.action(KeyCode::Char('c'), KeyModifiers::NONE),
+            Some(Action::Command(0))
+        );
+        assert_eq!(
+            keys.description(Action::Command(1)),
+            "Run 'git tag wip {hash}'"
+        );
+
+        let conflict: HashMap<_, _> = [("q".to_string(), "git show {hash}".to_string())]
+            .into_iter()
+            .collect();
+        assert!(KeyBindings::default().with_commands(&conflict).is_err());
+    }
+}
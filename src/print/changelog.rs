@@ -0,0 +1,185 @@
+//! Create changelogs in Markdown format, grouping commits by the merges that brought them in.
+
+use crate::graph::{parse_merge_summary, GitGraph};
+use crate::settings::Settings;
+use crate::source::Oid;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Creates a changelog with a section per merge commit, listing the commits brought in by the merge.
+/// Merges within merged branches are nested, commits not brought in by any merge are listed last.
+pub fn print_changelog(graph: &GitGraph, settings: &Settings) -> Result<String, String> {
+    let mut out = String::new();
+    // Boundary commits are not part of the release
+    let mut listed: HashSet<_> = graph
+        .boundary
+        .iter()
+        .filter_map(|oid| graph.indices.get(oid).cloned())
+        .collect();
+
+    writeln!(out, "## Changelog").unwrap();
+
+    for idx in 0..graph.commits.len() {
+        if graph.commits[idx].is_merge && !listed.contains(&idx) {
+            // Merges that bring in no further commits are listed like other commits
+            let mut items = String::new();
+            write_merged(&mut items, graph, idx, 0, &mut listed, settings)?;
+            if !items.is_empty() {
+                listed.insert(idx);
+                writeln!(out, "\n### {}", title(graph, idx, settings)?).unwrap();
+                out.push_str(&items);
+            }
+        }
+    }
+
+    if listed.len() < graph.commits.len() {
+        writeln!(out, "\n### Other commits").unwrap();
+        for idx in 0..graph.commits.len() {
+            if !listed.contains(&idx) {
+                writeln!(out, "- {}", title(graph, idx, settings)?).unwrap();
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Lists the commits brought in by a merge that are not listed yet, with nested merges indented.
+fn write_merged(
+    out: &mut String,
+    graph: &GitGraph,
+    merge: usize,
+    depth: usize,
+    listed: &mut HashSet<usize>,
+    settings: &Settings,
+) -> Result<(), String> {
+    let info = &graph.commits[merge];
+    let merged = ancestors(graph, info.parents[1]);
+    let base = ancestors(graph, info.parents[0]);
+    for idx in merge..graph.commits.len() {
+        if merged.contains(&idx) && !base.contains(&idx) && listed.insert(idx) {
+            writeln!(
+                out,
+                "{}- {}",
+                "  ".repeat(depth),
+                title(graph, idx, settings)?
+            )
+            .unwrap();
+            if graph.commits[idx].is_merge {
+                write_merged(out, graph, idx, depth + 1, listed, settings)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Indices of a commit and all its ancestors in the graph.
+fn ancestors(graph: &GitGraph, oid: Option<Oid>) -> HashSet<usize> {
+    let mut result = HashSet::new();
+    let mut stack: Vec<usize> = oid
+        .and_then(|oid| graph.indices.get(&oid))
+        .cloned()
+        .into_iter()
+        .collect();
+    while let Some(idx) = stack.pop() {
+        if result.insert(idx) {
+            stack.extend(
+                graph.commits[idx]
+                    .parents
+                    .iter()
+                    .flatten()
+                    .filter_map(|oid| graph.indices.get(oid)),
+            );
+        }
+    }
+    result
+}
+
+/// The commit's summary, or the merged branch for merge commits, followed by the abbreviated hash.
+fn title(graph: &GitGraph, index: usize, settings: &Settings) -> Result<String, String> {
+    let info = &graph.commits[index];
+    let summary = graph.commit(info.oid)?.summary();
+    let summary = if info.is_merge {
+        parse_merge_summary(&summary, &settings.merge_patterns).unwrap_or(summary)
+    } else {
+        summary
+    };
+    Ok(format!(
+        "{} ({})",
+        summary,
+        &info.oid.to_string()[..graph.abbrev]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::print_changelog;
+    use crate::graph::GitGraph;
+    use crate::print::unicode::print_unicode;
+    use crate::settings::Settings;
+    use crate::source::memory::{entry, oid, MemorySource};
+    use crate::source::range::RangeSource;
+
+    /// The graph of the commits between tags `v1` and `v2`.
+    fn release() -> (GitGraph, Settings) {
+        let log = [
+            entry(
+                6,
+                &[4, 5],
+                "HEAD -> refs/heads/main, tag: refs/tags/v2",
+                "Merge branch 'feature/a'",
+            ),
+            entry(5, &[3], "", "Add a"),
+            entry(4, &[2], "", "Fix b"),
+            entry(3, &[2], "", "Start a"),
+            entry(2, &[1], "tag: refs/tags/v1", "Release 1"),
+            entry(1, &[], "", "Initial commit"),
+        ]
+        .concat();
+        let settings = Settings::default();
+        let source = MemorySource::from_git_log(&log).unwrap();
+        let source = RangeSource::new(source, "v1", "v2").unwrap();
+        let graph = GitGraph::from_source(source, &settings, None).unwrap();
+        (graph, settings)
+    }
+
+    #[test]
+    fn release_graph() {
+        let (graph, settings) = release();
+        let (graph_lines, text_lines, _) = print_unicode(&graph, &settings).unwrap();
+        let lines: Vec<_> = graph_lines
+            .iter()
+            .zip(&text_lines)
+            .map(|(graph, text)| format!("{} {}", graph, text).trim_end().to_string())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                format!(
+                    "○<┐ {} (HEAD -> main) [v2] Merge branch 'feature/a'",
+                    oid(6)
+                ),
+                format!("│ ● {} Add a", oid(5)),
+                format!("● │ {} Fix b", oid(4)),
+                format!("  ● {} Start a", oid(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn changelog() {
+        let (graph, settings) = release();
+        assert_eq!(
+            print_changelog(&graph, &settings).unwrap(),
+            format!(
+                "## Changelog\n\n\
+                 ### feature/a ({})\n- Add a ({})\n- Start a ({})\n\n\
+                 ### Other commits\n- Fix b ({})\n",
+                oid(6),
+                oid(5),
+                oid(3),
+                oid(4)
+            )
+        );
+    }
+}
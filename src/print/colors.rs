@@ -14,6 +14,18 @@ pub fn to_terminal_color(color: &str) -> Result<u8, String> {
     }
 }
 
+/// Color palette for color vision deficiencies (Okabe & Ito), as terminal colors
+/// (nearest index in the 256-color palette) and SVG colors.
+pub const ACCESSIBLE_COLORS: [(u8, &str); 7] = [
+    (25, "#0072B2"),
+    (214, "#E69F00"),
+    (36, "#009E73"),
+    (166, "#D55E00"),
+    (74, "#56B4E9"),
+    (175, "#CC79A7"),
+    (227, "#F0E442"),
+];
+
 macro_rules! hashmap {
     ($( $key: expr => $val: expr ),*) => {{
          let mut map = ::std::collections::HashMap::new();
@@ -0,0 +1,241 @@
+//! Dump layout decisions in JSON format, for bug reports, and check the invariants of the layout.
+//!
+//! Commits are identified by index only, so that the dump contains no hashes, messages or authors.
+
+use crate::graph::GitGraph;
+use crate::print::unicode::{get_inserts, Occ};
+use crate::print::{branch_column, commit_branch, get_deviate_index};
+use crate::settings::Settings;
+use serde_derive::Serialize;
+
+/// Layout decisions of a graph.
+#[derive(Serialize)]
+pub struct DebugDump {
+    pub version: String,
+    pub density: u8,
+    pub reverse_commit_order: bool,
+    pub commits: Vec<DebugCommit>,
+    pub branches: Vec<DebugBranch>,
+    /// Rows at each commit, including those inserted for lines between columns
+    pub inserts: Vec<DebugInsert>,
+}
+
+/// A commit and the branch it is traced to.
+#[derive(Serialize)]
+pub struct DebugCommit {
+    /// Indices of the parents in `commits`, if within the graph
+    pub parents: [Option<usize>; 2],
+    pub is_merge: bool,
+    /// Index of the branch the commit is traced to
+    pub branch_trace: Option<usize>,
+    /// Indices of branches pointing to this commit
+    pub branches: Vec<usize>,
+    /// Indices of tags pointing to this commit
+    pub tags: Vec<usize>,
+}
+
+/// A branch or tag, with its column and the groups that determined it.
+#[derive(Serialize)]
+pub struct DebugBranch {
+    pub name: String,
+    pub persistence: u8,
+    pub is_remote: bool,
+    pub is_merged: bool,
+    pub is_tag: bool,
+    pub source_branch: Option<usize>,
+    pub target_branch: Option<usize>,
+    /// First and last commit index of the branch
+    pub range: (Option<usize>, Option<usize>),
+    pub order_group: usize,
+    pub source_order_group: Option<usize>,
+    pub target_order_group: Option<usize>,
+    pub column: Option<usize>,
+}
+
+/// The rows laid out at a commit. The first row usually contains the commit itself.
+#[derive(Serialize)]
+pub struct DebugInsert {
+    pub commit: usize,
+    pub rows: Vec<Vec<DebugOccupation>>,
+}
+
+/// A commit node or a line between columns, occupying part of a row.
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DebugOccupation {
+    Commit {
+        commit: usize,
+        column: usize,
+    },
+    Range {
+        commit: usize,
+        parent: usize,
+        start: usize,
+        end: usize,
+    },
+}
+
+impl DebugDump {
+    /// Records the layout decisions for a graph.
+    pub fn new(graph: &GitGraph, settings: &Settings) -> Result<Self, String> {
+        let commits = graph
+            .commits
+            .iter()
+            .map(|info| DebugCommit {
+                parents: [0, 1]
+                    .map(|p| info.parents[p].and_then(|oid| graph.indices.get(&oid).cloned())),
+                is_merge: info.is_merge,
+                branch_trace: info.branch_trace,
+                branches: info.branches.clone(),
+                tags: info.tags.clone(),
+            })
+            .collect();
+
+        let branches = graph
+            .all_branches
+            .iter()
+            .map(|branch| DebugBranch {
+                name: branch.name.to_string(),
+                persistence: branch.persistence,
+                is_remote: branch.is_remote,
+                is_merged: branch.is_merged,
+                is_tag: branch.is_tag,
+                source_branch: branch.source_branch,
+                target_branch: branch.target_branch,
+                range: branch.range,
+                order_group: branch.visual.order_group,
+                source_order_group: branch.visual.source_order_group,
+                target_order_group: branch.visual.target_order_group,
+                column: branch.visual.column,
+            })
+            .collect();
+
+        let mut inserts: Vec<_> = get_inserts(graph, &graph.commits, settings.density)?
+            .into_iter()
+            .map(|(commit, rows)| DebugInsert {
+                commit,
+                rows: rows
+                    .iter()
+                    .map(|row| row.iter().map(DebugOccupation::from).collect())
+                    .collect(),
+            })
+            .collect();
+        inserts.sort_by_key(|insert| insert.commit);
+
+        Ok(DebugDump {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            density: settings.density,
+            reverse_commit_order: settings.reverse_commit_order,
+            commits,
+            branches,
+            inserts,
+        })
+    }
+}
+
+impl From<&Occ> for DebugOccupation {
+    fn from(occ: &Occ) -> Self {
+        match *occ {
+            Occ::Commit(commit, column) => DebugOccupation::Commit { commit, column },
+            Occ::Range(commit, parent, start, end) => DebugOccupation::Range {
+                commit,
+                parent,
+                start,
+                end,
+            },
+        }
+    }
+}
+
+/// Creates a JSON dump of the layout decisions for a graph.
+pub fn print_debug_dump(graph: &GitGraph, settings: &Settings) -> Result<String, String> {
+    serde_json::to_string_pretty(&DebugDump::new(graph, settings)?).map_err(|err| err.to_string())
+}
+
+/// Checks the invariants of a graph's layout, see `GitGraph::validate`.
+pub(crate) fn layout_violations(graph: &GitGraph) -> Vec<String> {
+    let mut violations = vec![];
+    let short_hash = |idx: usize| {
+        let mut hash = graph.commits[idx].oid.to_string();
+        hash.truncate(graph.abbrev);
+        hash
+    };
+    let describe = |idx: usize| match graph.commits[idx].branch_trace {
+        Some(trace) => format!("{} ({})", short_hash(idx), graph.all_branches[trace].name),
+        None => short_hash(idx),
+    };
+
+    let columns: Vec<_> = (0..graph.commits.len())
+        .map(|idx| commit_branch(graph, idx).and_then(branch_column))
+        .collect();
+    for (idx, column) in columns.iter().enumerate() {
+        if let Err(err) = column {
+            violations.push(format!("Commit {}: {}", describe(idx), err));
+        }
+    }
+
+    let last = graph.commits.len();
+    let ranges: Vec<_> = graph
+        .all_branches
+        .iter()
+        .filter(|branch| branch.range.0.is_some() || branch.range.1.is_some())
+        .filter_map(|branch| {
+            let range = (branch.range.0.unwrap_or(0), branch.range.1.unwrap_or(last));
+            branch.visual.column.map(|column| (branch, column, range))
+        })
+        .collect();
+    for (idx, (branch, column, (start, end))) in ranges.iter().enumerate() {
+        for (other, other_column, (other_start, other_end)) in &ranges[(idx + 1)..] {
+            if column == other_column && start <= other_end && end >= other_start {
+                let from = *start.max(other_start);
+                let to = (*end.min(other_end)).min(last - 1);
+                violations.push(format!(
+                    "Branches '{}' and '{}' overlap in column {}, from commit {} to {}",
+                    branch.name,
+                    other.name,
+                    column,
+                    short_hash(from),
+                    short_hash(to)
+                ));
+            }
+        }
+    }
+
+    // Commits of other branches in the column of commit `idx`, between two indices (exclusive).
+    // Lines running along their own branch may share its column with the branch's commits.
+    let commit_between = |idx: usize, from: usize, to: usize| {
+        let (column, trace) = (columns[idx].as_ref().ok(), graph.commits[idx].branch_trace);
+        ((from + 1)..to).find(|&between| {
+            columns[between].as_ref().ok() == column && graph.commits[between].branch_trace != trace
+        })
+    };
+    for (idx, info) in graph.commits.iter().enumerate() {
+        for par_oid in info.parents.iter().flatten() {
+            let Some(&par_idx) = graph.indices.get(par_oid) else {
+                continue;
+            };
+            let (Ok(column), Ok(par_column)) = (&columns[idx], &columns[par_idx]) else {
+                continue;
+            };
+            let crossed = if column == par_column {
+                commit_between(idx, idx, par_idx)
+            } else {
+                get_deviate_index(graph, idx, par_idx)
+                    .ok()
+                    .and_then(|split_idx| {
+                        commit_between(idx, idx, split_idx + 1)
+                            .or_else(|| commit_between(par_idx, split_idx, par_idx))
+                    })
+            };
+            if let Some(crossed) = crossed {
+                violations.push(format!(
+                    "Line from commit {} to {} passes through commit {}",
+                    describe(idx),
+                    describe(par_idx),
+                    describe(crossed)
+                ));
+            }
+        }
+    }
+    violations
+}
@@ -0,0 +1,235 @@
+//! Create diagram sources for Graphviz DOT, PlantUML and D2, to include graphs in documentation.
+//!
+//! All formats are written from the same abstract diagram: one lane per branch,
+//! containing the commits of the branch, and edges from commits to their parents.
+
+use crate::graph::GitGraph;
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// Diagram languages.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DiagramFormat {
+    /// Graphviz DOT
+    Dot,
+    /// PlantUML, approximated by nested rectangles
+    PlantUml,
+    /// D2 (Terrastruct)
+    D2,
+}
+
+impl FromStr for DiagramFormat {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "dot" | "graphviz" => Ok(DiagramFormat::Dot),
+            "plantuml" | "puml" => Ok(DiagramFormat::PlantUml),
+            "d2" => Ok(DiagramFormat::D2),
+            _ => Err(format!(
+                "Unknown diagram format '{}'. Must be one of [dot|plantuml|d2]",
+                str
+            )),
+        }
+    }
+}
+
+/// A graph independent of the diagram language.
+pub struct Diagram {
+    pub lanes: Vec<Lane>,
+    pub edges: Vec<Edge>,
+}
+
+/// The commits of a branch, in the order of the graph.
+pub struct Lane {
+    pub id: String,
+    pub name: String,
+    pub color: String,
+    pub nodes: Vec<DiagramNode>,
+}
+
+/// A commit.
+pub struct DiagramNode {
+    pub id: String,
+    pub label: String,
+}
+
+/// A line from a commit to one of its parents, by node ids.
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    /// From a merge commit to a parent other than the first
+    pub merge: bool,
+}
+
+impl Diagram {
+    /// The diagram of a graph, with lanes ordered by column.
+    pub fn new(graph: &GitGraph) -> Result<Self, String> {
+        let mut lanes: Vec<(usize, Lane)> = vec![];
+        let mut edges = vec![];
+        for info in &graph.commits {
+            let Some(trace) = info.branch_trace else {
+                continue;
+            };
+            let commit = graph.commit(info.oid)?;
+            let hash = info.oid.to_string();
+            let abbrev = &hash[..graph.abbrev.min(hash.len())];
+            let node = DiagramNode {
+                id: node_id(abbrev),
+                label: format!("{} {}", abbrev, commit.summary()),
+            };
+            match lanes.iter_mut().find(|(branch, _)| *branch == trace) {
+                Some((_, lane)) => lane.nodes.push(node),
+                None => {
+                    let branch = &graph.all_branches[trace];
+                    lanes.push((
+                        trace,
+                        Lane {
+                            id: String::new(),
+                            name: branch.display_name().to_string(),
+                            color: branch.visual.svg_color.to_string(),
+                            nodes: vec![node],
+                        },
+                    ));
+                }
+            }
+            for (p, parent) in info.parents.iter().enumerate() {
+                if let Some(parent) = parent.filter(|oid| graph.indices.contains_key(oid)) {
+                    let hash = parent.to_string();
+                    edges.push(Edge {
+                        from: node_id(abbrev),
+                        to: node_id(&hash[..graph.abbrev.min(hash.len())]),
+                        merge: p > 0,
+                    });
+                }
+            }
+        }
+        let lanes = lanes
+            .into_iter()
+            .sorted_by_key(|(branch, _)| (graph.all_branches[*branch].visual.column, *branch))
+            .enumerate()
+            .map(|(idx, (_, lane))| Lane {
+                id: format!("lane{}", idx),
+                ..lane
+            })
+            .collect();
+        Ok(Diagram { lanes, edges })
+    }
+
+    /// The diagram's source in the given language.
+    pub fn print(&self, format: DiagramFormat) -> String {
+        match format {
+            DiagramFormat::Dot => self.print_dot(),
+            DiagramFormat::PlantUml => self.print_plantuml(),
+            DiagramFormat::D2 => self.print_d2(),
+        }
+    }
+
+    fn print_dot(&self) -> String {
+        let mut out = String::new();
+        out.push_str("digraph git {\n");
+        out.push_str("  rankdir=TB;\n");
+        out.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+        for lane in &self.lanes {
+            writeln!(out, "  subgraph cluster_{} {{", lane.id).unwrap();
+            writeln!(out, "    label=\"{}\";", quoted(&lane.name)).unwrap();
+            writeln!(out, "    color=\"{}\";", quoted(&lane.color)).unwrap();
+            for node in &lane.nodes {
+                writeln!(out, "    {} [label=\"{}\"];", node.id, quoted(&node.label)).unwrap();
+            }
+            out.push_str("  }\n");
+        }
+        for edge in &self.edges {
+            let style = if edge.merge { " [style=dashed]" } else { "" };
+            writeln!(out, "  {} -> {}{};", edge.from, edge.to, style).unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn print_plantuml(&self) -> String {
+        let mut out = String::new();
+        out.push_str("@startuml\n");
+        for lane in &self.lanes {
+            // PlantUML has no escapes in quoted names
+            writeln!(
+                out,
+                "rectangle \"{}\" as {} {} {{",
+                lane.name.replace('"', "'"),
+                lane.id,
+                plantuml_color(&lane.color)
+            )
+            .unwrap();
+            for node in &lane.nodes {
+                writeln!(
+                    out,
+                    "  card \"{}\" as {}",
+                    node.label.replace('"', "'"),
+                    node.id
+                )
+                .unwrap();
+            }
+            out.push_str("}\n");
+        }
+        for edge in &self.edges {
+            let arrow = if edge.merge { "..>" } else { "-->" };
+            writeln!(out, "{} {} {}", edge.from, arrow, edge.to).unwrap();
+        }
+        out.push_str("@enduml\n");
+        out
+    }
+
+    fn print_d2(&self) -> String {
+        let mut out = String::new();
+        out.push_str("direction: down\n");
+        // Edges between containers need the container's id as prefix
+        let mut lane_of = HashMap::new();
+        for lane in &self.lanes {
+            writeln!(out, "{}: \"{}\" {{", lane.id, quoted(&lane.name)).unwrap();
+            writeln!(out, "  style.stroke: \"{}\"", quoted(&lane.color)).unwrap();
+            for node in &lane.nodes {
+                writeln!(out, "  {}: \"{}\"", node.id, quoted(&node.label)).unwrap();
+                lane_of.insert(&node.id[..], &lane.id[..]);
+            }
+            out.push_str("}\n");
+        }
+        for edge in &self.edges {
+            if let (Some(from), Some(to)) = (lane_of.get(&edge.from[..]), lane_of.get(&edge.to[..]))
+            {
+                let style = if edge.merge {
+                    " {style.stroke-dash: 3}"
+                } else {
+                    ""
+                };
+                writeln!(out, "{}.{} -> {}.{}{}", from, edge.from, to, edge.to, style).unwrap();
+            }
+        }
+        out
+    }
+}
+
+/// Creates the source of a diagram of the graph in the given language.
+pub fn print_diagram(graph: &GitGraph, format: DiagramFormat) -> Result<String, String> {
+    Ok(Diagram::new(graph)?.print(format))
+}
+
+/// The id of a commit's node, from its abbreviated hash. Ids can't start with a digit in all languages.
+fn node_id(abbrev: &str) -> String {
+    format!("c_{}", abbrev)
+}
+
+/// Escapes a string for double quotes in DOT and D2.
+fn quoted(str: &str) -> String {
+    str.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// A color in PlantUML, which prefixes color names with `#` as well.
+fn plantuml_color(color: &str) -> String {
+    if color.starts_with('#') {
+        color.to_string()
+    } else {
+        format!("#{}", color)
+    }
+}
@@ -1,10 +1,14 @@
 //! Formatting of commits.
 
-use chrono::{FixedOffset, Local, TimeZone};
-use git2::{Commit, Time};
+use crate::source::{CommitData, Signature, TagData};
+use chrono::{FixedOffset, Local, TimeZone, Utc};
+use itertools::Itertools;
 use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
 use std::fmt::Write;
 use std::str::FromStr;
+use textwrap::core::display_width;
 use textwrap::Options;
 use yansi::Paint;
 
@@ -18,6 +22,104 @@ pub enum CommitFormat {
     Format(String),
 }
 
+/// Time zone for displaying dates.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum DateTimeZone {
+    /// The time zone recorded in the commit, like Git does
+    Commit,
+    /// The local time zone
+    Local,
+    Utc,
+}
+
+impl FromStr for DateTimeZone {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "commit" => Ok(DateTimeZone::Commit),
+            "local" => Ok(DateTimeZone::Local),
+            "utc" => Ok(DateTimeZone::Utc),
+            _ => Err(format!(
+                "Unknown date time zone '{}'. Must be one of [local|utc|commit]",
+                str
+            )),
+        }
+    }
+}
+
+/// Rendering of emojis and gitmoji shortcodes like `:sparkles:` in commit messages.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum Emoji {
+    /// Messages as they are
+    #[default]
+    Keep,
+    /// Remove shortcodes and emojis, which break the alignment in some terminals
+    Strip,
+    /// Replace shortcodes by their emojis, like `:sparkles:` by ✨
+    Replace,
+}
+
+impl FromStr for Emoji {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "keep" => Ok(Emoji::Keep),
+            "strip" => Ok(Emoji::Strip),
+            "replace" => Ok(Emoji::Replace),
+            _ => Err(format!(
+                "Unknown emoji rendering '{}'. Must be one of [keep|strip|replace]",
+                str
+            )),
+        }
+    }
+}
+
+impl Emoji {
+    /// Applies the rendering to a commit message. Unknown shortcodes, like `:foo:`, are kept.
+    /// Lines with stripped emojis get single spaces between words.
+    pub fn apply(self, message: &str) -> String {
+        match self {
+            Emoji::Keep => message.to_string(),
+            Emoji::Replace => SHORTCODE
+                .replace_all(message, |caps: &Captures| {
+                    GITMOJIS
+                        .get(&caps[1])
+                        .map(|emoji| emoji.to_string())
+                        .unwrap_or_else(|| caps[0].to_string())
+                })
+                .into_owned(),
+            Emoji::Strip => message
+                .split('\n')
+                .map(|line| {
+                    let stripped = SHORTCODE.replace_all(line, |caps: &Captures| {
+                        if GITMOJIS.contains_key(&caps[1]) {
+                            String::new()
+                        } else {
+                            caps[0].to_string()
+                        }
+                    });
+                    let stripped: String = stripped.chars().filter(|c| !is_emoji(*c)).collect();
+                    if stripped == line {
+                        return stripped;
+                    }
+                    let indent = &line[..line.len() - line.trim_start().len()];
+                    format!("{}{}", indent, stripped.split_whitespace().join(" "))
+                })
+                .join("\n"),
+        }
+    }
+}
+
+/// Whether a character is an emoji, or a modifier or joiner of emoji sequences.
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0x23E9..=0x23FA | 0xFE0F | 0x200D
+    )
+}
+
 impl FromStr for CommitFormat {
     type Err = String;
 
@@ -32,6 +134,28 @@ impl FromStr for CommitFormat {
     }
 }
 
+impl CommitFormat {
+    /// Parses a preset, a named format from the configuration, or a format string.
+    /// Presets take precedence over equally named formats.
+    pub fn from_str_with(str: &str, formats: &HashMap<String, String>) -> Result<Self, String> {
+        match (CommitFormat::from_str(str)?, formats.get(str)) {
+            (CommitFormat::Format(_), Some(format)) => CommitFormat::from_str(format),
+            (format, _) => Ok(format),
+        }
+    }
+
+    /// The name of a preset, like `medium`, or `None` for format strings.
+    pub fn preset_name(&self) -> Option<&'static str> {
+        match self {
+            CommitFormat::OneLine => Some("oneline"),
+            CommitFormat::Short => Some("short"),
+            CommitFormat::Medium => Some("medium"),
+            CommitFormat::Full => Some("full"),
+            CommitFormat::Format(_) => None,
+        }
+    }
+}
+
 const NEW_LINE: usize = 0;
 const HASH: usize = 1;
 const HASH_ABBREV: usize = 2;
@@ -49,6 +173,22 @@ const COMMITTER_DATE: usize = 13;
 const COMMITTER_DATE_SHORT: usize = 14;
 const BODY: usize = 15;
 const BODY_RAW: usize = 16;
+const TREE: usize = 17;
+const TREE_ABBREV: usize = 18;
+const ENCODING: usize = 19;
+const DECORATIONS: usize = 20;
+const SANITIZED_SUBJECT: usize = 21;
+const TRAILERS: usize = 22;
+const RELATIONS: usize = 23;
+const TAG_NAME: usize = 24;
+const TAGGER_NAME: usize = 25;
+const TAGGER_EMAIL: usize = 26;
+const TAGGER_DATE: usize = 27;
+const TAG_MESSAGE: usize = 28;
+const REBASED: usize = 29;
+
+/// Default marker of commits with a committer other than the author, for `%(rebased)`
+pub const REBASED_MARKER: &str = "↻";
 
 const MODE_SPACE: usize = 1;
 const MODE_PLUS: usize = 2;
@@ -58,7 +198,7 @@ lazy_static! {
     pub static ref PLACEHOLDERS: Vec<[String; 4]> = {
         let base = vec![
             "n", "H", "h", "P", "p", "d", "s", "an", "ae", "ad", "as", "cn", "ce", "cd", "cs", "b",
-            "B",
+            "B", "T", "t", "e", "D", "f",
         ];
         base.iter()
             .map(|b| {
@@ -71,15 +211,222 @@ lazy_static! {
             })
             .collect()
     };
+    /// Trailer placeholders like `%(trailers:key=Signed-off-by,valueonly)`, with optional mode
+    static ref TRAILER_PLACEHOLDER: Regex = Regex::new(r"%[ +-]?\(trailers(:[^)]*)?\)").unwrap();
+    /// Placeholder for the relations of the commit's branch, with optional mode
+    static ref RELATIONS_PLACEHOLDER: Regex = Regex::new(r"%[ +-]?\(relations\)").unwrap();
+    /// Placeholders for the commit's annotated tag, like `%(taggername)`, with optional mode
+    static ref TAG_PLACEHOLDER: Regex =
+        Regex::new(r"%[ +-]?\((tag|taggername|taggeremail|taggerdate|tagmessage)\)").unwrap();
+    /// Placeholder for the marker of commits with a committer other than the author, with optional mode
+    static ref REBASED_PLACEHOLDER: Regex = Regex::new(r"%[ +-]?\(rebased\)").unwrap();
+    /// A shortcode like `:sparkles:`
+    static ref SHORTCODE: Regex = Regex::new(r":([a-z0-9_+-]+):").unwrap();
+    /// Emojis of the gitmoji shortcodes, and of a few other common ones
+    static ref GITMOJIS: HashMap<&'static str, &'static str> = [
+        ("adhesive_bandage", "🩹"),
+        ("airplane", "✈\u{fe0f}"),
+        ("alembic", "⚗\u{fe0f}"),
+        ("alien", "👽"),
+        ("ambulance", "🚑"),
+        ("arrow_down", "⬇\u{fe0f}"),
+        ("arrow_up", "⬆\u{fe0f}"),
+        ("art", "🎨"),
+        ("beers", "🍻"),
+        ("bento", "🍱"),
+        ("bookmark", "🔖"),
+        ("books", "📚"),
+        ("boom", "💥"),
+        ("bricks", "🧱"),
+        ("bug", "🐛"),
+        ("building_construction", "🏗\u{fe0f}"),
+        ("bulb", "💡"),
+        ("busts_in_silhouette", "👥"),
+        ("camera_flash", "📸"),
+        ("card_file_box", "🗃\u{fe0f}"),
+        ("chart_with_upwards_trend", "📈"),
+        ("children_crossing", "🚸"),
+        ("closed_lock_with_key", "🔐"),
+        ("clown_face", "🤡"),
+        ("coffin", "⚰\u{fe0f}"),
+        ("construction", "🚧"),
+        ("construction_worker", "👷"),
+        ("dizzy", "💫"),
+        ("egg", "🥚"),
+        ("fire", "🔥"),
+        ("globe_with_meridians", "🌐"),
+        ("goal_net", "🥅"),
+        ("green_heart", "💚"),
+        ("hammer", "🔨"),
+        ("heavy_check_mark", "✔\u{fe0f}"),
+        ("heavy_minus_sign", "➖"),
+        ("heavy_plus_sign", "➕"),
+        ("iphone", "📱"),
+        ("label", "🏷\u{fe0f}"),
+        ("lipstick", "💄"),
+        ("lock", "🔒"),
+        ("loud_sound", "🔊"),
+        ("mag", "🔍"),
+        ("memo", "📝"),
+        ("money_with_wings", "💸"),
+        ("monocle_face", "🧐"),
+        ("mute", "🔇"),
+        ("necktie", "👔"),
+        ("package", "📦"),
+        ("page_facing_up", "📄"),
+        ("passport_control", "🛂"),
+        ("pencil2", "✏\u{fe0f}"),
+        ("poop", "💩"),
+        ("pushpin", "📌"),
+        ("recycle", "♻\u{fe0f}"),
+        ("rewind", "⏪"),
+        ("rocket", "🚀"),
+        ("rotating_light", "🚨"),
+        ("safety_vest", "🦺"),
+        ("see_no_evil", "🙈"),
+        ("seedling", "🌱"),
+        ("sparkles", "✨"),
+        ("speech_balloon", "💬"),
+        ("stethoscope", "🩺"),
+        ("tada", "🎉"),
+        ("technologist", "🧑\u{200d}💻"),
+        ("test_tube", "🧪"),
+        ("thread", "🧵"),
+        ("triangular_flag_on_post", "🚩"),
+        ("truck", "🚚"),
+        ("twisted_rightwards_arrows", "🔀"),
+        ("warning", "⚠\u{fe0f}"),
+        ("wastebasket", "🗑\u{fe0f}"),
+        ("wheelchair", "♿"),
+        ("white_check_mark", "✅"),
+        ("wrench", "🔧"),
+        ("x", "❌"),
+        ("zap", "⚡"),
+    ]
+    .into_iter()
+    .collect();
+    /// A trailer line like `Signed-off-by: Name <email>`
+    static ref TRAILER_LINE: Regex = Regex::new(r"^([A-Za-z0-9][A-Za-z0-9-]*)[ \t]*:[ \t]*(.*)$").unwrap();
+}
+
+/// Options of a trailer placeholder, like `%(trailers:key=Reviewed-by,valueonly)`.
+#[derive(Default, PartialEq, Eq, Debug)]
+struct TrailerOptions {
+    /// Keys of the trailers to show, matched case-insensitively. All trailers if empty
+    keys: Vec<String>,
+    /// Show only the values, without keys
+    value_only: bool,
+    /// Separator between trailers, instead of a line break each
+    separator: Option<String>,
+}
+
+impl FromStr for TrailerOptions {
+    type Err = String;
+
+    /// Parses a placeholder like `%+(trailers:key=Change-Id,separator=%x2C )`.
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        let mut options = TrailerOptions::default();
+        let args = str
+            .split_once(':')
+            .map(|(_, args)| args.trim_end_matches(')'))
+            .unwrap_or("");
+        for arg in args.split(',').filter(|arg| !arg.is_empty()) {
+            match arg.split_once('=') {
+                Some(("key", key)) => options.keys.push(key.trim_end_matches(':').to_lowercase()),
+                Some(("separator", separator)) => {
+                    options.separator = Some(separator.replace("%n", "\n").replace("%x2C", ","))
+                }
+                None if arg == "valueonly" => options.value_only = true,
+                // Trailers are always unfolded, and only trailers are shown
+                None if arg == "unfold" || arg == "only" => {}
+                _ => {
+                    return Err(format!(
+                        "Unknown trailer option '{}' in '{}'. Must be one of [key=<key>|valueonly|separator=<sep>|unfold|only]",
+                        arg, str
+                    ))
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Parses the trailers of a commit message, like `Signed-off-by: Name <email>`, as key-value pairs.
+///
+/// Trailers are the lines of the last paragraph of the message, if it is not the subject
+/// and consists of trailers only. Continuation lines, starting with whitespace, are unfolded.
+pub fn parse_trailers(message: &str) -> Vec<(String, String)> {
+    let paragraphs: Vec<&str> = message
+        .trim()
+        .split("\n\n")
+        .filter(|paragraph| !paragraph.trim().is_empty())
+        .collect();
+    if paragraphs.len() < 2 {
+        return vec![];
+    }
+
+    let mut trailers: Vec<(String, String)> = vec![];
+    for line in paragraphs[paragraphs.len() - 1].lines() {
+        if line.starts_with(char::is_whitespace) {
+            match trailers.last_mut() {
+                Some((_, value)) => {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+                None => return vec![],
+            }
+        } else if let Some(caps) = TRAILER_LINE.captures(line) {
+            trailers.push((caps[1].to_string(), caps[2].trim_end().to_string()));
+        } else {
+            return vec![];
+        }
+    }
+    trailers
+}
+
+/// The subject in a form suitable for file names, like `git log --format=%f`.
+/// Runs of characters other than ASCII letters, digits, `.` and `_` become a single `-`.
+pub fn sanitized_subject(subject: &str) -> String {
+    let mut out = String::with_capacity(subject.len());
+    let mut separate = false;
+    let mut previous = None;
+    for char in subject.chars() {
+        if char.is_ascii_alphanumeric() || char == '.' || char == '_' {
+            if separate && !out.is_empty() {
+                out.push('-');
+            }
+            separate = false;
+            if !(char == '.' && previous == Some('.')) {
+                out.push(char);
+            }
+        } else {
+            separate = true;
+        }
+        previous = Some(char);
+    }
+    out.trim_end_matches(['.', '-']).to_string()
 }
 
 /// Format a commit for `CommitFormat::Format(String)`.
+///
+/// `branches` are the commit's references as shown by git-graph (`%d`),
+/// `decorations` those as shown by `git log --format=%D`,
+/// and `relations` those of the commit's branch, like `forked from develop` (`%(relations)`).
+/// `tag` is the commit's annotated tag, if any, with its name like `v1.0` (`%(tag)`, `%(taggername)`, ...).
+/// `rebased_marker` is shown for commits with a committer other than the author (`%(rebased)`).
+#[allow(clippy::too_many_arguments)]
 pub fn format_commit(
     format: &str,
-    commit: &Commit,
+    commit: &CommitData,
     branches: String,
+    decorations: String,
+    relations: String,
+    tag: Option<(&str, &TagData)>,
+    rebased_marker: &str,
     wrapping: &Option<Options>,
     hash_color: Option<u8>,
+    abbrev: usize,
+    timezone: DateTimeZone,
 ) -> Result<Vec<String>, String> {
     let mut replacements = vec![];
 
@@ -101,6 +448,54 @@ pub fn format_commit(
         }
     }
 
+    // Placeholders within trailer options, like `%n` in a separator, are part of the trailer placeholder
+    let trailers: Vec<_> = TRAILER_PLACEHOLDER.find_iter(format).collect();
+    replacements.retain(|(start, _, _, _)| !trailers.iter().any(|mat| mat.range().contains(start)));
+    for mat in trailers {
+        let mode = match format.as_bytes()[mat.start() + 1] {
+            b' ' => MODE_SPACE,
+            b'+' => MODE_PLUS,
+            b'-' => MODE_MINUS,
+            _ => 0,
+        };
+        replacements.push((mat.start(), mat.end() - mat.start(), TRAILERS, mode));
+    }
+    for mat in RELATIONS_PLACEHOLDER.find_iter(format) {
+        let mode = match format.as_bytes()[mat.start() + 1] {
+            b' ' => MODE_SPACE,
+            b'+' => MODE_PLUS,
+            b'-' => MODE_MINUS,
+            _ => 0,
+        };
+        replacements.push((mat.start(), mat.end() - mat.start(), RELATIONS, mode));
+    }
+    for mat in REBASED_PLACEHOLDER.find_iter(format) {
+        let mode = match format.as_bytes()[mat.start() + 1] {
+            b' ' => MODE_SPACE,
+            b'+' => MODE_PLUS,
+            b'-' => MODE_MINUS,
+            _ => 0,
+        };
+        replacements.push((mat.start(), mat.end() - mat.start(), REBASED, mode));
+    }
+    for caps in TAG_PLACEHOLDER.captures_iter(format) {
+        let mat = caps.get(0).unwrap();
+        let mode = match format.as_bytes()[mat.start() + 1] {
+            b' ' => MODE_SPACE,
+            b'+' => MODE_PLUS,
+            b'-' => MODE_MINUS,
+            _ => 0,
+        };
+        let idx = match &caps[1] {
+            "tag" => TAG_NAME,
+            "taggername" => TAGGER_NAME,
+            "taggeremail" => TAGGER_EMAIL,
+            "taggerdate" => TAGGER_DATE,
+            _ => TAG_MESSAGE,
+        };
+        replacements.push((mat.start(), mat.end() - mat.start(), idx, mode));
+    }
+
     replacements.sort_by_key(|p| p.0);
 
     let mut lines = vec![];
@@ -124,9 +519,9 @@ pub fn format_commit(
                             _ => {}
                         }
                         if let Some(color) = hash_color {
-                            write!(out, "{}", Paint::fixed(color, commit.id()))
+                            write!(out, "{}", Paint::fixed(color, commit.id))
                         } else {
-                            write!(out, "{}", commit.id())
+                            write!(out, "{}", commit.id)
                         }
                     }
                     HASH_ABBREV => {
@@ -139,10 +534,10 @@ pub fn format_commit(
                             write!(
                                 out,
                                 "{}",
-                                Paint::fixed(color, &commit.id().to_string()[..7])
+                                Paint::fixed(color, &commit.id.to_string()[..abbrev])
                             )
                         } else {
-                            write!(out, "{}", &commit.id().to_string()[..7])
+                            write!(out, "{}", &commit.id.to_string()[..abbrev])
                         }
                     }
                     PARENT_HASHES => {
@@ -151,13 +546,7 @@ pub fn format_commit(
                             MODE_PLUS => add_line(&mut lines, &mut out, wrapping),
                             _ => {}
                         }
-                        for i in 0..commit.parent_count() {
-                            write!(out, "{}", commit.parent_id(i).unwrap()).unwrap();
-                            if i < commit.parent_count() - 1 {
-                                write!(out, " ").unwrap();
-                            }
-                        }
-                        Ok(())
+                        write!(out, "{}", commit.parents.iter().join(" "))
                     }
                     PARENT_HASHES_ABBREV => {
                         match mode {
@@ -165,21 +554,15 @@ pub fn format_commit(
                             MODE_PLUS => add_line(&mut lines, &mut out, wrapping),
                             _ => {}
                         }
-                        for i in 0..commit.parent_count() {
-                            write!(
-                                out,
-                                "{}",
-                                &commit
-                                    .parent_id(i)
-                                    .map_err(|err| err.to_string())?
-                                    .to_string()[..7]
-                            )
-                            .unwrap();
-                            if i < commit.parent_count() - 1 {
-                                write!(out, " ").unwrap();
-                            }
-                        }
-                        Ok(())
+                        write!(
+                            out,
+                            "{}",
+                            commit
+                                .parents
+                                .iter()
+                                .map(|oid| oid.to_string()[..abbrev].to_string())
+                                .join(" ")
+                        )
                     }
                     REFS => {
                         match mode {
@@ -202,8 +585,130 @@ pub fn format_commit(
                         }
                         write!(out, "{}", branches)
                     }
+                    DECORATIONS => {
+                        match mode {
+                            MODE_SPACE if !decorations.is_empty() => write!(out, " ").unwrap(),
+                            MODE_PLUS if !decorations.is_empty() => {
+                                add_line(&mut lines, &mut out, wrapping)
+                            }
+                            MODE_MINUS if decorations.is_empty() => {
+                                out = remove_empty_lines(&mut lines, out)
+                            }
+                            _ => {}
+                        }
+                        write!(out, "{}", decorations)
+                    }
+                    RELATIONS => {
+                        match mode {
+                            MODE_SPACE if !relations.is_empty() => write!(out, " ").unwrap(),
+                            MODE_PLUS if !relations.is_empty() => {
+                                add_line(&mut lines, &mut out, wrapping)
+                            }
+                            MODE_MINUS if relations.is_empty() => {
+                                out = remove_empty_lines(&mut lines, out)
+                            }
+                            _ => {}
+                        }
+                        write!(out, "{}", relations)
+                    }
+                    REBASED => {
+                        let marker = if commit.committer_differs() {
+                            rebased_marker
+                        } else {
+                            ""
+                        };
+                        match mode {
+                            MODE_SPACE if !marker.is_empty() => write!(out, " ").unwrap(),
+                            MODE_PLUS if !marker.is_empty() => {
+                                add_line(&mut lines, &mut out, wrapping)
+                            }
+                            MODE_MINUS if marker.is_empty() => {
+                                out = remove_empty_lines(&mut lines, out)
+                            }
+                            _ => {}
+                        }
+                        write!(out, "{}", marker)
+                    }
+                    TAG_NAME | TAGGER_NAME | TAGGER_EMAIL | TAGGER_DATE | TAG_MESSAGE => {
+                        let tagger = tag.and_then(|(_, data)| data.tagger.as_ref());
+                        let value = match idx {
+                            TAG_NAME => tag.map(|(name, _)| name.to_string()),
+                            TAGGER_NAME => tagger.map(|tagger| tagger.name.clone()),
+                            TAGGER_EMAIL => tagger.map(|tagger| tagger.email.clone()),
+                            TAGGER_DATE => tagger.map(|tagger| {
+                                format_date(tagger, "%a %b %e %H:%M:%S %Y %z", timezone)
+                            }),
+                            _ => tag.map(|(_, data)| data.message.trim_end().to_string()),
+                        }
+                        .unwrap_or_default();
+                        match mode {
+                            MODE_SPACE if !value.is_empty() => write!(out, " ").unwrap(),
+                            MODE_PLUS if !value.is_empty() => {
+                                add_line(&mut lines, &mut out, wrapping)
+                            }
+                            MODE_MINUS if value.is_empty() => {
+                                out = remove_empty_lines(&mut lines, out)
+                            }
+                            _ => {}
+                        }
+                        // Lines of the tag message
+                        for (cnt, line) in value.lines().enumerate() {
+                            if cnt > 0 {
+                                add_line(&mut lines, &mut out, wrapping);
+                            }
+                            write!(out, "{}", line).unwrap();
+                        }
+                        Ok(())
+                    }
+                    TREE | TREE_ABBREV => {
+                        let tree = commit.tree.map(|tree| tree.to_string()).unwrap_or_default();
+                        let tree = if idx == TREE_ABBREV && !tree.is_empty() {
+                            &tree[..abbrev]
+                        } else {
+                            &tree[..]
+                        };
+                        match mode {
+                            MODE_SPACE if !tree.is_empty() => write!(out, " ").unwrap(),
+                            MODE_PLUS if !tree.is_empty() => {
+                                add_line(&mut lines, &mut out, wrapping)
+                            }
+                            MODE_MINUS if tree.is_empty() => {
+                                out = remove_empty_lines(&mut lines, out)
+                            }
+                            _ => {}
+                        }
+                        write!(out, "{}", tree)
+                    }
+                    ENCODING => {
+                        let encoding = commit.encoding.as_deref().unwrap_or("");
+                        match mode {
+                            MODE_SPACE if !encoding.is_empty() => write!(out, " ").unwrap(),
+                            MODE_PLUS if !encoding.is_empty() => {
+                                add_line(&mut lines, &mut out, wrapping)
+                            }
+                            MODE_MINUS if encoding.is_empty() => {
+                                out = remove_empty_lines(&mut lines, out)
+                            }
+                            _ => {}
+                        }
+                        write!(out, "{}", encoding)
+                    }
+                    SANITIZED_SUBJECT => {
+                        let subject = sanitized_subject(&commit.summary());
+                        match mode {
+                            MODE_SPACE if !subject.is_empty() => write!(out, " ").unwrap(),
+                            MODE_PLUS if !subject.is_empty() => {
+                                add_line(&mut lines, &mut out, wrapping)
+                            }
+                            MODE_MINUS if subject.is_empty() => {
+                                out = remove_empty_lines(&mut lines, out)
+                            }
+                            _ => {}
+                        }
+                        write!(out, "{}", subject)
+                    }
                     SUBJECT => {
-                        let summary = commit.summary().unwrap_or("");
+                        let summary = commit.summary();
                         match mode {
                             MODE_SPACE => {
                                 if !summary.is_empty() {
@@ -230,7 +735,7 @@ pub fn format_commit(
                             MODE_PLUS => add_line(&mut lines, &mut out, wrapping),
                             _ => {}
                         }
-                        write!(out, "{}", &commit.author().name().unwrap_or(""))
+                        write!(out, "{}", &commit.author.name)
                     }
                     AUTHOR_EMAIL => {
                         match mode {
@@ -238,7 +743,7 @@ pub fn format_commit(
                             MODE_PLUS => add_line(&mut lines, &mut out, wrapping),
                             _ => {}
                         }
-                        write!(out, "{}", &commit.author().email().unwrap_or(""))
+                        write!(out, "{}", &commit.author.email)
                     }
                     AUTHOR_DATE => {
                         match mode {
@@ -249,7 +754,7 @@ pub fn format_commit(
                         write!(
                             out,
                             "{}",
-                            format_date(commit.author().when(), "%a %b %e %H:%M:%S %Y %z")
+                            format_date(&commit.author, "%a %b %e %H:%M:%S %Y %z", timezone)
                         )
                     }
                     AUTHOR_DATE_SHORT => {
@@ -258,7 +763,7 @@ pub fn format_commit(
                             MODE_PLUS => add_line(&mut lines, &mut out, wrapping),
                             _ => {}
                         }
-                        write!(out, "{}", format_date(commit.author().when(), "%F"))
+                        write!(out, "{}", format_date(&commit.author, "%F", timezone))
                     }
                     COMMITTER => {
                         match mode {
@@ -266,7 +771,7 @@ pub fn format_commit(
                             MODE_PLUS => add_line(&mut lines, &mut out, wrapping),
                             _ => {}
                         }
-                        write!(out, "{}", &commit.committer().name().unwrap_or(""))
+                        write!(out, "{}", &commit.committer.name)
                     }
                     COMMITTER_EMAIL => {
                         match mode {
@@ -274,7 +779,7 @@ pub fn format_commit(
                             MODE_PLUS => add_line(&mut lines, &mut out, wrapping),
                             _ => {}
                         }
-                        write!(out, "{}", &commit.committer().email().unwrap_or(""))
+                        write!(out, "{}", &commit.committer.email)
                     }
                     COMMITTER_DATE => {
                         match mode {
@@ -285,7 +790,7 @@ pub fn format_commit(
                         write!(
                             out,
                             "{}",
-                            format_date(commit.committer().when(), "%a %b %e %H:%M:%S %Y %z")
+                            format_date(&commit.committer, "%a %b %e %H:%M:%S %Y %z", timezone)
                         )
                     }
                     COMMITTER_DATE_SHORT => {
@@ -294,14 +799,10 @@ pub fn format_commit(
                             MODE_PLUS => add_line(&mut lines, &mut out, wrapping),
                             _ => {}
                         }
-                        write!(out, "{}", format_date(commit.committer().when(), "%F"))
+                        write!(out, "{}", format_date(&commit.committer, "%F", timezone))
                     }
                     BODY => {
-                        let message = commit
-                            .message()
-                            .unwrap_or("")
-                            .lines()
-                            .collect::<Vec<&str>>();
+                        let message = commit.message.lines().collect::<Vec<&str>>();
 
                         let num_parts = message.len();
                         match mode {
@@ -331,11 +832,7 @@ pub fn format_commit(
                         Ok(())
                     }
                     BODY_RAW => {
-                        let message = commit
-                            .message()
-                            .unwrap_or("")
-                            .lines()
-                            .collect::<Vec<&str>>();
+                        let message = commit.message.lines().collect::<Vec<&str>>();
 
                         let num_parts = message.len();
 
@@ -365,6 +862,53 @@ pub fn format_commit(
                         }
                         Ok(())
                     }
+                    TRAILERS => {
+                        let options = TrailerOptions::from_str(&format[start..start + len])?;
+                        let trailers: Vec<String> = parse_trailers(&commit.message)
+                            .into_iter()
+                            .filter(|(key, _)| {
+                                options.keys.is_empty()
+                                    || options.keys.contains(&key.to_lowercase())
+                            })
+                            .map(|(key, value)| {
+                                if options.value_only {
+                                    value
+                                } else {
+                                    format!("{}: {}", key, value)
+                                }
+                            })
+                            .collect();
+                        match mode {
+                            MODE_SPACE if !trailers.is_empty() => write!(out, " ").unwrap(),
+                            MODE_PLUS if !trailers.is_empty() => {
+                                add_line(&mut lines, &mut out, wrapping)
+                            }
+                            MODE_MINUS if trailers.is_empty() => {
+                                out = remove_empty_lines(&mut lines, out)
+                            }
+                            _ => {}
+                        }
+                        if let Some(separator) = &options.separator {
+                            // A separator may contain line breaks (`%n`), which must become
+                            // separate lines to keep the graph intact
+                            let joined = trailers.join(separator);
+                            let mut parts = joined.split('\n').peekable();
+                            while let Some(part) = parts.next() {
+                                write!(out, "{}", part).unwrap();
+                                if parts.peek().is_some() {
+                                    add_line(&mut lines, &mut out, wrapping);
+                                }
+                            }
+                            Ok(())
+                        } else {
+                            // Like git, each trailer is terminated by a line break
+                            for trailer in trailers {
+                                write!(out, "{}", trailer).unwrap();
+                                add_line(&mut lines, &mut out, wrapping);
+                            }
+                            Ok(())
+                        }
+                    }
                     x => return Err(format!("No commit field at index {}", x)),
                 }
                 .unwrap();
@@ -381,47 +925,107 @@ pub fn format_commit(
 
 /// Format a commit for `CommitFormat::OneLine`.
 pub fn format_oneline(
-    commit: &Commit,
+    commit: &CommitData,
     branches: String,
     wrapping: &Option<Options>,
     hash_color: Option<u8>,
+    abbrev: usize,
 ) -> Vec<String> {
     let mut out = String::new();
     if let Some(color) = hash_color {
         write!(
             out,
             "{}",
-            Paint::fixed(color, &commit.id().to_string()[..7])
+            Paint::fixed(color, &commit.id.to_string()[..abbrev])
         )
     } else {
-        write!(out, "{}", &commit.id().to_string()[..7])
+        write!(out, "{}", &commit.id.to_string()[..abbrev])
     }
     .unwrap();
 
-    write!(out, "{} {}", branches, commit.summary().unwrap_or("")).unwrap();
+    write!(out, "{}", branches).unwrap();
+    let refs_width = display_width(&out);
+    let summary = commit.summary();
+    write!(out, " {}", summary).unwrap();
 
-    if let Some(wrap) = wrapping {
-        textwrap::fill(&out, wrap)
+    match wrapping {
+        // References taking more than half of the line are wrapped on their own
+        Some(wrap)
+            if refs_width > wrap.width / 2
+                && display_width(wrap.initial_indent) + display_width(&out) > wrap.width =>
+        {
+            out.truncate(out.len() - summary.len() - 1);
+            wrap_refs_and_subject(out, &summary, wrap, abbrev + 1)
+        }
+        Some(wrap) => textwrap::fill(&out, wrap)
             .lines()
             .map(|str| str.to_string())
-            .collect()
-    } else {
-        vec![out]
+            .collect(),
+        None => vec![out],
     }
 }
 
+/// Wraps a long list of references separately from the subject, which starts on a new line,
+/// so that it is not squeezed into the end of the references. Continuation lines of the references
+/// are aligned after the commit hash, given by `hash_width`, including the following space.
+fn wrap_refs_and_subject(
+    hash_and_refs: String,
+    subject: &str,
+    wrapping: &Options,
+    hash_width: usize,
+) -> Vec<String> {
+    let refs_indent = format!("{}{}", wrapping.initial_indent, " ".repeat(hash_width));
+    let refs_wrapping = wrapping.clone().subsequent_indent(&refs_indent);
+    let subject_wrapping = wrapping.clone().initial_indent(wrapping.subsequent_indent);
+
+    let mut lines: Vec<String> = textwrap::fill(&hash_and_refs, &refs_wrapping)
+        .lines()
+        .map(|str| str.to_string())
+        .collect();
+    lines.extend(
+        textwrap::fill(subject, &subject_wrapping)
+            .lines()
+            .map(|str| str.to_string()),
+    );
+    lines
+}
+
 /// Format a commit for `CommitFormat::Short`, `CommitFormat::Medium` or `CommitFormat::Full`.
+/// `CommitFormat::Full` includes the commit's annotated tag, if any.
+#[allow(clippy::too_many_arguments)]
 pub fn format(
-    commit: &Commit,
+    commit: &CommitData,
     branches: String,
+    decorations: String,
+    relations: String,
+    tag: Option<(&str, &TagData)>,
+    rebased_marker: &str,
     wrapping: &Option<Options>,
     hash_color: Option<u8>,
+    abbrev: usize,
+    timezone: DateTimeZone,
     format: &CommitFormat,
 ) -> Result<Vec<String>, String> {
     match format {
-        CommitFormat::OneLine => return Ok(format_oneline(commit, branches, wrapping, hash_color)),
+        CommitFormat::OneLine => {
+            return Ok(format_oneline(
+                commit, branches, wrapping, hash_color, abbrev,
+            ))
+        }
         CommitFormat::Format(format) => {
-            return format_commit(format, commit, branches, wrapping, hash_color)
+            return format_commit(
+                format,
+                commit,
+                branches,
+                decorations,
+                relations,
+                tag,
+                rebased_marker,
+                wrapping,
+                hash_color,
+                abbrev,
+                timezone,
+            )
         }
         _ => {}
     }
@@ -430,22 +1034,22 @@ pub fn format(
     let mut out = String::new();
 
     if let Some(color) = hash_color {
-        write!(out, "commit {}", Paint::fixed(color, &commit.id()))
+        write!(out, "commit {}", Paint::fixed(color, &commit.id))
     } else {
-        write!(out, "commit {}", &commit.id())
+        write!(out, "commit {}", &commit.id)
     }
     .map_err(|err| err.to_string())?;
 
     write!(out, "{}", branches).map_err(|err| err.to_string())?;
     append_wrapped(&mut out_vec, out, wrapping);
 
-    if commit.parent_count() > 1 {
+    if commit.parents.len() > 1 {
         out = String::new();
         write!(
             out,
             "Merge: {} {}",
-            &commit.parent_id(0).unwrap().to_string()[..7],
-            &commit.parent_id(1).unwrap().to_string()[..7]
+            &commit.parents[0].to_string()[..abbrev],
+            &commit.parents[1].to_string()[..abbrev]
         )
         .map_err(|err| err.to_string())?;
         append_wrapped(&mut out_vec, out, wrapping);
@@ -455,8 +1059,7 @@ pub fn format(
     write!(
         out,
         "Author: {} <{}>",
-        commit.author().name().unwrap_or(""),
-        commit.author().email().unwrap_or("")
+        commit.author.name, commit.author.email
     )
     .map_err(|err| err.to_string())?;
     append_wrapped(&mut out_vec, out, wrapping);
@@ -466,8 +1069,7 @@ pub fn format(
         write!(
             out,
             "Commit: {} <{}>",
-            commit.committer().name().unwrap_or(""),
-            commit.committer().email().unwrap_or("")
+            commit.committer.name, commit.committer.email
         )
         .map_err(|err| err.to_string())?;
         append_wrapped(&mut out_vec, out, wrapping);
@@ -478,7 +1080,7 @@ pub fn format(
         write!(
             out,
             "Date:   {}",
-            format_date(commit.author().when(), "%a %b %e %H:%M:%S %Y %z")
+            format_date(&commit.author, "%a %b %e %H:%M:%S %Y %z", timezone)
         )
         .map_err(|err| err.to_string())?;
         append_wrapped(&mut out_vec, out, wrapping);
@@ -486,16 +1088,12 @@ pub fn format(
 
     if format == &CommitFormat::Short {
         out_vec.push("".to_string());
-        append_wrapped(
-            &mut out_vec,
-            format!("    {}", commit.summary().unwrap_or("")),
-            wrapping,
-        );
+        append_wrapped(&mut out_vec, format!("    {}", commit.summary()), wrapping);
         out_vec.push("".to_string());
     } else {
         out_vec.push("".to_string());
         let mut add_line = true;
-        for line in commit.message().unwrap_or("").lines() {
+        for line in commit.message.lines() {
             if line.is_empty() {
                 out_vec.push(line.to_string());
             } else {
@@ -508,13 +1106,48 @@ pub fn format(
         }
     }
 
+    if let (CommitFormat::Full, Some((name, data))) = (format, tag) {
+        out_vec.push(format!("tag {}", name));
+        if let Some(tagger) = &data.tagger {
+            append_wrapped(
+                &mut out_vec,
+                format!("Tagger: {} <{}>", tagger.name, tagger.email),
+                wrapping,
+            );
+            append_wrapped(
+                &mut out_vec,
+                format!(
+                    "Date:   {}",
+                    format_date(tagger, "%a %b %e %H:%M:%S %Y %z", timezone)
+                ),
+                wrapping,
+            );
+        }
+        out_vec.push("".to_string());
+        for line in data.message.trim_end().lines() {
+            if line.is_empty() {
+                out_vec.push(line.to_string());
+            } else {
+                append_wrapped(&mut out_vec, format!("    {}", line), wrapping);
+            }
+        }
+        out_vec.push("".to_string());
+    }
+
     Ok(out_vec)
 }
 
-pub fn format_date(time: Time, format: &str) -> String {
-    let date =
-        Local::from_offset(&FixedOffset::east(time.offset_minutes())).timestamp(time.seconds(), 0);
-    format!("{}", date.format(format))
+/// Formats the time of a signature in the given time zone.
+pub fn format_date(signature: &Signature, format: &str, timezone: DateTimeZone) -> String {
+    let date = Utc.timestamp(signature.time, 0);
+    match timezone {
+        DateTimeZone::Commit => {
+            let offset = FixedOffset::east(signature.offset * 60);
+            format!("{}", date.with_timezone(&offset).format(format))
+        }
+        DateTimeZone::Local => format!("{}", date.with_timezone(&Local).format(format)),
+        DateTimeZone::Utc => format!("{}", date.format(format)),
+    }
 }
 
 fn append_wrapped(vec: &mut Vec<String>, str: String, wrapping: &Option<Options>) {
@@ -546,3 +1179,232 @@ fn remove_empty_lines(lines: &mut Vec<String>, mut line: String) -> String {
     }
     line
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::print::format::{
+        format_commit, format_oneline, parse_trailers, sanitized_subject, DateTimeZone, Emoji,
+        REBASED_MARKER,
+    };
+    use crate::source::{CommitData, Oid, Signature, TagData};
+    use std::str::FromStr;
+
+    const MESSAGE: &str = "Fix parsing of empty refs\n\n\
+        Refs without target are skipped now.\n\
+        Reviewed-by: not a trailer, as the paragraph has other lines\n\n\
+        Signed-off-by: Alice <alice@example.com>\n\
+        Reviewed-by: Bob <bob@example.com>\n\
+        Change-Id: I1234\n\
+        \x20 continued\n\
+        Signed-off-by: Carol <carol@example.com>\n";
+
+    fn commit(message: &str) -> CommitData {
+        let signature = Signature {
+            name: "Alice".to_string(),
+            email: "alice@example.com".to_string(),
+            time: 0,
+            offset: 0,
+        };
+        CommitData {
+            id: Oid::from_str("0000000000000000000000000000000000000001").unwrap(),
+            parents: vec![],
+            message: message.to_string(),
+            author: signature.clone(),
+            committer: signature,
+            tree: Some(Oid::from_str("00000000000000000000000000000000000000ff").unwrap()),
+            encoding: Some("ISO-8859-1".to_string()),
+        }
+    }
+
+    fn format(format: &str, message: &str) -> Vec<String> {
+        format_commit(
+            format,
+            &commit(message),
+            String::new(),
+            "HEAD -> main, tag: v1.0".to_string(),
+            "forked from develop".to_string(),
+            None,
+            REBASED_MARKER,
+            &None,
+            None,
+            7,
+            DateTimeZone::Utc,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parse_multiple_trailers() {
+        let trailers = parse_trailers(MESSAGE);
+        let keys: Vec<_> = trailers.iter().map(|(key, _)| &key[..]).collect();
+        assert_eq!(
+            keys,
+            ["Signed-off-by", "Reviewed-by", "Change-Id", "Signed-off-by"]
+        );
+        assert_eq!(trailers[2].1, "I1234 continued");
+        assert!(parse_trailers("Signed-off-by: Alice <alice@example.com>").is_empty());
+        assert!(parse_trailers("Subject\n\nNo trailers here").is_empty());
+    }
+
+    #[test]
+    fn format_trailers_by_key() {
+        assert_eq!(
+            format("%h%n%(trailers:key=signed-off-by)", MESSAGE),
+            [
+                "0000000",
+                "Signed-off-by: Alice <alice@example.com>",
+                "Signed-off-by: Carol <carol@example.com>"
+            ]
+        );
+        assert_eq!(
+            format(
+                "%h %(trailers:key=Reviewed-by:,key=Change-Id,valueonly,separator=%x2C )",
+                MESSAGE
+            ),
+            ["0000000 Bob <bob@example.com>, I1234 continued"]
+        );
+        assert_eq!(
+            format("%h%+(trailers:key=Reviewed-by,separator=%n)", "Subject"),
+            ["0000000"]
+        );
+        assert_eq!(
+            format("%h %(trailers:key=signed-off-by,separator=%n)", MESSAGE),
+            [
+                "0000000 Signed-off-by: Alice <alice@example.com>",
+                "Signed-off-by: Carol <carol@example.com>"
+            ]
+        );
+        assert!(format_commit(
+            "%(trailers:unknown)",
+            &commit(MESSAGE),
+            String::new(),
+            String::new(),
+            String::new(),
+            None,
+            REBASED_MARKER,
+            &None,
+            None,
+            7,
+            DateTimeZone::Utc
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn format_pretty_placeholders() {
+        assert_eq!(
+            format("%t %e%n%D%n%f", "Fix: parsing of  `refs/heads/*`..."),
+            [
+                "0000000 ISO-8859-1",
+                "HEAD -> main, tag: v1.0",
+                "Fix-parsing-of-refs-heads"
+            ]
+        );
+        assert_eq!(
+            sanitized_subject("..Version 1..2 (beta)"),
+            ".Version-1.2-beta"
+        );
+        assert_eq!(
+            format("%h%+(relations)", "Subject"),
+            ["0000000", "forked from develop"]
+        );
+    }
+
+    #[test]
+    fn render_emojis() {
+        let message = ":sparkles: Add parser :foo:\n\n    Fix ✨ the 🐛 bug\n";
+        assert_eq!(Emoji::Keep.apply(message), message);
+        assert_eq!(
+            Emoji::Replace.apply(message),
+            "✨ Add parser :foo:\n\n    Fix ✨ the 🐛 bug\n"
+        );
+        assert_eq!(
+            Emoji::Strip.apply(message),
+            "Add parser :foo:\n\n    Fix the bug\n"
+        );
+        assert_eq!(Emoji::Strip.apply("⬆\u{fe0f} Bump"), "Bump");
+    }
+
+    #[test]
+    fn format_rebased_placeholder() {
+        let mut rebased = commit("Subject");
+        rebased.committer.email = "bob@example.com".to_string();
+        let format = |commit: &CommitData| {
+            format_commit(
+                "%h% (rebased) %s",
+                commit,
+                String::new(),
+                String::new(),
+                String::new(),
+                None,
+                REBASED_MARKER,
+                &None,
+                None,
+                7,
+                DateTimeZone::Utc,
+            )
+            .unwrap()
+        };
+        assert_eq!(format(&rebased), ["0000000 ↻ Subject"]);
+        assert_eq!(format(&commit("Subject")), ["0000000 Subject"]);
+    }
+
+    #[test]
+    fn format_tag_placeholders() {
+        let tag = TagData {
+            tagger: Some(commit("").author),
+            message: "Release 1.0\n\nWith the new parser\n".to_string(),
+        };
+        let format = |format: &str, tag: Option<(&str, &TagData)>| {
+            format_commit(
+                format,
+                &commit("Subject"),
+                String::new(),
+                String::new(),
+                String::new(),
+                tag,
+                REBASED_MARKER,
+                &None,
+                None,
+                7,
+                DateTimeZone::Utc,
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            format(
+                "%h% (tag) %(taggername) %(taggerdate)",
+                Some(("v1.0", &tag))
+            ),
+            ["0000000 v1.0 Alice Thu Jan  1 00:00:00 1970 +0000"]
+        );
+        assert_eq!(
+            format("%h%+(tagmessage)", Some(("v1.0", &tag))),
+            ["0000000", "Release 1.0", "", "With the new parser"]
+        );
+        assert_eq!(format("%h% (tag)%+(tagmessage)", None), ["0000000"]);
+    }
+
+    #[test]
+    fn wrap_long_refs_separately() {
+        let wrapping = Some(textwrap::Options::new(40).subsequent_indent("  "));
+        let oneline = |refs: &str| {
+            format_oneline(
+                &commit("Update the parser"),
+                refs.to_string(),
+                &wrapping,
+                None,
+                7,
+            )
+        };
+        assert_eq!(
+            oneline(" (HEAD -> main, feature/a, feature/b, release/1.0)"),
+            [
+                "0000000 (HEAD -> main, feature/a,",
+                "        feature/b, release/1.0)",
+                "  Update the parser"
+            ]
+        );
+        assert_eq!(oneline(" (main)"), ["0000000 (main) Update the parser"]);
+    }
+}
@@ -0,0 +1,83 @@
+//! Create graphs as HTML pages, with the SVG graph next to the commit summaries.
+
+use crate::graph::GitGraph;
+use crate::print::format::CommitFormat;
+use crate::print::svg::{print_svg_graph, stripes, Coords, STRIPE_OPACITY};
+use crate::print::unicode::format_commit_at;
+use crate::settings::Settings;
+
+/// Creates a standalone HTML page showing the graph. `head` is inserted into the page's `<head>`.
+pub fn print_html(graph: &GitGraph, settings: &Settings, head: &str) -> Result<String, String> {
+    let svg = print_svg_graph(graph, settings)?;
+    let coords = Coords::new(graph, settings)?;
+
+    let mut rows = String::new();
+    for (index, shaded) in stripes(graph, settings)?.into_iter().enumerate() {
+        if let (Some(y), Some(status), Some(head_idx)) =
+            (coords.worktree_y(), graph.worktree, graph.worktree_parent())
+        {
+            if head_idx == index {
+                let height = coords.y(index) - y;
+                rows.push_str(&format!("<div style=\"height: {}px\">", height));
+                rows.push_str(&escape(&status.summary()));
+                rows.push_str("</div>\n");
+            }
+        }
+        // Rows are as high as the distance to the next commit in the graph
+        let height = coords.row_height(index);
+        let lines = format_commit_at(
+            graph,
+            index,
+            &CommitFormat::OneLine,
+            false,
+            settings.date_timezone,
+        )?;
+        let class = if shaded { " class=\"stripe\"" } else { "" };
+        rows.push_str(&format!("<div{} style=\"height: {}px\">", class, height));
+        rows.push_str(&escape(lines.first().map(|l| &l[..]).unwrap_or("")));
+        rows.push_str("</div>\n");
+    }
+
+    let header = match &settings.header {
+        Some(header) => format!(
+            "<div class=\"header\">{}</div>\n",
+            escape(&header.line(graph.commits.len()))
+        ),
+        None => String::new(),
+    };
+
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>git-graph</title>
+<style>
+body {{ font-family: monospace; font-size: 12px; }}
+.header {{ font-family: sans-serif; font-size: 14px; margin-bottom: 8px; }}
+.graph {{ display: flex; align-items: flex-start; }}
+svg {{ flex-shrink: 0; }}
+.commits {{ padding-top: 7.5px; white-space: pre; }}
+.commits div {{ line-height: 15px; }}
+.commits div.stripe {{ background: rgba(0, 0, 0, {}); }}
+</style>
+{}
+</head>
+<body>
+{}<div class="graph">
+{}
+<div class="commits">
+{}</div>
+</div>
+</body>
+</html>
+"#,
+        STRIPE_OPACITY, head, header, svg, rows
+    ))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
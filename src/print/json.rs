@@ -0,0 +1,118 @@
+//! Create graphs in JSON format, for consumption by other tools.
+
+use crate::graph::GitGraph;
+use serde_derive::Serialize;
+
+/// JSON representation of a graph.
+#[derive(Serialize)]
+pub struct JsonGraph {
+    pub head: JsonHead,
+    pub commits: Vec<JsonCommit>,
+    pub branches: Vec<JsonBranch>,
+}
+
+/// JSON representation of the HEAD.
+#[derive(Serialize)]
+pub struct JsonHead {
+    pub id: String,
+    pub name: String,
+    pub is_branch: bool,
+}
+
+/// JSON representation of a commit.
+#[derive(Serialize)]
+pub struct JsonCommit {
+    pub id: String,
+    pub parents: Vec<String>,
+    pub summary: String,
+    pub author: String,
+    pub time: i64,
+    /// Index of the commit's branch in `branches`
+    pub branch: Option<usize>,
+    /// The commit's column
+    pub column: Option<usize>,
+    /// Indices of branches pointing to this commit
+    pub branches: Vec<usize>,
+    /// Indices of tags pointing to this commit
+    pub tags: Vec<usize>,
+}
+
+/// JSON representation of a branch or tag.
+#[derive(Serialize)]
+pub struct JsonBranch {
+    pub name: String,
+    pub target: String,
+    pub column: Option<usize>,
+    pub term_color: u8,
+    pub svg_color: String,
+    pub persistence: u8,
+    pub is_remote: bool,
+    pub is_merged: bool,
+    pub is_tag: bool,
+    /// First and last commit index of the branch
+    pub range: (Option<usize>, Option<usize>),
+    /// Index of the branch this one forks from, in `branches`
+    pub source_branch: Option<usize>,
+    /// Index of the branch this one is merged into, in `branches`
+    pub target_branch: Option<usize>,
+}
+
+impl JsonGraph {
+    /// Creates the JSON representation of a graph.
+    pub fn new(graph: &GitGraph) -> Result<Self, String> {
+        let commits = graph
+            .commits
+            .iter()
+            .map(|info| {
+                let commit = graph.commit(info.oid)?;
+                Ok(JsonCommit {
+                    id: info.oid.to_string(),
+                    parents: commit.parents.iter().map(|oid| oid.to_string()).collect(),
+                    summary: commit.summary(),
+                    author: commit.author.name,
+                    time: commit.committer.time,
+                    branch: info.branch_trace,
+                    column: info
+                        .branch_trace
+                        .and_then(|trace| graph.all_branches[trace].visual.column),
+                    branches: info.branches.clone(),
+                    tags: info.tags.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let branches = graph
+            .all_branches
+            .iter()
+            .map(|branch| JsonBranch {
+                name: branch.name.to_string(),
+                target: branch.target.to_string(),
+                column: branch.visual.column,
+                term_color: branch.visual.term_color,
+                svg_color: branch.visual.svg_color.to_string(),
+                persistence: branch.persistence,
+                is_remote: branch.is_remote,
+                is_merged: branch.is_merged,
+                is_tag: branch.is_tag,
+                range: branch.range,
+                source_branch: branch.source_branch,
+                target_branch: branch.target_branch,
+            })
+            .collect();
+
+        Ok(JsonGraph {
+            head: JsonHead {
+                id: graph.head.oid.to_string(),
+                name: graph.head.name.clone(),
+                is_branch: graph.head.is_branch,
+            },
+            commits,
+            branches,
+        })
+    }
+}
+
+/// Creates a JSON representation of a graph.
+pub fn print_json(graph: &GitGraph) -> Result<String, String> {
+    serde_json::to_string(&JsonGraph::new(graph)?).map_err(|err| err.to_string())
+}
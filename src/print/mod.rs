@@ -1,22 +1,60 @@
 //! Create visual representations of git graphs.
 
-use crate::graph::GitGraph;
+use crate::graph::{BranchInfo, GitGraph};
+use crate::settings::Settings;
 use std::cmp::max;
 
+pub mod changelog;
 pub mod colors;
+pub mod debug;
+pub mod diagram;
 pub mod format;
+pub mod html;
+pub mod json;
+pub mod porcelain;
 pub mod svg;
+#[cfg(feature = "tui")]
+pub mod tui;
 pub mod unicode;
 
+/// Number of distinct commit node shapes in accessible mode.
+const NODE_SHAPES: u8 = 5;
+
+/// The shape of a branch's commit nodes. `0` is the default circle,
+/// further shapes are only used in accessible mode and depend on the branch persistence.
+fn node_shape(branch: &BranchInfo, settings: &Settings) -> u8 {
+    if settings.accessible {
+        branch.persistence % NODE_SHAPES
+    } else {
+        0
+    }
+}
+
+/// The branch a commit is traced to. Fails only for inconsistent graphs.
+fn commit_branch(graph: &GitGraph, index: usize) -> Result<&BranchInfo, String> {
+    let info = &graph.commits[index];
+    info.branch_trace
+        .map(|trace| &graph.all_branches[trace])
+        .ok_or_else(|| format!("Commit {} is not assigned to a branch", info.oid))
+}
+
+/// The column of a branch with commits in the graph. Fails only for inconsistent graphs.
+fn branch_column(branch: &BranchInfo) -> Result<usize, String> {
+    branch
+        .visual
+        .column
+        .ok_or_else(|| format!("Branch '{}' is not assigned to a column", branch.name))
+}
+
 /// Find the index at which a between-branch connection
 /// has to deviate from the current branch's column.
 ///
 /// Returns the last index on the current column.
-fn get_deviate_index(graph: &GitGraph, index: usize, par_index: usize) -> usize {
+fn get_deviate_index(graph: &GitGraph, index: usize, par_index: usize) -> Result<usize, String> {
     let info = &graph.commits[index];
 
     let par_info = &graph.commits[par_index];
-    let par_branch = &graph.all_branches[par_info.branch_trace.unwrap()];
+    let par_branch = commit_branch(graph, par_index)?;
 
     let mut min_split_idx = index;
     for sibling_oid in &par_info.children {
@@ -37,9 +75,9 @@ fn get_deviate_index(graph: &GitGraph, index: usize, par_index: usize) -> usize
 
     // TODO: in cases where no crossings occur, the rule for merge commits can also be applied to normal commits
     // See also branch::trace_branch()
-    if info.is_merge {
+    Ok(if info.is_merge {
         max(index, min_split_idx)
     } else {
         (par_index as i32 - 1) as usize
-    }
+    })
 }
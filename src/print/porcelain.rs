@@ -0,0 +1,107 @@
+//! Create machine-readable output for scripts, with one record per commit.
+
+use crate::graph::{CommitInfo, GitGraph};
+use std::str::FromStr;
+
+/// Versions of the porcelain format. The output of a version does not change in future releases.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PorcelainVersion {
+    /// Tab-separated fields: hash, parent hashes (space-separated), column, branch, references, subject
+    V1,
+}
+
+impl FromStr for PorcelainVersion {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "v1" | "1" => Ok(PorcelainVersion::V1),
+            _ => Err(format!(
+                "Unknown porcelain version '{}'. Must be one of [v1]",
+                str
+            )),
+        }
+    }
+}
+
+/// Creates one line per commit, with tab-separated fields.
+/// Tabs and line breaks within fields are replaced by spaces.
+///
+/// With `nul_terminated`, each field is terminated by a NUL character instead, and fields are not altered.
+/// Records are not separated further, as each version has a fixed number of fields.
+pub fn print_porcelain(
+    graph: &GitGraph,
+    version: PorcelainVersion,
+    nul_terminated: bool,
+) -> Result<String, String> {
+    let mut out = String::new();
+    for info in &graph.commits {
+        let fields = record(graph, info, version)?;
+        if nul_terminated {
+            for field in fields {
+                out.push_str(&field);
+                out.push('\0');
+            }
+        } else {
+            let fields: Vec<_> = fields
+                .iter()
+                .map(|field| field.replace(['\t', '\n', '\r'], " "))
+                .collect();
+            out.push_str(&fields.join("\t"));
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+/// The fields of a commit's record.
+fn record(
+    graph: &GitGraph,
+    info: &CommitInfo,
+    version: PorcelainVersion,
+) -> Result<Vec<String>, String> {
+    match version {
+        PorcelainVersion::V1 => {
+            let commit = graph.commit(info.oid)?;
+            let branch = info.branch_trace.map(|trace| &graph.all_branches[trace]);
+            Ok(vec![
+                info.oid.to_string(),
+                commit
+                    .parents
+                    .iter()
+                    .map(|oid| oid.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                branch
+                    .and_then(|branch| branch.visual.column)
+                    .map(|column| column.to_string())
+                    .unwrap_or_default(),
+                branch
+                    .map(|branch| branch.name.to_string())
+                    .unwrap_or_default(),
+                refs(graph, info),
+                commit.summary(),
+            ])
+        }
+    }
+}
+
+/// References pointing to a commit, like `git log --format=%D`.
+pub(crate) fn refs(graph: &GitGraph, info: &CommitInfo) -> String {
+    let head = &graph.head;
+    let mut refs = Vec::new();
+    if !head.is_branch && head.oid == info.oid {
+        refs.push("HEAD".to_string());
+    }
+    for branch in info.branches.iter().map(|idx| &graph.all_branches[*idx]) {
+        if head.is_branch && head.name[..] == branch.name[..] {
+            refs.insert(0, format!("HEAD -> {}", branch.name));
+        } else {
+            refs.push(branch.name.to_string());
+        }
+    }
+    for tag in info.tags.iter().map(|idx| &graph.all_branches[*idx]) {
+        refs.push(format!("tag: {}", tag.display_name()));
+    }
+    refs.join(", ")
+}
@@ -1,28 +1,54 @@
 //! Create graphs in SVG format (Scalable Vector Graphics).
 
-use crate::graph::GitGraph;
-use crate::settings::Settings;
+use crate::graph::{BisectMark, BranchInfo, GitGraph};
+use crate::logging::span;
+use crate::print::format::format_date;
+use crate::print::unicode::MORE;
+use crate::settings::{CommitOrder, LineStyle, Settings, Stripes};
+use crate::source::Oid;
 use svg::node::element::path::Data;
-use svg::node::element::{Circle, Line, Path};
-use svg::Document;
+use svg::node::element::{Circle, Definitions, Element, Line, Marker, Path, Polygon, Rectangle};
+use svg::{Document, Node};
 
-/// Creates a SVG visual representation of a graph.
+/// Creates a SVG visual representation of a graph, below a title block with `Settings::header`, if given.
 pub fn print_svg(graph: &GitGraph, settings: &Settings) -> Result<String, String> {
+    let (document, width, height) = graph_document(graph, settings)?;
+    let document = match &settings.header {
+        Some(header) => with_header(document, &header.line(graph.commits.len()), width, height),
+        None => document,
+    };
+    write_document(&document)
+}
+
+/// Creates a SVG visual representation of a graph, without a header.
+pub(crate) fn print_svg_graph(graph: &GitGraph, settings: &Settings) -> Result<String, String> {
+    write_document(&graph_document(graph, settings)?.0)
+}
+
+/// Creates the SVG document of a graph, with its width and height.
+fn graph_document(graph: &GitGraph, settings: &Settings) -> Result<(Document, f32, f32), String> {
+    let _render = span("render");
     let mut document = Document::new();
 
-    let max_idx = graph.commits.len();
+    let coords = Coords::new(graph, settings)?;
+    for (index, _) in stripes(graph, settings)?
+        .into_iter()
+        .enumerate()
+        .filter(|(_, shaded)| *shaded)
+    {
+        document = document.add(stripe(&coords, index));
+    }
     let mut max_column = 0;
+    let mut x_extent: f32 = 0.0;
+    // Colors of arrow markers, with marker `arrow-N` for the Nth color
+    let mut arrow_colors: Vec<&str> = vec![];
 
     if settings.debug {
         for branch in &graph.all_branches {
-            if let (Some(start), Some(end)) = branch.range {
-                document = document.add(bold_line(
-                    start,
-                    branch.visual.column.unwrap(),
-                    end,
-                    branch.visual.column.unwrap(),
-                    "cyan",
-                ));
+            if let (Some(start), Some(end), Some(column)) =
+                (branch.range.0, branch.range.1, branch.visual.column)
+            {
+                document = document.add(bold_line(&coords, start, column, end, column, "cyan"));
             }
         }
     }
@@ -30,68 +56,407 @@ pub fn print_svg(graph: &GitGraph, settings: &Settings) -> Result<String, String
     for (idx, info) in graph.commits.iter().enumerate() {
         if let Some(trace) = info.branch_trace {
             let branch = &graph.all_branches[trace];
+            let column = super::branch_column(branch)?;
             let branch_color = &branch.visual.svg_color;
+            let node_color = match graph.bisect_mark(info.oid) {
+                Some(mark) => bisect_color(mark),
+                None => branch_color,
+            };
 
-            if branch.visual.column.unwrap() > max_column {
-                max_column = branch.visual.column.unwrap();
+            if column > max_column {
+                max_column = column;
             }
 
+            let back_merge = settings.back_merges.is_some() && graph.is_back_merge(info);
             for p in 0..2 {
                 if let Some(par_oid) = info.parents[p] {
                     if let Some(par_idx) = graph.indices.get(&par_oid) {
-                        let par_info = &graph.commits[*par_idx];
-                        let par_branch = &graph.all_branches[par_info.branch_trace.unwrap()];
+                        let par_branch = super::commit_branch(graph, *par_idx)?;
+                        let par_column = super::branch_column(par_branch)?;
 
-                        let color = if info.is_merge {
-                            &par_branch.visual.svg_color
+                        let edge_branch = if info.is_merge { par_branch } else { branch };
+                        let (color, style) = if p > 0 && back_merge {
+                            (BACK_MERGE_COLOR, LineStyle::Dotted)
                         } else {
-                            branch_color
+                            (
+                                &edge_branch.visual.svg_color[..],
+                                edge_branch.visual.line_style,
+                            )
                         };
+                        let width = stroke_width(edge_branch, settings);
 
-                        if branch.visual.column == par_branch.visual.column {
-                            document = document.add(line(
-                                idx,
-                                branch.visual.column.unwrap(),
-                                *par_idx,
-                                par_branch.visual.column.unwrap(),
-                                color,
-                            ));
+                        let edge = if settings.svg_ids {
+                            Some((info.oid, par_oid))
                         } else {
-                            let split_index = super::get_deviate_index(graph, idx, *par_idx);
-                            document = document.add(path(
+                            None
+                        };
+                        let title = if settings.svg_tooltips {
+                            Some(tooltip(graph, info.oid, edge_branch, settings)?)
+                        } else {
+                            None
+                        };
+                        if column == par_column {
+                            let line = line(
+                                &coords, idx, column, *par_idx, par_column, color, style, width,
+                            );
+                            document = document.add(with_title(identify_edge(line, edge), title));
+                        } else {
+                            let split_index = super::get_deviate_index(graph, idx, *par_idx)?;
+                            let arrow = if settings.arrows.on_edge(info.is_merge && p > 0) {
+                                let marker = match arrow_colors.iter().position(|c| *c == color) {
+                                    Some(marker) => marker,
+                                    None => {
+                                        arrow_colors.push(color);
+                                        arrow_colors.len() - 1
+                                    }
+                                };
+                                Some(marker)
+                            } else {
+                                None
+                            };
+                            let path = path(
+                                &coords,
                                 idx,
-                                branch.visual.column.unwrap(),
+                                column,
                                 *par_idx,
-                                par_branch.visual.column.unwrap(),
+                                par_column,
                                 split_index,
                                 color,
-                            ));
+                                style,
+                                width,
+                                arrow,
+                            );
+                            document = document.add(with_title(identify_edge(path, edge), title));
                         }
                     }
                 }
             }
 
-            document = document.add(commit_dot(
-                idx,
-                branch.visual.column.unwrap(),
-                branch_color,
-                !info.is_merge,
-            ));
+            let shape = super::node_shape(branch, settings);
+            let oid = if settings.svg_ids {
+                Some(info.oid)
+            } else {
+                None
+            };
+            let title = if settings.svg_tooltips {
+                Some(tooltip(graph, info.oid, branch, settings)?)
+            } else {
+                None
+            };
+            if graph.is_boundary(info.oid) {
+                let square = commit_shape(&coords, idx, column, node_color, false, 1);
+                document = document.add(with_title(identify(square, oid), title));
+            } else if shape == 0 {
+                let dot = commit_dot(&coords, idx, column, node_color, !info.is_merge);
+                document = document.add(with_title(identify(dot, oid), title));
+            } else {
+                let shape = commit_shape(&coords, idx, column, node_color, !info.is_merge, shape);
+                document = document.add(with_title(identify(shape, oid), title));
+            }
+
+            if let Some(bubble) = settings
+                .bubbles
+                .as_ref()
+                .and_then(|bubbles| bubbles.get(&info.oid))
+            {
+                document = document.add(with_title(
+                    bubble_ring(&coords, idx, column, &bubble.svg_color),
+                    Some(bubble.label.clone()),
+                ));
+            }
+
+            if settings.decorations.branch_counts {
+                let mut badge_x = coords.x(column) + BADGE_OFFSET;
+                for branch_idx in &info.branches {
+                    let count = graph.branch_commit_count(*branch_idx);
+                    if count > 0 {
+                        let color = &graph.all_branches[*branch_idx].visual.svg_color;
+                        let (badge, width) = badge(badge_x, coords.y(idx), count, color);
+                        document = document.add(badge);
+                        badge_x += width + BADGE_SPACING;
+                    }
+                }
+                x_extent = f32::max(x_extent, badge_x);
+            }
+
+            if info
+                .branches
+                .iter()
+                .any(|idx| graph.all_branches[*idx].diverged.is_some())
+            {
+                document = document.add(warning(&coords, idx, column));
+            }
+
+            for (pos, number) in graph.head_trail_at(info.oid).into_iter().enumerate() {
+                let marker_x = coords.x(column) + TRAIL_OFFSET + TRAIL_SPACING * pos as f32;
+                x_extent = f32::max(x_extent, marker_x + 6.0);
+                document = document.add(trail_marker(&coords, idx, column, pos, number));
+            }
+        }
+    }
+    for idx in (0..graph.commits.len()).filter(|idx| graph.continues_past_end(*idx)) {
+        let branch = super::commit_branch(graph, idx)?;
+        let (column, color) = (super::branch_column(branch)?, &branch.visual.svg_color);
+        let (x, y) = coords.commit(idx, column);
+        document = document.add(
+            Line::new()
+                .set("x1", x)
+                .set("y1", y)
+                .set("x2", x)
+                .set("y2", y + ROW_HEIGHT)
+                .set("stroke", &color[..])
+                .set("stroke-width", 1)
+                .set("stroke-dasharray", dash_array(LineStyle::Dotted).unwrap()),
+        );
+    }
+    if graph.truncated {
+        let y = coords.height() - 0.5 * ROW_HEIGHT;
+        x_extent = f32::max(x_extent, 5.0 + MORE.len() as f32 * LABEL_CHAR_WIDTH);
+        document = document.add(text(5.0, y, 9, "grey", MORE));
+    }
+
+    if let (Some(y), Some(head_idx)) = (coords.worktree_y(), graph.worktree_parent()) {
+        let branch = super::commit_branch(graph, head_idx)?;
+        let (column, color) = (super::branch_column(branch)?, &branch.visual.svg_color);
+        let (head_x, head_y) = coords.commit(head_idx, column);
+        // Beside the line from a child in HEAD's column, which passes the row of the virtual commit
+        let child_in_column = graph.commits[head_idx]
+            .children
+            .iter()
+            .filter_map(|oid| graph.indices.get(oid))
+            .filter_map(|idx| graph.commits[*idx].branch_trace)
+            .any(|trace| graph.all_branches[trace].visual.column == Some(column));
+        let x = if child_in_column {
+            head_x + 0.5 * ROW_HEIGHT
+        } else {
+            head_x
+        };
+        let summary = graph
+            .worktree
+            .map(|status| status.summary())
+            .unwrap_or_default();
+        let mut title = Element::new("title");
+        title.append(svg::node::Text::new(summary));
+        document = document
+            .add(
+                Line::new()
+                    .set("x1", x)
+                    .set("y1", y)
+                    .set("x2", head_x)
+                    .set("y2", head_y)
+                    .set("stroke", &color[..])
+                    .set("stroke-width", 1)
+                    .set("stroke-dasharray", dash_array(LineStyle::Dashed).unwrap()),
+            )
+            .add(
+                Circle::new()
+                    .set("cx", x)
+                    .set("cy", y)
+                    .set("r", 4)
+                    .set("fill", "white")
+                    .set("stroke", &color[..])
+                    .set("stroke-width", 1)
+                    .set("stroke-dasharray", "2 1")
+                    .add(title),
+            );
+    }
+
+    let (mut x_max, y_max) = (
+        f32::max(coords.x(max_column + 1), x_extent),
+        coords.height(),
+    );
+
+    if settings.svg_divergence {
+        for local in graph
+            .all_branches
+            .iter()
+            .filter(|branch| !branch.is_remote && !branch.is_tag)
+        {
+            let remote_name = format!("origin/{}", local.name);
+            let remote = graph
+                .all_branches
+                .iter()
+                .find(|branch| branch.is_remote && branch.name[..] == remote_name);
+            if let (Some((ahead, behind)), Some(remote)) = (local.diverged, remote) {
+                let (index1, index2) = match (
+                    graph.indices.get(&local.target),
+                    graph.indices.get(&remote.target),
+                ) {
+                    (Some(index1), Some(index2)) => (*index1, *index2),
+                    _ => continue,
+                };
+                let (c1, c2) = (
+                    coords.commit(index1, commit_column(graph, index1)?),
+                    coords.commit(index2, commit_column(graph, index2)?),
+                );
+                let label = format!("diverged ({}/{})", ahead, behind);
+                let (x, y) = (f32::max(c1.0, c2.0) + 8.0, 0.5 * (c1.1 + c2.1) + 3.0);
+                x_max = f32::max(x_max, x + label.len() as f32 * LABEL_CHAR_WIDTH);
+                document = document
+                    .add(
+                        Line::new()
+                            .set("x1", c1.0)
+                            .set("y1", c1.1)
+                            .set("x2", c2.0)
+                            .set("y2", c2.1)
+                            .set("stroke", "grey")
+                            .set("stroke-width", 1)
+                            .set("stroke-dasharray", dash_array(LineStyle::Dotted).unwrap()),
+                    )
+                    .add(text(x, y, 9, "grey", &label));
+            }
+        }
+    }
+
+    if !arrow_colors.is_empty() {
+        let mut definitions = Definitions::new();
+        for (marker, color) in arrow_colors.iter().enumerate() {
+            definitions = definitions.add(arrow_marker(marker, color));
         }
+        document = document.add(definitions);
     }
-    let (x_max, y_max) = commit_coord(max_idx + 1, max_column + 1);
+
+    if settings.svg_metadata {
+        document = document.add(metadata(graph, settings));
+    }
+
     document = document
         .set("viewBox", (0, 0, x_max, y_max))
         .set("width", x_max)
         .set("height", y_max);
+    Ok((document, x_max, y_max))
+}
+
+/// Places a graph below a title block with the given header line.
+fn with_header(graph: Document, header: &str, width: f32, height: f32) -> Document {
+    let width = f32::max(
+        width,
+        10.0 + header.chars().count() as f32 * HEADER_CHAR_WIDTH,
+    );
+    let height = height + HEADER_HEIGHT;
+    Document::new()
+        .set("viewBox", (0, 0, width, height))
+        .set("width", width)
+        .set("height", height)
+        .add(text(5.0, 14.0, 11, "black", header))
+        .add(graph.set("y", HEADER_HEIGHT))
+}
 
+fn write_document(document: &Document) -> Result<String, String> {
     let mut out: Vec<u8> = vec![];
-    svg::write(&mut out, &document).map_err(|err| err.to_string())?;
+    svg::write(&mut out, document).map_err(|err| err.to_string())?;
     Ok(String::from_utf8(out).unwrap_or_else(|_| "Invalid UTF8 character.".to_string()))
 }
 
-fn commit_dot(index: usize, column: usize, color: &str, filled: bool) -> Circle {
-    let (x, y) = commit_coord(index, column);
+/// Adds a commit's hash as attributes `id` (like `c-0a1b...`, as ids can't start with a digit) and `data-oid`.
+fn identify<T: Node>(mut node: T, oid: Option<Oid>) -> T {
+    if let Some(oid) = oid {
+        node.assign("id", format!("c-{}", oid));
+        node.assign("data-oid", oid.to_string());
+    }
+    node
+}
+
+/// Adds the hashes of the commit and parent connected by a line as attributes `data-from` and `data-to`.
+fn identify_edge<T: Node>(mut node: T, edge: Option<(Oid, Oid)>) -> T {
+    if let Some((from, to)) = edge {
+        node.assign("data-from", from.to_string());
+        node.assign("data-to", to.to_string());
+    }
+    node
+}
+
+/// Adds a `<title>` child, shown as a tooltip by SVG viewers.
+fn with_title<T: Node>(mut node: T, title: Option<String>) -> T {
+    if let Some(title) = title {
+        let mut element = Element::new("title");
+        element.append(svg::node::Text::new(title));
+        node.append(element);
+    }
+    node
+}
+
+/// The tooltip of a commit, or of a line from it on the given branch:
+/// abbreviated hash and summary, branch, author and date.
+fn tooltip(
+    graph: &GitGraph,
+    oid: Oid,
+    branch: &BranchInfo,
+    settings: &Settings,
+) -> Result<String, String> {
+    let commit = graph.commit(oid)?;
+    let hash = oid.to_string();
+    Ok(format!(
+        "{} {}\n{}\n{}, {}",
+        &hash[..graph.abbrev.min(hash.len())],
+        commit.summary(),
+        branch.display_name(),
+        commit.author.name,
+        format_date(&commit.author, "%F %R", settings.date_timezone)
+    ))
+}
+
+/// Element `<metadata>` with the parameters of the graph, as attributes of a `git-graph` element.
+fn metadata(graph: &GitGraph, settings: &Settings) -> Element {
+    let order = match settings.commit_order {
+        CommitOrder::Date => "date",
+        CommitOrder::AuthorDate => "author-date",
+        CommitOrder::Topo => "topo",
+    };
+    let mut parameters = Element::new("git-graph");
+    parameters.assign("xmlns", "https://github.com/mlange-42/git-graph");
+    parameters.assign("version", env!("CARGO_PKG_VERSION"));
+    parameters.assign("head", graph.head.oid.to_string());
+    parameters.assign("head-name", &graph.head.name[..]);
+    parameters.assign("commits", graph.commits.len());
+    parameters.assign("truncated", graph.truncated);
+    parameters.assign("commit-order", order);
+    parameters.assign("reverse", settings.reverse_commit_order);
+    parameters.assign("row-spacing", settings.row_spacing);
+    if let Some(px_per_day) = settings.svg_time_axis {
+        parameters.assign("time-axis", px_per_day);
+    }
+    let mut metadata = Element::new("metadata");
+    metadata.append(parameters);
+    metadata
+}
+
+/// Whether the row of each commit is shaded by `Settings::svg_stripes`.
+pub(crate) fn stripes(graph: &GitGraph, settings: &Settings) -> Result<Vec<bool>, String> {
+    match settings.svg_stripes {
+        None => Ok(vec![false; graph.commits.len()]),
+        Some(Stripes::Rows) => Ok((0..graph.commits.len()).map(|idx| idx % 2 == 1).collect()),
+        Some(Stripes::Months) => {
+            let mut shaded = Vec::with_capacity(graph.commits.len());
+            let mut prev_month = None;
+            let mut band = true;
+            for info in &graph.commits {
+                let commit = graph.commit(info.oid)?;
+                let month = format_date(&commit.committer, "%Y-%m", settings.date_timezone);
+                if prev_month.as_ref() != Some(&month) {
+                    band = !band;
+                    prev_month = Some(month);
+                }
+                shaded.push(band);
+            }
+            Ok(shaded)
+        }
+    }
+}
+
+/// A band across the complete width behind the row of a commit.
+fn stripe(coords: &Coords, index: usize) -> Rectangle {
+    Rectangle::new()
+        .set("x", 0)
+        .set("y", coords.y(index) - 0.5 * ROW_HEIGHT)
+        .set("width", "100%")
+        .set("height", coords.row_height(index))
+        .set("fill", "black")
+        .set("fill-opacity", STRIPE_OPACITY)
+}
+
+fn commit_dot(coords: &Coords, index: usize, column: usize, color: &str, filled: bool) -> Circle {
+    let (x, y) = coords.commit(index, column);
     Circle::new()
         .set("cx", x)
         .set("cy", y)
@@ -101,21 +466,166 @@ fn commit_dot(index: usize, column: usize, color: &str, filled: bool) -> Circle
         .set("stroke-width", 1)
 }
 
-fn line(index1: usize, column1: usize, index2: usize, column2: usize, color: &str) -> Line {
-    let (x1, y1) = commit_coord(index1, column1);
-    let (x2, y2) = commit_coord(index2, column2);
-    Line::new()
+/// A commit node with a shape other than a circle: square, diamond, triangle or inverted triangle.
+/// A pill with the number of commits of a branch, left-aligned at `x`. Returns it with its width.
+fn badge(x: f32, y: f32, count: usize, color: &str) -> (Element, f32) {
+    let text_width = count.to_string().len() as f32 * BADGE_CHAR_WIDTH;
+    let width = text_width + BADGE_HEIGHT;
+    let mut group = Element::new("g");
+    group.append(
+        Rectangle::new()
+            .set("x", x)
+            .set("y", y - BADGE_HEIGHT / 2.0)
+            .set("width", width)
+            .set("height", BADGE_HEIGHT)
+            .set("rx", BADGE_HEIGHT / 2.0)
+            .set("fill", color),
+    );
+    group.append(text(
+        x + BADGE_HEIGHT / 2.0,
+        y + 3.0,
+        8,
+        "white",
+        &count.to_string(),
+    ));
+    (group, width)
+}
+
+/// A ring around a commit, for the branches collapsed into it by `Settings::bubbles`.
+fn bubble_ring(coords: &Coords, index: usize, column: usize, color: &str) -> Circle {
+    let (x, y) = coords.commit(index, column);
+    Circle::new()
+        .set("cx", x)
+        .set("cy", y)
+        .set("r", 7)
+        .set("fill", "none")
+        .set("stroke", color)
+        .set("stroke-width", 2)
+}
+
+fn commit_shape(
+    coords: &Coords,
+    index: usize,
+    column: usize,
+    color: &str,
+    filled: bool,
+    shape: u8,
+) -> Polygon {
+    let (x, y) = coords.commit(index, column);
+    let offsets: &[(f32, f32)] = match shape {
+        1 => &[(-3.5, -3.5), (3.5, -3.5), (3.5, 3.5), (-3.5, 3.5)],
+        2 => &[(0.0, -5.0), (5.0, 0.0), (0.0, 5.0), (-5.0, 0.0)],
+        3 => &[(0.0, -5.0), (5.0, 4.0), (-5.0, 4.0)],
+        _ => &[(0.0, 5.0), (5.0, -4.0), (-5.0, -4.0)],
+    };
+    let points = offsets
+        .iter()
+        .map(|(dx, dy)| format!("{},{}", x + dx, y + dy))
+        .collect::<Vec<_>>()
+        .join(" ");
+    Polygon::new()
+        .set("points", points)
+        .set("fill", if filled { color } else { "white" })
+        .set("stroke", color)
+        .set("stroke-width", 1)
+}
+
+/// Warning sign next to the tip of a branch that diverged from its remote counterpart.
+fn warning(coords: &Coords, index: usize, column: usize) -> Element {
+    let (x, y) = coords.commit(index, column);
+    let mut warning = text(x + 5.0, y - 2.0, 10, "red", "!");
+    warning.assign("font-weight", "bold");
+    warning
+}
+
+/// Small numbered marker of a previous HEAD position (`HEAD@{number}`), the `pos`-th at the commit.
+fn trail_marker(
+    coords: &Coords,
+    index: usize,
+    column: usize,
+    pos: usize,
+    number: usize,
+) -> Element {
+    let (x, y) = coords.commit(index, column);
+    let (x, y) = (x + TRAIL_OFFSET + TRAIL_SPACING * pos as f32, y - 6.0);
+    let mut marker = Element::new("g");
+    marker.append(
+        Circle::new()
+            .set("cx", x)
+            .set("cy", y)
+            .set("r", 5)
+            .set("fill", "white")
+            .set("stroke", "grey")
+            .set("stroke-width", 1),
+    );
+    let mut label = text(x, y + 2.5, 7, "grey", &number.to_string());
+    label.assign("text-anchor", "middle");
+    marker.append(label);
+    marker
+}
+
+fn text(x: f32, y: f32, size: usize, color: &str, content: &str) -> Element {
+    let mut text = Element::new("text");
+    text.assign("x", x);
+    text.assign("y", y);
+    text.assign("font-family", "sans-serif");
+    text.assign("font-size", size);
+    text.assign("fill", color);
+    text.append(svg::node::Text::new(content));
+    text
+}
+
+/// Color of commits with a role in a bisect.
+fn bisect_color(mark: BisectMark) -> &'static str {
+    match mark {
+        BisectMark::Bad => "red",
+        BisectMark::Good => "green",
+        BisectMark::Skip => "orange",
+        BisectMark::Current => "darkcyan",
+    }
+}
+
+/// The column of the commit at the given index.
+fn commit_column(graph: &GitGraph, index: usize) -> Result<usize, String> {
+    super::branch_column(super::commit_branch(graph, index)?)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn line(
+    coords: &Coords,
+    index1: usize,
+    column1: usize,
+    index2: usize,
+    column2: usize,
+    color: &str,
+    style: LineStyle,
+    width: usize,
+) -> Line {
+    let (x1, y1) = coords.commit(index1, column1);
+    let (x2, y2) = coords.commit(index2, column2);
+    let line = Line::new()
         .set("x1", x1)
         .set("y1", y1)
         .set("x2", x2)
         .set("y2", y2)
         .set("stroke", color)
-        .set("stroke-width", 1)
+        .set("stroke-width", width);
+    match dash_array(style) {
+        Some(dashes) => line.set("stroke-dasharray", dashes),
+        None => line,
+    }
 }
 
-fn bold_line(index1: usize, column1: usize, index2: usize, column2: usize, color: &str) -> Line {
-    let (x1, y1) = commit_coord(index1, column1);
-    let (x2, y2) = commit_coord(index2, column2);
+fn bold_line(
+    coords: &Coords,
+    index1: usize,
+    column1: usize,
+    index2: usize,
+    column2: usize,
+    color: &str,
+) -> Line {
+    let (x1, y1) = coords.commit(index1, column1);
+    let (x2, y2) = coords.commit(index2, column2);
     Line::new()
         .set("x1", x1)
         .set("y1", y1)
@@ -125,37 +635,186 @@ fn bold_line(index1: usize, column1: usize, index2: usize, column2: usize, color
         .set("stroke-width", 5)
 }
 
+/// A line from a commit to its parent in another column, turning at `split_idx`.
+/// With an arrow marker, which points to the turn in the commit's column.
+#[allow(clippy::too_many_arguments)]
 fn path(
+    coords: &Coords,
     index1: usize,
     column1: usize,
     index2: usize,
     column2: usize,
     split_idx: usize,
     color: &str,
+    style: LineStyle,
+    width: usize,
+    arrow: Option<usize>,
 ) -> Path {
-    let c0 = commit_coord(index1, column1);
+    let c0 = coords.commit(index1, column1);
 
-    let c1 = commit_coord(split_idx, column1);
-    let c2 = commit_coord(split_idx + 1, column2);
+    let c1 = coords.commit(split_idx, column1);
+    let c2 = (coords.x(column2), c1.1 + ROW_HEIGHT);
 
-    let c3 = commit_coord(index2, column2);
+    let c3 = coords.commit(index2, column2);
 
     let m = (0.5 * (c1.0 + c2.0), 0.5 * (c1.1 + c2.1));
 
+    // Start at the turn, where `marker-start` places the arrow
     let data = Data::new()
-        .move_to(c0)
-        .line_to(c1)
+        .move_to(c1)
         .quadratic_curve_to((c1.0, m.1, m.0, m.1))
         .quadratic_curve_to((c2.0, m.1, c2.0, c2.1))
         .line_to(c3);
+    let data = if c0 != c1 {
+        data.move_to(c1).line_to(c0)
+    } else {
+        data
+    };
 
-    Path::new()
+    let path = Path::new()
         .set("d", data)
         .set("fill", "none")
         .set("stroke", color)
-        .set("stroke-width", 1)
+        .set("stroke-width", width);
+    let path = match arrow {
+        Some(marker) => path.set("marker-start", format!("url(#arrow-{})", marker)),
+        None => path,
+    };
+    match dash_array(style) {
+        Some(dashes) => path.set("stroke-dasharray", dashes),
+        None => path,
+    }
+}
+
+/// The stroke width of a branch's lines, thicker for long-lived branches with `--emphasis`.
+fn stroke_width(branch: &BranchInfo, settings: &Settings) -> usize {
+    if settings.emphasis && usize::from(branch.persistence) < settings.branches.emphasized {
+        2
+    } else {
+        1
+    }
+}
+
+/// An arrowhead for lines of the given color, ending short of the commit dot at the line's start.
+fn arrow_marker(marker: usize, color: &str) -> Marker {
+    Marker::new()
+        .set("id", format!("arrow-{}", marker))
+        .set("viewBox", "0 0 10 10")
+        .set("refX", 18)
+        .set("refY", 5)
+        .set("markerWidth", 6)
+        .set("markerHeight", 6)
+        .set("orient", "auto-start-reverse")
+        .add(
+            Path::new()
+                .set("d", "M 0 0 L 10 5 L 0 10 z")
+                .set("fill", color),
+        )
+}
+
+fn dash_array(style: LineStyle) -> Option<&'static str> {
+    match style {
+        LineStyle::Solid => None,
+        LineStyle::Dashed => Some("4 2"),
+        LineStyle::Dotted => Some("1 2"),
+    }
+}
+
+/// Approximate width of a character in labels, for sizing the document.
+const LABEL_CHAR_WIDTH: f32 = 5.5;
+
+/// Color of the lines of back-merges, with `Settings::back_merges`.
+const BACK_MERGE_COLOR: &str = "lightgrey";
+
+/// Distance of the first commit count badge from its commit, spacing and height of badges,
+/// and approximate width of their digits.
+const BADGE_OFFSET: f32 = 8.0;
+const BADGE_SPACING: f32 = 3.0;
+const BADGE_HEIGHT: f32 = 10.0;
+const BADGE_CHAR_WIDTH: f32 = 5.0;
+
+/// Height of the title block of `Settings::header`, and approximate width of its characters.
+const HEADER_HEIGHT: f32 = 20.0;
+const HEADER_CHAR_WIDTH: f32 = 6.5;
+
+/// Horizontal distance of the first HEAD trail marker from its commit, and between markers.
+const TRAIL_OFFSET: f32 = 10.0;
+const TRAIL_SPACING: f32 = 11.0;
+
+/// Distance between columns, and between commits in uniform rows.
+const ROW_HEIGHT: f32 = 15.0;
+
+/// Opacity of the black background bands of shaded rows, subtle on light and dark backgrounds.
+pub(crate) const STRIPE_OPACITY: f32 = 0.06;
+
+/// Mapping of commits and columns to SVG coordinates.
+pub(crate) struct Coords {
+    /// The y coordinate of each commit
+    ys: Vec<f32>,
+    /// The y coordinate of the virtual commit for uncommitted changes, if shown
+    worktree: Option<f32>,
 }
 
-fn commit_coord(index: usize, column: usize) -> (f32, f32) {
-    (15.0 * (column as f32 + 1.0), 15.0 * (index as f32 + 1.0))
+impl Coords {
+    /// Places commits in uniform rows, or on a time axis if `Settings::svg_time_axis` is given.
+    pub(crate) fn new(graph: &GitGraph, settings: &Settings) -> Result<Self, String> {
+        let mut ys = Vec::with_capacity(graph.commits.len());
+        let spacing = ROW_HEIGHT * (settings.row_spacing + 1) as f32;
+        let worktree_parent = graph.worktree_parent();
+        let mut worktree = None;
+        let mut prev_time = None;
+        for (idx, info) in graph.commits.iter().enumerate() {
+            let time = match settings.svg_time_axis {
+                Some(_) => Some(graph.commit(info.oid)?.committer.time),
+                None => None,
+            };
+            // Commits are not strictly ordered by time, so `spacing` is also the minimum gap
+            let gap = match (settings.svg_time_axis, prev_time, time) {
+                (Some(px_per_day), Some(prev), Some(time)) => {
+                    f32::max(spacing, (prev - time) as f32 / 86400.0 * px_per_day)
+                }
+                _ => spacing,
+            };
+            let mut y = ys.last().map_or(ROW_HEIGHT, |y| y + gap);
+            // The virtual commit of uncommitted changes gets a row right above HEAD
+            if worktree_parent == Some(idx) {
+                worktree = Some(y);
+                y += spacing;
+            }
+            ys.push(y);
+            prev_time = time;
+        }
+        Ok(Coords { ys, worktree })
+    }
+
+    fn x(&self, column: usize) -> f32 {
+        ROW_HEIGHT * (column as f32 + 1.0)
+    }
+
+    fn commit(&self, index: usize, column: usize) -> (f32, f32) {
+        (self.x(column), self.y(index))
+    }
+
+    pub(crate) fn y(&self, index: usize) -> f32 {
+        self.ys[index]
+    }
+
+    /// Height of a commit's row, up to the next commit or the virtual commit of uncommitted changes.
+    pub(crate) fn row_height(&self, index: usize) -> f32 {
+        let y = self.ys[index];
+        match (self.ys.get(index + 1), self.worktree) {
+            (Some(next), Some(worktree)) if worktree > y && worktree < *next => worktree - y,
+            (Some(next), _) => next - y,
+            (None, _) => ROW_HEIGHT,
+        }
+    }
+
+    pub(crate) fn worktree_y(&self) -> Option<f32> {
+        self.worktree
+    }
+
+    /// Height of the complete graph.
+    fn height(&self) -> f32 {
+        self.ys.last().map_or(0.0, |y| *y) + 2.0 * ROW_HEIGHT
+    }
 }
@@ -0,0 +1,86 @@
+//! Widgets for embedding graphs into [ratatui](https://github.com/tui-rs-revival/ratatui) applications.
+//!
+//! Requires feature `tui`. Rows should be created with `Settings::colored` set to `false`,
+//! as the widget applies colors itself.
+
+use crate::print::unicode::GraphRow;
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{StatefulWidget, Widget};
+
+/// Scroll and selection state of a `GitGraphWidget`.
+#[derive(Default, Clone, Debug)]
+pub struct GitGraphState {
+    /// Index of the first visible row
+    pub offset: usize,
+    /// Index of the selected commit in `GitGraph::commits`
+    pub selected: Option<usize>,
+}
+
+/// Widget rendering the rows of a graph, as produced by `GitGraph::rows`.
+///
+/// Only the visible rows are consumed from the iterator.
+pub struct GitGraphWidget<I> {
+    rows: I,
+    text_style: Style,
+    highlight_style: Style,
+}
+
+impl<I: Iterator<Item = GraphRow>> GitGraphWidget<I> {
+    pub fn new(rows: I) -> Self {
+        GitGraphWidget {
+            rows,
+            text_style: Style::default(),
+            highlight_style: Style::default().add_modifier(Modifier::REVERSED),
+        }
+    }
+
+    /// Sets the style of the commit text.
+    pub fn text_style(mut self, style: Style) -> Self {
+        self.text_style = style;
+        self
+    }
+
+    /// Sets the style of the selected commit's row.
+    pub fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+}
+
+impl<I: Iterator<Item = GraphRow>> Widget for GitGraphWidget<I> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        StatefulWidget::render(self, area, buf, &mut GitGraphState::default());
+    }
+}
+
+impl<I: Iterator<Item = GraphRow>> StatefulWidget for GitGraphWidget<I> {
+    type State = GitGraphState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let rows = self.rows.skip(state.offset).take(area.height as usize);
+        for (y, row) in (area.top()..area.bottom()).zip(rows) {
+            let mut x = area.left();
+            for cell in &row.cells {
+                if x >= area.right() {
+                    break;
+                }
+                buf.get_mut(x, y)
+                    .set_char(cell.character)
+                    .set_fg(Color::Indexed(cell.color));
+                x += 1;
+            }
+            if x + 2 < area.right() {
+                let width = (area.right() - x - 2) as usize;
+                buf.set_stringn(x + 2, y, &row.text, width, self.text_style);
+            }
+            if row.commit.is_some() && row.commit == state.selected {
+                buf.set_style(
+                    Rect::new(area.left(), y, area.width, 1),
+                    self.highlight_style,
+                );
+            }
+        }
+    }
+}
@@ -1,13 +1,19 @@
 //! Create graphs in SVG format (Scalable Vector Graphics).
 
-use crate::graph::{CommitInfo, GitGraph, HeadInfo};
-use crate::print::format::CommitFormat;
-use crate::settings::{Characters, Settings};
+use crate::graph::{BisectMark, CommitInfo, GitGraph, HeadInfo};
+use crate::logging::span;
+use crate::print::format::{CommitFormat, DateTimeZone, REBASED_MARKER};
+use crate::settings::{
+    BackMerges, Characters, Decorations, LineNumbers, LineStyle, NodeGlyphs, Settings,
+    DENSITY_COMPACT, DENSITY_SPARSE,
+};
+use crate::source::Oid;
 use itertools::Itertools;
 use std::cmp::max;
 use std::collections::hash_map::Entry::{Occupied, Vacant};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Write;
+use textwrap::core::display_width;
 use textwrap::Options;
 use yansi::Paint;
 
@@ -29,34 +35,298 @@ const HOR_D: u8 = 13;
 const ARR_L: u8 = 14;
 const ARR_R: u8 = 15;
 
+const VER_DASHED: u8 = 16;
+const HOR_DASHED: u8 = 17;
+const VER_DOTTED: u8 = 18;
+const HOR_DOTTED: u8 = 19;
+
+const NODES: u8 = 20;
+const NODES_HOLLOW: u8 = 24;
+const WARNING: u8 = 28;
+const VIRTUAL: u8 = 29;
+const STUB: u8 = 30;
+
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+const HORIZONTAL: u8 = LEFT | RIGHT;
+
+/// Line characters, by the directions they connect to
+const LINE_DIRECTIONS: [(u8, u8); 14] = [
+    (SPACE, 0),
+    (VER, UP | DOWN),
+    (HOR, HORIZONTAL),
+    (ARR_L, HORIZONTAL),
+    (ARR_R, HORIZONTAL),
+    (CROSS, UP | DOWN | HORIZONTAL),
+    (R_U, UP | RIGHT),
+    (R_D, DOWN | RIGHT),
+    (L_D, DOWN | LEFT),
+    (L_U, UP | LEFT),
+    (VER_L, UP | DOWN | LEFT),
+    (VER_R, UP | DOWN | RIGHT),
+    (HOR_U, UP | HORIZONTAL),
+    (HOR_D, DOWN | HORIZONTAL),
+];
+
+const SOLID: u8 = 0;
+const DASHED: u8 = 1;
+const DOTTED: u8 = 2;
+/// Node shapes from this value on refer to `Grid::glyphs`
+const CUSTOM_NODES: u8 = 32;
+/// Node shape of boundary commits, a hollow square when combined with `CIRCLE`
+const BOUNDARY: u8 = 1;
+/// Node shape of branch tips that diverged from their remote counterpart
+const DIVERGED: u8 = u8::MAX;
+
 const WHITE: u8 = 7;
 const HEAD_COLOR: u8 = 14;
 const HASH_COLOR: u8 = 11;
+const OPERATION_COLOR: u8 = 13;
+const BISECT_BAD_COLOR: u8 = 9;
+const BISECT_GOOD_COLOR: u8 = 10;
+const BISECT_SKIP_COLOR: u8 = 3;
+const SEPARATOR_COLOR: u8 = 8;
+
+/// Label of back-merges with `BackMerges::Label`
+const BACK_MERGE: &str = "[back-merge]";
+
+/// Text of the last row of a history truncated by `max_count`
+pub(crate) const MORE: &str = "... more";
 
 type UnicodeGraphInfo = (Vec<String>, Vec<String>, Vec<usize>);
 
 /// Creates a text-based visual representation of a graph.
 pub fn print_unicode(graph: &GitGraph, settings: &Settings) -> Result<UnicodeGraphInfo, String> {
-    let num_cols = 2 * graph
-        .all_branches
-        .iter()
-        .map(|b| b.visual.column.unwrap_or(0))
-        .max()
-        .unwrap()
-        + 1;
+    print_unicode_prefix(graph, settings, graph.commits.len())
+}
 
-    let head_idx = graph.indices.get(&graph.head.oid);
+/// Creates a text-based visual representation of the first `count` commits of a graph,
+/// without laying out the rest. Lines to later commits continue to the bottom.
+///
+/// The rows are the same as the first rows of `print_unicode`, so the rest of the graph
+/// can be laid out when needed. Always lays out all commits with `reverse_commit_order`.
+pub fn print_unicode_prefix(
+    graph: &GitGraph,
+    settings: &Settings,
+    count: usize,
+) -> Result<UnicodeGraphInfo, String> {
+    let count = if settings.reverse_commit_order {
+        graph.commits.len()
+    } else {
+        count.min(graph.commits.len())
+    };
+    let _render = span("render");
+    let rows = layout_rows(graph, settings, count)?;
+    let layout = draw_edges(graph, settings, rows)?;
+    Ok(emit(graph, settings, layout))
+}
 
-    let inserts = get_inserts(graph, settings.compact);
+/// Prints a drawn graph: the lines of the graph, prefixed by line numbers if requested, and the text lines.
+/// Returns them together with the row of each commit.
+pub fn emit(graph: &GitGraph, settings: &Settings, layout: GraphLayout) -> UnicodeGraphInfo {
+    let GraphLayout {
+        grid,
+        text_lines,
+        index_map,
+    } = layout;
+    let (mut graph_lines, text_lines) =
+        print_graph(&settings.characters, &grid, text_lines, settings.colored);
+
+    if let Some(numbers) = settings.line_numbers {
+        number_rows(
+            &mut graph_lines,
+            &index_map,
+            numbers,
+            graph.commits.len(),
+            settings.reverse_commit_order,
+        );
+    }
 
-    let (indent1, indent2) = if let Some((_, ind1, ind2)) = settings.wrapping {
-        (" ".repeat(ind1.unwrap_or(0)), " ".repeat(ind2.unwrap_or(0)))
-    } else {
-        ("".to_string(), "".to_string())
+    (graph_lines, text_lines, index_map)
+}
+
+/// Prefixes the rows of commits with their index or row number, in a left gutter.
+/// The width of the gutter depends on the number of commits, to be the same for all prefixes of a graph.
+fn number_rows(
+    lines: &mut [String],
+    index_map: &[usize],
+    numbers: LineNumbers,
+    num_commits: usize,
+    reverse: bool,
+) {
+    let mut gutter = vec![None; lines.len()];
+    for (idx, row) in index_map.iter().enumerate() {
+        let row = if reverse { lines.len() - 1 - row } else { *row };
+        gutter[row] = Some(match numbers {
+            LineNumbers::Index => idx,
+            LineNumbers::Row => row + 1,
+        });
+    }
+    let max = match numbers {
+        LineNumbers::Index => num_commits,
+        LineNumbers::Row => num_commits.max(lines.len()),
     };
+    let width = gutter_width(Some(numbers), max) - 1;
+    for (line, number) in lines.iter_mut().zip(gutter) {
+        let number = number.map(|n| n.to_string()).unwrap_or_default();
+        *line = format!("{:>width$} {}", number, line, width = width);
+    }
+}
+
+/// The width of the line number gutter, including a trailing space, for numbers up to `max`.
+fn gutter_width(numbers: Option<LineNumbers>, max: usize) -> usize {
+    match numbers {
+        Some(_) => max.to_string().len() + 1,
+        None => 0,
+    }
+}
+
+/// A single cell of a text-based graph.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GraphCell {
+    /// The character to draw
+    pub character: char,
+    /// The cell's terminal color (index in 256-color palette)
+    pub color: u8,
+}
+
+/// A single row of a text-based graph.
+pub struct GraphRow {
+    /// Index of the commit in `GitGraph::commits`, if this is the commit's (first) row
+    pub commit: Option<usize>,
+    /// The graph cells, left to right
+    pub cells: Vec<GraphCell>,
+    /// The formatted commit text in this row
+    pub text: String,
+}
+
+/// Iterator over the rows of a text-based graph. Created by `GitGraph::rows`.
+pub struct GraphRows {
+    grid: Grid,
+    text_lines: std::vec::IntoIter<Option<String>>,
+    row_commits: Vec<Option<usize>>,
+    characters: Characters,
+    emphasized: Characters,
+    row: usize,
+}
+
+impl Iterator for GraphRows {
+    type Item = GraphRow;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let text = self.text_lines.next()?;
+        let cells = self.grid.data
+            [(self.row * self.grid.width)..((self.row + 1) * self.grid.width)]
+            .iter()
+            .map(|arr| {
+                let characters = if self.grid.is_emphasized(arr) {
+                    &self.emphasized
+                } else {
+                    &self.characters
+                };
+                GraphCell {
+                    character: cell_char(characters, &self.grid.glyphs, arr),
+                    color: arr[1],
+                }
+            })
+            .collect();
+        let row = GraphRow {
+            commit: self.row_commits[self.row],
+            cells,
+            text: text.unwrap_or_default(),
+        };
+        self.row += 1;
+        Some(row)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.text_lines.size_hint()
+    }
+}
 
-    let wrap_options = if let Some((width, _, _)) = settings.wrapping {
-        create_wrapping_options(width, &indent1, &indent2, num_cols + 4)?
+/// Lays out a graph and creates an iterator over its rows. See `GitGraph::rows`.
+pub fn graph_rows(graph: &GitGraph, settings: &Settings) -> Result<GraphRows, String> {
+    let rows = layout_rows(graph, settings, graph.commits.len())?;
+    let GraphLayout {
+        grid,
+        text_lines,
+        index_map,
+    } = draw_edges(graph, settings, rows)?;
+
+    let mut row_commits = vec![None; grid.height];
+    for (commit, row) in index_map.iter().enumerate() {
+        if settings.reverse_commit_order {
+            row_commits[grid.height - 1 - row] = Some(commit);
+        } else {
+            row_commits[*row] = Some(commit);
+        }
+    }
+
+    Ok(GraphRows {
+        grid,
+        text_lines: text_lines.into_iter(),
+        row_commits,
+        characters: settings.characters.clone(),
+        emphasized: settings.characters.emphasized(),
+        row: 0,
+    })
+}
+
+/// The rows of a text-based graph before drawing. Created by `layout_rows`, and drawn by `draw_edges`.
+pub struct RowLayout {
+    /// The text of each row, `None` for rows only needed for lines between columns
+    pub text_lines: Vec<Option<String>>,
+    /// The row of each laid out commit
+    pub index_map: Vec<usize>,
+    /// Number of rows of the graph
+    height: usize,
+    /// Lines between columns, in rows inserted below the commit they are keyed by
+    inserts: HashMap<usize, Vec<Vec<Occ>>>,
+}
+
+/// A drawn text-based graph, with a row of the grid for each text line.
+/// Created by `draw_edges`, and printed by `emit`.
+pub struct GraphLayout {
+    grid: Grid,
+    /// The text of each row, `None` for rows only needed for lines between columns
+    pub text_lines: Vec<Option<String>>,
+    /// The row of each laid out commit, irrespective of `reverse_commit_order`
+    pub index_map: Vec<usize>,
+}
+
+/// Lays out the rows of the first `count` commits: formats their text lines,
+/// and reserves rows for lines between columns.
+/// As commits only draw into their own and later rows, these rows do not depend on later commits.
+pub fn layout_rows(
+    graph: &GitGraph,
+    settings: &Settings,
+    count: usize,
+) -> Result<RowLayout, String> {
+    let commits = &graph.commits[..count];
+
+    let head_idx = graph.indices.get(&graph.head.oid);
+    let worktree_parent = graph.worktree_parent();
+
+    let inserts = get_inserts(graph, commits, settings.density)?;
+
+    let hanging = settings
+        .wrapping
+        .as_ref()
+        .map_or("", |wrapping| &wrapping.hanging[..]);
+    let wrap_options = if let Some(wrapping) = &settings.wrapping {
+        // Lines are wrapped before adding the hanging indent
+        let hanging_width = display_width(hanging);
+        let gutter = gutter_width(settings.line_numbers, graph.commits.len());
+        create_wrapping_options(
+            wrapping
+                .width
+                .map(|width| width.saturating_sub(hanging_width).max(1)),
+            &wrapping.indent1,
+            &wrapping.indent2,
+            graph.width() + 4 + gutter + hanging_width,
+        )?
     } else {
         None
     };
@@ -64,7 +334,20 @@ pub fn print_unicode(graph: &GitGraph, settings: &Settings) -> Result<UnicodeGra
     let mut index_map = vec![];
     let mut text_lines = vec![];
     let mut offset = 0;
-    for (idx, info) in graph.commits.iter().enumerate() {
+    for (idx, info) in commits.iter().enumerate() {
+        if let Some(label) = release_band(settings, info) {
+            let band = format!("\u{2500}\u{2500} {}", label);
+            text_lines.push(Some(if settings.colored {
+                Paint::new(band).bold().to_string()
+            } else {
+                band
+            }));
+            offset += 1;
+        }
+        if worktree_parent == Some(idx) {
+            text_lines.push(graph.worktree.map(|status| status.summary()));
+            offset += 1;
+        }
         index_map.push(idx + offset);
         let cnt_inserts = if let Some(inserts) = inserts.get(&idx) {
             inserts
@@ -86,14 +369,49 @@ pub fn print_unicode(graph: &GitGraph, settings: &Settings) -> Result<UnicodeGra
             None
         };
 
-        let lines = format(
+        let mut lines = format(
             &settings.format,
             graph,
             info,
             head,
             settings.colored,
+            settings.date_timezone,
             &wrap_options,
+            &settings.decorations,
         )?;
+        if let Some(bubble) = settings
+            .bubbles
+            .as_ref()
+            .and_then(|bubbles| bubbles.get(&info.oid))
+        {
+            let label = format!("({})", bubble.label);
+            let label = if settings.colored {
+                Paint::fixed(bubble.term_color, label).to_string()
+            } else {
+                label
+            };
+            match lines.first_mut() {
+                Some(line) => line.insert_str(0, &format!("{} ", label)),
+                None => lines.push(label),
+            }
+        }
+        if settings.back_merges == Some(BackMerges::Label) && graph.is_back_merge(info) {
+            let label = if settings.colored {
+                Paint::fixed(SEPARATOR_COLOR, BACK_MERGE).to_string()
+            } else {
+                BACK_MERGE.to_string()
+            };
+            match lines.first_mut() {
+                Some(line) => line.push_str(&format!(" {}", label)),
+                None => lines.push(label),
+            }
+        }
+        // Empty lines stay empty, unless the indent is visible
+        for line in lines.iter_mut().skip(1) {
+            if !line.is_empty() || !hanging.trim().is_empty() {
+                line.insert_str(0, hanging);
+            }
+        }
 
         let num_lines = if lines.is_empty() { 0 } else { lines.len() - 1 };
         let max_inserts = max(cnt_inserts, num_lines);
@@ -103,81 +421,198 @@ pub fn print_unicode(graph: &GitGraph, settings: &Settings) -> Result<UnicodeGra
         text_lines.extend((0..add_lines).map(|_| None));
 
         offset += max_inserts;
+
+        // Spacing rows have empty text, so that they are not pruned
+        if idx + 1 < graph.commits.len() {
+            text_lines.extend((0..settings.row_spacing).map(|_| Some(String::new())));
+            offset += settings.row_spacing;
+        }
     }
 
-    let mut grid = Grid::new(
-        num_cols,
-        graph.commits.len() + offset,
-        [SPACE, WHITE, settings.branches.persistence.len() as u8 + 2],
-    );
+    Ok(RowLayout {
+        text_lines,
+        index_map,
+        height: count + offset,
+        inserts,
+    })
+}
 
-    for (idx, info) in graph.commits.iter().enumerate() {
-        if let Some(trace) = info.branch_trace {
-            let branch = &graph.all_branches[trace];
-            let column = branch.visual.column.unwrap();
-            let idx_map = index_map[idx];
+/// Draws the commits of laid out rows and the lines between them into a grid, as well as uncommitted changes
+/// and the end of a truncated history. Lines to commits that are not laid out end at the bottom of the grid.
+/// With `reverse_commit_order`, the rows are reversed.
+pub fn draw_edges(
+    graph: &GitGraph,
+    settings: &Settings,
+    rows: RowLayout,
+) -> Result<GraphLayout, String> {
+    let RowLayout {
+        mut text_lines,
+        mut index_map,
+        height,
+        inserts,
+    } = rows;
+    let count = index_map.len();
+    let mut grid = Grid::new(graph.width(), height, empty_cell(settings));
+    if settings.emphasis {
+        grid.emphasized = settings.branches.emphasized.min(usize::from(u8::MAX)) as u8;
+    }
 
-            let branch_color = branch.visual.term_color;
+    for (idx, info) in graph.commits[..count].iter().enumerate() {
+        draw_commit(&mut grid, graph, settings, &index_map, &inserts, idx, info)?;
+    }
+    draw_worktree(&mut grid, graph, &index_map, settings)?;
+    if graph.truncated && count == graph.commits.len() {
+        draw_more(&mut grid, graph, &mut text_lines, &index_map, settings)?;
+    }
 
-            grid.set(
-                column * 2,
-                idx_map,
-                if info.is_merge { CIRCLE } else { DOT },
-                branch_color,
-                branch.persistence,
-            );
+    if settings.density == DENSITY_SPARSE {
+        prune_rows(&mut grid, &mut text_lines, &mut index_map);
+    }
+    if settings.group_separators {
+        draw_group_separators(&mut grid, graph, settings);
+    }
+    if settings.release_bands.is_some() {
+        draw_release_bands(&mut grid, graph, settings, &index_map);
+    }
 
-            for p in 0..2 {
-                if let Some(par_oid) = info.parents[p] {
-                    if let Some(par_idx) = graph.indices.get(&par_oid) {
-                        let par_idx_map = index_map[*par_idx];
-                        let par_info = &graph.commits[*par_idx];
-                        let par_branch = &graph.all_branches[par_info.branch_trace.unwrap()];
-                        let par_column = par_branch.visual.column.unwrap();
-
-                        let (color, pers) = if info.is_merge {
-                            (par_branch.visual.term_color, par_branch.persistence)
-                        } else {
-                            (branch_color, branch.persistence)
-                        };
-
-                        if branch.visual.column == par_branch.visual.column {
-                            if par_idx_map > idx_map + 1 {
-                                vline(&mut grid, (idx_map, par_idx_map), column, color, pers);
-                            }
-                        } else {
-                            let split_index = super::get_deviate_index(graph, idx, *par_idx);
-                            let split_idx_map = index_map[split_index];
-                            let inserts = &inserts[&split_index];
-                            for (insert_idx, sub_entry) in inserts.iter().enumerate() {
-                                for occ in sub_entry {
-                                    match occ {
-                                        Occ::Commit(_, _) => {}
-                                        Occ::Range(i1, i2, _, _) => {
-                                            if *i1 == idx && i2 == par_idx {
-                                                vline(
-                                                    &mut grid,
-                                                    (idx_map, split_idx_map + insert_idx),
-                                                    column,
-                                                    color,
-                                                    pers,
-                                                );
-                                                hline(
-                                                    &mut grid,
-                                                    split_idx_map + insert_idx,
-                                                    (par_column, column),
-                                                    info.is_merge && p > 0,
-                                                    color,
-                                                    pers,
-                                                );
-                                                vline(
-                                                    &mut grid,
-                                                    (split_idx_map + insert_idx, par_idx_map),
-                                                    par_column,
-                                                    color,
-                                                    pers,
-                                                );
-                                            }
+    if settings.reverse_commit_order {
+        text_lines.reverse();
+        grid.reverse();
+    }
+
+    Ok(GraphLayout {
+        grid,
+        text_lines,
+        index_map,
+    })
+}
+
+/// An empty cell of the grid, with a persistence below that of all branches.
+fn empty_cell(settings: &Settings) -> [u8; 4] {
+    [
+        SPACE,
+        WHITE,
+        settings.branches.persistence.len() as u8 + 2,
+        SOLID,
+    ]
+}
+
+/// Draws a commit and the lines to its parents.
+#[allow(clippy::too_many_arguments)]
+fn draw_commit(
+    grid: &mut Grid,
+    graph: &GitGraph,
+    settings: &Settings,
+    index_map: &[usize],
+    inserts: &HashMap<usize, Vec<Vec<Occ>>>,
+    idx: usize,
+    info: &CommitInfo,
+) -> Result<(), String> {
+    let count = index_map.len();
+    let height = grid.height;
+    let row_of = |idx: usize| index_map.get(idx).cloned().unwrap_or(height);
+
+    if let Some(trace) = info.branch_trace {
+        let branch = &graph.all_branches[trace];
+        let column = super::branch_column(branch)?;
+        let idx_map = index_map[idx];
+
+        let branch_color = branch.visual.term_color;
+        let branch_style = line_style(branch.visual.line_style);
+
+        let shape = if is_diverged_tip(graph, info) {
+            DIVERGED
+        } else {
+            match (
+                super::node_shape(branch, settings),
+                branch.visual.node_glyphs,
+            ) {
+                (0, Some(glyphs)) => grid.glyph_shape(glyphs),
+                (shape, _) => shape,
+            }
+        };
+        let (node, shape) = if graph.is_boundary(info.oid) {
+            (CIRCLE, BOUNDARY)
+        } else if info.is_merge {
+            (CIRCLE, shape)
+        } else {
+            (DOT, shape)
+        };
+        grid.set(
+            column * 2,
+            idx_map,
+            node,
+            graph
+                .bisect_mark(info.oid)
+                .map_or(branch_color, bisect_color),
+            branch.persistence,
+            shape,
+        );
+
+        let back_merge = settings.back_merges.is_some() && graph.is_back_merge(info);
+        for p in 0..2 {
+            if let Some(par_oid) = info.parents[p] {
+                if let Some(par_idx) = graph.indices.get(&par_oid) {
+                    let par_idx_map = row_of(*par_idx);
+                    let par_branch = super::commit_branch(graph, *par_idx)?;
+                    let par_column = super::branch_column(par_branch)?;
+
+                    // Back-merge lines give way to all others
+                    let (color, pers, style) = if back_merge && p > 0 {
+                        (SEPARATOR_COLOR, empty_cell(settings)[2] - 1, DOTTED)
+                    } else if info.is_merge {
+                        (
+                            par_branch.visual.term_color,
+                            par_branch.persistence,
+                            line_style(par_branch.visual.line_style),
+                        )
+                    } else {
+                        (branch_color, branch.persistence, branch_style)
+                    };
+
+                    if column == par_column {
+                        if par_idx_map > idx_map + 1 {
+                            vline(grid, (idx_map, par_idx_map), column, color, pers, style);
+                        }
+                    } else {
+                        let split_index = super::get_deviate_index(graph, idx, *par_idx)?;
+                        if split_index >= count {
+                            vline(grid, (idx_map, height), column, color, pers, style);
+                            continue;
+                        }
+                        let split_idx_map = index_map[split_index];
+                        let inserts = &inserts[&split_index];
+                        for (insert_idx, sub_entry) in inserts.iter().enumerate() {
+                            for occ in sub_entry {
+                                match occ {
+                                    Occ::Commit(_, _) => {}
+                                    Occ::Range(i1, i2, _, _) => {
+                                        if *i1 == idx && i2 == par_idx {
+                                            vline(
+                                                grid,
+                                                (idx_map, split_idx_map + insert_idx),
+                                                column,
+                                                color,
+                                                pers,
+                                                style,
+                                            );
+                                            hline(
+                                                grid,
+                                                split_idx_map + insert_idx,
+                                                (par_column, column),
+                                                settings.arrows.on_edge(info.is_merge && p > 0),
+                                                color,
+                                                pers,
+                                                style,
+                                            );
+                                            vline(
+                                                grid,
+                                                (split_idx_map + insert_idx, par_idx_map),
+                                                par_column,
+                                                color,
+                                                pers,
+                                                style,
+                                            );
                                         }
                                     }
                                 }
@@ -188,15 +623,100 @@ pub fn print_unicode(graph: &GitGraph, settings: &Settings) -> Result<UnicodeGra
             }
         }
     }
+    Ok(())
+}
 
-    if settings.reverse_commit_order {
-        text_lines.reverse();
-        grid.reverse();
+/// Draws the virtual commit of uncommitted changes into the row reserved right above HEAD.
+/// If a line to HEAD passes through HEAD's column there, the node is drawn in the first free column
+/// to the right instead, connected to HEAD by a dashed line in HEAD's row.
+fn draw_worktree(
+    grid: &mut Grid,
+    graph: &GitGraph,
+    index_map: &[usize],
+    settings: &Settings,
+) -> Result<(), String> {
+    let Some(head_idx) = graph.worktree_parent().filter(|idx| *idx < index_map.len()) else {
+        return Ok(());
+    };
+    let head_branch = super::commit_branch(graph, head_idx)?;
+    let column = super::branch_column(head_branch)? * 2;
+    let (color, pers) = (head_branch.visual.term_color, head_branch.persistence);
+    let head_row = index_map[head_idx];
+    let row = head_row - 1;
+    if grid.get_tuple(column, row).0 == SPACE {
+        grid.set(column, row, VIRTUAL, color, pers, SOLID);
+        return Ok(());
+    }
+
+    let free = |grid: &Grid, x: usize| {
+        grid.get_tuple(x, row).0 == SPACE && matches!(grid.get_tuple(x, head_row).0, SPACE | HOR)
+    };
+    let node_column = match ((column + 2)..grid.width)
+        .step_by(2)
+        .find(|x| free(grid, *x))
+    {
+        Some(x) => x,
+        None => {
+            grid.add_columns(2, empty_cell(settings));
+            grid.width - 1
+        }
+    };
+    grid.set(node_column, row, VIRTUAL, color, pers, SOLID);
+    for x in (column + 1)..node_column {
+        match grid.get_tuple(x, head_row).0 {
+            SPACE => grid.set(x, head_row, HOR, color, pers, DASHED),
+            VER => grid.set_opt(x, head_row, Some(CROSS), None, None, None),
+            _ => {}
+        }
+    }
+    match grid.get_tuple(node_column, head_row).0 {
+        HOR => grid.set_opt(node_column, head_row, Some(HOR_U), None, None, None),
+        _ => grid.set(node_column, head_row, L_U, color, pers, DASHED),
     }
+    Ok(())
+}
+
+/// Adds the row `... more` below a truncated history, with stubs of the lines continuing past the end.
+fn draw_more(
+    grid: &mut Grid,
+    graph: &GitGraph,
+    text_lines: &mut Vec<Option<String>>,
+    index_map: &[usize],
+    settings: &Settings,
+) -> Result<(), String> {
+    let height = grid.height;
+    grid.insert_row(height, empty_cell(settings));
+    text_lines.push(Some(MORE.to_string()));
+    for (idx, row) in index_map.iter().enumerate() {
+        if graph.continues_past_end(idx) {
+            let branch = super::commit_branch(graph, idx)?;
+            let column = super::branch_column(branch)? * 2;
+            let row = row + 1;
+            if grid.get_tuple(column, row).0 == SPACE {
+                let color = branch.visual.term_color;
+                grid.set(column, row, STUB, color, branch.persistence, SOLID);
+            }
+        }
+    }
+    Ok(())
+}
 
-    let lines = print_graph(&settings.characters, &grid, text_lines, settings.colored);
+/// The width of the terminal, or `None` if not writing to a terminal.
+#[cfg(not(target_arch = "wasm32"))]
+fn terminal_width() -> Result<Option<usize>, String> {
+    if atty::is(atty::Stream::Stdout) {
+        let width = crossterm::terminal::size()
+            .map_err(|err| err.to_string())?
+            .0;
+        Ok(Some(width as usize))
+    } else {
+        Ok(None)
+    }
+}
 
-    Ok((lines.0, lines.1, index_map))
+#[cfg(target_arch = "wasm32")]
+fn terminal_width() -> Result<Option<usize>, String> {
+    Ok(None)
 }
 
 /// Create `textwrap::Options` from width and indent.
@@ -212,12 +732,9 @@ fn create_wrapping_options<'a>(
                 .initial_indent(indent1)
                 .subsequent_indent(indent2),
         )
-    } else if atty::is(atty::Stream::Stdout) {
-        let width = crossterm::terminal::size()
-            .map_err(|err| err.to_string())?
-            .0;
-        let width = if width as usize > graph_width {
-            width as usize - graph_width
+    } else if let Some(width) = terminal_width()? {
+        let width = if width > graph_width {
+            width - graph_width
         } else {
             1
         };
@@ -233,44 +750,68 @@ fn create_wrapping_options<'a>(
 }
 
 /// Draws a vertical line
-fn vline(grid: &mut Grid, (from, to): (usize, usize), column: usize, color: u8, pers: u8) {
+fn vline(
+    grid: &mut Grid,
+    (from, to): (usize, usize),
+    column: usize,
+    color: u8,
+    pers: u8,
+    style: u8,
+) {
     for i in (from + 1)..to {
         let (curr, _, old_pers) = grid.get_tuple(column * 2, i);
-        let (new_col, new_pers) = if pers < old_pers {
-            (Some(color), Some(pers))
+        let (new_col, new_pers, new_style) = if pers < old_pers {
+            (Some(color), Some(pers), Some(style))
         } else {
-            (None, None)
+            (None, None, None)
         };
         match curr {
             DOT | CIRCLE => {}
             HOR => {
-                grid.set_opt(column * 2, i, Some(CROSS), Some(color), Some(pers));
+                grid.set_opt(
+                    column * 2,
+                    i,
+                    Some(CROSS),
+                    Some(color),
+                    Some(pers),
+                    Some(style),
+                );
             }
             HOR_U | HOR_D => {
-                grid.set_opt(column * 2, i, Some(CROSS), Some(color), Some(pers));
+                grid.set_opt(
+                    column * 2,
+                    i,
+                    Some(CROSS),
+                    Some(color),
+                    Some(pers),
+                    Some(style),
+                );
+            }
+            CROSS | VER | VER_L | VER_R => {
+                grid.set_opt(column * 2, i, None, new_col, new_pers, new_style)
             }
-            CROSS | VER | VER_L | VER_R => grid.set_opt(column * 2, i, None, new_col, new_pers),
             L_D | L_U => {
-                grid.set_opt(column * 2, i, Some(VER_L), new_col, new_pers);
+                grid.set_opt(column * 2, i, Some(VER_L), new_col, new_pers, new_style);
             }
             R_D | R_U => {
-                grid.set_opt(column * 2, i, Some(VER_R), new_col, new_pers);
+                grid.set_opt(column * 2, i, Some(VER_R), new_col, new_pers, new_style);
             }
             _ => {
-                grid.set_opt(column * 2, i, Some(VER), new_col, new_pers);
+                grid.set_opt(column * 2, i, Some(VER), new_col, new_pers, new_style);
             }
         }
     }
 }
 
-/// Draws a horizontal line
+/// Draws a horizontal line, with an arrow pointing to `to` if `arrow`
 fn hline(
     grid: &mut Grid,
     index: usize,
     (from, to): (usize, usize),
-    merge: bool,
+    arrow: bool,
     color: u8,
     pers: u8,
+    style: u8,
 ) {
     if from == to {
         return;
@@ -279,151 +820,338 @@ fn hline(
     let to_2 = to * 2;
     if from < to {
         for column in (from_2 + 1)..to_2 {
-            if merge && column == to_2 - 1 {
-                grid.set(column, index, ARR_R, color, pers);
+            if arrow && column == to_2 - 1 {
+                grid.set(column, index, ARR_R, color, pers, style);
             } else {
                 let (curr, _, old_pers) = grid.get_tuple(column, index);
-                let (new_col, new_pers) = if pers < old_pers {
-                    (Some(color), Some(pers))
+                let (new_col, new_pers, new_style) = if pers < old_pers {
+                    (Some(color), Some(pers), Some(style))
                 } else {
-                    (None, None)
+                    (None, None, None)
                 };
                 match curr {
                     DOT | CIRCLE => {}
-                    VER => grid.set_opt(column, index, Some(CROSS), None, None),
+                    VER => grid.set_opt(column, index, Some(CROSS), None, None, None),
                     HOR | CROSS | HOR_U | HOR_D => {
-                        grid.set_opt(column, index, None, new_col, new_pers)
+                        grid.set_opt(column, index, None, new_col, new_pers, new_style)
+                    }
+                    L_U | R_U => {
+                        grid.set_opt(column, index, Some(HOR_U), new_col, new_pers, new_style)
+                    }
+                    L_D | R_D => {
+                        grid.set_opt(column, index, Some(HOR_D), new_col, new_pers, new_style)
                     }
-                    L_U | R_U => grid.set_opt(column, index, Some(HOR_U), new_col, new_pers),
-                    L_D | R_D => grid.set_opt(column, index, Some(HOR_D), new_col, new_pers),
                     _ => {
-                        grid.set_opt(column, index, Some(HOR), new_col, new_pers);
+                        grid.set_opt(column, index, Some(HOR), new_col, new_pers, new_style);
                     }
                 }
             }
         }
 
         let (left, _, old_pers) = grid.get_tuple(from_2, index);
-        let (new_col, new_pers) = if pers < old_pers {
-            (Some(color), Some(pers))
+        let (new_col, new_pers, new_style) = if pers < old_pers {
+            (Some(color), Some(pers), Some(style))
         } else {
-            (None, None)
+            (None, None, None)
         };
         match left {
             DOT | CIRCLE => {}
-            VER => grid.set_opt(from_2, index, Some(VER_R), new_col, new_pers),
-            VER_L => grid.set_opt(from_2, index, Some(CROSS), None, None),
+            VER => grid.set_opt(from_2, index, Some(VER_R), new_col, new_pers, new_style),
+            VER_L => grid.set_opt(from_2, index, Some(CROSS), None, None, None),
             VER_R => {}
-            HOR | L_U => grid.set_opt(from_2, index, Some(HOR_U), new_col, new_pers),
+            HOR | L_U => grid.set_opt(from_2, index, Some(HOR_U), new_col, new_pers, new_style),
             _ => {
-                grid.set_opt(from_2, index, Some(R_D), new_col, new_pers);
+                grid.set_opt(from_2, index, Some(R_D), new_col, new_pers, new_style);
             }
         }
 
         let (right, _, old_pers) = grid.get_tuple(to_2, index);
-        let (new_col, new_pers) = if pers < old_pers {
-            (Some(color), Some(pers))
+        let (new_col, new_pers, new_style) = if pers < old_pers {
+            (Some(color), Some(pers), Some(style))
         } else {
-            (None, None)
+            (None, None, None)
         };
         match right {
             DOT | CIRCLE => {}
-            VER => grid.set_opt(to_2, index, Some(VER_L), None, None),
-            VER_L | HOR_U => grid.set_opt(to_2, index, None, new_col, new_pers),
-            HOR | R_U => grid.set_opt(to_2, index, Some(HOR_U), new_col, new_pers),
+            VER => grid.set_opt(to_2, index, Some(VER_L), None, None, None),
+            VER_L | HOR_U => grid.set_opt(to_2, index, None, new_col, new_pers, new_style),
+            HOR | R_U => grid.set_opt(to_2, index, Some(HOR_U), new_col, new_pers, new_style),
             _ => {
-                grid.set_opt(to_2, index, Some(L_U), new_col, new_pers);
+                grid.set_opt(to_2, index, Some(L_U), new_col, new_pers, new_style);
             }
         }
     } else {
         for column in (to_2 + 1)..from_2 {
-            if merge && column == to_2 + 1 {
-                grid.set(column, index, ARR_L, color, pers);
+            if arrow && column == to_2 + 1 {
+                grid.set(column, index, ARR_L, color, pers, style);
             } else {
                 let (curr, _, old_pers) = grid.get_tuple(column, index);
-                let (new_col, new_pers) = if pers < old_pers {
-                    (Some(color), Some(pers))
+                let (new_col, new_pers, new_style) = if pers < old_pers {
+                    (Some(color), Some(pers), Some(style))
                 } else {
-                    (None, None)
+                    (None, None, None)
                 };
                 match curr {
                     DOT | CIRCLE => {}
-                    VER => grid.set_opt(column, index, Some(CROSS), None, None),
+                    VER => grid.set_opt(column, index, Some(CROSS), None, None, None),
                     HOR | CROSS | HOR_U | HOR_D => {
-                        grid.set_opt(column, index, None, new_col, new_pers)
+                        grid.set_opt(column, index, None, new_col, new_pers, new_style)
+                    }
+                    L_U | R_U => {
+                        grid.set_opt(column, index, Some(HOR_U), new_col, new_pers, new_style)
+                    }
+                    L_D | R_D => {
+                        grid.set_opt(column, index, Some(HOR_D), new_col, new_pers, new_style)
                     }
-                    L_U | R_U => grid.set_opt(column, index, Some(HOR_U), new_col, new_pers),
-                    L_D | R_D => grid.set_opt(column, index, Some(HOR_D), new_col, new_pers),
                     _ => {
-                        grid.set_opt(column, index, Some(HOR), new_col, new_pers);
+                        grid.set_opt(column, index, Some(HOR), new_col, new_pers, new_style);
                     }
                 }
             }
         }
 
         let (left, _, old_pers) = grid.get_tuple(to_2, index);
-        let (new_col, new_pers) = if pers < old_pers {
-            (Some(color), Some(pers))
+        let (new_col, new_pers, new_style) = if pers < old_pers {
+            (Some(color), Some(pers), Some(style))
         } else {
-            (None, None)
+            (None, None, None)
         };
         match left {
             DOT | CIRCLE => {}
-            VER => grid.set_opt(to_2, index, Some(VER_R), None, None),
-            VER_R => grid.set_opt(to_2, index, None, new_col, new_pers),
-            HOR | L_U => grid.set_opt(to_2, index, Some(HOR_U), new_col, new_pers),
+            VER => grid.set_opt(to_2, index, Some(VER_R), None, None, None),
+            VER_R => grid.set_opt(to_2, index, None, new_col, new_pers, new_style),
+            HOR | L_U => grid.set_opt(to_2, index, Some(HOR_U), new_col, new_pers, new_style),
             _ => {
-                grid.set_opt(to_2, index, Some(R_U), new_col, new_pers);
+                grid.set_opt(to_2, index, Some(R_U), new_col, new_pers, new_style);
             }
         }
 
         let (right, _, old_pers) = grid.get_tuple(from_2, index);
-        let (new_col, new_pers) = if pers < old_pers {
-            (Some(color), Some(pers))
+        let (new_col, new_pers, new_style) = if pers < old_pers {
+            (Some(color), Some(pers), Some(style))
         } else {
-            (None, None)
+            (None, None, None)
         };
         match right {
             DOT | CIRCLE => {}
-            VER => grid.set_opt(from_2, index, Some(VER_L), new_col, new_pers),
-            VER_R => grid.set_opt(from_2, index, Some(CROSS), None, None),
-            VER_L => grid.set_opt(from_2, index, None, new_col, new_pers),
-            HOR | R_D => grid.set_opt(from_2, index, Some(HOR_D), new_col, new_pers),
+            VER => grid.set_opt(from_2, index, Some(VER_L), new_col, new_pers, new_style),
+            VER_R => grid.set_opt(from_2, index, Some(CROSS), None, None, None),
+            VER_L => grid.set_opt(from_2, index, None, new_col, new_pers, new_style),
+            HOR | R_D => grid.set_opt(from_2, index, Some(HOR_D), new_col, new_pers, new_style),
             _ => {
-                grid.set_opt(from_2, index, Some(L_D), new_col, new_pers);
+                grid.set_opt(from_2, index, Some(L_D), new_col, new_pers, new_style);
+            }
+        }
+    }
+}
+
+/// Merges each inserted row without text into the row above, if that is an inserted row without text as well,
+/// and their lines do not conflict. Updates the row of each commit accordingly.
+fn prune_rows(grid: &mut Grid, text_lines: &mut Vec<Option<String>>, index_map: &mut [usize]) {
+    let mut connector: Vec<bool> = text_lines.iter().map(|text| text.is_none()).collect();
+    for row in index_map.iter() {
+        connector[*row] = false;
+    }
+
+    let mut keep = vec![true; connector.len()];
+    let mut target = None;
+    for row in 0..connector.len() {
+        if !connector[row] {
+            target = None;
+            continue;
+        }
+        match target {
+            Some(target) if merge_rows(grid, target, row) => keep[row] = false,
+            _ => target = Some(row),
+        }
+    }
+    if keep.iter().all(|k| *k) {
+        return;
+    }
+
+    let mut removed = 0;
+    let mut shift = Vec::with_capacity(keep.len());
+    for k in &keep {
+        shift.push(removed);
+        if !k {
+            removed += 1;
+        }
+    }
+    for row in index_map.iter_mut() {
+        *row -= shift[*row];
+    }
+
+    let mut keep_iter = keep.iter();
+    text_lines.retain(|_| *keep_iter.next().unwrap());
+    grid.retain_rows(&keep);
+}
+
+/// Merges row `bottom` into row `top` above it, if their lines do not conflict:
+/// vertical lines must continue from one row to the other, and horizontal lines in the same cell
+/// must point in opposite directions from a vertical line joining them. Returns whether the rows were merged.
+fn merge_rows(grid: &mut Grid, top: usize, bottom: usize) -> bool {
+    let mut merged = Vec::with_capacity(grid.width);
+    for x in 0..grid.width {
+        let upper = grid.data[grid.index(x, top)];
+        let lower = grid.data[grid.index(x, bottom)];
+        let (Some(upper_dirs), Some(lower_dirs)) = (directions(upper[0]), directions(lower[0]))
+        else {
+            return false;
+        };
+        let both_horizontal = upper_dirs & HORIZONTAL != 0 && lower_dirs & HORIZONTAL != 0;
+        if (upper_dirs & DOWN != 0) != (lower_dirs & UP != 0)
+            || upper_dirs & lower_dirs & HORIZONTAL != 0
+            || (both_horizontal && upper_dirs & DOWN == 0)
+        {
+            return false;
+        }
+        let dirs = (upper_dirs & (UP | HORIZONTAL)) | (lower_dirs & (DOWN | HORIZONTAL));
+        merged.push(if dirs == upper_dirs {
+            upper
+        } else if dirs == lower_dirs {
+            lower
+        } else {
+            let Some(&(character, _)) = LINE_DIRECTIONS.iter().find(|(_, d)| *d == dirs) else {
+                return false;
+            };
+            let [_, color, pers, style] = if lower[2] < upper[2] { lower } else { upper };
+            [character, color, pers, style]
+        });
+    }
+    let idx = grid.index(0, top);
+    grid.data[idx..idx + grid.width].copy_from_slice(&merged);
+    true
+}
+
+/// The directions a line character connects to. `None` for nodes and other characters.
+fn directions(character: u8) -> Option<u8> {
+    LINE_DIRECTIONS
+        .iter()
+        .find(|(c, _)| *c == character)
+        .map(|(_, dirs)| *dirs)
+}
+
+/// Draws faint dotted lines in the blank columns between the branch order groups, in all rows without other lines.
+fn draw_group_separators(grid: &mut Grid, graph: &GitGraph, settings: &Settings) {
+    let pers = empty_cell(settings)[2];
+    for column in group_separators(graph) {
+        for row in 0..grid.height {
+            if grid.get_tuple(column * 2, row).0 == SPACE {
+                grid.set(column * 2, row, VER, SEPARATOR_COLOR, pers, DOTTED);
+            }
+        }
+    }
+}
+
+/// The label of the band above a commit starting a release, by `Settings::release_bands`.
+fn release_band<'a>(settings: &'a Settings, info: &CommitInfo) -> Option<&'a String> {
+    settings.release_bands.as_ref()?.get(&info.oid)
+}
+
+/// Draws faint dotted lines across the rows of the release bands, where no other lines cross them.
+/// The band of a release is the row right above its first commit, or above the uncommitted changes on it.
+fn draw_release_bands(grid: &mut Grid, graph: &GitGraph, settings: &Settings, index_map: &[usize]) {
+    let pers = empty_cell(settings)[2];
+    let worktree_parent = graph.worktree_parent();
+    for (idx, info) in graph.commits[..index_map.len()].iter().enumerate() {
+        if release_band(settings, info).is_some() {
+            let worktree_rows = usize::from(worktree_parent == Some(idx));
+            let row = index_map[idx] - 1 - worktree_rows;
+            for x in 0..grid.width {
+                if grid.get_tuple(x, row).0 == SPACE {
+                    grid.set(x, row, HOR, SEPARATOR_COLOR, pers, DOTTED);
+                }
+            }
+        }
+    }
+}
+
+/// The blank columns in front of the branch order groups following other ones,
+/// as left by `Settings::group_separators`.
+fn group_separators(graph: &GitGraph) -> Vec<usize> {
+    let mut first_columns: BTreeMap<usize, usize> = BTreeMap::new();
+    for branch in &graph.all_branches {
+        if let Some(column) = branch.visual.column {
+            let first = first_columns
+                .entry(branch.visual.order_group)
+                .or_insert(column);
+            *first = (*first).min(column);
+        }
+    }
+    first_columns
+        .into_values()
+        .skip(1)
+        .filter(|column| *column > 0)
+        .map(|column| column - 1)
+        .collect()
+}
+
+/// Creates the lines of a legend of the columns of a text-based graph: the index of each column,
+/// followed by the names of the branches occupying it, from top to bottom.
+pub fn lane_legend(graph: &GitGraph, settings: &Settings) -> Vec<String> {
+    let mut lanes: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for info in &graph.commits {
+        let Some(branch_idx) = info.branch_trace else {
+            continue;
+        };
+        if let Some(column) = graph.all_branches[branch_idx].visual.column {
+            let branches = lanes.entry(column).or_default();
+            if !branches.contains(&branch_idx) {
+                branches.push(branch_idx);
             }
         }
     }
+    let width = lanes
+        .keys()
+        .last()
+        .map_or(1, |column| column.to_string().len());
+    lanes
+        .iter()
+        .map(|(column, branches)| {
+            let names = branches
+                .iter()
+                .map(|branch_idx| {
+                    let branch = &graph.all_branches[*branch_idx];
+                    if settings.colored {
+                        Paint::fixed(branch.visual.term_color, branch.display_name()).to_string()
+                    } else {
+                        branch.display_name().to_string()
+                    }
+                })
+                .join(", ");
+            format!("{:>width$}  {}", column, names, width = width)
+        })
+        .collect()
 }
 
-/// Calculates required additional rows
-fn get_inserts(graph: &GitGraph, compact: bool) -> HashMap<usize, Vec<Vec<Occ>>> {
+/// Calculates required additional rows for the given leading commits of the graph
+pub(crate) fn get_inserts(
+    graph: &GitGraph,
+    commits: &[CommitInfo],
+    density: u8,
+) -> Result<HashMap<usize, Vec<Vec<Occ>>>, String> {
     let mut inserts: HashMap<usize, Vec<Vec<Occ>>> = HashMap::new();
 
-    for (idx, info) in graph.commits.iter().enumerate() {
-        let column = graph.all_branches[info.branch_trace.unwrap()]
-            .visual
-            .column
-            .unwrap();
+    for idx in 0..commits.len() {
+        let column = super::branch_column(super::commit_branch(graph, idx)?)?;
 
         inserts.insert(idx, vec![vec![Occ::Commit(idx, column)]]);
     }
 
-    for (idx, info) in graph.commits.iter().enumerate() {
+    for (idx, info) in commits.iter().enumerate() {
         if let Some(trace) = info.branch_trace {
             let branch = &graph.all_branches[trace];
-            let column = branch.visual.column.unwrap();
+            let column = super::branch_column(branch)?;
 
             for p in 0..2 {
                 if let Some(par_oid) = info.parents[p] {
                     if let Some(par_idx) = graph.indices.get(&par_oid) {
-                        let par_info = &graph.commits[*par_idx];
-                        let par_branch = &graph.all_branches[par_info.branch_trace.unwrap()];
-                        let par_column = par_branch.visual.column.unwrap();
+                        let par_column =
+                            super::branch_column(super::commit_branch(graph, *par_idx)?)?;
                         let column_range = sorted(column, par_column);
 
                         if column != par_column {
-                            let split_index = super::get_deviate_index(graph, idx, *par_idx);
+                            let split_index = super::get_deviate_index(graph, idx, *par_idx)?;
                             match inserts.entry(split_index) {
                                 Occupied(mut entry) => {
                                     let mut insert_at = entry.get().len();
@@ -433,11 +1161,15 @@ fn get_inserts(graph: &GitGraph, compact: bool) -> HashMap<usize, Vec<Vec<Occ>>>
                                             if other_range.overlaps(&column_range) {
                                                 match other_range {
                                                     Occ::Commit(target_index, _) => {
-                                                        if !compact
-                                                            || !info.is_merge
-                                                            || idx != *target_index
-                                                            || p == 0
-                                                        {
+                                                        let shared = idx == *target_index
+                                                            && match density {
+                                                                DENSITY_SPARSE => false,
+                                                                DENSITY_COMPACT => {
+                                                                    info.is_merge && p > 0
+                                                                }
+                                                                _ => true,
+                                                            };
+                                                        if !shared {
                                                             occ = true;
                                                             break;
                                                         }
@@ -489,7 +1221,7 @@ fn get_inserts(graph: &GitGraph, compact: bool) -> HashMap<usize, Vec<Vec<Occ>>>
         }
     }
 
-    inserts
+    Ok(inserts)
 }
 
 /// Creates the complete graph visualization, incl. formatter commits.
@@ -499,6 +1231,7 @@ fn print_graph(
     text_lines: Vec<Option<String>>,
     color: bool,
 ) -> (Vec<String>, Vec<String>) {
+    let emphasized = characters.emphasized();
     let mut g_lines = vec![];
     let mut t_lines = vec![];
 
@@ -506,25 +1239,26 @@ fn print_graph(
         let mut g_out = String::new();
         let mut t_out = String::new();
 
-        if color {
-            for arr in row {
-                if arr[0] == SPACE {
-                    write!(g_out, "{}", characters.chars[arr[0] as usize])
-                } else {
-                    write!(
-                        g_out,
-                        "{}",
-                        Paint::fixed(arr[1], characters.chars[arr[0] as usize])
-                    )
-                }
-                .unwrap();
+        // Double-width node glyphs cover the following cell
+        let mut covered = false;
+        for arr in row {
+            if covered {
+                covered = false;
+                continue;
             }
-        } else {
-            let str = row
-                .iter()
-                .map(|arr| characters.chars[arr[0] as usize])
-                .collect::<String>();
-            write!(g_out, "{}", str).unwrap();
+            let chars = if grid.is_emphasized(arr) {
+                &emphasized
+            } else {
+                characters
+            };
+            let character = cell_char(chars, &grid.glyphs, arr);
+            covered = display_width(character.encode_utf8(&mut [0; 4])) > 1;
+            if color && arr[0] != SPACE {
+                write!(g_out, "{}", Paint::fixed(arr[1], character))
+            } else {
+                write!(g_out, "{}", character)
+            }
+            .unwrap();
         }
 
         if let Some(line) = line {
@@ -538,34 +1272,134 @@ fn print_graph(
     (g_lines, t_lines)
 }
 
-/// Format a commit.
+/// Format a commit, with its references according to `decorations`.
+#[allow(clippy::too_many_arguments)]
 fn format(
     format: &CommitFormat,
     graph: &GitGraph,
     info: &CommitInfo,
     head: Option<&HeadInfo>,
     color: bool,
+    timezone: DateTimeZone,
     wrapping: &Option<Options>,
+    decorations: &Decorations,
 ) -> Result<Vec<String>, String> {
-    let commit = graph
-        .repository
-        .find_commit(info.oid)
-        .map_err(|err| err.message().to_string())?;
+    let commit = graph.commit(info.oid)?;
 
-    let branch_str = format_branches(graph, info, head, color);
+    let branch_str = format_branches(graph, info, head, color, decorations)?;
+    // Only full and custom formats show annotated tags
+    let tag = match format {
+        CommitFormat::Full | CommitFormat::Format(_) => graph.annotated_tag(info)?,
+        _ => None,
+    };
 
     let hash_color = if color { Some(HASH_COLOR) } else { None };
 
-    crate::print::format::format(&commit, branch_str, wrapping, hash_color, format)
+    let rebased_marker = decorations.rebased_marker.as_deref();
+    let mut lines = crate::print::format::format(
+        &commit,
+        branch_str,
+        super::porcelain::refs(graph, info),
+        format_relations(graph, info),
+        tag.as_ref().map(|(name, data)| (*name, data)),
+        rebased_marker.unwrap_or(REBASED_MARKER),
+        wrapping,
+        hash_color,
+        graph.abbrev,
+        timezone,
+        format,
+    )?;
+    // Custom formats place the marker with `%(rebased)`
+    if let (Some(marker), false) = (rebased_marker, matches!(format, CommitFormat::Format(_))) {
+        if commit.committer_differs() {
+            match lines.first_mut() {
+                Some(line) => line.push_str(&format!(" {}", marker)),
+                None => lines.push(marker.to_string()),
+            }
+        }
+    }
+    Ok(lines)
 }
 
-/// Format branches and tags.
+/// Format the commit at the given index in `graph.commits`, e.g. for detail views.
+pub fn format_commit_at(
+    graph: &GitGraph,
+    index: usize,
+    format: &CommitFormat,
+    color: bool,
+    timezone: DateTimeZone,
+) -> Result<Vec<String>, String> {
+    let info = &graph.commits[index];
+    let head = if info.oid == graph.head.oid {
+        Some(&graph.head)
+    } else {
+        None
+    };
+    self::format(
+        format,
+        graph,
+        info,
+        head,
+        color,
+        timezone,
+        &None,
+        &Decorations::default(),
+    )
+}
+
+/// Format the relations of a commit's branch, like `forked from develop, merged into main`.
+/// The source branch is named at the commit forking from it, target branches at the commit merged into them.
+pub fn format_relations(graph: &GitGraph, info: &CommitInfo) -> String {
+    let Some(trace) = info.branch_trace else {
+        return String::new();
+    };
+    let branch = &graph.all_branches[trace];
+    let traced_to = |oid: &Oid, branch: usize| {
+        graph
+            .indices
+            .get(oid)
+            .is_some_and(|idx| graph.commits[*idx].branch_trace == Some(branch))
+    };
+
+    let mut relations = vec![];
+    if let Some(source) = branch.source_branch {
+        if info.parents[0].is_some_and(|oid| traced_to(&oid, source)) {
+            relations.push(format!("forked from {}", graph.all_branches[source].name));
+        }
+    }
+    // Like releases merged into `main` and `develop`, a branch can have further targets than its target branch
+    let mut targets: Vec<_> = branch.target_branch.into_iter().collect();
+    for child in info
+        .children
+        .iter()
+        .filter_map(|oid| graph.indices.get(oid))
+    {
+        let child = &graph.commits[*child];
+        if let Some(child_trace) = child.branch_trace {
+            if child.parents[1] == Some(info.oid) && !targets.contains(&child_trace) {
+                targets.push(child_trace);
+            }
+        }
+    }
+    let targets: Vec<_> = targets
+        .into_iter()
+        .filter(|target| info.children.iter().any(|oid| traced_to(oid, *target)))
+        .map(|target| &graph.all_branches[target].name[..])
+        .collect();
+    if !targets.is_empty() {
+        relations.push(format!("merged into {}", targets.join(" and ")));
+    }
+    relations.join(", ")
+}
+
+/// Format branches and tags according to `decorations`, followed by other markers of the commit.
 pub fn format_branches(
     graph: &GitGraph,
     info: &CommitInfo,
     head: Option<&HeadInfo>,
     color: bool,
-) -> String {
+    decorations: &Decorations,
+) -> Result<String, String> {
     let curr_color = info
         .branch_trace
         .map(|branch_idx| &graph.all_branches[branch_idx].visual.term_color);
@@ -584,71 +1418,138 @@ pub fn format_branches(
         }
     }
 
-    if !info.branches.is_empty() {
-        write!(branch_str, " (").unwrap();
-
-        let branches = info.branches.iter().sorted_by_key(|br| {
-            if let Some(head) = head {
-                head.name != graph.all_branches[**br].name
-            } else {
-                false
-            }
-        });
+    let mut branches_str = String::new();
+    let branches = info.branches.iter().sorted_by_key(|br| {
+        if let Some(head) = head {
+            head.name[..] != graph.all_branches[**br].name[..]
+        } else {
+            false
+        }
+    });
 
-        for (idx, branch_index) in branches.enumerate() {
-            let branch = &graph.all_branches[*branch_index];
-            let branch_color = branch.visual.term_color;
+    for (idx, branch_index) in branches.enumerate() {
+        let branch = &graph.all_branches[*branch_index];
+        let branch_color = branch.visual.term_color;
 
-            if let Some(head) = head {
-                if idx == 0 && head.is_branch {
-                    if color {
-                        write!(branch_str, "{} ", Paint::fixed(14, head_str))
-                    } else {
-                        write!(branch_str, "{} ", head_str)
-                    }
-                    .unwrap();
+        if let Some(head) = head {
+            if idx == 0 && head.is_branch {
+                if color {
+                    write!(branches_str, "{} ", Paint::fixed(14, head_str))
+                } else {
+                    write!(branches_str, "{} ", head_str)
                 }
+                .unwrap();
             }
+        }
 
-            if color {
-                write!(branch_str, "{}", Paint::fixed(branch_color, &branch.name))
-            } else {
-                write!(branch_str, "{}", &branch.name)
+        let name = decorations.shorten.apply(&branch.name);
+        if color {
+            write!(branches_str, "{}", Paint::fixed(branch_color, name))
+        } else {
+            write!(branches_str, "{}", name)
+        }
+        .unwrap();
+
+        if let Some(tracking) = branch.tracking {
+            branches_str.push_str(&format_tracking(tracking));
+        }
+        if decorations.branch_counts {
+            let count = graph.branch_commit_count(*branch_index);
+            if count > 0 {
+                write!(branches_str, " [{}]", count).unwrap();
             }
-            .unwrap();
+        }
 
-            if idx < info.branches.len() - 1 {
-                write!(branch_str, ", ").unwrap();
+        if idx < info.branches.len() - 1 {
+            write!(branches_str, ", ").unwrap();
+        }
+    }
+
+    let mut tags_str = String::new();
+    for (idx, tag_index) in info.tags.iter().enumerate() {
+        let tag = &graph.all_branches[*tag_index];
+        let tag_color = curr_color.unwrap_or(&tag.visual.term_color);
+        let name = match &decorations.tag_marker {
+            Some(marker) if graph.tag_data(tag)?.is_some() => {
+                format!("{}{}", tag.display_name(), marker)
             }
+            _ => tag.display_name().to_string(),
+        };
+
+        if color {
+            write!(tags_str, "{}", Paint::fixed(*tag_color, name))
+        } else {
+            write!(tags_str, "{}", name)
+        }
+        .unwrap();
+
+        if idx < info.tags.len() - 1 {
+            write!(tags_str, ", ").unwrap();
         }
-        write!(branch_str, ")").unwrap();
     }
 
-    if !info.tags.is_empty() {
-        write!(branch_str, " [").unwrap();
-        for (idx, tag_index) in info.tags.iter().enumerate() {
-            let tag = &graph.all_branches[*tag_index];
-            let tag_color = curr_color.unwrap_or(&tag.visual.term_color);
+    let refs = decorations.apply(&branches_str, &tags_str);
+    if !refs.is_empty() {
+        write!(branch_str, " {}", refs).unwrap();
+    }
 
-            if color {
-                write!(branch_str, "{}", Paint::fixed(*tag_color, &tag.name[5..]))
-            } else {
-                write!(branch_str, "{}", &tag.name[5..])
-            }
-            .unwrap();
+    if let Some(mark) = graph.bisect_mark(info.oid) {
+        if color {
+            write!(
+                branch_str,
+                " {}",
+                Paint::fixed(bisect_color(mark), mark.label())
+            )
+        } else {
+            write!(branch_str, " {}", mark.label())
+        }
+        .unwrap();
+    }
 
-            if idx < info.tags.len() - 1 {
-                write!(branch_str, ", ").unwrap();
-            }
+    for name in graph.operation_heads_at(info.oid) {
+        if color {
+            write!(branch_str, " {}", Paint::fixed(OPERATION_COLOR, name))
+        } else {
+            write!(branch_str, " {}", name)
+        }
+        .unwrap();
+    }
+
+    for number in graph.head_trail_at(info.oid) {
+        let marker = format!("@{{{}}}", number);
+        if color {
+            write!(branch_str, " {}", Paint::fixed(HEAD_COLOR, marker))
+        } else {
+            write!(branch_str, " {}", marker)
         }
-        write!(branch_str, "]").unwrap();
+        .unwrap();
+    }
+
+    Ok(branch_str)
+}
+
+/// Terminal color of commits with a role in a bisect.
+fn bisect_color(mark: BisectMark) -> u8 {
+    match mark {
+        BisectMark::Bad => BISECT_BAD_COLOR,
+        BisectMark::Good => BISECT_GOOD_COLOR,
+        BisectMark::Skip => BISECT_SKIP_COLOR,
+        BisectMark::Current => HEAD_COLOR,
     }
+}
 
-    branch_str
+/// Upstream tracking info like `git status -sb`, e.g. ` [ahead 2, behind 1]`. Empty if up to date.
+fn format_tracking((ahead, behind): (usize, usize)) -> String {
+    match (ahead, behind) {
+        (0, 0) => String::new(),
+        (ahead, 0) => format!(" [ahead {}]", ahead),
+        (0, behind) => format!(" [behind {}]", behind),
+        (ahead, behind) => format!(" [ahead {}, behind {}]", ahead, behind),
+    }
 }
 
 /// Occupied row ranges
-enum Occ {
+pub(crate) enum Occ {
     Commit(usize, usize),
     Range(usize, usize, usize, usize),
 }
@@ -671,25 +1572,149 @@ fn sorted(v1: usize, v2: usize) -> (usize, usize) {
     }
 }
 
-/// Two-dimensional grid with 3 layers, used to produce the graph representation.
+/// Whether the commit is the tip of a branch that diverged from its remote counterpart.
+fn is_diverged_tip(graph: &GitGraph, info: &CommitInfo) -> bool {
+    info.branches
+        .iter()
+        .any(|idx| graph.all_branches[*idx].diverged.is_some())
+}
+
+/// Converts a line style to its grid layer value.
+fn line_style(style: LineStyle) -> u8 {
+    match style {
+        LineStyle::Solid => SOLID,
+        LineStyle::Dashed => DASHED,
+        LineStyle::Dotted => DOTTED,
+    }
+}
+
+/// The character of a grid cell, with glyphs of commit nodes from the branch or the settings,
+/// and glyphs of arrows from the settings, if given.
+fn cell_char(characters: &Characters, glyphs: &[NodeGlyphs], arr: &[u8; 4]) -> char {
+    let nodes = match (arr[0], arr[3]) {
+        (DOT | CIRCLE, 0) => characters.nodes,
+        (DOT | CIRCLE, shape) if (CUSTOM_NODES..DIVERGED).contains(&shape) => {
+            glyphs.get(usize::from(shape - CUSTOM_NODES)).copied()
+        }
+        _ => None,
+    };
+    match (nodes, characters.arrows) {
+        (Some(nodes), _) if arr[0] == DOT => nodes.commit,
+        (Some(nodes), _) => nodes.merge,
+        (None, Some(arrows)) if arr[0] == ARR_L => arrows.left,
+        (None, Some(arrows)) if arr[0] == ARR_R => arrows.right,
+        _ => characters.chars[char_index(arr)],
+    }
+}
+
+/// The index in `Characters::chars` of a grid cell, with straight lines replaced according to their style,
+/// and commits according to their node shape.
+fn char_index(arr: &[u8; 4]) -> usize {
+    let character = match (arr[0], arr[3]) {
+        (DOT, 0) | (CIRCLE, 0) => arr[0],
+        (DOT, DIVERGED) | (CIRCLE, DIVERGED) => WARNING,
+        (DOT, shape) if shape < CUSTOM_NODES => NODES + shape - 1,
+        (CIRCLE, shape) if shape < CUSTOM_NODES => NODES_HOLLOW + shape - 1,
+        (VER, DASHED) => VER_DASHED,
+        (HOR, DASHED) => HOR_DASHED,
+        (VER, DOTTED) => VER_DOTTED,
+        (HOR, DOTTED) => HOR_DOTTED,
+        (character, _) => character,
+    };
+    character as usize
+}
+
+/// Two-dimensional grid with 4 layers, used to produce the graph representation.
+///
+/// Layers are character, color, persistence, and line style (or node shape for commits).
 #[allow(dead_code)]
 struct Grid {
     width: usize,
     height: usize,
-    data: Vec<[u8; 3]>,
+    data: Vec<[u8; 4]>,
+    /// Node glyphs of branches, referred to by node shapes from `CUSTOM_NODES` on
+    glyphs: Vec<NodeGlyphs>,
+    /// Cells with a persistence below this are drawn with the characters for emphasized branches
+    emphasized: u8,
 }
 
 impl Grid {
-    pub fn new(width: usize, height: usize, initial: [u8; 3]) -> Self {
+    pub fn new(width: usize, height: usize, initial: [u8; 4]) -> Self {
         Grid {
             width,
             height,
             data: vec![initial; width * height],
+            glyphs: Vec::new(),
+            emphasized: 0,
         }
     }
 
+    /// Whether a cell belongs to an emphasized branch.
+    pub fn is_emphasized(&self, arr: &[u8; 4]) -> bool {
+        arr[2] < self.emphasized
+    }
+
+    /// The node shape referring to the given glyphs. The default shape if there are too many distinct glyphs.
+    pub fn glyph_shape(&mut self, glyphs: NodeGlyphs) -> u8 {
+        let index = match self.glyphs.iter().position(|g| *g == glyphs) {
+            Some(index) => index,
+            None => {
+                self.glyphs.push(glyphs);
+                self.glyphs.len() - 1
+            }
+        };
+        match u8::try_from(index) {
+            Ok(index) if index < DIVERGED - CUSTOM_NODES => CUSTOM_NODES + index,
+            _ => 0,
+        }
+    }
+
+    /// Reverses the order of rows, and turns corners and junctions upside down to match.
     pub fn reverse(&mut self) {
-        self.data.reverse();
+        self.data = self
+            .data
+            .chunks(self.width.max(1))
+            .rev()
+            .flatten()
+            .map(|&[character, color, pers, style]| {
+                let character = match character {
+                    R_U => R_D,
+                    R_D => R_U,
+                    L_U => L_D,
+                    L_D => L_U,
+                    HOR_U => HOR_D,
+                    HOR_D => HOR_U,
+                    other => other,
+                };
+                [character, color, pers, style]
+            })
+            .collect();
+    }
+    pub fn retain_rows(&mut self, keep: &[bool]) {
+        let width = self.width;
+        self.data = self
+            .data
+            .chunks(width)
+            .zip(keep)
+            .filter(|(_, keep)| **keep)
+            .flat_map(|(row, _)| row.iter().copied())
+            .collect();
+        self.height = self.data.len() / width;
+    }
+    /// Appends columns to the right.
+    pub fn add_columns(&mut self, count: usize, initial: [u8; 4]) {
+        let mut data = Vec::with_capacity((self.width + count) * self.height);
+        for row in self.data.chunks(self.width.max(1)) {
+            data.extend_from_slice(row);
+            data.extend(vec![initial; count]);
+        }
+        self.data = data;
+        self.width += count;
+    }
+    pub fn insert_row(&mut self, y: usize, initial: [u8; 4]) {
+        let idx = self.index(0, y);
+        self.data.splice(idx..idx, vec![initial; self.width]);
+        self.height += 1;
     }
     pub fn index(&self, x: usize, y: usize) -> usize {
         y * self.width + x
@@ -698,9 +1723,9 @@ impl Grid {
         let v = self.data[self.index(x, y)];
         (v[0], v[1], v[2])
     }
-    pub fn set(&mut self, x: usize, y: usize, character: u8, color: u8, pers: u8) {
+    pub fn set(&mut self, x: usize, y: usize, character: u8, color: u8, pers: u8, style: u8) {
         let idx = self.index(x, y);
-        self.data[idx] = [character, color, pers];
+        self.data[idx] = [character, color, pers, style];
     }
     pub fn set_opt(
         &mut self,
@@ -709,6 +1734,7 @@ impl Grid {
         character: Option<u8>,
         color: Option<u8>,
         pers: Option<u8>,
+        style: Option<u8>,
     ) {
         let idx = self.index(x, y);
         let arr = &mut self.data[idx];
@@ -721,5 +1747,8 @@ impl Grid {
         if let Some(pers) = pers {
             arr[2] = pers;
         }
+        if let Some(style) = style {
+            arr[3] = style;
+        }
     }
 }
@@ -0,0 +1,462 @@
+//! Server modes: answers requests about a graph via JSON-RPC 2.0, e.g. for editor integrations,
+//! or serves it as a self-updating HTML page via HTTP (see `serve_http`).
+//!
+//! JSON-RPC requests and responses are single lines of JSON. Supported methods:
+//!
+//! * `layout`: rows of the text graph, with optional params `start` and `end` (row range)
+//! * `format`: formatted commit, with params `id` (hash or unique prefix) and optional `format`
+//! * `graph`: commits and branches, incl. their columns in the layout
+//! * `refresh`: re-reads the repository and lays out the graph again
+
+use crate::graph::GitGraph;
+use crate::pager::RowMap;
+use crate::print::format::CommitFormat;
+use crate::print::html::print_html;
+use crate::print::json::JsonGraph;
+use crate::print::unicode::{format_commit_at, print_unicode};
+use crate::settings::Settings;
+use crate::source::CommitSource;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+const PARSE_ERROR: i32 = -32700;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const SERVER_ERROR: i32 = -32000;
+
+/// Interval in which HTML pages check for changed references, in milliseconds
+const POLL_INTERVAL: u32 = 2000;
+
+#[derive(Deserialize)]
+struct Request {
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Serialize)]
+struct RpcError {
+    code: i32,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i32, message: String) -> Self {
+        RpcError { code, message }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct LayoutParams {
+    start: Option<usize>,
+    end: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct FormatParams {
+    id: String,
+    format: Option<String>,
+}
+
+/// A graph laid out for text output, answering requests about it.
+pub struct Server {
+    graph: GitGraph,
+    settings: Settings,
+    max_count: Option<usize>,
+    graph_lines: Vec<String>,
+    text_lines: Vec<String>,
+    rows: RowMap,
+    /// Named commit formats from the configuration
+    formats: HashMap<String, String>,
+}
+
+impl Server {
+    pub fn new(
+        graph: GitGraph,
+        settings: Settings,
+        max_count: Option<usize>,
+    ) -> Result<Self, String> {
+        let (graph_lines, text_lines, indices) = print_unicode(&graph, &settings)?;
+        let rows = RowMap::new(&indices, graph_lines.len(), settings.reverse_commit_order);
+        Ok(Server {
+            graph,
+            settings,
+            max_count,
+            graph_lines,
+            text_lines,
+            rows,
+            formats: HashMap::new(),
+        })
+    }
+
+    /// Makes named commit formats from the configuration available to requests.
+    pub fn with_formats(mut self, formats: HashMap<String, String>) -> Self {
+        self.formats = formats;
+        self
+    }
+
+    /// Answers requests from `input` (one per line), until the end of the input is reached.
+    pub fn run<R: BufRead, W: Write>(mut self, input: R, mut output: W) -> Result<(), String> {
+        for line in input.lines() {
+            let line = line.map_err(|err| err.to_string())?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => {
+                    let result = self.handle(&request.method, request.params);
+                    // Notifications (without id) get no response
+                    request.id.map(|id| to_response(id, result))
+                }
+                Err(err) => Some(to_response(
+                    Value::Null,
+                    Err(RpcError::new(PARSE_ERROR, err.to_string())),
+                )),
+            };
+            if let Some(response) = response {
+                let str = serde_json::to_string(&response).map_err(|err| err.to_string())?;
+                writeln!(output, "{}", str).map_err(|err| err.to_string())?;
+                output.flush().map_err(|err| err.to_string())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "layout" => {
+                let params: LayoutParams = parse_params(params)?;
+                Ok(self.layout(params))
+            }
+            "format" => {
+                let params: FormatParams = parse_params(params)?;
+                self.format(params)
+            }
+            "graph" => {
+                let graph =
+                    JsonGraph::new(&self.graph).map_err(|err| RpcError::new(SERVER_ERROR, err))?;
+                serde_json::to_value(graph)
+                    .map_err(|err| RpcError::new(SERVER_ERROR, err.to_string()))
+            }
+            "refresh" => {
+                self.refresh()
+                    .map_err(|err| RpcError::new(SERVER_ERROR, err))?;
+                Ok(json!({
+                    "commits": self.graph.commits.len(),
+                    "rows": self.graph_lines.len(),
+                }))
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method '{}'", method),
+            )),
+        }
+    }
+
+    fn layout(&self, params: LayoutParams) -> Value {
+        let total = self.graph_lines.len();
+        let end = params.end.unwrap_or(total).min(total);
+        let start = params.start.unwrap_or(0).min(end);
+        let rows: Vec<_> = (start..end)
+            .map(|row| {
+                let commit = self.rows.row_commits[row];
+                json!({
+                    "graph": self.graph_lines[row],
+                    "text": self.text_lines[row],
+                    "commit": self.graph.commits[commit].oid.to_string(),
+                    "is_commit": self.rows.commit_rows[commit] == row,
+                })
+            })
+            .collect();
+        json!({ "total": total, "start": start, "rows": rows })
+    }
+
+    fn format(&self, params: FormatParams) -> Result<Value, RpcError> {
+        let format = match params.format {
+            Some(format) => CommitFormat::from_str_with(&format, &self.formats)
+                .map_err(|err| RpcError::new(INVALID_PARAMS, err))?,
+            None => CommitFormat::Full,
+        };
+        let mut matches = self
+            .graph
+            .commits
+            .iter()
+            .enumerate()
+            .filter(|(_, info)| info.oid.to_string().starts_with(&params.id));
+        let index = match (matches.next(), matches.next()) {
+            (Some((index, _)), None) => index,
+            (None, _) => {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    format!("No commit '{}' in graph", params.id),
+                ))
+            }
+            (Some(_), Some(_)) => {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    format!("Commit id '{}' is ambiguous", params.id),
+                ))
+            }
+        };
+        let lines = format_commit_at(
+            &self.graph,
+            index,
+            &format,
+            self.settings.colored,
+            self.settings.date_timezone,
+        )
+        .map_err(|err| RpcError::new(SERVER_ERROR, err))?;
+        Ok(json!({ "lines": lines }))
+    }
+
+    fn refresh(&mut self) -> Result<(), String> {
+        self.graph.reload(&self.settings, self.max_count)?;
+        let (graph_lines, text_lines, indices) = print_unicode(&self.graph, &self.settings)?;
+        self.rows = RowMap::new(
+            &indices,
+            graph_lines.len(),
+            self.settings.reverse_commit_order,
+        );
+        self.graph_lines = graph_lines;
+        self.text_lines = text_lines;
+        Ok(())
+    }
+}
+
+/// Serves the graph as an HTML page via HTTP, on the given address (like `127.0.0.1:8080`).
+///
+/// The page polls the server and reloads as soon as any branch, tag or HEAD changes.
+/// Runs until the process is terminated.
+pub fn serve_http(
+    mut graph: GitGraph,
+    settings: &Settings,
+    max_count: Option<usize>,
+    address: &str,
+) -> Result<(), String> {
+    let listener = TcpListener::bind(address)
+        .map_err(|err| format!("Unable to listen on {}: {}", address, err))?;
+    let local = listener.local_addr().map_err(|err| err.to_string())?;
+    eprintln!("Serving graph on http://{}", local);
+
+    let mut state = refs_state(graph.source())?;
+    let mut page = None;
+    for stream in listener.incoming() {
+        let result = stream.map_err(|err| err.to_string()).and_then(|stream| {
+            let path = read_request(&stream)?;
+
+            let current = refs_state(graph.source())?;
+            if current != state {
+                graph.reload(settings, max_count)?;
+                state = current;
+                page = None;
+            }
+
+            match path.as_deref() {
+                Some("/") => {
+                    if page.is_none() {
+                        page = Some(print_html(&graph, settings, &reload_script(&state))?);
+                    }
+                    respond(
+                        &stream,
+                        "200 OK",
+                        "text/html; charset=utf-8",
+                        page.as_deref().unwrap_or(""),
+                    )
+                }
+                Some("/state") => respond(&stream, "200 OK", "text/plain", &state),
+                Some(_) => respond(&stream, "404 Not Found", "text/plain", "Not found"),
+                None => respond(
+                    &stream,
+                    "405 Method Not Allowed",
+                    "text/plain",
+                    "Method not allowed",
+                ),
+            }
+        });
+        if let Err(err) = result {
+            eprintln!("{}", err);
+        }
+    }
+    Ok(())
+}
+
+/// Reads an HTTP request, and returns the requested path for GET requests.
+fn read_request(stream: &TcpStream) -> Result<Option<String>, String> {
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .map_err(|err| err.to_string())?;
+    let mut reader = BufReader::new(stream);
+
+    let mut request = String::new();
+    reader
+        .read_line(&mut request)
+        .map_err(|err| err.to_string())?;
+    // Skip headers
+    let mut line = String::new();
+    while reader.read_line(&mut line).map_err(|err| err.to_string())? > 0 && line.trim() != "" {
+        line.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) => Ok(Some(path.split('?').next().unwrap_or(path).to_string())),
+        _ => Ok(None),
+    }
+}
+
+fn respond(
+    mut stream: &TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> Result<(), String> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+    .and_then(|_| stream.flush())
+    .map_err(|err| err.to_string())
+}
+
+/// Script reloading the page when the state of references differs from `state`.
+fn reload_script(state: &str) -> String {
+    format!(
+        r#"<script>
+setInterval(function () {{
+  fetch("/state").then(function (response) {{ return response.text(); }}).then(function (state) {{
+    if (state !== "{}") {{ location.reload(); }}
+  }});
+}}, {});
+</script>"#,
+        state, POLL_INTERVAL
+    )
+}
+
+/// A fingerprint of HEAD and all references, to detect changes in the repository.
+fn refs_state(source: &dyn CommitSource) -> Result<String, String> {
+    let head = source.head()?;
+    let mut refs: Vec<_> = source
+        .refs()?
+        .into_iter()
+        .map(|rf| (rf.name, rf.target))
+        .collect();
+    refs.sort();
+
+    let mut hasher = DefaultHasher::new();
+    (head.oid, head.name, refs).hash(&mut hasher);
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+fn parse_params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))
+}
+
+fn to_response(id: Value, result: Result<Value, RpcError>) -> Response {
+    let (result, error) = match result {
+        Ok(result) => (Some(result), None),
+        Err(error) => (None, Some(error)),
+    };
+    Response {
+        jsonrpc: "2.0",
+        id,
+        result,
+        error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::GitGraph;
+    use crate::serve::Server;
+    use crate::settings::{BranchSettings, BranchSettingsDef, Settings};
+    use crate::source::memory::MemorySource;
+    use serde_json::Value;
+
+    const DATA: &str = r#"{
+        "head": { "oid": "0000000000000000000000000000000000000002", "name": "main", "is_branch": true },
+        "refs": [ { "name": "refs/heads/main", "target": "0000000000000000000000000000000000000002" } ],
+        "commits": [
+            {
+                "id": "0000000000000000000000000000000000000002",
+                "parents": ["0000000000000000000000000000000000000001"],
+                "message": "Second commit",
+                "author": { "name": "A. U. Thor", "email": "author@example.com", "time": 1600000100 },
+                "committer": { "name": "A. U. Thor", "email": "author@example.com", "time": 1600000100 }
+            },
+            {
+                "id": "0000000000000000000000000000000000000001",
+                "parents": [],
+                "message": "Initial commit",
+                "author": { "name": "A. U. Thor", "email": "author@example.com", "time": 1600000000 },
+                "committer": { "name": "A. U. Thor", "email": "author@example.com", "time": 1600000000 }
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn serve_requests() {
+        let settings = Settings {
+            branches: BranchSettings::from(BranchSettingsDef::simple()).unwrap(),
+            ..Default::default()
+        };
+        let source = MemorySource::from_json(DATA).unwrap();
+        let graph = GitGraph::from_source(source, &settings, None).unwrap();
+        let server = Server::new(graph, settings, None).unwrap();
+
+        let input = [
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "layout", "params": {"start": 1}}"#,
+            r#"{"jsonrpc": "2.0", "method": "refresh"}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "format", "params": {"id": "00000000000000000000000000000000000000", "format": "%s"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "format", "params": {"id": "0000000000000000000000000000000000000001", "format": "%s"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 4, "method": "unknown"}"#,
+            "not json",
+        ]
+        .join("\n");
+        let mut output = Vec::new();
+        server.run(input.as_bytes(), &mut output).unwrap();
+
+        let responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 5);
+
+        let layout = &responses[0]["result"];
+        assert_eq!(layout["total"], 2);
+        assert_eq!(layout["rows"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            layout["rows"][0]["commit"],
+            "0000000000000000000000000000000000000001"
+        );
+
+        assert_eq!(responses[1]["error"]["code"], -32602);
+        assert_eq!(responses[2]["result"]["lines"][0], "Initial commit");
+        assert_eq!(responses[3]["error"]["code"], -32601);
+        assert_eq!(responses[4]["error"]["code"], -32700);
+        assert_eq!(responses[4]["id"], Value::Null);
+    }
+}
@@ -1,16 +1,137 @@
 //! Graph generation settings.
 
-use crate::print::format::CommitFormat;
+use crate::print::format::{CommitFormat, DateTimeZone, Emoji};
+use crate::source::Oid;
 use regex::{Error, Regex};
 use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::Arc;
 
-/// Repository settings for the branching model.
+/// Repository settings for the branching model and named formats.
 /// Used to read repo's git-graph.toml
 #[derive(Serialize, Deserialize)]
 pub struct RepoSettings {
     /// The repository's branching model
-    pub model: String,
+    pub model: Option<String>,
+    /// Named commit formats, in addition to those of the user configuration
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub formats: HashMap<String, String>,
+    /// Hanging indents of commit formats, in addition to those of the user configuration
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub format_indents: HashMap<String, Indent>,
+}
+
+/// User settings, read from `APP_DATA/git-graph/config.toml`.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct UserConfig {
+    /// Key bindings for the pager
+    pub keys: KeysDef,
+    /// Additional patterns for merge commit summaries
+    pub merge_patterns: MergePatternsDef,
+    /// Safety limits for huge repositories
+    pub limits: Limits,
+    /// Output style, like `round` or `ascii`. Default: `auto`
+    pub style: Option<String>,
+    /// Glyphs of commit and merge nodes, like `◉◎`. Default: those of the style
+    pub nodes: Option<NodeGlyphs>,
+    /// Edges drawn with arrows. Default: `merges`
+    pub arrows: Option<Arrows>,
+    /// Glyphs of left and right arrows, like `◂▸`. Default: those of the style
+    pub arrow_glyphs: Option<ArrowGlyphs>,
+    /// Marker of annotated tags in the references, like `*` for `v1.0*`. Default: none
+    pub tag_marker: Option<String>,
+    /// Template of the references, like `refs: {branches} {tags}`. Default: `({branches}) [{tags}]`
+    pub decorations: Option<String>,
+    /// Badges with the number of commits of each branch at its tip. Default: false
+    pub branch_counts: bool,
+    /// Marker of commits with a committer other than the author, like `↻`. Default: none
+    pub rebased_marker: Option<String>,
+    /// Shortening of long branch names in the references. Default: full names
+    pub shorten_branches: ShortenNames,
+    /// Named commit formats, like `review = "%h %as %an %s%+b"`
+    pub formats: HashMap<String, String>,
+    /// Hanging indents of commit formats, by preset or format name, like `medium = 4`
+    pub format_indents: HashMap<String, Indent>,
+    /// Commands run on the selected commit in the pager, by key, like `c = "git checkout {hash}"`
+    pub actions: HashMap<String, String>,
+}
+
+/// Safety limits for huge repositories, with 0 for no limit.
+/// Exceeding a limit degrades the graph, with a notice, instead of rendering an unusable graph.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct Limits {
+    /// Maximum number of commits. Further commits are cut off.
+    pub max_commits: usize,
+    /// Maximum number of branches. If exceeded, only the first-parent history of HEAD is shown.
+    pub max_branches: usize,
+    /// Maximum number of columns. If exceeded, only the first-parent history of HEAD is shown.
+    pub max_columns: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_commits: 100_000,
+            max_branches: 1000,
+            max_columns: 200,
+        }
+    }
+}
+
+impl Limits {
+    /// No limits at all.
+    pub fn none() -> Self {
+        Limits {
+            max_commits: 0,
+            max_branches: 0,
+            max_columns: 0,
+        }
+    }
+}
+
+/// Key bindings for the pager, as key names per action.
+///
+/// Key names are single characters or one of `esc`, `enter`, `space`, `tab`, `backspace`,
+/// `up`, `down`, `left`, `right`, `pgup`, `pgdn`, `home`, `end`, `f1`-`f12`,
+/// optionally prefixed by modifiers like `ctrl+` or `alt+`.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeysDef {
+    pub quit: Vec<String>,
+    pub down: Vec<String>,
+    pub up: Vec<String>,
+    pub page_down: Vec<String>,
+    pub page_up: Vec<String>,
+    pub home: Vec<String>,
+    pub end: Vec<String>,
+    pub goto_ref: Vec<String>,
+    pub pick: Vec<String>,
+    pub mark: Vec<String>,
+    pub copy: Vec<String>,
+    pub help: Vec<String>,
+}
+
+impl Default for KeysDef {
+    fn default() -> Self {
+        let keys = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect();
+        KeysDef {
+            quit: keys(&["q", "esc", "ctrl+c"]),
+            down: keys(&["down"]),
+            up: keys(&["up"]),
+            page_down: keys(&["pgdn", "enter"]),
+            page_up: keys(&["pgup"]),
+            home: keys(&["home"]),
+            end: keys(&["end"]),
+            goto_ref: keys(&["g"]),
+            pick: keys(&["p"]),
+            mark: keys(&["space"]),
+            copy: keys(&["y"]),
+            help: keys(&["?"]),
+        }
+    }
 }
 
 /// Ordering policy for branches in visual columns.
@@ -27,22 +148,474 @@ pub enum BranchOrder {
     LongestFirst(bool),
 }
 
+/// Order of commits, with no parents before all of their children.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum CommitOrder {
+    /// By commit time, newest first
+    Date,
+    /// By author time, newest first
+    AuthorDate,
+    /// Strictly topological: the commits of a line are shown together, like `git log --topo-order`
+    Topo,
+}
+
+impl FromStr for CommitOrder {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "date" => Ok(CommitOrder::Date),
+            "author-date" => Ok(CommitOrder::AuthorDate),
+            "topo" => Ok(CommitOrder::Topo),
+            _ => Err(format!(
+                "Unknown commit order '{}'. Must be one of [date|author-date|topo]",
+                str
+            )),
+        }
+    }
+}
+
+/// Numbers in the left gutter of text-based graphs, for referring to commits.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum LineNumbers {
+    /// Index of the commit, 0-based from the top
+    Index,
+    /// Row number, 1-based like the lines of the pager
+    Row,
+}
+
+impl FromStr for LineNumbers {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "index" => Ok(LineNumbers::Index),
+            "row" => Ok(LineNumbers::Row),
+            _ => Err(format!(
+                "Unknown line numbers '{}'. Must be one of [index|row]",
+                str
+            )),
+        }
+    }
+}
+
+/// A merged branch collapsed into its merge commit, shown as a bubble with `--feature-bubbles`.
+#[derive(Clone, Debug)]
+pub struct Bubble {
+    /// Names and numbers of commits of the collapsed branches, like `feature/x · 4 commits`
+    pub label: String,
+    /// Terminal color of the (first) collapsed branch
+    pub term_color: u8,
+    /// SVG color of the (first) collapsed branch
+    pub svg_color: String,
+}
+
+/// Description of a graph, shown above it with `--header`, like `my-repo · model git-flow · local · 120 commits`.
+#[derive(Clone, Debug)]
+pub struct Header {
+    /// Name of the repository
+    pub repository: String,
+    /// Name of the branching model
+    pub model: String,
+    /// The options restricting the commits and branches shown, like `local` or `v1.0..v1.1`
+    pub filters: Vec<String>,
+}
+
+impl Header {
+    /// The header line for a graph with the given number of commits.
+    pub fn line(&self, commits: usize) -> String {
+        let mut parts = vec![self.repository.clone(), format!("model {}", self.model)];
+        parts.extend(self.filters.iter().cloned());
+        parts.push(match commits {
+            1 => "1 commit".to_string(),
+            n => format!("{} commits", n),
+        });
+        parts.join(" \u{b7} ")
+    }
+}
+
+/// Highlighting of back-merges, i.e. merges of a more persistent branch into a less persistent one,
+/// like develop into a feature branch.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum BackMerges {
+    /// Faint dotted lines to the merged parent
+    Dim,
+    /// Faint dotted lines, and a `[back-merge]` label after the commit's text
+    Label,
+}
+
+impl FromStr for BackMerges {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "dim" => Ok(BackMerges::Dim),
+            "label" => Ok(BackMerges::Label),
+            _ => Err(format!(
+                "Unknown back-merges highlighting '{}'. Must be one of [dim|label]",
+                str
+            )),
+        }
+    }
+}
+
+/// Background bands for readability of long graphs in SVG and HTML output.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Stripes {
+    /// Every other commit row
+    Rows,
+    /// The commits of every other month
+    Months,
+}
+
+impl FromStr for Stripes {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "rows" => Ok(Stripes::Rows),
+            "months" => Ok(Stripes::Months),
+            _ => Err(format!(
+                "Unknown stripes '{}'. Must be one of [rows|months]",
+                str
+            )),
+        }
+    }
+}
+
+/// Edges of graphs drawn with arrows, pointing from the parent's column to the child's column.
+/// Edges within a column never get arrows.
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Arrows {
+    /// No arrows
+    None,
+    /// Arrows on edges from merged parents to merge commits
+    #[default]
+    Merges,
+    /// Arrows on all edges between columns, including those of branches forking off
+    All,
+}
+
+impl Arrows {
+    /// Whether an edge between columns gets an arrow.
+    pub fn on_edge(self, merge: bool) -> bool {
+        match self {
+            Arrows::None => false,
+            Arrows::Merges => merge,
+            Arrows::All => true,
+        }
+    }
+}
+
+impl FromStr for Arrows {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "none" => Ok(Arrows::None),
+            "merges" => Ok(Arrows::Merges),
+            "all" => Ok(Arrows::All),
+            _ => Err(format!(
+                "Unknown arrows '{}'. Must be one of [none|merges|all]",
+                str
+            )),
+        }
+    }
+}
+
+/// Formatting of the references of commits (`%d`), like ` (main, develop) [v1.0]`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Decorations {
+    /// Template with placeholders `{branches}` and `{tags}`, replaced by comma-separated lists.
+    /// Words of the template with the placeholder of an empty list are omitted
+    pub template: String,
+    /// Marker appended to annotated tags, like `*` for `v1.0*`
+    pub tag_marker: Option<String>,
+    /// Badges with the number of commits of each branch after its name, like `main [12]`
+    pub branch_counts: bool,
+    /// Marker appended to commits with a committer other than the author, like `↻`
+    pub rebased_marker: Option<String>,
+    /// Shortening of long branch names
+    pub shorten: ShortenNames,
+}
+
+impl Default for Decorations {
+    fn default() -> Self {
+        Decorations {
+            template: "({branches}) [{tags}]".to_string(),
+            tag_marker: None,
+            branch_counts: false,
+            rebased_marker: None,
+            shorten: ShortenNames::default(),
+        }
+    }
+}
+
+impl Decorations {
+    /// Sets the marker of annotated tags.
+    pub fn with_tag_marker(mut self, tag_marker: Option<String>) -> Self {
+        self.tag_marker = tag_marker;
+        self
+    }
+
+    /// Sets whether branches show badges with their number of commits.
+    pub fn with_branch_counts(mut self, branch_counts: bool) -> Self {
+        self.branch_counts = branch_counts;
+        self
+    }
+
+    /// Sets the marker of commits with a committer other than the author.
+    pub fn with_rebased_marker(mut self, rebased_marker: Option<String>) -> Self {
+        self.rebased_marker = rebased_marker;
+        self
+    }
+
+    /// Sets the shortening of branch names.
+    pub fn with_shorten(mut self, shorten: ShortenNames) -> Self {
+        self.shorten = shorten;
+        self
+    }
+
+    /// Fills the template with the lists of branches and tags. Empty if both are empty.
+    ///
+    /// For example, template `({branches}) [{tags}]` gives `(main) [v1.0]`, or `[v1.0]` without branches.
+    pub fn apply(&self, branches: &str, tags: &str) -> String {
+        if branches.is_empty() && tags.is_empty() {
+            return String::new();
+        }
+        let mut out = String::new();
+        let mut rest = &self.template[..];
+        while !rest.is_empty() {
+            let start = rest
+                .find(|c: char| !c.is_whitespace())
+                .unwrap_or(rest.len());
+            let end = rest[start..]
+                .find(char::is_whitespace)
+                .map_or(rest.len(), |end| start + end);
+            // Words are separated by the whitespace in front of them
+            let (space, word) = (&rest[..start], &rest[start..end]);
+            rest = &rest[end..];
+
+            let omit = word.is_empty()
+                || (branches.is_empty() && word.contains("{branches}"))
+                || (tags.is_empty() && word.contains("{tags}"));
+            if !omit {
+                if !out.is_empty() {
+                    out.push_str(space);
+                }
+                let parts: Vec<_> = word
+                    .split("{branches}")
+                    .map(|part| part.replace("{tags}", tags))
+                    .collect();
+                out.push_str(&parts.join(branches));
+            }
+        }
+        out
+    }
+}
+
+impl FromStr for Decorations {
+    type Err = String;
+
+    /// Parses a template, which may only contain the placeholders `{branches}` and `{tags}`.
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        let placeholders = Regex::new(r"\{[^}]*\}").unwrap();
+        let unknown = placeholders
+            .find_iter(str)
+            .map(|mat| mat.as_str())
+            .find(|placeholder| !matches!(*placeholder, "{branches}" | "{tags}"));
+        match unknown {
+            Some(placeholder) => Err(format!(
+                "Unknown placeholder '{}' in decorations '{}'. Must be one of [{{branches}}|{{tags}}]",
+                placeholder, str
+            )),
+            None => Ok(Decorations {
+                template: str.to_string(),
+                tag_marker: None,
+                branch_counts: false,
+                rebased_marker: None,
+                shorten: ShortenNames::default(),
+            }),
+        }
+    }
+}
+
+/// Position of the ellipsis in shortened branch names.
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Ellipsis {
+    /// Keep the end of the name, like `…long-description`
+    Start,
+    /// Keep the start and the end of the name, like `ABC-12…cription`
+    Middle,
+    /// Keep the start of the name, like `ABC-1234-very…`
+    #[default]
+    End,
+}
+
+/// Shortening of long branch names in the references, like `ABC-1234-very…` for
+/// `feature/ABC-1234-very-long-description`. SVG tooltips and JSON output keep the full names.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, Default)]
+#[serde(default)]
+pub struct ShortenNames {
+    /// Maximum number of characters of names, including the ellipsis. 0 for no limit
+    pub max_length: usize,
+    /// Position of the ellipsis in names longer than the maximum. Default: `end`
+    pub ellipsis: Ellipsis,
+    /// Prefixes removed from names, like `feature/`. Only the first matching prefix is removed
+    pub strip_prefixes: Vec<String>,
+}
+
+impl ShortenNames {
+    /// The shortened name. Names consisting of a prefix only are kept.
+    pub fn apply(&self, name: &str) -> String {
+        let name = self
+            .strip_prefixes
+            .iter()
+            .find_map(|prefix| name.strip_prefix(&prefix[..]))
+            .filter(|name| !name.is_empty())
+            .unwrap_or(name);
+        let len = name.chars().count();
+        if self.max_length == 0 || len <= self.max_length {
+            return name.to_string();
+        }
+        let keep = self.max_length - 1;
+        let (head, tail) = match self.ellipsis {
+            Ellipsis::Start => (0, keep),
+            Ellipsis::Middle => (keep - keep / 2, keep / 2),
+            Ellipsis::End => (keep, 0),
+        };
+        let mut short: String = name.chars().take(head).collect();
+        short.push('…');
+        short.extend(name.chars().skip(len - tail));
+        short
+    }
+}
+
+/// Wrapping of formatted commit text.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Wrapping {
+    /// Width of the text. `None` to fit the terminal, if on a terminal
+    pub width: Option<usize>,
+    /// Indent of each line of formatted text
+    pub indent1: String,
+    /// Indent of the continuation lines of wrapped lines
+    pub indent2: String,
+    /// Indent of the lines of a commit after its first line, like the body after the subject
+    pub hanging: String,
+}
+
+/// An indent, written as a number of spaces or as the text to indent with, like `4` or `"  │ "`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(untagged)]
+pub enum Indent {
+    Spaces(usize),
+    Text(String),
+}
+
+impl Indent {
+    /// The text to indent with.
+    pub fn text(&self) -> String {
+        match self {
+            Indent::Spaces(spaces) => " ".repeat(*spaces),
+            Indent::Text(text) => text.clone(),
+        }
+    }
+}
+
+impl FromStr for Indent {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        Ok(match str.parse::<usize>() {
+            Ok(spaces) => Indent::Spaces(spaces),
+            Err(_) => Indent::Text(str.to_string()),
+        })
+    }
+}
+
+/// Graph density where merge lines point to target lines rather than merge commits
+pub const DENSITY_SPARSE: u8 = 0;
+/// Graph density where merge lines start in the row of the merge commit
+pub const DENSITY_COMPACT: u8 = 1;
+/// Graph density where lines to parents in other columns also start in the commit's row, if nothing is crossed
+pub const DENSITY_AGGRESSIVE: u8 = 2;
+
 /// Top-level settings
 pub struct Settings {
     /// Reverse the order of commits
     pub reverse_commit_order: bool,
     /// Debug printing and drawing
     pub debug: bool,
-    /// Compact text-based graph
-    pub compact: bool,
+    /// Density of text-based graphs, from `DENSITY_SPARSE` to `DENSITY_AGGRESSIVE`
+    pub density: u8,
+    /// Number of blank rows between commits
+    pub row_spacing: usize,
+    /// Numbers in front of the rows of commits
+    pub line_numbers: Option<LineNumbers>,
+    /// A line describing the graph above it, and a title block in SVG and HTML output
+    pub header: Option<Header>,
+    /// Place commits in SVG output on a time axis with the given pixels per day
+    pub svg_time_axis: Option<f32>,
+    /// Connect diverged local and remote branches in SVG output
+    pub svg_divergence: bool,
+    /// Commit hashes as `id` and `data-oid` attributes of commits and edges in SVG output
+    pub svg_ids: bool,
+    /// A `<metadata>` element with the parameters of the graph in SVG output
+    pub svg_metadata: bool,
+    /// `<title>` elements on commits and lines in SVG output, shown as tooltips by viewers
+    pub svg_tooltips: bool,
+    /// Background bands behind commit rows in SVG and HTML output
+    pub svg_stripes: Option<Stripes>,
     /// Colored text-based graph
     pub colored: bool,
+    /// Color-blind friendly colors, and node shapes by branch persistence
+    pub accessible: bool,
+    /// Heavy lines for long-lived branches, according to `BranchSettings::emphasized`
+    pub emphasis: bool,
+    /// Blank columns with faint separators between the branch order groups of text-based graphs
+    pub group_separators: bool,
+    /// Labels of the bands above the commits starting a release, as partitioned by `GitGraph::releases`
+    pub release_bands: Option<HashMap<Oid, String>>,
+    /// Merged branches collapsed into bubbles, by merge commit, as created by `GitGraph::feature_bubbles`
+    pub bubbles: Option<HashMap<Oid, Bubble>>,
+    /// Highlighting of merges of more persistent branches into less persistent ones
+    pub back_merges: Option<BackMerges>,
+    /// Print a legend of the branches in each column after text-based graphs
+    pub lane_legend: bool,
     /// Include remote branches?
     pub include_remote: bool,
+    /// Show how far local branches are ahead of and behind their upstream
+    pub track_info: bool,
+    /// Number of previous HEAD positions from the reflog to mark
+    pub head_trail: usize,
+    /// Show uncommitted changes as a virtual commit on top of HEAD
+    pub uncommitted: bool,
+    /// Select the commits for `max_count` only among those reachable from HEAD, like `git log -n`
+    pub count_from_head: bool,
+    /// Show only the branch with this name and the branches related to it
+    pub related: Option<String>,
+    /// Order of commits
+    pub commit_order: CommitOrder,
+    /// Order commits with equal times by hash, for identical output across runs, platforms and backends
+    pub deterministic: bool,
+    /// Safety limits for huge repositories
+    pub limits: Limits,
     /// Formatting for commits
     pub format: CommitFormat,
+    /// Length of abbreviated commit hashes. `None` for the repository's `core.abbrev`, or the shortest unique length
+    pub abbrev: Option<usize>,
+    /// Time zone for displaying dates
+    pub date_timezone: DateTimeZone,
+    /// Rendering of emojis and shortcodes like `:sparkles:` in commit messages
+    pub emoji: Emoji,
+    /// Formatting of the references of commits, like ` (main) [v1.0]`
+    pub decorations: Decorations,
     /// Text wrapping options
-    pub wrapping: Option<(Option<usize>, Option<usize>, Option<usize>)>,
+    pub wrapping: Option<Wrapping>,
+    /// Edges drawn with arrows, in text-based graphs and SVG
+    pub arrows: Arrows,
     /// Characters to use for text-based graph
     pub characters: Characters,
     /// Branch column sorting algorithm
@@ -53,17 +626,117 @@ pub struct Settings {
     pub merge_patterns: MergePatterns,
 }
 
+impl Default for Settings {
+    /// Uncolored one-line output with the built-in git-flow model, and no optional decorations of the graph.
+    fn default() -> Self {
+        Settings {
+            reverse_commit_order: false,
+            debug: false,
+            colored: false,
+            accessible: false,
+            emphasis: false,
+            group_separators: false,
+            release_bands: None,
+            bubbles: None,
+            back_merges: None,
+            lane_legend: false,
+            density: DENSITY_COMPACT,
+            row_spacing: 0,
+            line_numbers: None,
+            header: None,
+            svg_time_axis: None,
+            svg_divergence: false,
+            svg_ids: false,
+            svg_metadata: false,
+            svg_tooltips: false,
+            svg_stripes: None,
+            include_remote: true,
+            track_info: false,
+            head_trail: 0,
+            uncommitted: false,
+            count_from_head: false,
+            related: None,
+            commit_order: CommitOrder::Date,
+            deterministic: false,
+            limits: Limits::default(),
+            format: CommitFormat::OneLine,
+            abbrev: None,
+            date_timezone: DateTimeZone::Commit,
+            emoji: Emoji::Keep,
+            decorations: Decorations::default(),
+            wrapping: None,
+            arrows: Arrows::Merges,
+            characters: Characters::thin(),
+            branch_order: BranchOrder::ShortestFirst(true),
+            branches: BranchSettings::from(BranchSettingsDef::git_flow()).unwrap(),
+            merge_patterns: MergePatterns::default(),
+        }
+    }
+}
+
 /// Helper for reading BranchSettings, required due to RegEx.
 #[derive(Serialize, Deserialize)]
 pub struct BranchSettingsDef {
     /// Branch persistence
     pub persistence: Vec<String>,
+    /// Number of leading persistence patterns that match long-lived branches, emphasized with `--emphasis`
+    #[serde(default = "default_emphasized")]
+    pub emphasized: usize,
     /// Branch ordering
     pub order: Vec<String>,
     /// Branch colors
     pub terminal_colors: ColorsDef,
     /// Branch colors for SVG output
     pub svg_colors: ColorsDef,
+    /// Branch line styles
+    #[serde(default)]
+    pub line_styles: Vec<(String, LineStyle)>,
+    /// Glyphs of branches' commit and merge nodes in text-based graphs
+    #[serde(default)]
+    pub node_glyphs: Vec<(String, NodeGlyphs)>,
+    /// Branches that are always shown, even with `--local` or `--max-count`
+    #[serde(default)]
+    pub pinned: Vec<String>,
+    /// Branches that are never shown, together with their commits
+    #[serde(default)]
+    pub hidden: Vec<String>,
+    /// Rules of the branching model, checked by `git-graph lint`
+    #[serde(default)]
+    pub lint: Vec<LintRuleDef>,
+}
+
+fn default_emphasized() -> usize {
+    1
+}
+
+/// A rule of the branching model for branches matching a pattern, checked by `git-graph lint`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LintRuleDef {
+    /// Pattern of the branches the rule applies to
+    pub branches: String,
+    /// Pattern of the branches they must fork from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forks_from: Option<String>,
+    /// Patterns of the branches they must all be merged into, once merged
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub merges_into: Vec<String>,
+}
+
+impl LintRuleDef {
+    fn new(branches: &str, forks_from: &str, merges_into: &[&str]) -> Self {
+        LintRuleDef {
+            branches: branches.to_string(),
+            forks_from: Some(forks_from.to_string()),
+            merges_into: merges_into.iter().map(|str| str.to_string()).collect(),
+        }
+    }
+}
+
+/// A rule of the branching model, checked by `git-graph lint`.
+pub struct LintRule {
+    pub branches: Regex,
+    pub forks_from: Option<Regex>,
+    pub merges_into: Vec<Regex>,
 }
 
 /// Helper for reading branch colors, required due to RegEx.
@@ -74,6 +747,25 @@ pub struct ColorsDef {
 }
 
 impl BranchSettingsDef {
+    /// Prepends persistence patterns, so that matching branches are the most persistent ones.
+    pub fn with_persistent(mut self, patterns: &[String]) -> Self {
+        self.persistence.splice(0..0, patterns.iter().cloned());
+        self.emphasized += patterns.len();
+        self
+    }
+
+    /// Prepends order patterns, so that matching branches are shown left-most.
+    pub fn with_order_first(mut self, patterns: &[String]) -> Self {
+        self.order.splice(0..0, patterns.iter().cloned());
+        self
+    }
+
+    /// Adds patterns of branches to hide, together with their commits.
+    pub fn with_hidden(mut self, patterns: &[String]) -> Self {
+        self.hidden.extend(patterns.iter().cloned());
+        self
+    }
+
     /// The Git-Flow model.
     pub fn git_flow() -> Self {
         BranchSettingsDef {
@@ -85,6 +777,7 @@ impl BranchSettingsDef {
                 r"^hotfix.*$".to_string(),
                 r"^bugfix.*$".to_string(),
             ],
+            emphasized: 2,
             order: vec![
                 r"^(master|main)$".to_string(),
                 r"^(hotfix|release).*$".to_string(),
@@ -128,6 +821,23 @@ impl BranchSettingsDef {
                 ],
                 unknown: vec!["gray".to_string()],
             },
+            line_styles: vec![],
+            node_glyphs: vec![],
+            pinned: vec![],
+            hidden: vec![],
+            lint: vec![
+                LintRuleDef::new(r"^feature.*$", r"^(develop|dev)$", &[r"^(develop|dev)$"]),
+                LintRuleDef::new(
+                    r"^release.*$",
+                    r"^(develop|dev)$",
+                    &[r"^(master|main)$", r"^(develop|dev)$"],
+                ),
+                LintRuleDef::new(
+                    r"^hotfix.*$",
+                    r"^(master|main)$",
+                    &[r"^(master|main)$", r"^(develop|dev)$"],
+                ),
+            ],
         }
     }
 
@@ -135,6 +845,7 @@ impl BranchSettingsDef {
     pub fn simple() -> Self {
         BranchSettingsDef {
             persistence: vec![r"^(master|main)$".to_string()],
+            emphasized: 1,
             order: vec![r"^tags/.*$".to_string(), r"^(master|main)$".to_string()],
             terminal_colors: ColorsDef {
                 matches: vec![
@@ -166,6 +877,143 @@ impl BranchSettingsDef {
                     "turquoise".to_string(),
                 ],
             },
+            line_styles: vec![],
+            node_glyphs: vec![],
+            pinned: vec![],
+            hidden: vec![],
+            lint: vec![],
+        }
+    }
+
+    /// Trunk-based development: a single main lane, short-lived branches, and prominent release tags.
+    pub fn trunk() -> Self {
+        BranchSettingsDef {
+            persistence: vec![
+                r"^(master|main|trunk)$".to_string(),
+                r"^release.*$".to_string(),
+            ],
+            emphasized: 1,
+            order: vec![
+                r"^(master|main|trunk)$".to_string(),
+                r"^release.*$".to_string(),
+                r"^tags/.*$".to_string(),
+            ],
+            terminal_colors: ColorsDef {
+                matches: vec![
+                    (
+                        r"^(master|main|trunk)$".to_string(),
+                        vec!["bright_blue".to_string()],
+                    ),
+                    (r"^release.*$".to_string(), vec!["bright_green".to_string()]),
+                    (r"^tags/.*$".to_string(), vec!["bright_yellow".to_string()]),
+                ],
+                unknown: vec![
+                    "bright_magenta".to_string(),
+                    "bright_cyan".to_string(),
+                    "bright_red".to_string(),
+                ],
+            },
+
+            svg_colors: ColorsDef {
+                matches: vec![
+                    (
+                        r"^(master|main|trunk)$".to_string(),
+                        vec!["blue".to_string()],
+                    ),
+                    (r"^release.*$".to_string(), vec!["green".to_string()]),
+                    (r"^tags/.*$".to_string(), vec!["gold".to_string()]),
+                ],
+                unknown: vec![
+                    "purple".to_string(),
+                    "turquoise".to_string(),
+                    "red".to_string(),
+                ],
+            },
+            line_styles: vec![],
+            node_glyphs: vec![],
+            pinned: vec![],
+            hidden: vec![],
+            lint: vec![],
+        }
+    }
+
+    /// GitLab Flow: `main` flowing downstream into environment branches like `staging` and `production`.
+    pub fn gitlab_flow() -> Self {
+        BranchSettingsDef {
+            persistence: vec![
+                r"^(production|prod)$".to_string(),
+                r"^(pre-production|preprod)$".to_string(),
+                r"^(staging|stage)$".to_string(),
+                r"^(master|main)$".to_string(),
+                r"^(env|environment)/.*$".to_string(),
+                r"^feature.*$".to_string(),
+                r"^(bugfix|hotfix).*$".to_string(),
+            ],
+            emphasized: 4,
+            order: vec![
+                r"^(production|prod)$".to_string(),
+                r"^(pre-production|preprod)$".to_string(),
+                r"^(staging|stage)$".to_string(),
+                r"^(env|environment)/.*$".to_string(),
+                r"^(master|main)$".to_string(),
+            ],
+            terminal_colors: ColorsDef {
+                matches: vec![
+                    (
+                        r"^(production|prod)$".to_string(),
+                        vec!["bright_red".to_string()],
+                    ),
+                    (
+                        r"^(pre-production|preprod|staging|stage)$".to_string(),
+                        vec!["bright_yellow".to_string()],
+                    ),
+                    (
+                        r"^(env|environment)/.*$".to_string(),
+                        vec!["yellow".to_string()],
+                    ),
+                    (
+                        r"^(master|main)$".to_string(),
+                        vec!["bright_blue".to_string()],
+                    ),
+                    (
+                        r"^(feature|fork/).*$".to_string(),
+                        vec!["bright_magenta".to_string(), "bright_cyan".to_string()],
+                    ),
+                    (r"^(bugfix|hotfix).*$".to_string(), vec!["red".to_string()]),
+                    (r"^tags/.*$".to_string(), vec!["bright_green".to_string()]),
+                ],
+                unknown: vec!["white".to_string()],
+            },
+
+            svg_colors: ColorsDef {
+                matches: vec![
+                    (r"^(production|prod)$".to_string(), vec!["red".to_string()]),
+                    (
+                        r"^(pre-production|preprod|staging|stage)$".to_string(),
+                        vec!["orange".to_string()],
+                    ),
+                    (
+                        r"^(env|environment)/.*$".to_string(),
+                        vec!["goldenrod".to_string()],
+                    ),
+                    (r"^(master|main)$".to_string(), vec!["blue".to_string()]),
+                    (
+                        r"^(feature|fork/).*$".to_string(),
+                        vec!["purple".to_string(), "turquoise".to_string()],
+                    ),
+                    (
+                        r"^(bugfix|hotfix).*$".to_string(),
+                        vec!["darkred".to_string()],
+                    ),
+                    (r"^tags/.*$".to_string(), vec!["green".to_string()]),
+                ],
+                unknown: vec!["gray".to_string()],
+            },
+            line_styles: vec![],
+            node_glyphs: vec![],
+            pinned: vec![],
+            hidden: vec![],
+            lint: vec![],
         }
     }
 
@@ -173,6 +1021,7 @@ impl BranchSettingsDef {
     pub fn none() -> Self {
         BranchSettingsDef {
             persistence: vec![],
+            emphasized: 0,
             order: vec![],
             terminal_colors: ColorsDef {
                 matches: vec![],
@@ -197,6 +1046,11 @@ impl BranchSettingsDef {
                     "turquoise".to_string(),
                 ],
             },
+            line_styles: vec![],
+            node_glyphs: vec![],
+            pinned: vec![],
+            hidden: vec![],
+            lint: vec![],
         }
     }
 }
@@ -212,9 +1066,21 @@ pub struct BranchSettings {
     /// Colors for branches not matching any of `colors`
     pub terminal_colors_unknown: Vec<String>,
     /// Branch colors for SVG output
-    pub svg_colors: Vec<(Regex, Vec<String>)>,
+    pub svg_colors: Vec<(Regex, Vec<Arc<str>>)>,
     /// Colors for branches not matching any of `colors` for SVG output
-    pub svg_colors_unknown: Vec<String>,
+    pub svg_colors_unknown: Vec<Arc<str>>,
+    /// Branch line styles
+    pub line_styles: Vec<(Regex, LineStyle)>,
+    /// Glyphs of branches' commit and merge nodes
+    pub node_glyphs: Vec<(Regex, NodeGlyphs)>,
+    /// Branches that are always shown
+    pub pinned: Vec<Regex>,
+    /// Branches that are never shown
+    pub hidden: Vec<Regex>,
+    /// Number of leading persistence patterns of long-lived branches
+    pub emphasized: usize,
+    /// Rules of the branching model
+    pub lint: Vec<LintRule>,
 }
 
 impl BranchSettings {
@@ -240,14 +1106,57 @@ impl BranchSettings {
 
         let terminal_colors_unknown = def.terminal_colors.unknown;
 
+        // Shared, as they are assigned to every branch
         let svg_colors = def
             .svg_colors
             .matches
             .into_iter()
-            .map(|(str, vec)| Regex::new(&str).map(|re| (re, vec)))
+            .map(|(str, vec)| {
+                Regex::new(&str).map(|re| (re, vec.into_iter().map(Arc::from).collect()))
+            })
             .collect::<Result<Vec<_>, Error>>()?;
 
-        let svg_colors_unknown = def.svg_colors.unknown;
+        let svg_colors_unknown = def.svg_colors.unknown.into_iter().map(Arc::from).collect();
+
+        let line_styles = def
+            .line_styles
+            .into_iter()
+            .map(|(str, style)| Regex::new(&str).map(|re| (re, style)))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let node_glyphs = def
+            .node_glyphs
+            .into_iter()
+            .map(|(str, glyphs)| Regex::new(&str).map(|re| (re, glyphs)))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let pinned = def
+            .pinned
+            .iter()
+            .map(|str| Regex::new(str))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let hidden = def
+            .hidden
+            .iter()
+            .map(|str| Regex::new(str))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let lint = def
+            .lint
+            .iter()
+            .map(|rule| {
+                Ok(LintRule {
+                    branches: Regex::new(&rule.branches)?,
+                    forks_from: rule.forks_from.as_deref().map(Regex::new).transpose()?,
+                    merges_into: rule
+                        .merges_into
+                        .iter()
+                        .map(|str| Regex::new(str))
+                        .collect::<Result<Vec<_>, Error>>()?,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
 
         Ok(BranchSettings {
             persistence,
@@ -256,10 +1165,105 @@ impl BranchSettings {
             terminal_colors_unknown,
             svg_colors,
             svg_colors_unknown,
+            line_styles,
+            node_glyphs,
+            pinned,
+            hidden,
+            emphasized: def.emphasized,
+            lint,
         })
     }
 }
 
+/// Line style of a branch.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LineStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+/// Glyphs of commit and merge nodes in text-based graphs.
+/// Written as a string of one glyph for both, or two glyphs for commits and merges, like `◉◎`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub struct NodeGlyphs {
+    pub commit: char,
+    pub merge: char,
+}
+
+impl FromStr for NodeGlyphs {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        let chars: Vec<char> = str.chars().collect();
+        match chars[..] {
+            [glyph] => Ok(NodeGlyphs {
+                commit: glyph,
+                merge: glyph,
+            }),
+            [commit, merge] => Ok(NodeGlyphs { commit, merge }),
+            _ => Err(format!(
+                "Invalid node glyphs '{}'. Must be one glyph, or two for commits and merges",
+                str
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for NodeGlyphs {
+    type Error = String;
+
+    fn try_from(str: String) -> Result<Self, Self::Error> {
+        NodeGlyphs::from_str(&str)
+    }
+}
+
+impl From<NodeGlyphs> for String {
+    fn from(glyphs: NodeGlyphs) -> Self {
+        [glyphs.commit, glyphs.merge].iter().collect()
+    }
+}
+
+/// Glyphs of arrows pointing left and right in text-based graphs, written as two glyphs like `◂▸`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub struct ArrowGlyphs {
+    pub left: char,
+    pub right: char,
+}
+
+impl FromStr for ArrowGlyphs {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        let chars: Vec<char> = str.chars().collect();
+        match chars[..] {
+            [left, right] => Ok(ArrowGlyphs { left, right }),
+            _ => Err(format!(
+                "Invalid arrow glyphs '{}'. Must be two glyphs, for left and right",
+                str
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for ArrowGlyphs {
+    type Error = String;
+
+    fn try_from(str: String) -> Result<Self, Self::Error> {
+        ArrowGlyphs::from_str(&str)
+    }
+}
+
+impl From<ArrowGlyphs> for String {
+    fn from(glyphs: ArrowGlyphs) -> Self {
+        [glyphs.left, glyphs.right].iter().collect()
+    }
+}
+
 /// RegEx patterns for extracting branch names from merge commit summaries.
 pub struct MergePatterns {
     /// The patterns. Evaluated in the given order.
@@ -287,9 +1291,70 @@ impl Default for MergePatterns {
     }
 }
 
+impl MergePatterns {
+    /// The default patterns, followed by the pattern packs for the given locales.
+    pub fn from(def: &MergePatternsDef) -> Result<Self, String> {
+        let mut patterns = MergePatterns::default();
+        for locale in &def.locale {
+            patterns.patterns.extend(MergePatterns::locale(locale)?);
+        }
+        Ok(patterns)
+    }
+
+    /// Patterns for merge summaries of localized Git clients, for languages like `fr` or `de`.
+    pub fn locale(language: &str) -> Result<Vec<Regex>, String> {
+        let patterns: &[&str] = match language {
+            "de" => &[
+                r"^Branch '([^']+)' (?:in .+ )?zusammenführen$",
+                r"^Merge des Branches '([^']+)'(?: in .+)?$",
+            ],
+            "es" => &[
+                r"^Fusionar (?:la )?rama '([^']+)'(?: en .+)?$",
+                r"^Merge de la rama '([^']+)'(?: en .+)?$",
+            ],
+            "fr" => &[
+                r"^Fusionner la branche '([^']+)'(?: dans .+)?$",
+                r"^Fusion de la branche '([^']+)'(?: dans .+)?$",
+            ],
+            "it" => &[
+                r"^Unisci (?:il )?branch '([^']+)'(?: in .+)?$",
+                r"^Merge del branch '([^']+)'(?: in .+)?$",
+            ],
+            "pt" => &[
+                r"^Mesclar (?:o )?branch '([^']+)'(?: em .+)?$",
+                r"^Merge do branch '([^']+)'(?: em .+)?$",
+            ],
+            _ => {
+                return Err(format!(
+                    "Unknown locale '{}' for merge patterns. Must be one of [de|es|fr|it|pt]",
+                    language
+                ))
+            }
+        };
+        patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern).map_err(|err| err.to_string()))
+            .collect()
+    }
+}
+
+/// Additional patterns for finding branch names in merge commit summaries,
+/// read from section `[merge_patterns]` of the user configuration.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct MergePatternsDef {
+    /// Languages of pattern packs to use in addition to the defaults, like `["fr", "de"]`
+    pub locale: Vec<String>,
+}
+
 /// The characters used for drawing text-based graphs.
+#[derive(Clone)]
 pub struct Characters {
     pub chars: Vec<char>,
+    /// Glyphs of commit and merge nodes, instead of those in `chars`
+    pub nodes: Option<NodeGlyphs>,
+    /// Glyphs of arrows, instead of those in `chars`
+    pub arrows: Option<ArrowGlyphs>,
 }
 
 impl FromStr for Characters {
@@ -311,43 +1376,115 @@ impl Characters {
     /// Default/thin graphs
     pub fn thin() -> Self {
         Characters {
-            chars: " ●○│─┼└┌┐┘┤├┴┬<>".chars().collect(),
+            chars: " ●○│─┼└┌┐┘┤├┴┬<>┆┄┊┈■◆▲▼□◇△▽!◌⋮".chars().collect(),
+            nodes: None,
+            arrows: None,
         }
     }
     /// Graphs with rounded corners
     pub fn round() -> Self {
         Characters {
-            chars: " ●○│─┼╰╭╮╯┤├┴┬<>".chars().collect(),
+            chars: " ●○│─┼╰╭╮╯┤├┴┬<>┆┄┊┈■◆▲▼□◇△▽!◌⋮".chars().collect(),
+            nodes: None,
+            arrows: None,
         }
     }
     /// Bold/fat graphs
     pub fn bold() -> Self {
         Characters {
-            chars: " ●○┃━╋┗┏┓┛┫┣┻┳<>".chars().collect(),
+            chars: " ●○┃━╋┗┏┓┛┫┣┻┳<>┇┅┋┉■◆▲▼□◇△▽!◌⋮".chars().collect(),
+            nodes: None,
+            arrows: None,
         }
     }
     /// Double-lined graphs
     pub fn double() -> Self {
         Characters {
-            chars: " ●○║═╬╚╔╗╝╣╠╩╦<>".chars().collect(),
+            chars: " ●○║═╬╚╔╗╝╣╠╩╦<>┆┄┊┈■◆▲▼□◇△▽!◌⋮".chars().collect(),
+            nodes: None,
+            arrows: None,
+        }
+    }
+    /// The characters for lines of emphasized branches: bold for thin and round graphs,
+    /// double for bold graphs, and unchanged otherwise.
+    pub fn emphasized(&self) -> Self {
+        let chars = match self.chars.get(3) {
+            Some('│') => Characters::bold().chars,
+            Some('┃') => Characters::double().chars,
+            _ => self.chars.clone(),
+        };
+        Characters {
+            chars,
+            nodes: self.nodes,
+            arrows: self.arrows,
         }
     }
     /// ASCII-only graphs
     pub fn ascii() -> Self {
         Characters {
-            chars: " *o|-+'..'||++<>".chars().collect(),
+            chars: " *o|-+'..'||++<>:~..#@^vHQAV!o:".chars().collect(),
+            nodes: None,
+            arrows: None,
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Characters, Decorations, Ellipsis, ShortenNames};
+    use std::str::FromStr;
 
-    pub fn reverse(self) -> Self {
-        let mut chars = self.chars;
+    #[test]
+    fn apply_decorations() {
+        let default = Decorations::default();
+        assert_eq!(
+            default.apply("main, develop", "v1.0"),
+            "(main, develop) [v1.0]"
+        );
+        assert_eq!(default.apply("", "v1.0"), "[v1.0]");
+        assert_eq!(default.apply("", ""), "");
 
-        chars.swap(6, 8);
-        chars.swap(7, 9);
-        chars.swap(10, 11);
-        chars.swap(12, 13);
-        chars.swap(14, 15);
+        let custom = Decorations::from_str("refs:  {branches}  <{tags}>").unwrap();
+        assert_eq!(custom.apply("main", ""), "refs:  main");
+        assert_eq!(custom.apply("", "v1.0"), "refs:  <v1.0>");
+        assert!(Decorations::from_str("{branches} {refs}").is_err());
+    }
+
+    #[test]
+    fn shorten_names() {
+        let name = "feature/ABC-1234-very-long-description";
+        let mut shorten = ShortenNames {
+            max_length: 14,
+            ellipsis: Ellipsis::End,
+            strip_prefixes: vec!["hotfix/".to_string(), "feature/".to_string()],
+        };
+        assert_eq!(shorten.apply(name), "ABC-1234-very…");
+        assert_eq!(shorten.apply("feature/"), "feature/");
+        assert_eq!(shorten.apply("main"), "main");
+        shorten.ellipsis = Ellipsis::Middle;
+        assert_eq!(shorten.apply(name), "ABC-123…iption");
+        shorten.ellipsis = Ellipsis::Start;
+        assert_eq!(shorten.apply(name), "…g-description");
+        assert_eq!(ShortenNames::default().apply(name), name);
+    }
 
-        Characters { chars }
+    #[test]
+    fn emphasized_characters() {
+        assert_eq!(
+            Characters::thin().emphasized().chars,
+            Characters::bold().chars
+        );
+        assert_eq!(
+            Characters::round().emphasized().chars,
+            Characters::bold().chars
+        );
+        assert_eq!(
+            Characters::bold().emphasized().chars,
+            Characters::double().chars
+        );
+        assert_eq!(
+            Characters::ascii().emphasized().chars,
+            Characters::ascii().chars
+        );
     }
 }
@@ -0,0 +1,312 @@
+//! Commit source with pseudonyms for branch names, authors and commit messages, for sharing graphs of private repositories.
+
+use crate::graph::HeadInfo;
+use crate::source::{
+    CommitData, CommitSource, Oid, OperationState, RefData, Signature, TagData, WorktreeStatus,
+};
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::Mutex;
+
+/// Words kept by the anonymization, as branching models and merge summaries rely on them.
+/// Compared case-insensitively.
+const KEPT_WORDS: [&str; 33] = [
+    "refs",
+    "heads",
+    "remotes",
+    "tags",
+    "origin",
+    "upstream",
+    "head",
+    "merge_head",
+    "rebase_head",
+    "cherry_pick_head",
+    "revert_head",
+    "bisect",
+    "good",
+    "bad",
+    "skip",
+    "main",
+    "master",
+    "develop",
+    "dev",
+    "trunk",
+    "release",
+    "hotfix",
+    "bugfix",
+    "feature",
+    "staging",
+    "production",
+    "merge",
+    "merged",
+    "branch",
+    "into",
+    "of",
+    "pull",
+    "request",
+];
+
+/// Commit source that replaces words in reference names and commit messages,
+/// as well as authors and committers, by stable pseudonyms. Topology, hashes and times are preserved.
+///
+/// Words used by branching models and merge summaries, like `feature`, `develop` or `Merge branch`,
+/// as well as numbers, versions and commit hashes, are kept. As the same word always gets the same pseudonym,
+/// branch names in merge summaries still match the anonymized references.
+///
+/// Pseudonyms are keyed hashes, so that they can not be reversed by hashing guessed words.
+/// The key is random per source, so pseudonyms differ between runs, unless a key is given by `with_key`.
+pub struct AnonymizedSource<S: CommitSource> {
+    source: S,
+    /// Keyed hasher for pseudonyms
+    hasher: KeyedState,
+    /// Original words by pseudonym, to look up branches in the wrapped source
+    originals: Mutex<HashMap<String, String>>,
+}
+
+impl<S: CommitSource> AnonymizedSource<S> {
+    pub fn new(source: S) -> Self {
+        AnonymizedSource {
+            source,
+            hasher: KeyedState(random_key()),
+            originals: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Keys the pseudonyms by `key` instead of a random key, so that repeated runs give the same pseudonyms,
+    /// e.g. to compare graphs before and after a change. Pseudonyms may change with the version of git-graph.
+    pub fn with_key(mut self, key: &str) -> Self {
+        self.hasher = KeyedState(key.to_string());
+        self
+    }
+
+    /// Replaces each word of the text, except kept ones, by its pseudonym.
+    fn anonymize(&self, text: &str) -> String {
+        let mut originals = self.originals.lock().unwrap();
+        map_words(text, |word| {
+            if is_kept(word) {
+                word.to_string()
+            } else {
+                let pseudonym = format!("w{:016x}", self.hasher.hash_one(word));
+                originals.insert(pseudonym.clone(), word.to_string());
+                pseudonym
+            }
+        })
+    }
+
+    /// Restores the words of an anonymized text.
+    fn restore(&self, text: &str) -> String {
+        let originals = self.originals.lock().unwrap();
+        map_words(text, |word| {
+            originals
+                .get(word)
+                .cloned()
+                .unwrap_or_else(|| word.to_string())
+        })
+    }
+
+    /// Replaces the name and email of a signature by pseudonyms, keeping the time.
+    fn anonymize_signature(&self, signature: Signature) -> Signature {
+        Signature {
+            name: format!("Author {:016x}", self.hasher.hash_one(&signature.name)),
+            email: format!(
+                "{:016x}@example.com",
+                self.hasher.hash_one(&signature.email)
+            ),
+            ..signature
+        }
+    }
+
+    fn anonymize_refs(&self, refs: Vec<RefData>) -> Vec<RefData> {
+        refs.into_iter()
+            .map(|rf| RefData {
+                name: self.anonymize(&rf.name),
+                target: rf.target,
+            })
+            .collect()
+    }
+}
+
+impl<S: CommitSource> CommitSource for AnonymizedSource<S> {
+    fn commits(&self, max_count: Option<usize>) -> Result<Vec<Oid>, String> {
+        self.source.commits(max_count)
+    }
+
+    fn refs(&self) -> Result<Vec<RefData>, String> {
+        Ok(self.anonymize_refs(self.source.refs()?))
+    }
+
+    fn head(&self) -> Result<HeadInfo, String> {
+        let head = self.source.head()?;
+        Ok(HeadInfo {
+            name: self.anonymize(&head.name),
+            ..head
+        })
+    }
+
+    fn commit(&self, id: Oid) -> Result<CommitData, String> {
+        let commit = self.source.commit(id)?;
+        Ok(CommitData {
+            message: self.anonymize(&commit.message),
+            author: self.anonymize_signature(commit.author),
+            committer: self.anonymize_signature(commit.committer),
+            ..commit
+        })
+    }
+
+    fn tag(&self, name: &str) -> Result<Option<TagData>, String> {
+        Ok(self.source.tag(&self.restore(name))?.map(|tag| TagData {
+            tagger: tag.tagger.map(|tagger| self.anonymize_signature(tagger)),
+            message: self.anonymize(&tag.message),
+        }))
+    }
+
+    fn boundary(&self) -> Result<Vec<Oid>, String> {
+        self.source.boundary()
+    }
+
+    fn listed(&self) -> Result<Option<Vec<Oid>>, String> {
+        self.source.listed()
+    }
+
+    fn unreachable_commits(&self) -> bool {
+        self.source.unreachable_commits()
+    }
+
+    fn abbrev(&self) -> Result<Option<usize>, String> {
+        self.source.abbrev()
+    }
+
+    fn ahead_behind(&self, branch: &str) -> Result<Option<(usize, usize)>, String> {
+        self.source.ahead_behind(&self.restore(branch))
+    }
+
+    fn head_reflog(&self, max_count: usize) -> Result<Vec<Oid>, String> {
+        self.source.head_reflog(max_count)
+    }
+
+    fn operation(&self) -> Result<Option<OperationState>, String> {
+        Ok(self.source.operation()?.map(|state| OperationState {
+            operation: state.operation,
+            heads: self.anonymize_refs(state.heads),
+        }))
+    }
+
+    fn worktree_status(&self) -> Result<Option<WorktreeStatus>, String> {
+        self.source.worktree_status()
+    }
+}
+
+/// Builds the hashers for pseudonyms, keyed by hashing the key before the hashed value.
+struct KeyedState(String);
+
+impl BuildHasher for KeyedState {
+    type Hasher = DefaultHasher;
+
+    fn build_hasher(&self) -> DefaultHasher {
+        let mut hasher = DefaultHasher::new();
+        self.0.hash(&mut hasher);
+        hasher
+    }
+}
+
+/// A random key, from the randomly keyed hashers of the standard library.
+fn random_key() -> String {
+    let random = || RandomState::new().build_hasher().finish();
+    format!("{:016x}{:016x}", random(), random())
+}
+
+/// Replaces each word of a text, i.e. each run of alphanumeric characters, `_` and `.`.
+fn map_words<F: FnMut(&str) -> String>(text: &str, mut f: F) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut start = None;
+    for (idx, char) in text.char_indices() {
+        let is_word = char.is_alphanumeric() || char == '_' || char == '.';
+        match (start, is_word) {
+            (None, true) => start = Some(idx),
+            (Some(s), false) => {
+                result.push_str(&f(&text[s..idx]));
+                result.push(char);
+                start = None;
+            }
+            (None, false) => result.push(char),
+            (Some(_), true) => {}
+        }
+    }
+    if let Some(s) = start {
+        result.push_str(&f(&text[s..]));
+    }
+    result
+}
+
+/// Whether a word is kept: a word of `KEPT_WORDS`, a number or version like `v1.2`, or a commit hash.
+fn is_kept(word: &str) -> bool {
+    let lower = word.to_lowercase();
+    let version = lower.strip_prefix('v').unwrap_or(&lower);
+    KEPT_WORDS.contains(&&lower[..])
+        || !version.chars().any(|c| c.is_alphabetic())
+        || (word.len() >= 7
+            && word.chars().all(|c| c.is_ascii_hexdigit())
+            && word.chars().any(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_kept, map_words, AnonymizedSource};
+    use crate::source::memory::{entry, MemorySource};
+
+    #[test]
+    fn anonymize_words() {
+        let anonymize = |text: &str| {
+            map_words(text, |word| {
+                if is_kept(word) {
+                    word.to_string()
+                } else {
+                    "x".to_string()
+                }
+            })
+        };
+        assert_eq!(
+            anonymize("Merge branch 'feature/login-form' into develop"),
+            "Merge branch 'feature/x-x' into develop"
+        );
+        assert_eq!(
+            anonymize("refs/tags/v1.2.0-rc1 fixes #42 (3f2a9c1e)"),
+            "refs/tags/v1.2.0-x x #42 (3f2a9c1e)"
+        );
+        assert_eq!(anonymize("Änderung für Kunde"), "x x x");
+    }
+
+    #[test]
+    fn pseudonyms_are_stable_within_a_source() {
+        let log = entry(1, &[], "", "Initial commit");
+        let source = AnonymizedSource::new(MemorySource::from_git_log(&log).unwrap());
+        let text = source.anonymize("Merge branch 'feature/login' into develop");
+        assert_eq!(
+            text,
+            source.anonymize("Merge branch 'feature/login' into develop")
+        );
+        let pseudonym = &text["Merge branch 'feature/".len()..text.len() - "' into develop".len()];
+        assert_eq!(pseudonym.len(), 17);
+        assert_eq!(
+            source.restore(&text),
+            "Merge branch 'feature/login' into develop"
+        );
+    }
+
+    #[test]
+    fn pseudonyms_by_key() {
+        let log = entry(1, &[], "", "Initial commit");
+        let anonymize = |key: Option<&str>| {
+            let source = AnonymizedSource::new(MemorySource::from_git_log(&log).unwrap());
+            let source = match key {
+                Some(key) => source.with_key(key),
+                None => source,
+            };
+            source.anonymize("feature/login")
+        };
+        assert_eq!(anonymize(Some("secret")), anonymize(Some("secret")));
+        assert_ne!(anonymize(Some("secret")), anonymize(Some("other")));
+        assert_ne!(anonymize(None), anonymize(None));
+    }
+}
@@ -0,0 +1,362 @@
+//! Commit source reading from a Git repository, using libgit2.
+
+use crate::graph::HeadInfo;
+use crate::source::{
+    decode_text, parse_abbrev, read_operation_heads, CommitData, CommitSource, Oid, Operation,
+    OperationState, RefData, Signature, TagData, WorktreeStatus, OID_LEN,
+};
+use git2::{
+    Branch, BranchType, Error, Mailmap, Repository, RepositoryState, Status, StatusOptions,
+};
+use std::collections::HashSet;
+
+/// Commit source backed by a `git2::Repository`.
+pub struct GitSource {
+    repository: Repository,
+    mailmap: Option<SendMailmap>,
+}
+
+/// Wrapper to allow for sending a mailmap along with its repository.
+struct SendMailmap(Mailmap);
+
+// SAFETY: The mailmap is only read after loading it, and libgit2 does not tie it to a thread.
+unsafe impl Send for SendMailmap {}
+
+impl GitSource {
+    pub fn new(repository: Repository) -> Self {
+        GitSource {
+            repository,
+            mailmap: None,
+        }
+    }
+
+    /// Applies the repository's mailmap (`.mailmap`, `mailmap.file`, `mailmap.blob`) to authors and committers.
+    pub fn with_mailmap(mut self) -> Result<Self, String> {
+        let mailmap = self
+            .repository
+            .mailmap()
+            .map_err(|err| err.message().to_string())?;
+        self.mailmap = Some(SendMailmap(mailmap));
+        Ok(self)
+    }
+
+    pub fn repository(&self) -> &Repository {
+        &self.repository
+    }
+
+    pub fn take_repository(self) -> Repository {
+        self.repository
+    }
+}
+
+impl CommitSource for GitSource {
+    fn commits(&self, max_count: Option<usize>) -> Result<Vec<Oid>, String> {
+        let stashes: HashSet<_> = self
+            .repository
+            .reflog("refs/stash")
+            .map_err(|err| err.message().to_string())?
+            .iter()
+            .map(|entry| entry.id_new())
+            .collect();
+
+        let mut walk = self
+            .repository
+            .revwalk()
+            .map_err(|err| err.message().to_string())?;
+
+        walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)
+            .map_err(|err| err.message().to_string())?;
+
+        // Broken references, like to missing commits, are ignored like by `git log --all`
+        for reference in self
+            .repository
+            .references()
+            .map_err(|err| err.message().to_string())?
+            .flatten()
+        {
+            if let Some(target) = reference.resolve().ok().and_then(|rf| rf.target()) {
+                let _ = walk.push(target);
+            }
+        }
+
+        if self.repository.is_shallow() {
+            return Err("ERROR: git-graph does not support shallow clones due to a missing feature in the underlying libgit2 library.".to_string());
+        }
+
+        let mut commits = Vec::new();
+        for oid in walk {
+            if let Some(max) = max_count {
+                if commits.len() >= max {
+                    break;
+                }
+            }
+            let oid = oid.map_err(|err| err.message().to_string())?;
+            if !stashes.contains(&oid) {
+                commits.push(Oid::from(oid));
+            }
+        }
+        Ok(commits)
+    }
+
+    fn refs(&self) -> Result<Vec<RefData>, String> {
+        let branches = self
+            .repository
+            .branches(None)
+            .map_err(|err| err.message().to_string())?
+            .collect::<Result<Vec<_>, Error>>()
+            .map_err(|err| err.message().to_string())?;
+
+        let mut refs: Vec<_> = branches
+            .iter()
+            .filter_map(|(br, _)| {
+                br.get().target().map(|target| RefData {
+                    name: decode_text(br.get().name_bytes(), None),
+                    target: Oid::from(target),
+                })
+            })
+            .collect();
+
+        for reference in self
+            .repository
+            .references_glob("refs/bisect/*")
+            .map_err(|err| err.message().to_string())?
+        {
+            let reference = reference.map_err(|err| err.message().to_string())?;
+            if let Some(target) = reference.target() {
+                refs.push(RefData {
+                    name: decode_text(reference.name_bytes(), None),
+                    target: Oid::from(target),
+                });
+            }
+        }
+
+        let mut tags = Vec::new();
+        self.repository
+            .tag_foreach(|oid, name| {
+                tags.push((oid, name.to_vec()));
+                true
+            })
+            .map_err(|err| err.message().to_string())?;
+
+        for (oid, name) in tags {
+            let name = decode_text(&name, None);
+
+            let target = self
+                .repository
+                .find_tag(oid)
+                .map(|tag| tag.target_id())
+                .or_else(|_| self.repository.find_commit(oid).map(|_| oid));
+
+            if let Ok(target) = target {
+                refs.push(RefData {
+                    name,
+                    target: Oid::from(target),
+                });
+            }
+        }
+
+        Ok(refs)
+    }
+
+    fn head(&self) -> Result<HeadInfo, String> {
+        let head = self
+            .repository
+            .head()
+            .map_err(|err| err.message().to_string())?;
+        let name = decode_text(head.name_bytes(), None);
+        let name = match name.strip_prefix("refs/heads/") {
+            Some(branch) => branch.to_string(),
+            None => name,
+        };
+
+        Ok(HeadInfo {
+            oid: Oid::from(head.target().ok_or_else(|| "No id for HEAD".to_string())?),
+            name,
+            is_branch: head.is_branch(),
+        })
+    }
+
+    fn commit(&self, id: Oid) -> Result<CommitData, String> {
+        let commit = git2::Oid::from_bytes(id.as_bytes())
+            .and_then(|oid| self.repository.find_commit(oid))
+            .map_err(|err| err.message().to_string())?;
+
+        let (author, committer) = match &self.mailmap {
+            Some(SendMailmap(mailmap)) => (
+                commit
+                    .author_with_mailmap(mailmap)
+                    .map_err(|err| err.message().to_string())?,
+                commit
+                    .committer_with_mailmap(mailmap)
+                    .map_err(|err| err.message().to_string())?,
+            ),
+            None => (commit.author(), commit.committer()),
+        };
+        let encoding = commit.message_encoding();
+        Ok(CommitData {
+            id,
+            parents: commit.parent_ids().map(Oid::from).collect(),
+            message: decode_text(commit.message_bytes(), encoding),
+            author: to_signature(&author, encoding),
+            committer: to_signature(&committer, encoding),
+            tree: Some(Oid::from(commit.tree_id())),
+            encoding: encoding.map(str::to_string),
+        })
+    }
+
+    fn tag(&self, name: &str) -> Result<Option<TagData>, String> {
+        let target = match self.repository.find_reference(name) {
+            Ok(reference) => reference.target(),
+            Err(_) => return Ok(None),
+        };
+        // Lightweight tags point to commits directly
+        match target.map(|target| self.repository.find_tag(target)) {
+            Some(Ok(tag)) => Ok(Some(TagData {
+                tagger: tag.tagger().map(|tagger| to_signature(&tagger, None)),
+                message: decode_text(tag.message_bytes().unwrap_or_default(), None),
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    fn abbrev(&self) -> Result<Option<usize>, String> {
+        let config = self
+            .repository
+            .config()
+            .map_err(|err| err.message().to_string())?;
+        match config.get_string("core.abbrev") {
+            Ok(value) => parse_abbrev(&value),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn ahead_behind(&self, branch: &str) -> Result<Option<(usize, usize)>, String> {
+        let branch = match self.repository.find_branch(branch, BranchType::Local) {
+            Ok(branch) => branch,
+            // Names with invalid UTF-8 are decoded lossily, so look the branch up by its original name
+            Err(_) => match self.find_branch_by_decoded_name(branch)? {
+                Some(branch) => branch,
+                None => return Ok(None),
+            },
+        };
+        let upstream = match branch.upstream() {
+            Ok(upstream) => upstream,
+            Err(_) => return Ok(None),
+        };
+        match (branch.get().target(), upstream.get().target()) {
+            (Some(local), Some(upstream)) => self
+                .repository
+                .graph_ahead_behind(local, upstream)
+                .map(Some)
+                .map_err(|err| err.message().to_string()),
+            _ => Ok(None),
+        }
+    }
+
+    fn head_reflog(&self, max_count: usize) -> Result<Vec<Oid>, String> {
+        let reflog = self
+            .repository
+            .reflog("HEAD")
+            .map_err(|err| err.message().to_string())?;
+        Ok(reflog
+            .iter()
+            .take(max_count)
+            .map(|entry| Oid::from(entry.id_new()))
+            .collect())
+    }
+
+    fn operation(&self) -> Result<Option<OperationState>, String> {
+        let operation = match self.repository.state() {
+            RepositoryState::Clean => return Ok(None),
+            RepositoryState::Merge => Operation::Merge,
+            RepositoryState::Rebase
+            | RepositoryState::RebaseInteractive
+            | RepositoryState::RebaseMerge => Operation::Rebase,
+            RepositoryState::CherryPick | RepositoryState::CherryPickSequence => {
+                Operation::CherryPick
+            }
+            RepositoryState::Revert | RepositoryState::RevertSequence => Operation::Revert,
+            RepositoryState::Bisect => Operation::Bisect,
+            RepositoryState::ApplyMailbox | RepositoryState::ApplyMailboxOrRebase => {
+                Operation::ApplyMailbox
+            }
+        };
+        Ok(Some(OperationState {
+            operation,
+            heads: read_operation_heads(self.repository.path()),
+        }))
+    }
+
+    fn worktree_status(&self) -> Result<Option<WorktreeStatus>, String> {
+        if self.repository.is_bare() {
+            return Ok(None);
+        }
+        let mut options = StatusOptions::new();
+        options.include_untracked(true).include_ignored(false);
+        let statuses = self
+            .repository
+            .statuses(Some(&mut options))
+            .map_err(|err| err.message().to_string())?;
+
+        let staged_flags = Status::INDEX_NEW
+            | Status::INDEX_MODIFIED
+            | Status::INDEX_DELETED
+            | Status::INDEX_RENAMED
+            | Status::INDEX_TYPECHANGE;
+        let unstaged_flags = Status::WT_NEW
+            | Status::WT_MODIFIED
+            | Status::WT_DELETED
+            | Status::WT_RENAMED
+            | Status::WT_TYPECHANGE
+            | Status::CONFLICTED;
+        let count = |flags: Status| {
+            statuses
+                .iter()
+                .filter(|entry| entry.status().intersects(flags))
+                .count()
+        };
+        let status = WorktreeStatus {
+            staged: count(staged_flags),
+            unstaged: count(unstaged_flags),
+        };
+        if status.staged == 0 && status.unstaged == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(status))
+        }
+    }
+}
+
+impl GitSource {
+    /// The local branch with the given name when decoded like in `refs`.
+    fn find_branch_by_decoded_name(&self, name: &str) -> Result<Option<Branch<'_>>, String> {
+        for branch in self
+            .repository
+            .branches(Some(BranchType::Local))
+            .map_err(|err| err.message().to_string())?
+        {
+            let (branch, _) = branch.map_err(|err| err.message().to_string())?;
+            if matches!(branch.name_bytes(), Ok(bytes) if decode_text(bytes, None) == name) {
+                return Ok(Some(branch));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl From<git2::Oid> for Oid {
+    fn from(oid: git2::Oid) -> Self {
+        let mut bytes = [0; OID_LEN];
+        bytes.copy_from_slice(oid.as_bytes());
+        Oid(bytes)
+    }
+}
+
+fn to_signature(signature: &git2::Signature, encoding: Option<&str>) -> Signature {
+    Signature {
+        name: decode_text(signature.name_bytes(), encoding),
+        email: decode_text(signature.email_bytes(), encoding),
+        time: signature.when().seconds(),
+        offset: signature.when().offset_minutes(),
+    }
+}
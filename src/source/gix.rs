@@ -0,0 +1,401 @@
+//! Commit source reading from a Git repository, using the pure-Rust gitoxide (`gix`) crate.
+
+use crate::graph::HeadInfo;
+use crate::source::{
+    decode_text, parse_abbrev, read_operation_heads, CommitData, CommitSource, Oid, Operation,
+    OperationState, RefData, Signature, TagData, WorktreeStatus, OID_LEN,
+};
+use gix::refs::{FullName, FullNameRef};
+use gix::revision::walk::Sorting;
+use gix::state::InProgress;
+use gix::status::{Item, UntrackedFiles};
+use gix::traverse::commit::simple::CommitTimeOrder;
+use gix::{remote, ObjectId, Repository};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::path::Path;
+
+const STASH: &str = "refs/stash";
+
+/// Commit source backed by a `gix::Repository`.
+pub struct GixSource {
+    repository: Repository,
+}
+
+impl GixSource {
+    pub fn new(repository: Repository) -> Self {
+        GixSource { repository }
+    }
+
+    /// Opens the repository at or above `path`.
+    pub fn discover<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        gix::discover(path.as_ref())
+            .map(GixSource::new)
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn repository(&self) -> &Repository {
+        &self.repository
+    }
+}
+
+impl CommitSource for GixSource {
+    fn commits(&self, max_count: Option<usize>) -> Result<Vec<Oid>, String> {
+        let mut tips = HashSet::new();
+        let references = self
+            .repository
+            .references()
+            .map_err(|err| err.to_string())?;
+        for reference in references.all().map_err(|err| err.to_string())? {
+            let mut reference = reference.map_err(|err| err.to_string())?;
+            if reference.name().as_bstr() == STASH {
+                continue;
+            }
+            if let Ok(id) = reference.peel_to_id() {
+                tips.insert(id.detach());
+            }
+        }
+        if let Ok(head) = self.repository.head_id() {
+            tips.insert(head.detach());
+        }
+        let mut tips: Vec<_> = tips
+            .into_iter()
+            .filter(|id| self.repository.find_commit(*id).is_ok())
+            .collect();
+        // The walk order decides the order of commits with equal times
+        tips.sort();
+
+        // The revision walk respects shallow boundaries, but is not topologically sorted.
+        let walk = self
+            .repository
+            .rev_walk(tips)
+            .sorting(Sorting::ByCommitTime(CommitTimeOrder::NewestFirst))
+            .all()
+            .map_err(|err| err.to_string())?;
+
+        let mut commits = Vec::new();
+        for info in walk {
+            let info = info.map_err(|err| err.to_string())?;
+            commits.push((
+                info.id,
+                info.commit_time.unwrap_or(0),
+                info.parent_ids.to_vec(),
+            ));
+        }
+
+        let mut commits = topo_sort(commits)?;
+        if let Some(max) = max_count {
+            commits.truncate(max);
+        }
+        Ok(commits)
+    }
+
+    fn refs(&self) -> Result<Vec<RefData>, String> {
+        let references = self
+            .repository
+            .references()
+            .map_err(|err| err.to_string())?;
+
+        let mut refs = Vec::new();
+        let platforms = [
+            references.local_branches(),
+            references.remote_branches(),
+            references.tags(),
+            references.prefixed("refs/bisect/"),
+        ];
+        for platform in platforms {
+            for reference in platform.map_err(|err| err.to_string())? {
+                let mut reference = reference.map_err(|err| err.to_string())?;
+                // Symbolic references like `refs/remotes/origin/HEAD` are no branches
+                if reference.target().try_id().is_none() {
+                    continue;
+                }
+                let name = decode_text(reference.name().as_bstr(), None);
+                if let Ok(target) = reference.peel_to_id() {
+                    refs.push(RefData {
+                        name,
+                        target: Oid::try_from(target.detach())?,
+                    });
+                }
+            }
+        }
+        Ok(refs)
+    }
+
+    fn head(&self) -> Result<HeadInfo, String> {
+        let oid = self.repository.head_id().map_err(|err| err.to_string())?;
+        let name = self.repository.head_name().map_err(|err| err.to_string())?;
+
+        Ok(HeadInfo {
+            oid: Oid::try_from(oid.detach())?,
+            is_branch: name.is_some(),
+            name: match name {
+                Some(name) => decode_text(name.shorten(), None),
+                None => "HEAD".to_string(),
+            },
+        })
+    }
+
+    fn commit(&self, id: Oid) -> Result<CommitData, String> {
+        let oid = ObjectId::try_from(id.as_bytes()).map_err(|err| err.to_string())?;
+        let commit = self
+            .repository
+            .find_commit(oid)
+            .map_err(|err| err.to_string())?;
+        let commit = commit.decode().map_err(|err| err.to_string())?;
+        let encoding = commit
+            .encoding
+            .and_then(|encoding| std::str::from_utf8(encoding).ok());
+
+        Ok(CommitData {
+            id,
+            parents: commit
+                .parents()
+                .map(Oid::try_from)
+                .collect::<Result<_, _>>()?,
+            message: decode_text(commit.message, encoding),
+            author: to_signature(commit.author(), encoding)?,
+            committer: to_signature(commit.committer(), encoding)?,
+            tree: Some(Oid::try_from(commit.tree())?),
+            encoding: encoding.map(str::to_string),
+        })
+    }
+
+    fn tag(&self, name: &str) -> Result<Option<TagData>, String> {
+        let id = match self.repository.try_find_reference(name) {
+            Ok(Some(reference)) => reference.target().try_id().map(|id| id.to_owned()),
+            Ok(None) => None,
+            Err(err) => return Err(err.to_string()),
+        };
+        let object = match id {
+            Some(id) => self
+                .repository
+                .find_object(id)
+                .map_err(|err| err.to_string())?,
+            None => return Ok(None),
+        };
+        // Lightweight tags point to commits directly
+        if object.kind != gix::object::Kind::Tag {
+            return Ok(None);
+        }
+        let tag = object.into_tag();
+        let tag = tag.decode().map_err(|err| err.to_string())?;
+        Ok(Some(TagData {
+            tagger: tag
+                .tagger
+                .map(|tagger| to_signature(tagger, None))
+                .transpose()?,
+            message: decode_text(tag.message, None),
+        }))
+    }
+
+    fn abbrev(&self) -> Result<Option<usize>, String> {
+        match self.repository.config_snapshot().string("core.abbrev") {
+            Some(value) => parse_abbrev(&value.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    fn ahead_behind(&self, branch: &str) -> Result<Option<(usize, usize)>, String> {
+        // Names with invalid UTF-8 are decoded lossily, so look the branch up by its original name
+        let Some(name) = self.find_branch_by_decoded_name(branch)? else {
+            return Ok(None);
+        };
+        let name = name.as_ref();
+        let upstream = match self
+            .repository
+            .branch_remote_tracking_ref_name(name, remote::Direction::Fetch)
+        {
+            Some(Ok(upstream)) => upstream,
+            _ => return Ok(None),
+        };
+        let (local, upstream) = match (self.target(name)?, self.target(upstream.as_ref())?) {
+            (Some(local), Some(upstream)) => (local, upstream),
+            _ => return Ok(None),
+        };
+        Ok(Some((
+            self.count_hidden(local, upstream)?,
+            self.count_hidden(upstream, local)?,
+        )))
+    }
+
+    fn head_reflog(&self, max_count: usize) -> Result<Vec<Oid>, String> {
+        let head = self.repository.head().map_err(|err| err.to_string())?;
+        let mut log = head.log_iter();
+        let lines = match log.rev().map_err(|err| err.to_string())? {
+            Some(lines) => lines,
+            None => return Ok(Vec::new()),
+        };
+        lines
+            .take(max_count)
+            .map(|line| {
+                line.map_err(|err| err.to_string())
+                    .and_then(|line| Oid::try_from(line.new_oid))
+            })
+            .collect()
+    }
+
+    fn operation(&self) -> Result<Option<OperationState>, String> {
+        let operation = match self.repository.state() {
+            None => return Ok(None),
+            Some(InProgress::Merge) => Operation::Merge,
+            Some(InProgress::Rebase) | Some(InProgress::RebaseInteractive) => Operation::Rebase,
+            Some(InProgress::CherryPick) | Some(InProgress::CherryPickSequence) => {
+                Operation::CherryPick
+            }
+            Some(InProgress::Revert) | Some(InProgress::RevertSequence) => Operation::Revert,
+            Some(InProgress::Bisect) => Operation::Bisect,
+            Some(InProgress::ApplyMailbox) | Some(InProgress::ApplyMailboxRebase) => {
+                Operation::ApplyMailbox
+            }
+        };
+        Ok(Some(OperationState {
+            operation,
+            heads: read_operation_heads(self.repository.git_dir()),
+        }))
+    }
+
+    fn worktree_status(&self) -> Result<Option<WorktreeStatus>, String> {
+        if self.repository.workdir().is_none() {
+            return Ok(None);
+        }
+        let mut status = WorktreeStatus {
+            staged: 0,
+            unstaged: 0,
+        };
+        for item in self
+            .repository
+            .status(gix::progress::Discard)
+            .map_err(|err| err.to_string())?
+            .untracked_files(UntrackedFiles::Files)
+            .into_iter(None)
+            .map_err(|err| err.to_string())?
+        {
+            match item.map_err(|err| err.to_string())? {
+                Item::TreeIndex(_) => status.staged += 1,
+                Item::IndexWorktree(_) => status.unstaged += 1,
+            }
+        }
+        if status.staged == 0 && status.unstaged == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(status))
+        }
+    }
+}
+
+impl GixSource {
+    /// The full name of the local branch with the given name when decoded like in `refs`.
+    fn find_branch_by_decoded_name(&self, name: &str) -> Result<Option<FullName>, String> {
+        let full_name = format!("refs/heads/{}", name);
+        if let Ok(Some(reference)) = self.repository.try_find_reference(full_name.as_str()) {
+            return Ok(Some(reference.name().to_owned()));
+        }
+        let references = self
+            .repository
+            .references()
+            .map_err(|err| err.to_string())?;
+        for reference in references.local_branches().map_err(|err| err.to_string())? {
+            let reference = reference.map_err(|err| err.to_string())?;
+            if decode_text(reference.name().as_bstr(), None) == full_name {
+                return Ok(Some(reference.name().to_owned()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The commit a reference points to, or `None` if it does not exist.
+    fn target(&self, name: &FullNameRef) -> Result<Option<ObjectId>, String> {
+        match self.repository.try_find_reference(name) {
+            Ok(Some(mut reference)) => reference
+                .peel_to_id()
+                .map(|id| Some(id.detach()))
+                .map_err(|err| err.to_string()),
+            Ok(None) => Ok(None),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    /// Number of commits reachable from `tip`, but not from `hidden`.
+    fn count_hidden(&self, tip: ObjectId, hidden: ObjectId) -> Result<usize, String> {
+        let mut count = 0;
+        for info in self
+            .repository
+            .rev_walk([tip])
+            .with_hidden([hidden])
+            .all()
+            .map_err(|err| err.to_string())?
+        {
+            info.map_err(|err| err.to_string())?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+impl TryFrom<ObjectId> for Oid {
+    type Error = String;
+
+    fn try_from(oid: ObjectId) -> Result<Self, Self::Error> {
+        if oid.as_bytes().len() != OID_LEN {
+            return Err("SHA-256 repositories are not supported".to_string());
+        }
+        let mut bytes = [0; OID_LEN];
+        bytes.copy_from_slice(oid.as_bytes());
+        Ok(Oid(bytes))
+    }
+}
+
+/// Sorts commits topologically (no parents before all of their children), and by commit time otherwise.
+/// Expects commits sorted by time, newest first.
+fn topo_sort(commits: Vec<(ObjectId, i64, Vec<ObjectId>)>) -> Result<Vec<Oid>, String> {
+    let indices: HashMap<_, _> = commits
+        .iter()
+        .enumerate()
+        .map(|(idx, (id, _, _))| (*id, idx))
+        .collect();
+
+    let mut num_children = vec![0; commits.len()];
+    for (_, _, parents) in &commits {
+        for parent in parents {
+            if let Some(idx) = indices.get(parent) {
+                num_children[*idx] += 1;
+            }
+        }
+    }
+
+    // Newest first, and in walk order for equal times
+    let mut queue: BinaryHeap<_> = num_children
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count == 0)
+        .map(|(idx, _)| (commits[idx].1, std::cmp::Reverse(idx)))
+        .collect();
+
+    let mut sorted = Vec::with_capacity(commits.len());
+    while let Some((_, std::cmp::Reverse(idx))) = queue.pop() {
+        let (id, _, parents) = &commits[idx];
+        sorted.push(Oid::try_from(*id)?);
+        for parent in parents {
+            if let Some(&par_idx) = indices.get(parent) {
+                num_children[par_idx] -= 1;
+                if num_children[par_idx] == 0 {
+                    queue.push((commits[par_idx].1, std::cmp::Reverse(par_idx)));
+                }
+            }
+        }
+    }
+    Ok(sorted)
+}
+
+fn to_signature(
+    signature: gix::actor::SignatureRef,
+    encoding: Option<&str>,
+) -> Result<Signature, String> {
+    let time = signature.time().map_err(|err| err.to_string())?;
+    Ok(Signature {
+        name: decode_text(signature.name, encoding),
+        email: decode_text(signature.email, encoding),
+        time: time.seconds,
+        offset: time.offset / 60,
+    })
+}
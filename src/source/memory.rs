@@ -0,0 +1,531 @@
+//! Commit source holding all data in memory, e.g. supplied as JSON or parsed from `git log` output.
+
+use crate::graph::HeadInfo;
+use crate::source::{CommitData, CommitSource, Oid, OidIndex, RefData, Signature};
+use serde_derive::{Deserialize, Serialize};
+
+/// Format for `git log` output readable by `MemorySource::from_git_log`.
+pub const GIT_LOG_FORMAT: &str =
+    "%H%x00%P%x00%D%x00%an%x00%ae%x00%ad%x00%cn%x00%ce%x00%cd%x00%B%x1e";
+
+const FIELD_SEPARATOR: char = '\x00';
+const RECORD_SEPARATOR: char = '\x1e';
+
+/// Commit source with all commits and references held in memory.
+pub struct MemorySource {
+    commits: Vec<CommitData>,
+    indices: OidIndex,
+    refs: Vec<RefData>,
+    head: HeadInfo,
+}
+
+/// Commits and references for a `MemorySource`, e.g. deserialized from JSON.
+#[derive(Serialize, Deserialize)]
+pub struct MemorySourceDef {
+    /// Commits in topological order, newest first
+    pub commits: Vec<CommitData>,
+    #[serde(default)]
+    pub refs: Vec<RefData>,
+    pub head: HeadInfo,
+}
+
+impl MemorySource {
+    pub fn from(def: MemorySourceDef) -> Self {
+        let indices = def
+            .commits
+            .iter()
+            .enumerate()
+            .map(|(idx, commit)| (commit.id, idx))
+            .collect();
+        MemorySource {
+            commits: def.commits,
+            indices,
+            refs: def.refs,
+            head: def.head,
+        }
+    }
+
+    /// Reads commits and references from JSON, in the format of `MemorySourceDef`.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let def: MemorySourceDef = serde_json::from_str(json).map_err(|err| err.to_string())?;
+        Ok(MemorySource::from(def))
+    }
+
+    /// Reads commits and references from the output of
+    ///
+    /// ```text
+    /// git log --all --exclude=refs/stash --topo-order --decorate=full --date=raw --format=<GIT_LOG_FORMAT>
+    /// ```
+    ///
+    /// If HEAD is not contained in the output, the first commit is used as (detached) HEAD.
+    pub fn from_git_log(log: &str) -> Result<Self, String> {
+        let mut commits = Vec::new();
+        let mut refs = Vec::new();
+        let mut head = None;
+
+        for record in log.split(RECORD_SEPARATOR) {
+            let record = record.trim_start_matches(['\r', '\n']);
+            if record.is_empty() {
+                continue;
+            }
+            let fields: Vec<_> = record.split(FIELD_SEPARATOR).collect();
+            if fields.len() != 10 {
+                return Err(format!(
+                    "Invalid log entry, expected 10 fields but found {}: '{}'",
+                    fields.len(),
+                    record
+                ));
+            }
+            let id: Oid = fields[0].parse()?;
+            let parents = fields[1]
+                .split_whitespace()
+                .map(|oid| oid.parse())
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for decoration in fields[2].split(", ").filter(|dec| !dec.is_empty()) {
+                if decoration == "HEAD" {
+                    head = Some(HeadInfo {
+                        oid: id,
+                        name: "HEAD".to_string(),
+                        is_branch: false,
+                    });
+                } else if let Some(branch) = decoration.strip_prefix("HEAD -> ") {
+                    head = Some(HeadInfo {
+                        oid: id,
+                        name: branch.trim_start_matches("refs/heads/").to_string(),
+                        is_branch: true,
+                    });
+                    refs.push(RefData {
+                        name: branch.to_string(),
+                        target: id,
+                    });
+                } else {
+                    let name = decoration.trim_start_matches("tag: ");
+                    // Symbolic refs like `origin/HEAD` are no branches
+                    if !name.ends_with("/HEAD") {
+                        refs.push(RefData {
+                            name: name.to_string(),
+                            target: id,
+                        });
+                    }
+                }
+            }
+
+            commits.push(CommitData {
+                id,
+                parents,
+                message: fields[9].to_string(),
+                author: parse_signature(fields[3], fields[4], fields[5])?,
+                committer: parse_signature(fields[6], fields[7], fields[8])?,
+                tree: None,
+                encoding: None,
+            });
+        }
+
+        let head = match head {
+            Some(head) => head,
+            None => HeadInfo {
+                oid: commits
+                    .first()
+                    .map(|commit| commit.id)
+                    .ok_or_else(|| "No commits found in log".to_string())?,
+                name: "HEAD".to_string(),
+                is_branch: false,
+            },
+        };
+
+        Ok(MemorySource::from(MemorySourceDef {
+            commits,
+            refs,
+            head,
+        }))
+    }
+}
+
+impl CommitSource for MemorySource {
+    fn commits(&self, max_count: Option<usize>) -> Result<Vec<Oid>, String> {
+        let count = max_count.unwrap_or(self.commits.len());
+        Ok(self
+            .commits
+            .iter()
+            .take(count)
+            .map(|commit| commit.id)
+            .collect())
+    }
+
+    fn refs(&self) -> Result<Vec<RefData>, String> {
+        Ok(self.refs.clone())
+    }
+
+    fn head(&self) -> Result<HeadInfo, String> {
+        Ok(self.head.clone())
+    }
+
+    fn commit(&self, id: Oid) -> Result<CommitData, String> {
+        self.indices
+            .get(&id)
+            .map(|idx| self.commits[*idx].clone())
+            .ok_or_else(|| format!("Commit {} not found", id))
+    }
+}
+
+/// Parses a signature with a date in `git log --date=raw` format, like `1600000000 +0100`.
+fn parse_signature(name: &str, email: &str, date: &str) -> Result<Signature, String> {
+    let err = || format!("Invalid raw date: '{}'", date);
+    let (time, offset) = date.split_once(' ').ok_or_else(err)?;
+    let time = time.parse().map_err(|_| err())?;
+    let offset: i32 = offset.parse().map_err(|_| err())?;
+    Ok(Signature {
+        name: name.to_string(),
+        email: email.to_string(),
+        time,
+        offset: offset.signum() * (offset.abs() / 100 * 60 + offset.abs() % 100),
+    })
+}
+
+/// The object id numbered `id` in test fixtures.
+#[cfg(test)]
+pub(crate) fn oid(id: u8) -> Oid {
+    format!("{:040x}", id).parse().unwrap()
+}
+
+/// A commit numbered `id` in the format of `from_git_log`, committed `id` seconds after the fixtures' epoch.
+#[cfg(test)]
+pub(crate) fn entry(id: u8, parents: &[u8], decorations: &str, message: &str) -> String {
+    entry_with_times(id, parents, decorations, message, (id.into(), id.into()))
+}
+
+/// Like `entry`, with the author and commit times in seconds after the fixtures' epoch.
+#[cfg(test)]
+pub(crate) fn entry_with_times(
+    id: u8,
+    parents: &[u8],
+    decorations: &str,
+    message: &str,
+    (author_time, commit_time): (i64, i64),
+) -> String {
+    let parents: Vec<_> = parents.iter().map(|id| oid(*id).to_string()).collect();
+    format!(
+        "{}\x00{}\x00{}\x00A. U. Thor\x00author@example.com\x00{} +0100\x00A. U. Thor\x00author@example.com\x00{} +0100\x00{}\n\x1e\n",
+        oid(id),
+        parents.join(" "),
+        decorations,
+        1600000000 + author_time,
+        1600000000 + commit_time,
+        message
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::GitGraph;
+    use crate::lint::lint;
+    use crate::print::porcelain::{print_porcelain, PorcelainVersion};
+    use crate::print::svg::print_svg;
+    use crate::print::unicode::{layout_rows, print_unicode};
+    use crate::settings::{Settings, DENSITY_SPARSE};
+    use crate::source::memory::{entry, oid, MemorySource};
+    use crate::source::{RefData, WorktreeStatus};
+    use regex::Regex;
+    use std::collections::HashMap;
+
+    #[test]
+    fn graph_from_git_log() {
+        let log = [
+            entry(
+                4,
+                &[2, 3],
+                "HEAD -> refs/heads/main, tag: refs/tags/v1.0",
+                "Merge branch 'feature/x'",
+            ),
+            entry(3, &[1], "refs/remotes/origin/HEAD", "Feature\n\nDetails"),
+            entry(2, &[1], "", "Fix"),
+            entry(1, &[], "", "Initial commit"),
+        ]
+        .concat();
+
+        let source = MemorySource::from_git_log(&log).unwrap();
+        assert_eq!(source.refs.len(), 2);
+        assert_eq!(source.commits[1].summary(), "Feature");
+        assert_eq!(source.commits[1].author.offset, 60);
+
+        let settings = Settings::default();
+        let graph = GitGraph::from_source(source, &settings, None).unwrap();
+
+        assert_eq!(graph.commits.len(), 4);
+        assert_eq!(graph.head.name, "main");
+        assert!(graph.head.is_branch);
+
+        let branch_name = |idx: usize| {
+            let trace = graph.commits[idx].branch_trace.unwrap();
+            graph.all_branches[trace].name.to_string()
+        };
+        assert_eq!(branch_name(0), "main");
+        assert_eq!(branch_name(1), "feature/x");
+        assert_eq!(branch_name(3), "main");
+        assert_eq!(graph.tags.len(), 1);
+    }
+
+    #[test]
+    fn porcelain_output() {
+        let log = [
+            entry(
+                3,
+                &[1, 2],
+                "HEAD -> refs/heads/main, tag: refs/tags/v1.0",
+                "Merge branch 'feature/x'",
+            ),
+            entry(
+                2,
+                &[1],
+                "refs/heads/feature/x",
+                "Tabs\tin subject\n\nDetails",
+            ),
+            entry(1, &[], "", "Initial commit"),
+        ]
+        .concat();
+        let settings = Settings::default();
+        let source = MemorySource::from_git_log(&log).unwrap();
+        let graph = GitGraph::from_source(source, &settings, None).unwrap();
+
+        // Tabs and line breaks within fields are replaced by spaces
+        let expected = format!(
+            "{c3}\t{c1} {c2}\t0\tmain\tHEAD -> main, tag: v1.0\tMerge branch 'feature/x'\n\
+             {c2}\t{c1}\t1\tfeature/x\tfeature/x\tTabs in subject\n\
+             {c1}\t\t0\tmain\t\tInitial commit\n",
+            c1 = oid(1),
+            c2 = oid(2),
+            c3 = oid(3),
+        );
+        assert_eq!(
+            print_porcelain(&graph, PorcelainVersion::V1, false).unwrap(),
+            expected
+        );
+
+        // With `-z`, fields are kept as they are
+        let expected = format!(
+            "{c3}\0{c1} {c2}\00\0main\0HEAD -> main, tag: v1.0\0Merge branch 'feature/x'\0\
+             {c2}\0{c1}\01\0feature/x\0feature/x\0Tabs\tin subject\0\
+             {c1}\0\00\0main\0\0Initial commit\0",
+            c1 = oid(1),
+            c2 = oid(2),
+            c3 = oid(3),
+        );
+        assert_eq!(
+            print_porcelain(&graph, PorcelainVersion::V1, true).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn graph_with_missing_commits() {
+        // A partial log: the parent of commit 3 and the target of `refs/heads/gone` are missing
+        let log = [
+            entry(3, &[2, 9], "HEAD -> refs/heads/main", "Merge branch 'gone'"),
+            entry(2, &[1], "", "Fix"),
+            entry(1, &[], "", "Initial commit"),
+        ]
+        .concat();
+        let mut source = MemorySource::from_git_log(&log).unwrap();
+        source.refs.push(RefData {
+            name: "refs/heads/gone".to_string(),
+            target: oid(8),
+        });
+
+        let settings = Settings::default();
+        let graph = GitGraph::from_source(source, &settings, None).unwrap();
+        assert_eq!(graph.commits.len(), 3);
+        assert!(print_unicode(&graph, &settings).is_ok());
+        assert!(print_svg(&graph, &settings).is_ok());
+    }
+
+    #[test]
+    fn inconsistent_graph_is_an_error() {
+        let log = [
+            entry(2, &[1], "HEAD -> refs/heads/main", "Fix"),
+            entry(1, &[], "", "Initial commit"),
+        ]
+        .concat();
+        let settings = Settings::default();
+        let source = MemorySource::from_git_log(&log).unwrap();
+        let mut graph = GitGraph::from_source(source, &settings, None).unwrap();
+        for branch in graph.all_branches.iter_mut() {
+            branch.visual.column = None;
+        }
+        assert!(print_unicode(&graph, &settings).is_err());
+        assert!(print_svg(&graph, &settings).is_err());
+    }
+
+    #[test]
+    fn releases_from_tags() {
+        let log = [
+            entry(5, &[4], "HEAD -> refs/heads/main", "Start 1.1"),
+            entry(
+                4,
+                &[3],
+                "tag: refs/tags/v1.0, tag: refs/tags/stable",
+                "Release 1.0",
+            ),
+            entry(3, &[2], "", "Fix"),
+            entry(2, &[1], "tag: refs/tags/v0.1", "Release 0.1"),
+            entry(1, &[], "", "Initial commit"),
+        ]
+        .concat();
+        let settings = Settings::default();
+        let source = MemorySource::from_git_log(&log).unwrap();
+        let graph = GitGraph::from_source(source, &settings, None).unwrap();
+
+        let releases = graph.releases(&Regex::new("^v").unwrap());
+        let releases: Vec<_> = releases
+            .iter()
+            .map(|release| (release.name.as_deref(), release.start, release.count))
+            .collect();
+        assert_eq!(
+            releases,
+            [(None, 0, 1), (Some("v1.0"), 1, 2), (Some("v0.1"), 3, 2)]
+        );
+    }
+
+    #[test]
+    fn back_merges() {
+        let log = [
+            entry(
+                7,
+                &[4, 6],
+                "HEAD -> refs/heads/develop",
+                "Merge branch 'feature/x' into develop",
+            ),
+            entry(6, &[5], "refs/heads/feature/x", "Feature"),
+            entry(5, &[3, 4], "", "Merge branch 'develop' into feature/x"),
+            entry(4, &[2], "", "Develop"),
+            entry(3, &[2], "", "Start feature"),
+            entry(2, &[1], "", "Start develop"),
+            entry(1, &[], "refs/heads/main", "Initial commit"),
+        ]
+        .concat();
+        let settings = Settings::default();
+        let source = MemorySource::from_git_log(&log).unwrap();
+        let graph = GitGraph::from_source(source, &settings, None).unwrap();
+
+        let back_merges: Vec<_> = graph
+            .commits
+            .iter()
+            .filter(|info| graph.is_back_merge(info))
+            .map(|info| graph.commit(info.oid).unwrap().summary())
+            .collect();
+        assert_eq!(back_merges, ["Merge branch 'develop' into feature/x"]);
+    }
+
+    #[test]
+    fn worktree_above_head() {
+        // Detached HEAD below the tip of its branch
+        let log = [
+            entry(3, &[2], "refs/heads/main", "Tip"),
+            entry(2, &[1], "HEAD", "Checked out"),
+            entry(1, &[], "", "Initial commit"),
+        ]
+        .concat();
+        let settings = Settings::default();
+        let source = MemorySource::from_git_log(&log).unwrap();
+        let mut graph = GitGraph::from_source(source, &settings, None).unwrap();
+        graph.worktree = Some(WorktreeStatus {
+            staged: 0,
+            unstaged: 1,
+        });
+
+        let (graph_lines, text_lines, index_map) = print_unicode(&graph, &settings).unwrap();
+        let worktree_row = index_map[1] - 1;
+        assert_eq!(
+            text_lines[worktree_row],
+            "Uncommitted changes (0 staged, 1 unstaged)"
+        );
+        assert_eq!(index_map[0], 0);
+        // The line from the tip passes, so the node is drawn in its own column
+        assert_eq!(graph_lines[worktree_row].trim_end(), "│ ◌");
+    }
+
+    #[test]
+    fn release_band_above_worktree() {
+        let log = [
+            entry(3, &[2], "HEAD -> refs/heads/main", "Start 1.1"),
+            entry(2, &[1], "tag: refs/tags/v1.0", "Release 1.0"),
+            entry(1, &[], "", "Initial commit"),
+        ]
+        .concat();
+        let source = MemorySource::from_git_log(&log).unwrap();
+        let mut graph = GitGraph::from_source(source, &Settings::default(), None).unwrap();
+        graph.worktree = Some(WorktreeStatus {
+            staged: 1,
+            unstaged: 0,
+        });
+        let settings = Settings {
+            release_bands: Some(HashMap::from([(
+                graph.commits[0].oid,
+                "Unreleased".to_string(),
+            )])),
+            ..Default::default()
+        };
+
+        let (graph_lines, text_lines, index_map) = print_unicode(&graph, &settings).unwrap();
+        assert_eq!(index_map[0], 2);
+        assert_eq!(text_lines[0], "── Unreleased");
+        assert_eq!(graph_lines[0].trim_end(), "┈");
+        assert_eq!(text_lines[1], "Uncommitted changes (1 staged, 0 unstaged)");
+        assert_eq!(graph_lines[1].trim_end(), "◌");
+    }
+
+    #[test]
+    fn sparse_rows_merged() {
+        let log = [
+            entry(6, &[1], "HEAD -> refs/heads/main", "Hotfix"),
+            entry(5, &[3], "refs/heads/develop", "Develop"),
+            entry(4, &[2], "refs/heads/feature/a", "Feature"),
+            entry(3, &[2, 1], "refs/heads/feature/b", "Merge branch 'main'"),
+            entry(2, &[1], "", "Start"),
+            entry(1, &[], "", "Initial commit"),
+        ]
+        .concat();
+        let settings = Settings {
+            density: DENSITY_SPARSE,
+            ..Default::default()
+        };
+        let source = MemorySource::from_git_log(&log).unwrap();
+        let graph = GitGraph::from_source(source, &settings, None).unwrap();
+
+        let reserved = layout_rows(&graph, &settings, graph.commits.len())
+            .unwrap()
+            .text_lines
+            .len();
+        let (graph_lines, _, index_map) = print_unicode(&graph, &settings).unwrap();
+        assert_eq!(graph_lines.len(), reserved - 1);
+        // The line of the merge and the line from the feature share a row
+        assert_eq!(graph_lines[index_map[3] + 1].trim_end(), "├>┼─┘");
+        assert_eq!(index_map[4], index_map[3] + 2);
+    }
+
+    #[test]
+    fn lint_violations() {
+        let log = [
+            entry(
+                5,
+                &[3, 4],
+                "HEAD -> refs/heads/develop",
+                "Merge branch 'feature/y' into develop",
+            ),
+            entry(4, &[1], "", "Feature from main"),
+            entry(3, &[2], "", "Develop"),
+            entry(2, &[1], "", "Start develop"),
+            entry(1, &[], "refs/heads/main", "Initial commit"),
+        ]
+        .concat();
+        let settings = Settings::default();
+        let source = MemorySource::from_git_log(&log).unwrap();
+        let graph = GitGraph::from_source(source, &settings, None).unwrap();
+
+        let violations: Vec<_> = lint(&graph, &settings)
+            .into_iter()
+            .map(|violation| (violation.branch, violation.commit))
+            .collect();
+        assert_eq!(violations, [("feature/y".to_string(), oid(1))]);
+    }
+}
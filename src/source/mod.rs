@@ -0,0 +1,486 @@
+//! Access to commit data, independent of the underlying repository implementation.
+
+use crate::graph::HeadInfo;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+pub mod anonymize;
+#[cfg(feature = "git2")]
+pub mod git;
+#[cfg(feature = "gix")]
+pub mod gix;
+pub mod memory;
+pub mod range;
+pub mod selection;
+
+pub(crate) const OID_LEN: usize = 20;
+/// Special references written by operations in progress
+const OPERATION_HEADS: [&str; 4] = [
+    "MERGE_HEAD",
+    "REBASE_HEAD",
+    "CHERRY_PICK_HEAD",
+    "REVERT_HEAD",
+];
+
+/// Library for reading Git repositories.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Backend {
+    /// libgit2, via the `git2` crate (feature `git2`)
+    Git2,
+    /// gitoxide, via the pure-Rust `gix` crate (feature `gix`)
+    Gix,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        if cfg!(feature = "git2") {
+            Backend::Git2
+        } else {
+            Backend::Gix
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "git2" | "libgit2" => Ok(Backend::Git2),
+            "gix" | "gitoxide" => Ok(Backend::Gix),
+            _ => Err(format!(
+                "Unknown backend '{}'. Must be one of [git2|gix]",
+                str
+            )),
+        }
+    }
+}
+
+/// Opens the repository at or above `path` with the given backend.
+/// Returns the commit source, and the path of the repository's `.git` directory.
+///
+/// With `use_mailmap`, the repository's mailmap is applied to authors and committers.
+/// Backend `gix` does not support mailmaps, and ignores `use_mailmap`.
+#[cfg_attr(not(any(feature = "git2", feature = "gix")), allow(unused_variables))]
+pub fn open_repository<P: AsRef<Path>>(
+    path: P,
+    backend: Backend,
+    use_mailmap: bool,
+) -> Result<(Box<dyn CommitSource + Send>, PathBuf), String> {
+    match backend {
+        #[cfg(feature = "git2")]
+        Backend::Git2 => {
+            let repository = crate::get_repo(path).map_err(|err| err.message().to_string())?;
+            let git_dir = repository.path().to_path_buf();
+            let source = git::GitSource::new(repository);
+            let source = if use_mailmap {
+                source.with_mailmap()?
+            } else {
+                source
+            };
+            Ok((Box::new(source), git_dir))
+        }
+        #[cfg(feature = "gix")]
+        Backend::Gix => {
+            let _ = use_mailmap;
+            let source = gix::GixSource::discover(path)?;
+            let git_dir = source.repository().git_dir().to_path_buf();
+            Ok((Box::new(source), git_dir))
+        }
+        #[allow(unreachable_patterns)]
+        _ => Err(format!(
+            "Backend {:?} is not available, git-graph was built without it",
+            backend
+        )),
+    }
+}
+
+/// Source of commits and references to build a graph from.
+pub trait CommitSource {
+    /// The ids of the commits to show, in topological order, newest first.
+    /// Stashes should be excluded.
+    fn commits(&self, max_count: Option<usize>) -> Result<Vec<Oid>, String>;
+    /// Branches, tags and bisect references, with full reference names like `refs/heads/main`,
+    /// `refs/remotes/origin/main`, `refs/tags/v1.0` or `refs/bisect/bad`.
+    /// Tags must be resolved to the commit they point to.
+    fn refs(&self) -> Result<Vec<RefData>, String>;
+    /// The current HEAD.
+    fn head(&self) -> Result<HeadInfo, String>;
+    /// Look up a single commit.
+    fn commit(&self, id: Oid) -> Result<CommitData, String>;
+    /// The annotated tag with the given full reference name, like `refs/tags/v1.0`.
+    /// `None` for lightweight tags, or if the source has no tag objects.
+    fn tag(&self, _name: &str) -> Result<Option<TagData>, String> {
+        Ok(None)
+    }
+    /// Commits just outside of a selected range that are shown among `commits` to anchor it,
+    /// like with `git log --boundary`. Empty if the source selects no range.
+    fn boundary(&self) -> Result<Vec<Oid>, String> {
+        Ok(Vec::new())
+    }
+    /// The only commits among `commits` to show, without their ancestors, like with `git log --no-walk`.
+    /// `None` to show all commits.
+    fn listed(&self) -> Result<Option<Vec<Oid>>, String> {
+        Ok(None)
+    }
+    /// Whether `commits` may contain commits that are not reachable from any branch, like a selection of commits.
+    /// These are shown on anonymous branches, instead of being left out.
+    fn unreachable_commits(&self) -> bool {
+        false
+    }
+    /// Length of abbreviated commit hashes configured for the repository (`core.abbrev`).
+    /// `None` to determine it automatically.
+    fn abbrev(&self) -> Result<Option<usize>, String> {
+        Ok(None)
+    }
+    /// Number of commits a local branch (like `main`) is ahead of and behind its configured upstream.
+    /// `None` if the branch has no upstream, or the source does not support upstreams.
+    fn ahead_behind(&self, _branch: &str) -> Result<Option<(usize, usize)>, String> {
+        Ok(None)
+    }
+    /// Up to `max_count` positions of HEAD from its reflog, most recent first, starting with `HEAD@{0}`.
+    /// Empty if the source has no reflog.
+    fn head_reflog(&self, _max_count: usize) -> Result<Vec<Oid>, String> {
+        Ok(Vec::new())
+    }
+    /// The operation the repository is in the middle of, like a merge or a rebase. `None` if there is none.
+    fn operation(&self) -> Result<Option<OperationState>, String> {
+        Ok(None)
+    }
+    /// Number of files with uncommitted changes. `None` if there are none, or the source has no working tree.
+    fn worktree_status(&self) -> Result<Option<WorktreeStatus>, String> {
+        Ok(None)
+    }
+}
+
+impl<S: CommitSource + ?Sized> CommitSource for Box<S> {
+    fn commits(&self, max_count: Option<usize>) -> Result<Vec<Oid>, String> {
+        self.as_ref().commits(max_count)
+    }
+    fn refs(&self) -> Result<Vec<RefData>, String> {
+        self.as_ref().refs()
+    }
+    fn head(&self) -> Result<HeadInfo, String> {
+        self.as_ref().head()
+    }
+    fn commit(&self, id: Oid) -> Result<CommitData, String> {
+        self.as_ref().commit(id)
+    }
+    fn tag(&self, name: &str) -> Result<Option<TagData>, String> {
+        self.as_ref().tag(name)
+    }
+    fn boundary(&self) -> Result<Vec<Oid>, String> {
+        self.as_ref().boundary()
+    }
+    fn listed(&self) -> Result<Option<Vec<Oid>>, String> {
+        self.as_ref().listed()
+    }
+    fn unreachable_commits(&self) -> bool {
+        self.as_ref().unreachable_commits()
+    }
+    fn abbrev(&self) -> Result<Option<usize>, String> {
+        self.as_ref().abbrev()
+    }
+    fn ahead_behind(&self, branch: &str) -> Result<Option<(usize, usize)>, String> {
+        self.as_ref().ahead_behind(branch)
+    }
+    fn head_reflog(&self, max_count: usize) -> Result<Vec<Oid>, String> {
+        self.as_ref().head_reflog(max_count)
+    }
+    fn operation(&self) -> Result<Option<OperationState>, String> {
+        self.as_ref().operation()
+    }
+    fn worktree_status(&self) -> Result<Option<WorktreeStatus>, String> {
+        self.as_ref().worktree_status()
+    }
+}
+
+/// Reads the special references of operations in progress, like `MERGE_HEAD`, from the `.git` directory.
+/// Merges of several branches have multiple entries for `MERGE_HEAD`.
+#[cfg_attr(not(any(feature = "git2", feature = "gix")), allow(dead_code))]
+fn read_operation_heads(git_dir: &Path) -> Vec<RefData> {
+    let mut heads = Vec::new();
+    for name in OPERATION_HEADS {
+        if let Ok(content) = std::fs::read_to_string(git_dir.join(name)) {
+            for line in content.lines() {
+                // Lines of `MERGE_HEAD` may be followed by a description
+                let hash = line.split_whitespace().next().unwrap_or("");
+                if let Ok(target) = Oid::from_str(hash) {
+                    heads.push(RefData {
+                        name: name.to_string(),
+                        target,
+                    });
+                }
+            }
+        }
+    }
+    heads
+}
+
+/// Names of the Latin-1 encoding in the `encoding` header of commits, in lower case.
+const LATIN1: [&str; 5] = ["iso-8859-1", "iso8859-1", "latin1", "latin-1", "l1"];
+
+/// Decodes text of a commit, like its message or author, given the commit's `encoding` header.
+///
+/// Latin-1, the most common legacy encoding, is decoded exactly. Other encodings are expected
+/// to be UTF-8, with invalid bytes replaced by `U+FFFD`, so that such commits still render.
+#[cfg_attr(not(any(feature = "git2", feature = "gix")), allow(dead_code))]
+fn decode_text(bytes: &[u8], encoding: Option<&str>) -> String {
+    match encoding {
+        Some(encoding) if LATIN1.contains(&&encoding.to_lowercase()[..]) => {
+            bytes.iter().map(|byte| *byte as char).collect()
+        }
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Parses a value of Git's `core.abbrev` setting into a hash length. Returns `None` for `auto`.
+#[cfg_attr(not(any(feature = "git2", feature = "gix")), allow(dead_code))]
+fn parse_abbrev(value: &str) -> Result<Option<usize>, String> {
+    match value.trim() {
+        "auto" => Ok(None),
+        "no" | "false" | "off" => Ok(Some(OID_LEN * 2)),
+        len => len
+            .parse::<usize>()
+            .map(|len| Some(len.clamp(4, OID_LEN * 2)))
+            .map_err(|_| format!("Invalid value for core.abbrev: '{}'", len)),
+    }
+}
+
+/// A commit id (SHA-1 hash).
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Oid([u8; OID_LEN]);
+
+/// Map from commit ids to indices, e.g. into a list of commits, for building graphs.
+pub(crate) type OidIndex = HashMap<Oid, usize, BuildHasherDefault<OidHasher>>;
+
+/// Hasher for maps keyed by commit ids, which are hashes already and need no further hashing.
+#[derive(Default)]
+pub(crate) struct OidHasher(u64);
+
+impl Hasher for OidHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut buffer = [0; 8];
+            buffer[..chunk.len()].copy_from_slice(chunk);
+            self.write_u64(u64::from_le_bytes(buffer));
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.0 = self.0.rotate_left(5) ^ value;
+    }
+}
+
+impl Oid {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let bytes = bytes
+            .try_into()
+            .map_err(|_| format!("Invalid object id length: {}", bytes.len()))?;
+        Ok(Oid(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl FromStr for Oid {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        if str.len() != 2 * OID_LEN || !str.is_ascii() {
+            return Err(format!("Invalid object id: '{}'", str));
+        }
+        let mut bytes = [0; OID_LEN];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&str[2 * i..2 * i + 2], 16)
+                .map_err(|_| format!("Invalid object id: '{}'", str))?;
+        }
+        Ok(Oid(bytes))
+    }
+}
+
+impl fmt::Display for Oid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Oid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Oid({})", self)
+    }
+}
+
+impl Serialize for Oid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Oid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// A commit's data, as needed for layout and formatting.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CommitData {
+    pub id: Oid,
+    pub parents: Vec<Oid>,
+    /// The full commit message
+    pub message: String,
+    pub author: Signature,
+    pub committer: Signature,
+    /// The commit's tree, if known
+    #[serde(default)]
+    pub tree: Option<Oid>,
+    /// The encoding of the message from the commit's header, if any
+    #[serde(default)]
+    pub encoding: Option<String>,
+}
+
+impl CommitData {
+    /// The first paragraph of the message, with line breaks replaced by spaces (like `git log --format=%s`).
+    pub fn summary(&self) -> String {
+        let paragraph = self.message.trim_start().split("\n\n").next().unwrap_or("");
+        let mut summary = String::with_capacity(paragraph.len());
+        let mut space_start = None;
+        for (idx, char) in paragraph.char_indices() {
+            if char.is_whitespace() {
+                space_start.get_or_insert(idx);
+            } else {
+                if let Some(start) = space_start.take() {
+                    let space = &paragraph[start..idx];
+                    summary.push_str(if space.contains('\n') { " " } else { space });
+                }
+                summary.push(char);
+            }
+        }
+        summary
+    }
+
+    /// Whether the committer differs from the author, by name or email, like for commits
+    /// rebased or cherry-picked by someone else.
+    pub fn committer_differs(&self) -> bool {
+        self.author.name != self.committer.name || self.author.email != self.committer.email
+    }
+}
+
+/// Author or committer of a commit.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    /// Seconds since the Unix epoch
+    pub time: i64,
+    /// Time zone offset in minutes
+    #[serde(default)]
+    pub offset: i32,
+}
+
+/// A branch or tag.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RefData {
+    /// Full reference name, like `refs/heads/main`
+    pub name: String,
+    /// The commit the reference points to
+    pub target: Oid,
+}
+
+/// The object of an annotated tag.
+#[derive(Clone)]
+pub struct TagData {
+    /// Who created the tag, if recorded
+    pub tagger: Option<Signature>,
+    /// The tag's message, or annotation
+    pub message: String,
+}
+
+/// An operation the repository can be in the middle of.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Operation {
+    Merge,
+    Rebase,
+    CherryPick,
+    Revert,
+    Bisect,
+    /// Applying patches from a mailbox (`git am`)
+    ApplyMailbox,
+}
+
+/// An operation in progress, with the commits it refers to.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OperationState {
+    pub operation: Operation,
+    /// Special references like `MERGE_HEAD` or `REBASE_HEAD`
+    pub heads: Vec<RefData>,
+}
+
+impl OperationState {
+    /// A short description, like `Merge in progress`.
+    pub fn banner(&self) -> String {
+        let name = match self.operation {
+            Operation::Merge => "Merge",
+            Operation::Rebase => "Rebase",
+            Operation::CherryPick => "Cherry-pick",
+            Operation::Revert => "Revert",
+            Operation::Bisect => "Bisect",
+            Operation::ApplyMailbox => "Applying patches",
+        };
+        format!("{} in progress", name)
+    }
+}
+
+/// Number of files with uncommitted changes.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct WorktreeStatus {
+    /// Files with changes in the index
+    pub staged: usize,
+    /// Files with changes in the working tree, including untracked files
+    pub unstaged: usize,
+}
+
+impl WorktreeStatus {
+    /// A short description, like `Uncommitted changes (2 staged, 1 unstaged)`.
+    pub fn summary(&self) -> String {
+        format!(
+            "Uncommitted changes ({} staged, {} unstaged)",
+            self.staged, self.unstaged
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_text;
+
+    #[test]
+    fn decode_invalid_text() {
+        assert_eq!(decode_text("Grüße".as_bytes(), None), "Grüße");
+        assert_eq!(decode_text(b"Gr\xfc\xdfe", Some("ISO-8859-1")), "Grüße");
+        assert_eq!(decode_text(b"Gr\xfc\xdfe", None), "Gr\u{fffd}\u{fffd}e");
+        assert_eq!(
+            decode_text(b"Gr\xfc\xdfe", Some("UTF-8")),
+            "Gr\u{fffd}\u{fffd}e"
+        );
+    }
+}
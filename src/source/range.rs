@@ -0,0 +1,283 @@
+//! Commit source restricted to the commits between two references, like `git log <from>..<to>`,
+//! or to a list of commits, like `git log --no-walk`.
+
+use crate::graph::{BisectInfo, HeadInfo};
+use crate::source::{
+    CommitData, CommitSource, Oid, OperationState, RefData, TagData, WorktreeStatus,
+};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// Commit source with the commits reachable from one reference, but not from another.
+pub struct RangeSource<S: CommitSource> {
+    source: S,
+    /// Commits of the range and boundary commits, with whether they are boundary commits
+    commits: Vec<(Oid, bool)>,
+    /// Whether to include boundary commits
+    boundary: bool,
+    /// The only commits to show, if given
+    listed: Option<Vec<Oid>>,
+}
+
+impl<S: CommitSource> RangeSource<S> {
+    /// Selects the commits reachable from `to`, but not from `from`.
+    /// References are given by name, like `v1.0`, `tags/v1.0` or `main`, or as full commit hash.
+    pub fn new(source: S, from: &str, to: &str) -> Result<Self, String> {
+        let refs = source.refs()?;
+        let from = resolve(&refs, from)?;
+        let to = resolve(&refs, to)?;
+        Self::between(source, &[from], to)
+    }
+
+    /// Selects the commits of the bisect in progress: reachable from the bad commit, but not from any good one.
+    pub fn bisect(source: S) -> Result<Self, String> {
+        let bisect = BisectInfo::from_refs(&source.refs()?)
+            .ok_or_else(|| "No bisect in progress".to_string())?;
+        let bad = bisect
+            .bad
+            .ok_or_else(|| "No bad commit marked in the bisect yet".to_string())?;
+        Self::between(source, &bisect.good, bad)
+    }
+
+    /// Shows only the given commits, without their ancestors, like `git log --no-walk`.
+    /// Revisions are given like for `new`, or as abbreviated commit hashes.
+    ///
+    /// All commits are kept for the layout, so that the listed commits are assigned to the same branches
+    /// as in the complete graph.
+    pub fn listed(source: S, revisions: &[String]) -> Result<Self, String> {
+        let all = source.commits(None)?;
+        let refs = source.refs()?;
+        let listed = revisions
+            .iter()
+            .map(|revision| resolve_revision(&refs, &all, revision))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(RangeSource {
+            source,
+            commits: all.into_iter().map(|oid| (oid, false)).collect(),
+            boundary: false,
+            listed: Some(listed),
+        })
+    }
+
+    /// Selects the commits reachable from `to`, but not from any of `from`.
+    fn between(source: S, from: &[Oid], to: Oid) -> Result<Self, String> {
+        let all = source.commits(None)?;
+        let mut reachable_to = HashSet::from([to]);
+        let mut reachable_from: HashSet<_> = from.iter().cloned().collect();
+        let mut range_parents = HashSet::new();
+        // Commits are in topological order, so children are visited before their parents
+        for oid in &all {
+            let (in_to, in_from) = (reachable_to.contains(oid), reachable_from.contains(oid));
+            if in_to || in_from {
+                for parent in source.commit(*oid)?.parents {
+                    if in_to {
+                        reachable_to.insert(parent);
+                    }
+                    if in_from {
+                        reachable_from.insert(parent);
+                    }
+                    if in_to && !in_from {
+                        range_parents.insert(parent);
+                    }
+                }
+            }
+        }
+
+        let commits = all
+            .into_iter()
+            .filter_map(
+                |oid| match (reachable_to.contains(&oid), reachable_from.contains(&oid)) {
+                    (true, false) => Some((oid, false)),
+                    (_, true) if range_parents.contains(&oid) => Some((oid, true)),
+                    _ => None,
+                },
+            )
+            .collect();
+        Ok(RangeSource {
+            source,
+            commits,
+            boundary: false,
+            listed: None,
+        })
+    }
+
+    /// Includes the boundary commits, i.e. the parents of commits in the range that are not in the range,
+    /// like `git log --boundary`.
+    pub fn with_boundary(mut self, boundary: bool) -> Self {
+        self.boundary = boundary;
+        self
+    }
+}
+
+impl<S: CommitSource> CommitSource for RangeSource<S> {
+    fn commits(&self, max_count: Option<usize>) -> Result<Vec<Oid>, String> {
+        let count = max_count.unwrap_or(self.commits.len());
+        Ok(self
+            .commits
+            .iter()
+            .filter(|(_, boundary)| self.boundary || !boundary)
+            .map(|(oid, _)| *oid)
+            .take(count)
+            .collect())
+    }
+
+    fn refs(&self) -> Result<Vec<RefData>, String> {
+        let commits: HashSet<_> = self.commits(None)?.into_iter().collect();
+        Ok(self
+            .source
+            .refs()?
+            .into_iter()
+            .filter(|rf| commits.contains(&rf.target))
+            .collect())
+    }
+
+    fn head(&self) -> Result<HeadInfo, String> {
+        self.source.head()
+    }
+
+    fn tag(&self, name: &str) -> Result<Option<TagData>, String> {
+        self.source.tag(name)
+    }
+
+    fn boundary(&self) -> Result<Vec<Oid>, String> {
+        if self.boundary {
+            Ok(self
+                .commits
+                .iter()
+                .filter(|(_, boundary)| *boundary)
+                .map(|(oid, _)| *oid)
+                .collect())
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    fn listed(&self) -> Result<Option<Vec<Oid>>, String> {
+        Ok(self.listed.clone())
+    }
+
+    fn commit(&self, id: Oid) -> Result<CommitData, String> {
+        self.source.commit(id)
+    }
+
+    fn abbrev(&self) -> Result<Option<usize>, String> {
+        self.source.abbrev()
+    }
+
+    fn ahead_behind(&self, branch: &str) -> Result<Option<(usize, usize)>, String> {
+        self.source.ahead_behind(branch)
+    }
+
+    fn head_reflog(&self, max_count: usize) -> Result<Vec<Oid>, String> {
+        self.source.head_reflog(max_count)
+    }
+
+    fn operation(&self) -> Result<Option<OperationState>, String> {
+        self.source.operation()
+    }
+
+    fn worktree_status(&self) -> Result<Option<WorktreeStatus>, String> {
+        self.source.worktree_status()
+    }
+}
+
+/// Finds the commit a reference name or commit hash points to.
+fn resolve(refs: &[RefData], name: &str) -> Result<Oid, String> {
+    ["", "refs/tags/", "refs/heads/", "refs/remotes/", "refs/"]
+        .iter()
+        .find_map(|prefix| {
+            refs.iter()
+                .find(|rf| rf.name.strip_prefix(prefix) == Some(name))
+                .map(|rf| rf.target)
+        })
+        .or_else(|| Oid::from_str(name).ok())
+        .ok_or_else(|| format!("Reference '{}' not found", name))
+}
+
+/// Finds the commit of a reference name, a commit hash, or an abbreviated hash among `commits`.
+pub(crate) fn resolve_revision(
+    refs: &[RefData],
+    commits: &[Oid],
+    revision: &str,
+) -> Result<Oid, String> {
+    match resolve(refs, revision) {
+        Ok(oid) => Ok(oid),
+        Err(err) => resolve_abbreviated(commits, revision).ok_or(err),
+    }
+}
+
+/// Finds the only commit whose hash starts with an abbreviated hash of at least 4 digits.
+fn resolve_abbreviated(commits: &[Oid], prefix: &str) -> Option<Oid> {
+    if prefix.len() < 4 || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let prefix = prefix.to_ascii_lowercase();
+    let mut matches = commits
+        .iter()
+        .filter(|oid| oid.to_string().starts_with(&prefix));
+    match (matches.next(), matches.next()) {
+        (Some(oid), None) => Some(*oid),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RangeSource;
+    use crate::source::memory::{entry, oid, MemorySource};
+    use crate::source::CommitSource;
+
+    #[test]
+    fn boundary_commits() {
+        let log = [
+            entry(5, &[3, 4], "HEAD -> refs/heads/main, tag: refs/tags/v2", ""),
+            entry(4, &[1], "", ""),
+            entry(3, &[2], "", ""),
+            entry(2, &[1], "tag: refs/tags/v1", ""),
+            entry(1, &[], "", ""),
+        ]
+        .concat();
+        let range = |boundary| {
+            let source = MemorySource::from_git_log(&log).unwrap();
+            RangeSource::new(source, "v1", "v2")
+                .unwrap()
+                .with_boundary(boundary)
+        };
+
+        let range_only = range(false);
+        assert_eq!(range_only.commits(None).unwrap(), [oid(5), oid(4), oid(3)]);
+        assert!(range_only.boundary().unwrap().is_empty());
+        assert_eq!(range_only.refs().unwrap().len(), 2);
+
+        let with_boundary = range(true);
+        assert_eq!(
+            with_boundary.commits(None).unwrap(),
+            [oid(5), oid(4), oid(3), oid(2), oid(1)]
+        );
+        assert_eq!(with_boundary.boundary().unwrap(), [oid(2), oid(1)]);
+        assert_eq!(with_boundary.refs().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn listed_commits() {
+        let log = [
+            entry(3, &[2], "HEAD -> refs/heads/main", ""),
+            entry(2, &[1], "tag: refs/tags/v1", ""),
+            entry(1, &[], "", ""),
+        ]
+        .concat();
+        let listed = |revisions: &[&str]| {
+            let source = MemorySource::from_git_log(&log).unwrap();
+            let revisions: Vec<_> = revisions.iter().map(|rev| rev.to_string()).collect();
+            RangeSource::listed(source, &revisions)
+        };
+
+        let full = format!("{:040x}", 1);
+        let source = listed(&[&full, "main"]).unwrap();
+        assert_eq!(source.listed().unwrap(), Some(vec![oid(1), oid(3)]));
+        assert_eq!(source.commits(None).unwrap().len(), 3);
+        // Ambiguous abbreviated hash, and unknown reference
+        assert!(listed(&["0000"]).is_err());
+        assert!(listed(&["develop"]).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+//! Commit source restricted to an arbitrary set of commits, like the output of `git rev-list`.
+
+use crate::graph::HeadInfo;
+use crate::source::range::resolve_revision;
+use crate::source::{
+    CommitData, CommitSource, Oid, OperationState, RefData, TagData, WorktreeStatus,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Commit source with only the selected commits, laid out as a history of their own.
+///
+/// Parents are rewritten to the nearest selected ancestors, like with `git log --graph` and a filter.
+/// Branches pointing to commits that are not selected are moved to their nearest selected
+/// first-parent ancestor, so that the selected commits are assigned to branches as usual.
+/// Commits that are still not reachable from any branch, like those of merged branches, are shown on anonymous branches.
+pub struct SelectionSource<S: CommitSource> {
+    source: S,
+    /// The selected commits, in the order of the source
+    commits: Vec<Oid>,
+    /// Parents of the selected commits, rewritten to their nearest selected ancestors
+    parents: HashMap<Oid, Vec<Oid>>,
+    /// References to selected commits, with moved branches
+    refs: Vec<RefData>,
+}
+
+impl<S: CommitSource> SelectionSource<S> {
+    /// Selects the given commits. Revisions are given like for `RangeSource::new`, or as abbreviated commit hashes.
+    pub fn new(source: S, revisions: &[String]) -> Result<Self, String> {
+        let all = source.commits(None)?;
+        let refs = source.refs()?;
+        let selected = revisions
+            .iter()
+            .map(|revision| resolve_revision(&refs, &all, revision))
+            .collect::<Result<HashSet<_>, String>>()?;
+
+        // The nearest selected commit on the first-parent line of each commit.
+        // Commits are in topological order, so parents are visited before their children in reverse.
+        let mut nearest = HashMap::new();
+        let mut parents = HashMap::new();
+        for oid in all.iter().rev() {
+            let commit_parents = source.commit(*oid)?.parents;
+            if selected.contains(oid) {
+                let mut rewritten = vec![];
+                for parent in commit_parents.iter().filter_map(|par| nearest.get(par)) {
+                    if !rewritten.contains(parent) {
+                        rewritten.push(*parent);
+                    }
+                }
+                parents.insert(*oid, rewritten);
+                nearest.insert(*oid, *oid);
+            } else if let Some(parent) = commit_parents.first().and_then(|par| nearest.get(par)) {
+                nearest.insert(*oid, *parent);
+            }
+        }
+
+        let refs = refs
+            .into_iter()
+            .filter_map(|rf| {
+                let is_branch =
+                    rf.name.starts_with("refs/heads/") || rf.name.starts_with("refs/remotes/");
+                if selected.contains(&rf.target) {
+                    Some(rf)
+                } else if is_branch {
+                    let target = *nearest.get(&rf.target)?;
+                    Some(RefData { target, ..rf })
+                } else {
+                    None
+                }
+            })
+            .collect();
+        let commits = all
+            .into_iter()
+            .filter(|oid| selected.contains(oid))
+            .collect();
+        Ok(SelectionSource {
+            source,
+            commits,
+            parents,
+            refs,
+        })
+    }
+
+    /// Drops the rewritten parents of merges that are reachable from the first parent,
+    /// like those of merged branches without selected commits, which would only duplicate lines.
+    pub fn with_simplified_merges(mut self) -> Self {
+        let index: HashMap<Oid, usize> = self
+            .commits
+            .iter()
+            .enumerate()
+            .map(|(idx, oid)| (*oid, idx))
+            .collect();
+        let mut simplified = HashMap::new();
+        for (oid, parents) in self.parents.iter().filter(|(_, parents)| parents.len() > 1) {
+            let mut kept = vec![parents[0]];
+            for parent in &parents[1..] {
+                if !self.reaches(parents[0], *parent, &index) {
+                    kept.push(*parent);
+                }
+            }
+            simplified.insert(*oid, kept);
+        }
+        self.parents.extend(simplified);
+        self
+    }
+
+    /// Whether `target` is an ancestor of `from` (or `from` itself), by the rewritten parents.
+    /// Commits are in topological order, so commits after the target in `index` are not searched.
+    fn reaches(&self, from: Oid, target: Oid, index: &HashMap<Oid, usize>) -> bool {
+        let target_idx = index[&target];
+        let mut visited = HashSet::new();
+        let mut stack = vec![from];
+        while let Some(oid) = stack.pop() {
+            if oid == target {
+                return true;
+            }
+            if index[&oid] < target_idx && visited.insert(oid) {
+                stack.extend(self.parents.get(&oid).into_iter().flatten());
+            }
+        }
+        false
+    }
+}
+
+impl<S: CommitSource> CommitSource for SelectionSource<S> {
+    fn commits(&self, max_count: Option<usize>) -> Result<Vec<Oid>, String> {
+        let count = max_count.unwrap_or(self.commits.len());
+        Ok(self.commits.iter().take(count).cloned().collect())
+    }
+
+    fn refs(&self) -> Result<Vec<RefData>, String> {
+        Ok(self.refs.clone())
+    }
+
+    fn head(&self) -> Result<HeadInfo, String> {
+        self.source.head()
+    }
+
+    fn unreachable_commits(&self) -> bool {
+        true
+    }
+
+    fn commit(&self, id: Oid) -> Result<CommitData, String> {
+        let mut commit = self.source.commit(id)?;
+        if let Some(parents) = self.parents.get(&id) {
+            commit.parents = parents.clone();
+        }
+        Ok(commit)
+    }
+
+    fn tag(&self, name: &str) -> Result<Option<TagData>, String> {
+        self.source.tag(name)
+    }
+
+    fn abbrev(&self) -> Result<Option<usize>, String> {
+        self.source.abbrev()
+    }
+
+    fn ahead_behind(&self, branch: &str) -> Result<Option<(usize, usize)>, String> {
+        self.source.ahead_behind(branch)
+    }
+
+    fn head_reflog(&self, max_count: usize) -> Result<Vec<Oid>, String> {
+        self.source.head_reflog(max_count)
+    }
+
+    fn operation(&self) -> Result<Option<OperationState>, String> {
+        self.source.operation()
+    }
+
+    fn worktree_status(&self) -> Result<Option<WorktreeStatus>, String> {
+        self.source.worktree_status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SelectionSource;
+    use crate::source::memory::{entry, oid, MemorySource};
+    use crate::source::CommitSource;
+
+    #[test]
+    fn rewritten_parents() {
+        let log = [
+            entry(6, &[5], "HEAD -> refs/heads/main", ""),
+            entry(5, &[3, 4], "", ""),
+            entry(4, &[2], "refs/heads/feature, tag: refs/tags/v2", ""),
+            entry(3, &[2], "", ""),
+            entry(2, &[1], "tag: refs/tags/v1", ""),
+            entry(1, &[], "", ""),
+        ]
+        .concat();
+        let source = MemorySource::from_git_log(&log).unwrap();
+        let revisions: Vec<_> = [5, 3, 1].iter().map(|id| format!("{:040x}", id)).collect();
+        let selection = SelectionSource::new(source, &revisions).unwrap();
+
+        assert_eq!(selection.commits(None).unwrap(), [oid(5), oid(3), oid(1)]);
+        assert_eq!(selection.commit(oid(5)).unwrap().parents, [oid(3), oid(1)]);
+        assert_eq!(selection.commit(oid(3)).unwrap().parents, [oid(1)]);
+
+        let mut refs: Vec<_> = selection
+            .refs()
+            .unwrap()
+            .into_iter()
+            .map(|rf| (rf.name, rf.target))
+            .collect();
+        refs.sort();
+        assert_eq!(
+            refs,
+            [
+                ("refs/heads/feature".to_string(), oid(1)),
+                ("refs/heads/main".to_string(), oid(5)),
+            ]
+        );
+
+        let selection = selection.with_simplified_merges();
+        assert_eq!(selection.commit(oid(5)).unwrap().parents, [oid(3)]);
+    }
+}
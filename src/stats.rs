@@ -0,0 +1,177 @@
+//! Per-branch metrics, like lifetime and number of merges.
+
+use crate::graph::GitGraph;
+use chrono::{TimeZone, Utc};
+use serde_derive::Serialize;
+use std::fmt::Write;
+
+/// Number of branches listed as longest-lived unmerged branches.
+const MAX_UNMERGED: usize = 5;
+
+/// Metrics of a single branch.
+#[derive(Serialize)]
+pub struct BranchStats {
+    pub name: String,
+    /// Number of commits traced to the branch
+    pub commits: usize,
+    /// Commit time of the branch's first commit, in seconds since the Unix epoch
+    pub first: i64,
+    /// Commit time of the branch's last commit, in seconds since the Unix epoch
+    pub last: i64,
+    /// Number of merges into more persistent branches
+    pub merges: usize,
+    /// Whether any commit of the branch was merged into another branch
+    pub merged: bool,
+    /// Whether the branch belongs to the most persistent group (e.g. `main`), which is usually not merged
+    pub persistent: bool,
+}
+
+impl BranchStats {
+    /// Time between the first and the last commit, in days.
+    pub fn lifetime_days(&self) -> f64 {
+        (self.last - self.first) as f64 / 86400.0
+    }
+}
+
+/// Computes metrics for all branches with commits in the graph, excluding tags.
+pub fn branch_stats(graph: &GitGraph) -> Result<Vec<BranchStats>, String> {
+    let mut stats: Vec<Option<BranchStats>> = graph.all_branches.iter().map(|_| None).collect();
+
+    for info in &graph.commits {
+        let trace = match info.branch_trace {
+            Some(trace) if !graph.all_branches[trace].is_tag => trace,
+            _ => continue,
+        };
+        let time = graph.commit(info.oid)?.committer.time;
+        let branch = &graph.all_branches[trace];
+        let entry = stats[trace].get_or_insert_with(|| BranchStats {
+            name: branch.name.to_string(),
+            commits: 0,
+            first: time,
+            last: time,
+            merges: 0,
+            merged: false,
+            persistent: branch.persistence == 0,
+        });
+        entry.commits += 1;
+        entry.first = entry.first.min(time);
+        entry.last = entry.last.max(time);
+    }
+
+    for info in graph.commits.iter().filter(|info| info.is_merge) {
+        let source = info.parents[1]
+            .and_then(|oid| graph.indices.get(&oid))
+            .and_then(|idx| graph.commits[*idx].branch_trace);
+        if let (Some(source), Some(target)) = (source, info.branch_trace) {
+            if let Some(entry) = &mut stats[source] {
+                entry.merged = true;
+                if graph.all_branches[target].persistence < graph.all_branches[source].persistence {
+                    entry.merges += 1;
+                }
+            }
+        }
+    }
+
+    Ok(stats.into_iter().flatten().collect())
+}
+
+/// Creates a table of branch metrics, followed by the longest-lived unmerged branches.
+pub fn print_stats(stats: &[BranchStats]) -> String {
+    let name_width = stats
+        .iter()
+        .map(|s| s.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Branch".len());
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{:<width$}  {:>7}  {:>6}  {:<10}  {:<10}  {:>8}",
+        "Branch",
+        "Commits",
+        "Merges",
+        "First",
+        "Last",
+        "Days",
+        width = name_width
+    )
+    .unwrap();
+    for s in stats {
+        writeln!(
+            out,
+            "{:<width$}  {:>7}  {:>6}  {:<10}  {:<10}  {:>8.1}",
+            s.name,
+            s.commits,
+            s.merges,
+            format_date(s.first),
+            format_date(s.last),
+            s.lifetime_days(),
+            width = name_width
+        )
+        .unwrap();
+    }
+
+    let mut unmerged: Vec<_> = stats
+        .iter()
+        .filter(|s| !s.merged && !s.persistent)
+        .collect();
+    if !unmerged.is_empty() {
+        unmerged.sort_by(|a, b| b.lifetime_days().total_cmp(&a.lifetime_days()));
+        writeln!(out, "\nLongest-lived unmerged branches:").unwrap();
+        for s in unmerged.iter().take(MAX_UNMERGED) {
+            writeln!(out, "    {} ({:.1} days)", s.name, s.lifetime_days()).unwrap();
+        }
+    }
+    out
+}
+
+fn format_date(time: i64) -> String {
+    Utc.timestamp(time, 0).format("%Y-%m-%d").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{branch_stats, print_stats};
+    use crate::graph::GitGraph;
+    use crate::settings::Settings;
+    use crate::source::memory::{entry_with_times, MemorySource};
+
+    /// A commit committed on day `id` after the fixtures' epoch.
+    fn entry(id: u8, parents: &[u8], decorations: &str, message: &str) -> String {
+        let time = i64::from(id) * 86400;
+        entry_with_times(id, parents, decorations, message, (time, time))
+    }
+
+    #[test]
+    fn stats_table() {
+        let log = [
+            entry(6, &[5], "refs/heads/feature/b", "Start feature b"),
+            entry(
+                5,
+                &[2, 4],
+                "HEAD -> refs/heads/main",
+                "Merge branch 'feature/a'",
+            ),
+            entry(4, &[3], "", "Feature a"),
+            entry(3, &[1], "", "Start feature a"),
+            entry(2, &[1], "", "Fix"),
+            entry(1, &[], "", "Initial commit"),
+        ]
+        .concat();
+        let settings = Settings::default();
+        let source = MemorySource::from_git_log(&log).unwrap();
+        let graph = GitGraph::from_source(source, &settings, None).unwrap();
+
+        let stats = branch_stats(&graph).unwrap();
+        assert_eq!(
+            print_stats(&stats),
+            "Branch     Commits  Merges  First       Last            Days\n\
+             main             3       0  2020-09-14  2020-09-18       4.0\n\
+             feature/a        2       1  2020-09-16  2020-09-17       1.0\n\
+             feature/b        1       0  2020-09-19  2020-09-19       0.0\n\
+             \n\
+             Longest-lived unmerged branches:\n    feature/b (0.0 days)\n"
+        );
+    }
+}
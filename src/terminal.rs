@@ -0,0 +1,91 @@
+//! Detection of what the terminal can display, like colors and box-drawing characters.
+
+/// Values of `TERM` for terminals without box-drawing characters.
+#[cfg(not(windows))]
+const ASCII_TERMINALS: [&str; 6] = ["dumb", "vt52", "vt100", "vt102", "vt220", "ansi"];
+
+/// Capabilities of the terminal connected to stdout, or another stream.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Capabilities {
+    /// Stdout is a terminal, and not redirected to a file or pipe
+    pub is_terminal: bool,
+    /// ANSI escape sequences, e.g. for colors, are interpreted
+    pub ansi: bool,
+    /// Box-drawing and other non-ASCII characters can be displayed
+    pub unicode: bool,
+}
+
+impl Capabilities {
+    /// Detects the capabilities of the terminal. Enables ANSI escape sequences on Windows consoles.
+    pub fn detect() -> Self {
+        Self::detect_on(atty::Stream::Stdout)
+    }
+
+    /// Detects the capabilities of the terminal connected to the given stream, like stderr for pickers.
+    pub fn detect_on(stream: atty::Stream) -> Self {
+        let is_terminal = atty::is(stream);
+        Capabilities {
+            is_terminal,
+            ansi: is_terminal && enable_ansi(),
+            unicode: supports_unicode(),
+        }
+    }
+}
+
+/// Enables ANSI escape sequences if required, and returns whether they are supported.
+///
+/// Windows Terminal, ConPTY and consoles of Windows 10 and later support them after enabling
+/// virtual terminal processing. Terminals like mintty (Git Bash, MSYS2, Cygwin) set `TERM` instead.
+#[cfg(windows)]
+pub fn enable_ansi() -> bool {
+    crossterm::ansi_support::supports_ansi()
+}
+
+/// Enables ANSI escape sequences if required, and returns whether they are supported.
+#[cfg(not(windows))]
+pub fn enable_ansi() -> bool {
+    !matches!(std::env::var("TERM"), Ok(term) if term == "dumb")
+}
+
+/// Whether the terminal can display box-drawing characters.
+///
+/// Windows Terminal, mintty and ConEmu render them with any console code page. Legacy consoles
+/// only do so with the UTF-8 code page, as their default raster fonts lack most of the glyphs.
+#[cfg(windows)]
+fn supports_unicode() -> bool {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetConsoleOutputCP() -> u32;
+    }
+    const CP_UTF8: u32 = 65001;
+
+    let modern = ["WT_SESSION", "TERM", "TERM_PROGRAM", "ConEmuANSI"]
+        .iter()
+        .any(|var| std::env::var_os(var).is_some());
+    // SAFETY: The function takes no arguments and only reads the code page of the console.
+    modern || unsafe { GetConsoleOutputCP() } == CP_UTF8
+}
+
+/// Whether the terminal can display box-drawing characters, according to `TERM` and the locale.
+///
+/// If no locale is set, UTF-8 is assumed, except in SSH sessions and on CI servers,
+/// which often lack the locale of the client or the log viewer.
+#[cfg(not(windows))]
+fn supports_unicode() -> bool {
+    if matches!(std::env::var("TERM"), Ok(term) if ASCII_TERMINALS.contains(&&term[..])) {
+        return false;
+    }
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty());
+    match locale {
+        Some(locale) => {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        }
+        None => ["SSH_CONNECTION", "CI"]
+            .iter()
+            .all(|var| std::env::var_os(var).is_none()),
+    }
+}
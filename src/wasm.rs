@@ -0,0 +1,86 @@
+//! JavaScript bindings, e.g. for running the layout engine in a browser.
+//!
+//! Requires feature `wasm`, and can be built without a Git backend:
+//!
+//! ```text
+//! wasm-pack build --target web --no-default-features --features wasm
+//! ```
+//!
+//! Commit data is passed as JSON, in the format of `source::memory::MemorySourceDef`:
+//!
+//! ```json
+//! {
+//!   "head": { "oid": "<hash>", "name": "main", "is_branch": true },
+//!   "refs": [ { "name": "refs/heads/main", "target": "<hash>" } ],
+//!   "commits": [
+//!     {
+//!       "id": "<hash>",
+//!       "parents": ["<hash>"],
+//!       "message": "Commit message",
+//!       "author": { "name": "A. U. Thor", "email": "author@example.com", "time": 1600000000 },
+//!       "committer": { "name": "A. U. Thor", "email": "author@example.com", "time": 1600000000 }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Branching models are given by name (`git-flow`, `gitlab-flow`, `simple`, `trunk` or `none`),
+//! or as the content of a model file in TOML format.
+
+use crate::graph::GitGraph;
+use crate::print::json::print_json;
+use crate::print::svg::print_svg;
+use crate::print::unicode::print_unicode;
+use crate::settings::{BranchSettings, BranchSettingsDef, Settings};
+use crate::source::memory::MemorySource;
+use wasm_bindgen::prelude::*;
+
+/// Renders the graph as SVG.
+#[wasm_bindgen]
+pub fn render_svg(data: &str, model: &str) -> Result<String, JsValue> {
+    build(data, model)
+        .and_then(|(graph, settings)| print_svg(&graph, &settings))
+        .map_err(|err| JsValue::from_str(&err))
+}
+
+/// Renders the graph as plain text, one line per row.
+#[wasm_bindgen]
+pub fn render_text(data: &str, model: &str) -> Result<String, JsValue> {
+    build(data, model)
+        .and_then(|(graph, settings)| {
+            let (g_lines, t_lines, _) = print_unicode(&graph, &settings)?;
+            Ok(g_lines
+                .iter()
+                .zip(t_lines.iter())
+                .map(|(g_line, t_line)| format!(" {}  {}\n", g_line, t_line))
+                .collect())
+        })
+        .map_err(|err| JsValue::from_str(&err))
+}
+
+/// Lays out the graph, and returns commits and branches (incl. columns) as JSON.
+#[wasm_bindgen]
+pub fn layout_json(data: &str, model: &str) -> Result<String, JsValue> {
+    build(data, model)
+        .and_then(|(graph, _)| print_json(&graph))
+        .map_err(|err| JsValue::from_str(&err))
+}
+
+fn build(data: &str, model: &str) -> Result<(GitGraph, Settings), String> {
+    let model = match model {
+        "git-flow" => BranchSettingsDef::git_flow(),
+        "simple" => BranchSettingsDef::simple(),
+        "gitlab-flow" => BranchSettingsDef::gitlab_flow(),
+        "trunk" => BranchSettingsDef::trunk(),
+        "none" => BranchSettingsDef::none(),
+        _ => toml::from_str(model).map_err(|err| err.to_string())?,
+    };
+
+    let settings = Settings {
+        branches: BranchSettings::from(model).map_err(|err| err.to_string())?,
+        ..Default::default()
+    };
+
+    let graph = GitGraph::from_source(MemorySource::from_json(data)?, &settings, None)?;
+    Ok((graph, settings))
+}
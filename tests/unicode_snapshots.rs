@@ -0,0 +1,145 @@
+//! Golden-file tests of text-based graphs in each style.
+//!
+//! The graphs of the histories in `tests/fixtures` (in the format of `MemorySource::from_git_log`)
+//! are compared to the files in `tests/snapshots`, in normal and in reverse order.
+//! After intended changes of the output, update the files by running the tests with `UPDATE_SNAPSHOTS=1`.
+//!
+//! Graphs of the first commits only, as printed before starting the pager, are compared to the full graphs.
+
+use git_graph::graph::GitGraph;
+use git_graph::print::unicode::{print_unicode, print_unicode_prefix};
+use git_graph::settings::{
+    BranchSettings, BranchSettingsDef, Characters, Settings, DENSITY_COMPACT, DENSITY_SPARSE,
+};
+use git_graph::source::memory::MemorySource;
+use std::fs;
+use std::path::Path;
+
+/// Constructor of the branching model of a fixture.
+type Model = fn() -> BranchSettingsDef;
+
+const FIXTURES: [(&str, Model); 3] = [
+    ("sample", BranchSettingsDef::git_flow),
+    ("git-flow", BranchSettingsDef::git_flow),
+    ("gitlab-flow", BranchSettingsDef::gitlab_flow),
+];
+const STYLES: [&str; 5] = ["thin", "round", "bold", "double", "ascii"];
+
+fn settings(model: BranchSettingsDef, style: &str, reverse: bool) -> Settings {
+    let characters: Characters = style.parse().unwrap();
+    Settings {
+        reverse_commit_order: reverse,
+        deterministic: true,
+        characters,
+        branches: BranchSettings::from(model).unwrap(),
+        ..Default::default()
+    }
+}
+
+fn render(log: &str, model: Model, style: &str, reverse: bool) -> String {
+    let settings = settings(model(), style, reverse);
+    let source = MemorySource::from_git_log(log).unwrap();
+    let graph = GitGraph::from_source(source, &settings, None).unwrap();
+    assert_eq!(graph.validate(), Vec::<String>::new());
+    let (graph_lines, text_lines, _) = print_unicode(&graph, &settings).unwrap();
+    graph_lines
+        .iter()
+        .zip(text_lines)
+        .map(|(graph_line, text_line)| {
+            format!("{}  {}", graph_line, text_line)
+                .trim_end()
+                .to_string()
+                + "\n"
+        })
+        .collect()
+}
+
+#[test]
+fn unicode_snapshots() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+    let mut failures = vec![];
+
+    for (fixture, model) in FIXTURES {
+        let log =
+            fs::read_to_string(dir.join("fixtures").join(format!("{}.log", fixture))).unwrap();
+        for style in STYLES {
+            let actual = format!(
+                "{}\n{}",
+                render(&log, model, style, false),
+                render(&log, model, style, true)
+            );
+            let file = dir
+                .join("snapshots")
+                .join(format!("{}-{}.txt", fixture, style));
+            if update {
+                fs::write(&file, &actual).unwrap();
+                continue;
+            }
+            let expected = fs::read_to_string(&file).unwrap_or_default();
+            if let Some((row, (expected, actual))) = expected
+                .lines()
+                .chain(std::iter::repeat(""))
+                .zip(actual.lines())
+                .enumerate()
+                .find(|(_, (expected, actual))| expected != actual)
+            {
+                failures.push(format!(
+                    "{}, line {}:\n  expected: {}\n  actual:   {}",
+                    file.display(),
+                    row + 1,
+                    expected,
+                    actual
+                ));
+            } else if expected.lines().count() != actual.lines().count() {
+                failures.push(format!("{}: different number of lines", file.display()));
+            }
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "Graphs differ from their snapshots. If intended, run the tests with UPDATE_SNAPSHOTS=1.\n{}",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn prefixes_match_full_graphs() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    for (fixture, model) in FIXTURES {
+        let log =
+            fs::read_to_string(dir.join("fixtures").join(format!("{}.log", fixture))).unwrap();
+        for (density, row_spacing) in [
+            (DENSITY_COMPACT, 0),
+            (DENSITY_COMPACT, 1),
+            (DENSITY_SPARSE, 0),
+        ] {
+            let mut settings = settings(model(), "thin", false);
+            settings.density = density;
+            settings.row_spacing = row_spacing;
+            let source = MemorySource::from_git_log(&log).unwrap();
+            let graph = GitGraph::from_source(source, &settings, None).unwrap();
+            let (full_graph, full_text, full_index) = print_unicode(&graph, &settings).unwrap();
+
+            for count in 0..=graph.commits.len() {
+                let (graph_lines, text_lines, index_map) =
+                    print_unicode_prefix(&graph, &settings, count).unwrap();
+                let rows = graph_lines.len();
+                assert_eq!(
+                    (&graph_lines[..], &text_lines[..], &index_map[..]),
+                    (
+                        &full_graph[..rows],
+                        &full_text[..rows],
+                        &full_index[..count]
+                    ),
+                    "{}, density {}, row spacing {}, first {} commits",
+                    fixture,
+                    density,
+                    row_spacing,
+                    count
+                );
+            }
+        }
+    }
+}